@@ -0,0 +1,12 @@
+use sad_machine::state_machine_from_file;
+
+state_machine_from_file!("examples/lock_from_file/lock.sm");
+
+fn main() {
+    let lock = Lock::locked()
+        .apply_all(vec![LockEvent::TurnKey, LockEvent::BreakKeyhole])
+        .unwrap();
+
+    assert_eq!(lock, Lock::Broken(BrokenState::FromBreakKeyhole));
+    assert!(lock.is_final());
+}
@@ -0,0 +1,44 @@
+//! A machine checked to build without `std` or `alloc`. `#[no_std]` makes the
+//! expansion fail if another option of the machine would need them.
+
+#![no_std]
+
+use sad_machine::{state_machine, History};
+
+state_machine! {
+    #[no_std]
+    Lock {
+        InitialStates { Locked }
+
+        TurnKey {
+            Locked => Unlocked
+            Unlocked => Locked
+        }
+    }
+}
+
+/// Turn the key `times` times, recording the last four turns.
+pub fn turn(times: usize) -> History<Lock, 4> {
+    let mut history = History::new(Lock::locked());
+
+    for _ in 0..times {
+        let _ = history.transition(LockEvent::TurnKey);
+    }
+
+    history
+}
+
+/// Queue a turn of the key, which queues another one until the lock is
+/// locked again.
+pub fn run() -> LockRunner {
+    let mut runner = LockRunner::new(Lock::locked());
+    let _ = runner.post(LockEvent::TurnKey);
+
+    let _ = runner.run(|_, _, to, queue| {
+        if to.kind() == LockKind::Unlocked {
+            let _ = queue.post(LockEvent::TurnKey);
+        }
+    });
+
+    runner
+}
@@ -0,0 +1,191 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{
+    dynamic::error_name,
+    event::{event_enum_name, event_marker_name},
+    ident::unraw,
+    machine::Machine,
+    state::kind_enum_name,
+};
+
+/// The name of the actor hosting a machine.
+pub(crate) fn actor_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Actor", unraw(machine)), machine.span())
+}
+
+/// `{Machine}Actor`, generated for machines with `#[actix]`, which hosts the
+/// machine as an actor handling `{Machine}Event` and the `{Event}Event` markers
+/// as messages.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct ActixImpls<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for ActixImpls<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let actor = actor_name(name);
+        let event_enum = event_enum_name(name);
+        let kind_enum = kind_enum_name(name);
+        let error = error_name(name);
+
+        let marker_impls = self.machine.events().into_iter().map(|e| {
+            let marker = event_marker_name(&e.name);
+
+            quote! {
+                impl ::actix::Message for #marker {
+                    type Result = Result<#kind_enum, #error>;
+                }
+
+                impl ::actix::Handler<#marker> for #actor {
+                    type Result = Result<#kind_enum, #error>;
+
+                    fn handle(
+                        &mut self,
+                        event: #marker,
+                        context: &mut ::actix::Context<Self>,
+                    ) -> Self::Result {
+                        ::actix::Handler::<#event_enum>::handle(self, event.into(), context)
+                    }
+                }
+            }
+        });
+
+        tokens.extend(quote! {
+            /// The machine hosted as an `actix` actor. Every event it handles
+            /// is answered with the kind of state entered, or with an error if
+            /// the current state does not accept it.
+            #[derive(Debug)]
+            pub struct #actor {
+                pub machine: #name,
+            }
+
+            impl From<#name> for #actor {
+                fn from(machine: #name) -> #actor {
+                    #actor { machine }
+                }
+            }
+
+            impl ::actix::Actor for #actor {
+                type Context = ::actix::Context<Self>;
+            }
+
+            impl ::actix::Message for #event_enum {
+                type Result = Result<#kind_enum, #error>;
+            }
+
+            impl ::actix::Handler<#event_enum> for #actor {
+                type Result = Result<#kind_enum, #error>;
+
+                fn handle(
+                    &mut self,
+                    event: #event_enum,
+                    _: &mut ::actix::Context<Self>,
+                ) -> Self::Result {
+                    self.machine = self.machine.try_transition(event)?;
+
+                    Ok(self.machine.kind())
+                }
+            }
+
+            #(#marker_impls)*
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_actix_impls_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            /// The machine hosted as an `actix` actor. Every event it handles
+            /// is answered with the kind of state entered, or with an error if
+            /// the current state does not accept it.
+            #[derive(Debug)]
+            pub struct TurnStileActor {
+                pub machine: TurnStile,
+            }
+
+            impl From<TurnStile> for TurnStileActor {
+                fn from(machine: TurnStile) -> TurnStileActor {
+                    TurnStileActor { machine }
+                }
+            }
+
+            impl ::actix::Actor for TurnStileActor {
+                type Context = ::actix::Context<Self>;
+            }
+
+            impl ::actix::Message for TurnStileEvent {
+                type Result = Result<TurnStileKind, TurnStileError>;
+            }
+
+            impl ::actix::Handler<TurnStileEvent> for TurnStileActor {
+                type Result = Result<TurnStileKind, TurnStileError>;
+
+                fn handle(
+                    &mut self,
+                    event: TurnStileEvent,
+                    _: &mut ::actix::Context<Self>,
+                ) -> Self::Result {
+                    self.machine = self.machine.try_transition(event)?;
+
+                    Ok(self.machine.kind())
+                }
+            }
+
+            impl ::actix::Message for CoinEvent {
+                type Result = Result<TurnStileKind, TurnStileError>;
+            }
+
+            impl ::actix::Handler<CoinEvent> for TurnStileActor {
+                type Result = Result<TurnStileKind, TurnStileError>;
+
+                fn handle(
+                    &mut self,
+                    event: CoinEvent,
+                    context: &mut ::actix::Context<Self>,
+                ) -> Self::Result {
+                    ::actix::Handler::<TurnStileEvent>::handle(self, event.into(), context)
+                }
+            }
+
+            impl ::actix::Message for PushEvent {
+                type Result = Result<TurnStileKind, TurnStileError>;
+            }
+
+            impl ::actix::Handler<PushEvent> for TurnStileActor {
+                type Result = Result<TurnStileKind, TurnStileError>;
+
+                fn handle(
+                    &mut self,
+                    event: PushEvent,
+                    context: &mut ::actix::Context<Self>,
+                ) -> Self::Result {
+                    ::actix::Handler::<TurnStileEvent>::handle(self, event.into(), context)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        ActixImpls { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
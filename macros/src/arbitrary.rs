@@ -0,0 +1,143 @@
+use crate::{event::event_enum_name, machine::Machine, state::kind_enum_name};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+/// Implementations of `arbitrary::Arbitrary` for the machine, its kinds and its
+/// events, generated for machines with `#[arbitrary]` so that fuzzers can pick
+/// starting states and event sequences directly. The machine is only ever built
+/// with a provenance it can actually be entered with.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct ArbitraryImpls<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for ArbitraryImpls<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+
+        let kinds = self.machine.states().0.into_iter().map(|s| {
+            let state = s.name;
+
+            quote! { #kind_enum::#state }
+        });
+
+        let events = self.machine.events().into_iter().map(|e| {
+            let event = e.name;
+
+            quote! { #event_enum::#event }
+        });
+
+        let values = self
+            .machine
+            .values()
+            .into_iter()
+            .map(|(s, p)| self.machine.value(&s.name, &p));
+
+        tokens.extend(quote! {
+            impl<'a> ::arbitrary::Arbitrary<'a> for #kind_enum {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(*u.choose(&[#(#kinds),*])?)
+                }
+            }
+
+            impl<'a> ::arbitrary::Arbitrary<'a> for #event_enum {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(*u.choose(&[#(#events),*])?)
+                }
+            }
+
+            impl<'a> ::arbitrary::Arbitrary<'a> for #name {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(u.choose(&[#(#values),*])?.clone())
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_arbitrary_impls_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            impl<'a> ::arbitrary::Arbitrary<'a> for TurnStileKind {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(*u.choose(&[TurnStileKind::Locked, TurnStileKind::Unlocked])?)
+                }
+            }
+
+            impl<'a> ::arbitrary::Arbitrary<'a> for TurnStileEvent {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(*u.choose(&[TurnStileEvent::Coin])?)
+                }
+            }
+
+            impl<'a> ::arbitrary::Arbitrary<'a> for TurnStile {
+                fn arbitrary(u: &mut ::arbitrary::Unstructured<'a>) -> ::arbitrary::Result<Self> {
+                    Ok(u
+                        .choose(&[
+                            TurnStile::Locked(LockedState::FromInit),
+                            TurnStile::Unlocked(UnlockedState::FromCoin)
+                        ])?
+                        .clone())
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        ArbitraryImpls { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream, Result},
+    Error, Ident, Token,
+};
+
+use crate::{machine::Machine, state::State};
+
+/// A transition declared with `on entry Validating => Validated`, which
+/// `transition` takes as soon as it enters `from`, without waiting for an
+/// event.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Completion {
+    pub from: State,
+    pub to: State,
+}
+
+impl Completion {
+    /// Whether the next tokens are an `on entry ... => ...` declaration.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "on")
+            && fork.parse::<Ident>().is_ok_and(|ident| ident == "entry")
+    }
+}
+
+impl Parse for Completion {
+    /// example completion tokens:
+    ///
+    /// ```text
+    /// on entry Validating => Validated
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `on entry Validating => Validated`
+        //  ^^^^^^^^
+        let on: Ident = input.parse()?;
+        let entry: Ident = input.parse()?;
+
+        if on != "on" || entry != "entry" {
+            return Err(Error::new(on.span(), "expected `on entry ... => ...`"));
+        }
+
+        // `on entry Validating => Validated`
+        //           ^^^^^^^^^^
+        let from = input.parse()?;
+
+        // `on entry Validating => Validated`
+        //                      ^^
+        let _: Token![=>] = input.parse()?;
+
+        // `on entry Validating => Validated`
+        //                         ^^^^^^^^^
+        let to = input.parse()?;
+
+        Ok(Completion { from, to })
+    }
+}
+
+/// Check that every state of `machine` has at most one completion
+/// transition, that they cannot loop forever, and that they leave neither
+/// initial nor final states.
+pub(crate) fn check(machine: &Machine) -> Result<()> {
+    let mut targets: HashMap<String, &State> = HashMap::new();
+
+    for c in &machine.completions {
+        if targets.insert(c.from.name.to_string(), &c.to).is_some() {
+            return Err(Error::new(
+                c.from.name.span(),
+                format!(
+                    "state `{}` already has a completion transition",
+                    c.from.name
+                ),
+            ));
+        }
+
+        if machine
+            .initial_states
+            .0
+            .iter()
+            .any(|i| i.name == c.from.name)
+        {
+            return Err(Error::new(
+                c.from.name.span(),
+                format!(
+                    "initial state `{}` cannot have a completion transition, \
+                     since it would be left before anything observes it",
+                    c.from.name
+                ),
+            ));
+        }
+
+        if machine.final_states.0.iter().any(|f| f.name == c.from.name) {
+            return Err(Error::new(
+                c.from.name.span(),
+                format!(
+                    "final state `{}` cannot have a completion transition",
+                    c.from.name
+                ),
+            ));
+        }
+    }
+
+    // A loop through `c.from` has at most as many completions as there are.
+    for c in &machine.completions {
+        let mut state = &c.to;
+
+        for _ in 0..targets.len() {
+            if state.name == c.from.name {
+                return Err(Error::new(
+                    c.from.name.span(),
+                    format!(
+                        "the completion transitions from `{}` lead back to it, \
+                         so they would never stop",
+                        c.from.name
+                    ),
+                ));
+            }
+
+            match targets.get(&state.name.to_string()) {
+                Some(&next) => state = next,
+                None => break,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `settle` and `complete_once`, generated for the machines with completion
+/// transitions, which take them from the current state. The transition
+/// methods stop at the states they declare, unlike `transition`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Settle<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Settle<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+
+        let arms = self
+            .machine
+            .values()
+            .into_iter()
+            .filter_map(|(s, p)| {
+                let to = self.machine.completed(&s);
+
+                if to.name == s.name {
+                    return None;
+                }
+
+                let from = self.machine.value(&s.name, &p);
+                let to = self.machine.value(&to.name, &p);
+
+                Some(quote! { #from => #to })
+            })
+            .collect::<Vec<_>>();
+
+        let body = if arms.is_empty() {
+            quote! { self.clone() }
+        } else {
+            quote! {
+                match self {
+                    #(#arms,)*
+                    other => other.clone(),
+                }
+            }
+        };
+
+        let step_arms = self
+            .machine
+            .values()
+            .into_iter()
+            .filter_map(|(s, p)| {
+                let c = self
+                    .machine
+                    .completions
+                    .iter()
+                    .find(|c| c.from.name == s.name)?;
+
+                let from = self.machine.value(&s.name, &p);
+                let to = self.machine.value(&c.to.name, &p);
+
+                Some(quote! { #from => Some(#to) })
+            })
+            .collect::<Vec<_>>();
+
+        let inline = self.machine.options.inline;
+
+        tokens.extend(quote! {
+            impl #name {
+                /// Take the completion transitions declared with
+                /// `on entry ... => ...` from the current state, until
+                /// reaching a state without one. `transition` takes them on
+                /// its own, but the transition methods do not.
+                #inline
+                pub fn settle(&self) -> #name {
+                    #body
+                }
+
+                /// Take the completion transition from the current state,
+                /// or return `None` if it has none.
+                #inline
+                pub fn complete_once(&self) -> Option<#name> {
+                    match self {
+                        #(#step_arms,)*
+                        _ => None,
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_completion_parse() {
+        let left: Completion = parse2(quote! { on entry Validating => Validated }).unwrap();
+        let right = Completion {
+            from: State {
+                name: parse_quote! { Validating },
+            },
+            to: State {
+                name: parse_quote! { Validated },
+            },
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_check() {
+        let cases = vec![
+            (
+                quote! {
+                    Order {
+                        InitialStates { Draft }
+                        on entry Validating => Validated
+                        on entry Validating => Draft
+
+                        Submit { Draft => Validating }
+                        Ship { Validated => Draft }
+                    }
+                },
+                "state `Validating` already has a completion transition",
+            ),
+            (
+                quote! {
+                    Order {
+                        InitialStates { Draft }
+                        on entry Draft => Validated
+
+                        Ship { Validated => Draft }
+                    }
+                },
+                "initial state `Draft` cannot have a completion transition, \
+                 since it would be left before anything observes it",
+            ),
+            (
+                quote! {
+                    Order {
+                        InitialStates { Draft }
+                        on entry Validating => Checking
+                        on entry Checking => Validating
+
+                        Submit { Draft => Validating }
+                    }
+                },
+                "the completion transitions from `Validating` lead back to it, \
+                 so they would never stop",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_settle_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            Order {
+                InitialStates { Draft }
+                on entry Validating => Validated
+
+                Submit { Draft => Validating }
+                Edit { Validated => Draft }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl Order {
+                /// Take the completion transitions declared with
+                /// `on entry ... => ...` from the current state, until
+                /// reaching a state without one. `transition` takes them on
+                /// its own, but the transition methods do not.
+                pub fn settle(&self) -> Order {
+                    match self {
+                        Order::Validating(ValidatingState::FromSubmit) => Order::Validated(ValidatedState::FromSubmit),
+                        other => other.clone(),
+                    }
+                }
+
+                /// Take the completion transition from the current state,
+                /// or return `None` if it has none.
+                pub fn complete_once(&self) -> Option<Order> {
+                    match self {
+                        Order::Validating(ValidatingState::FromSubmit) => Some(Order::Validated(ValidatedState::FromSubmit)),
+                        _ => None,
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Settle { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,258 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{
+    dynamic::step_struct_name,
+    event::event_enum_name,
+    ident::unraw,
+    machine::Machine,
+    options::ProvenanceMode,
+    provenance::{provenance_enum_name, Provenance},
+    state::{kind_enum_name, state_enum_name},
+};
+
+/// `impl defmt::Format` for a fieldless enum, printing the name of the case
+/// given by `name`.
+fn unit_enum_format(
+    enum_name: &Ident,
+    variants: &[Ident],
+    name: impl Fn(&Ident) -> String,
+) -> TokenStream {
+    let arms = variants.iter().map(|variant| {
+        let string = name(variant);
+
+        quote! { #enum_name::#variant => ::defmt::write!(f, #string) }
+    });
+
+    quote! {
+        impl ::defmt::Format for #enum_name {
+            fn format(&self, f: ::defmt::Formatter<'_>) {
+                match *self {
+                    #(#arms),*
+                }
+            }
+        }
+    }
+}
+
+/// Implementations of `defmt::Format` for every type generated for the machine,
+/// generated for machines with `#[defmt]` so that embedded firmware can log
+/// state changes without going through `core::fmt`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct DefmtImpls<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for DefmtImpls<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let labels = &self.machine.labels;
+        let state_provenances = self.machine.state_provenances();
+        let kinds = state_provenances
+            .iter()
+            .map(|(s, _)| s.name.clone())
+            .collect::<Vec<_>>();
+
+        if self.machine.options.provenance == ProvenanceMode::None {
+            // Without provenance, the machine is itself a fieldless enum.
+            tokens.extend(unit_enum_format(name, &kinds, |s| labels.state(s)));
+        } else {
+            for (s, provenances) in &state_provenances {
+                let state_enum = state_enum_name(&s.name);
+                let variants = provenances
+                    .iter()
+                    .map(Provenance::variant)
+                    .collect::<Vec<_>>();
+
+                tokens.extend(unit_enum_format(&state_enum, &variants, unraw));
+            }
+
+            let machine_arms = state_provenances.iter().map(|(s, _)| {
+                let state = &s.name;
+                let format = format!("{}({{}})", labels.state(state));
+
+                quote! { #name::#state(state) => ::defmt::write!(f, #format, state) }
+            });
+
+            tokens.extend(quote! {
+                impl ::defmt::Format for #name {
+                    fn format(&self, f: ::defmt::Formatter<'_>) {
+                        match self {
+                            #(#machine_arms),*
+                        }
+                    }
+                }
+            });
+        }
+
+        tokens.extend(unit_enum_format(&kind_enum_name(name), &kinds, |s| {
+            labels.state(s)
+        }));
+
+        let events = self
+            .machine
+            .events()
+            .into_iter()
+            .map(|e| e.name)
+            .collect::<Vec<_>>();
+        tokens.extend(unit_enum_format(&event_enum_name(name), &events, |e| {
+            labels.event(e)
+        }));
+
+        if self.machine.options.provenance != ProvenanceMode::None {
+            let mut provenances: Vec<Ident> = Vec::new();
+            for (_, p) in self.machine.values() {
+                let variant = p.variant();
+
+                if !provenances.contains(&variant) {
+                    provenances.push(variant);
+                }
+            }
+            tokens.extend(unit_enum_format(
+                &provenance_enum_name(name),
+                &provenances,
+                unraw,
+            ));
+        }
+
+        let step_struct = step_struct_name(name);
+        let step_format = format!(
+            "{} {{{{ from: {{}}, event: {{}}, to: {{}} }}}}",
+            step_struct
+        );
+
+        tokens.extend(quote! {
+            impl ::defmt::Format for #step_struct {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    ::defmt::write!(f, #step_format, self.from, self.event, self.to)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_defmt_impls_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            impl ::defmt::Format for LockedState {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match *self {
+                        LockedState::FromInit => ::defmt::write!(f, "FromInit")
+                    }
+                }
+            }
+
+            impl ::defmt::Format for UnlockedState {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match *self {
+                        UnlockedState::FromCoin => ::defmt::write!(f, "FromCoin")
+                    }
+                }
+            }
+
+            impl ::defmt::Format for TurnStile {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match self {
+                        TurnStile::Locked(state) => ::defmt::write!(f, "Locked({})", state),
+                        TurnStile::Unlocked(state) => ::defmt::write!(f, "Unlocked({})", state)
+                    }
+                }
+            }
+
+            impl ::defmt::Format for TurnStileKind {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match *self {
+                        TurnStileKind::Locked => ::defmt::write!(f, "Locked"),
+                        TurnStileKind::Unlocked => ::defmt::write!(f, "Unlocked")
+                    }
+                }
+            }
+
+            impl ::defmt::Format for TurnStileEvent {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match *self {
+                        TurnStileEvent::Coin => ::defmt::write!(f, "Coin")
+                    }
+                }
+            }
+
+            impl ::defmt::Format for TurnStileProvenance {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    match *self {
+                        TurnStileProvenance::FromInit => ::defmt::write!(f, "FromInit"),
+                        TurnStileProvenance::FromCoin => ::defmt::write!(f, "FromCoin")
+                    }
+                }
+            }
+
+            impl ::defmt::Format for TurnStileStep {
+                fn format(&self, f: ::defmt::Formatter<'_>) {
+                    ::defmt::write!(
+                        f,
+                        "TurnStileStep {{ from: {}, event: {}, to: {} }}",
+                        self.from,
+                        self.event,
+                        self.to
+                    )
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        DefmtImpls { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
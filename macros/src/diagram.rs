@@ -0,0 +1,553 @@
+use std::{env, fs, path::PathBuf};
+use syn::{Error, Ident, Result};
+
+use crate::{ident::unraw, machine::Machine, options::DiagramDir};
+
+/// `name` escaped to be quoted in DOT or JSON.
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A Graphviz description of the diagram: the states as nodes, with final
+/// states circled twice, the transitions as edges labelled with their event,
+/// and an edge from a point into every initial state. States named other than
+/// their identifier are labelled with their name.
+pub(crate) fn dot(machine: &Machine) -> String {
+    let labels = &machine.labels;
+    let mut lines = vec![format!("digraph {} {{", unraw(&machine.name))];
+
+    lines.push("    __start [shape=point];".to_owned());
+
+    for s in &machine.states() {
+        let shape = if machine.final_states.0.iter().any(|f| f.name == s.name) {
+            "doublecircle"
+        } else {
+            "circle"
+        };
+
+        let label = labels.state(&s.name);
+
+        if label == unraw(&s.name) {
+            lines.push(format!("    {} [shape={}];", unraw(&s.name), shape));
+        } else {
+            lines.push(format!(
+                "    {} [shape={}, label=\"{}\"];",
+                unraw(&s.name),
+                shape,
+                escape(&label)
+            ));
+        }
+    }
+
+    for i in &machine.initial_states.0 {
+        lines.push(format!("    __start -> {};", unraw(&i.name)));
+    }
+
+    for t in &machine.transitions.0 {
+        // The deprecated transitions are dashed.
+        let style = if t.deprecated.is_some() {
+            ", style=dashed"
+        } else {
+            ""
+        };
+
+        lines.push(format!(
+            "    {} -> {} [label=\"{}\"{}];",
+            unraw(&t.from.name),
+            unraw(&t.to.name),
+            escape(&labels.event(&t.event.name)),
+            style
+        ));
+    }
+
+    // The completion transitions are taken without an event.
+    for c in &machine.completions {
+        lines.push(format!(
+            "    {} -> {} [style=dotted];",
+            unraw(&c.from.name),
+            unraw(&c.to.name)
+        ));
+    }
+
+    lines.push("}".to_owned());
+
+    lines.join("\n")
+}
+
+/// A Mermaid `stateDiagram-v2` description of the diagram, with the initial
+/// and final states linked to the `[*]` pseudo-state and the states named
+/// other than their identifier declared with `state "name" as Identifier`.
+pub(crate) fn mermaid(machine: &Machine) -> String {
+    let labels = &machine.labels;
+    let mut lines = vec!["stateDiagram-v2".to_owned()];
+
+    for s in &machine.states() {
+        let label = labels.state(&s.name);
+
+        if label != unraw(&s.name) {
+            // Mermaid has no escape for quotes, only the `#quot;` entity.
+            lines.push(format!(
+                "    state \"{}\" as {}",
+                label.replace('"', "#quot;"),
+                unraw(&s.name)
+            ));
+        }
+    }
+
+    for i in &machine.initial_states.0 {
+        lines.push(format!("    [*] --> {}", unraw(&i.name)));
+    }
+
+    for t in &machine.transitions.0 {
+        // Mermaid has no dashed transitions in state diagrams.
+        let deprecated = if t.deprecated.is_some() {
+            " (deprecated)"
+        } else {
+            ""
+        };
+
+        lines.push(format!(
+            "    {} --> {} : {}{}",
+            unraw(&t.from.name),
+            unraw(&t.to.name),
+            labels.event(&t.event.name),
+            deprecated
+        ));
+    }
+
+    for c in &machine.completions {
+        lines.push(format!(
+            "    {} --> {}",
+            unraw(&c.from.name),
+            unraw(&c.to.name)
+        ));
+    }
+
+    for f in &machine.final_states.0 {
+        lines.push(format!("    {} --> [*]", unraw(&f.name)));
+    }
+
+    lines.join("\n")
+}
+
+/// A JSON array of `names`.
+fn json_names(names: impl IntoIterator<Item = String>) -> String {
+    let names = names
+        .into_iter()
+        .map(|name| format!("\"{}\"", escape(&name)))
+        .collect::<Vec<_>>();
+
+    format!("[{}]", names.join(","))
+}
+
+/// A JSON document describing the diagram, for services exposing the
+/// structure of their machines.
+pub(crate) fn description(machine: &Machine) -> String {
+    let labels = &machine.labels;
+    let state = |state: &Ident| labels.state(state);
+
+    let transitions = machine
+        .transitions
+        .0
+        .iter()
+        .map(|t| {
+            let label = match &t.label {
+                Some(label) => format!(",\"label\":\"{}\"", escape(&label.value())),
+                None => String::new(),
+            };
+
+            let weight = match t.weight {
+                Some(weight) => format!(",\"weight\":{}", weight),
+                None => String::new(),
+            };
+
+            let deprecated = if t.deprecated.is_some() {
+                ",\"deprecated\":true"
+            } else {
+                ""
+            };
+
+            format!(
+                "{{\"event\":\"{}\",\"from\":\"{}\",\"to\":\"{}\"{}{}{}}}",
+                escape(&labels.event(&t.event.name)),
+                escape(&state(&t.from.name)),
+                escape(&state(&t.to.name)),
+                label,
+                weight,
+                deprecated
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let completions = if machine.completions.is_empty() {
+        String::new()
+    } else {
+        let completions = machine
+            .completions
+            .iter()
+            .map(|c| {
+                format!(
+                    "{{\"from\":\"{}\",\"to\":\"{}\"}}",
+                    escape(&state(&c.from.name)),
+                    escape(&state(&c.to.name))
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(",\"completions\":[{}]", completions.join(","))
+    };
+
+    let on_invalid = match &machine.on_invalid {
+        Some(on_invalid) => format!(",\"on_invalid\":\"{}\"", escape(&state(&on_invalid.0.name))),
+        None => String::new(),
+    };
+
+    format!(
+        "{{\"name\":\"{}\",\"states\":{},\"events\":{},\"initial_states\":{},\
+         \"final_states\":{},\"transitions\":[{}]{}{}}}",
+        unraw(&machine.name),
+        json_names(machine.states().0.iter().map(|s| state(&s.name))),
+        json_names(machine.events().iter().map(|e| labels.event(&e.name))),
+        json_names(machine.initial_states.0.iter().map(|i| state(&i.name))),
+        json_names(machine.final_states.0.iter().map(|f| state(&f.name))),
+        transitions.join(","),
+        completions,
+        on_invalid
+    )
+}
+
+/// Write `{Machine}.dot` and `{Machine}.mmd` to the directory requested with
+/// `#[write_diagrams]`, if any.
+pub(crate) fn write_files(machine: &Machine) -> Result<()> {
+    let error = |message: String| Error::new(machine.name.span(), message);
+
+    let dir = match &machine.options.write_diagrams {
+        None => return Ok(()),
+        Some(DiagramDir::OutDir) => env::var_os("OUT_DIR").map(PathBuf::from).ok_or_else(|| {
+            error(
+                "`#[write_diagrams]` needs `OUT_DIR`, which is only set for crates with a \
+                 build script; add one or give a directory, e.g. \
+                 `#[write_diagrams(\"docs/diagrams\")]`"
+                    .to_owned(),
+            )
+        })?,
+        Some(DiagramDir::Path(path)) => env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(path),
+    };
+
+    let name = unraw(&machine.name);
+
+    fs::create_dir_all(&dir)
+        .and_then(|()| fs::write(dir.join(format!("{}.dot", name)), dot(machine)))
+        .and_then(|()| fs::write(dir.join(format!("{}.mmd", name)), mermaid(machine)))
+        .map_err(|e| {
+            error(format!(
+                "cannot write the diagrams to `{}`: {}",
+                dir.display(),
+                e
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::Machines;
+    use quote::quote;
+    use syn::parse2;
+
+    #[test]
+    fn test_dot() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            dot(&machine),
+            "digraph TurnStile {\n    \
+                __start [shape=point];\n    \
+                Locked [shape=circle];\n    \
+                Unlocked [shape=doublecircle];\n    \
+                __start -> Locked;\n    \
+                Locked -> Unlocked [label=\"Coin\"];\n\
+             }"
+        );
+    }
+
+    #[test]
+    fn test_description() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+                Kick "kicked \"open\"" * 2 { Locked => Unlocked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            description(&machine),
+            "{\"name\":\"TurnStile\",\
+              \"states\":[\"Locked\",\"Unlocked\"],\
+              \"events\":[\"Coin\",\"Kick\"],\
+              \"initial_states\":[\"Locked\"],\
+              \"final_states\":[\"Unlocked\"],\
+              \"transitions\":[{\"event\":\"Coin\",\"from\":\"Locked\",\"to\":\"Unlocked\"},\
+              {\"event\":\"Kick\",\"from\":\"Locked\",\"to\":\"Unlocked\",\"label\":\"kicked \\\"open\\\"\",\"weight\":2}]}"
+        );
+
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Broken }
+                OnInvalid => Broken
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            description(&machine),
+            "{\"name\":\"TurnStile\",\
+              \"states\":[\"Locked\",\"Unlocked\",\"Broken\"],\
+              \"events\":[\"Coin\",\"Push\"],\
+              \"initial_states\":[\"Locked\"],\
+              \"final_states\":[\"Broken\"],\
+              \"transitions\":[{\"event\":\"Coin\",\"from\":\"Locked\",\"to\":\"Unlocked\"},\
+              {\"event\":\"Push\",\"from\":\"Unlocked\",\"to\":\"Locked\"}],\
+              \"on_invalid\":\"Broken\"}"
+        );
+
+        let machine: Machine = parse2(quote! {
+            Order {
+                InitialStates { Draft }
+                FinalStates { Checked }
+                on entry Validating => Checked
+
+                Submit { Draft => Validating }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            description(&machine),
+            "{\"name\":\"Order\",\
+              \"states\":[\"Draft\",\"Validating\",\"Checked\"],\
+              \"events\":[\"Submit\"],\
+              \"initial_states\":[\"Draft\"],\
+              \"final_states\":[\"Checked\"],\
+              \"transitions\":[{\"event\":\"Submit\",\"from\":\"Draft\",\"to\":\"Validating\"}],\
+              \"completions\":[{\"from\":\"Validating\",\"to\":\"Checked\"}]}"
+        );
+    }
+
+    #[test]
+    fn test_write_files() {
+        let dir = env::temp_dir().join("sad_machine_test_write_files");
+        let path = dir.to_str().unwrap();
+
+        let machines: Machines = parse2(quote! {
+            #[write_diagrams(#path)]
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+            }
+        })
+        .unwrap();
+        let machine = &machines.0[0];
+
+        assert_eq!(
+            fs::read_to_string(dir.join("TurnStile.dot")).unwrap(),
+            dot(machine)
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("TurnStile.mmd")).unwrap(),
+            mermaid(machine)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The types generated for `Closed` clash across the two machines, so
+        // neither diagram is written.
+        let result: Result<Machines> = parse2(quote! {
+            #[write_diagrams(#path)]
+            Door {
+                InitialStates { Closed }
+                FinalStates { Opened }
+
+                Open { Closed => Opened }
+            }
+
+            #[write_diagrams(#path)]
+            Window {
+                InitialStates { Closed }
+                FinalStates { Opened }
+
+                Open { Closed => Opened }
+            }
+        });
+
+        assert!(result.is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_mermaid() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            mermaid(&machine),
+            "stateDiagram-v2\n    \
+                [*] --> Locked\n    \
+                Locked --> Unlocked : Coin\n    \
+                Unlocked --> [*]"
+        );
+    }
+    #[test]
+    fn test_labels() {
+        let machine: Machine = parse2(quote! {
+            Link {
+                InitialStates { idle }
+                FinalStates { Closed }
+                States { idle, "wait \"ack\"" as WaitForAck, Closed }
+
+                send { idle => WaitForAck }
+                Close { WaitForAck => Closed }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            dot(&machine),
+            "digraph Link {\n    \
+                __start [shape=point];\n    \
+                Idle [shape=circle, label=\"idle\"];\n    \
+                WaitForAck [shape=circle, label=\"wait \\\"ack\\\"\"];\n    \
+                Closed [shape=doublecircle];\n    \
+                __start -> Idle;\n    \
+                Idle -> WaitForAck [label=\"send\"];\n    \
+                WaitForAck -> Closed [label=\"Close\"];\n\
+             }"
+        );
+        assert_eq!(
+            mermaid(&machine),
+            "stateDiagram-v2\n    \
+                state \"idle\" as Idle\n    \
+                state \"wait #quot;ack#quot;\" as WaitForAck\n    \
+                [*] --> Idle\n    \
+                Idle --> WaitForAck : send\n    \
+                WaitForAck --> Closed : Close\n    \
+                Closed --> [*]"
+        );
+        assert_eq!(
+            description(&machine),
+            "{\"name\":\"Link\",\
+              \"states\":[\"idle\",\"wait \\\"ack\\\"\",\"Closed\"],\
+              \"events\":[\"send\",\"Close\"],\
+              \"initial_states\":[\"idle\"],\
+              \"final_states\":[\"Closed\"],\
+              \"transitions\":[\
+                {\"event\":\"send\",\"from\":\"idle\",\"to\":\"wait \\\"ack\\\"\"},\
+                {\"event\":\"Close\",\"from\":\"wait \\\"ack\\\"\",\"to\":\"Closed\"}]}"
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let machine: Machine = parse2(quote! {
+            Order {
+                InitialStates { Draft }
+                on entry Validating => Validated
+
+                Submit { Draft => Validating }
+                Edit { Validated => Draft }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            dot(&machine),
+            "digraph Order {\n    \
+                __start [shape=point];\n    \
+                Draft [shape=circle];\n    \
+                Validating [shape=circle];\n    \
+                Validated [shape=circle];\n    \
+                __start -> Draft;\n    \
+                Draft -> Validating [label=\"Submit\"];\n    \
+                Validated -> Draft [label=\"Edit\"];\n    \
+                Validating -> Validated [style=dotted];\n\
+             }"
+        );
+        assert_eq!(
+            mermaid(&machine),
+            "stateDiagram-v2\n    \
+                [*] --> Draft\n    \
+                Draft --> Validating : Submit\n    \
+                Validated --> Draft : Edit\n    \
+                Validating --> Validated"
+        );
+    }
+
+    #[test]
+    fn test_deprecated() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                #[deprecated]
+                Kick { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            dot(&machine),
+            "digraph TurnStile {\n    \
+                __start [shape=point];\n    \
+                Locked [shape=circle];\n    \
+                Unlocked [shape=circle];\n    \
+                __start -> Locked;\n    \
+                Locked -> Unlocked [label=\"Coin\"];\n    \
+                Locked -> Unlocked [label=\"Kick\", style=dashed];\n    \
+                Unlocked -> Locked [label=\"Push\"];\n\
+             }"
+        );
+        assert_eq!(
+            mermaid(&machine),
+            "stateDiagram-v2\n    \
+                [*] --> Locked\n    \
+                Locked --> Unlocked : Coin\n    \
+                Locked --> Unlocked : Kick (deprecated)\n    \
+                Unlocked --> Locked : Push"
+        );
+        assert!(description(&machine).contains(
+            "{\"event\":\"Kick\",\"from\":\"Locked\",\"to\":\"Unlocked\",\"deprecated\":true}"
+        ));
+    }
+}
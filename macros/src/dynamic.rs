@@ -0,0 +1,683 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{
+    event::{event_enum_name, event_fn_name},
+    hooks::hooks_trait_name,
+    ident::unraw,
+    machine::Machine,
+    metrics::dispatched_transition_counter,
+    options::DispatchMode,
+    provenance::Provenance,
+    state::kind_enum_name,
+    trace::dispatched_transition_event,
+    transition::{must_use_transition, Transition},
+};
+
+/// The name of the struct recording a single transition of a machine.
+pub(crate) fn step_struct_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Step", unraw(machine)), machine.span())
+}
+
+/// The name of the error returned when a machine rejects an event.
+pub(crate) fn error_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Error", unraw(machine)), machine.span())
+}
+
+/// The name of the error returned when an event log cannot be replayed.
+pub(crate) fn replay_error_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}ReplayError", unraw(machine)), machine.span())
+}
+
+/// The dynamic transition API, which applies a `{Machine}Event` value to
+/// whichever state the machine is in.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct DynamicTransitions<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for DynamicTransitions<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+        let kind_enum = kind_enum_name(name);
+        let step_struct = step_struct_name(name);
+        let error = error_name(name);
+
+        let arms = self.machine.transitions.0.iter().map(|t| {
+            let from = &t.from.name;
+            let event = &t.event.name;
+            let event_fn = event_fn_name(&t.event.name);
+
+            let allow = t
+                .deprecated
+                .as_ref()
+                .map(|_| quote! { #[allow(deprecated)] });
+
+            quote! { #allow (#name::#from(state), #event_enum::#event) => Some(state.#event_fn()) }
+        });
+
+        let events = self.machine.events();
+
+        // Only add a catch-all when some state doesn't accept some event,
+        // otherwise it triggers an unreachable pattern warning.
+        let catch_all =
+            if self.machine.transitions.0.len() < self.machine.states().0.len() * events.len() {
+                quote! { _ => None }
+            } else {
+                quote! {}
+            };
+
+        let methods = self.machine.has_transition_methods();
+        let invalid_transitions = self.machine.invalid_transitions();
+
+        let body = if events.is_empty() {
+            quote! { match event {} }
+        } else if !methods
+            || !invalid_transitions.is_empty()
+            || !self.machine.completions.is_empty()
+        {
+            let trace = if self.machine.options.tracing {
+                Some(dispatched_transition_event(name))
+            } else {
+                None
+            };
+
+            let count = if self.machine.options.metrics {
+                Some(dispatched_transition_counter(name))
+            } else {
+                None
+            };
+
+            let hook = self.machine.hooks.as_ref().map(|hooks| {
+                let hooks = &hooks.0;
+                let hooks_trait = hooks_trait_name(name);
+
+                quote! { <#hooks as #hooks_trait>::on_transition(self.kind(), event, &to); }
+            });
+
+            // Without transition methods to carry the instrumentation, the
+            // next state is looked up first and instrumented here.
+            let next = if self.machine.options.dispatch == DispatchMode::Table {
+                quote! {
+                    let (kind, provenance) = #name::TRANSITION_TABLE[self.kind() as usize][event as usize]?;
+                    let to = #name::from_table(kind, provenance);
+                }
+            } else {
+                let value_arm = |t: &Transition| {
+                    let from = self.machine.state_pattern(&t.from.name, quote! { _ });
+                    let event = &t.event.name;
+                    let to = self
+                        .machine
+                        .value(&t.to.name, &t.provenance(self.machine.options.provenance));
+
+                    quote! { (#from, #event_enum::#event) => #to }
+                };
+
+                // The transition methods are instrumented themselves, so only
+                // the `OnInvalid => ...` transitions and the ones followed by
+                // completion transitions fall through to the instrumentation.
+                let arms = self
+                    .machine
+                    .transitions
+                    .0
+                    .iter()
+                    .map(|t| {
+                        let completed = Transition {
+                            to: self.machine.completed(&t.to),
+                            ..t.clone()
+                        };
+
+                        if methods && completed.to.name == t.to.name {
+                            let from = &t.from.name;
+                            let event = &t.event.name;
+                            let event_fn = event_fn_name(&t.event.name);
+
+                            let allow =
+                                t.deprecated.as_ref().map(|_| quote! { #[allow(deprecated)] });
+
+                            quote! {
+                                #allow
+                                (#name::#from(state), #event_enum::#event) => return Some(state.#event_fn())
+                            }
+                        } else {
+                            value_arm(&completed)
+                        }
+                    })
+                    .chain(
+                        invalid_transitions
+                            .iter()
+                            .map(|t| {
+                                value_arm(&Transition {
+                                    to: self.machine.completed(&t.to),
+                                    ..t.clone()
+                                })
+                            }),
+                    )
+                    .collect::<Vec<_>>();
+
+                let catch_all = if arms.len() < self.machine.states().0.len() * events.len() {
+                    quote! { _ => return None }
+                } else {
+                    quote! {}
+                };
+
+                quote! {
+                    let to = match (self, event) {
+                        #(#arms,)*
+                        #catch_all
+                    };
+                }
+            };
+
+            quote! {
+                #next
+                #trace
+                #count
+                #hook
+                Some(to)
+            }
+        } else {
+            quote! {
+                match (self, event) {
+                    #(#arms,)*
+                    #catch_all
+                }
+            }
+        };
+
+        let label_arms = self
+            .machine
+            .transitions
+            .0
+            .iter()
+            .filter_map(|t| {
+                let from = &t.from.name;
+                let event = &t.event.name;
+                let label = t.label.as_ref()?;
+
+                Some(quote! { (#kind_enum::#from, #event_enum::#event) => Some(#label) })
+            })
+            .collect::<Vec<_>>();
+
+        let label = if label_arms.is_empty() {
+            quote! { None }
+        } else {
+            // Only add a catch-all when some transition has no label,
+            // otherwise it triggers an unreachable pattern warning.
+            let catch_all = if label_arms.len() < self.machine.states().0.len() * events.len() {
+                quote! { _ => None }
+            } else {
+                quote! {}
+            };
+
+            quote! {
+                match (self.from, self.event) {
+                    #(#label_arms,)*
+                    #catch_all
+                }
+            }
+        };
+
+        let must_use = must_use_transition();
+        let inline = self.machine.options.inline;
+
+        tokens.extend(quote! {
+            /// A transition taken by the machine.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct #step_struct {
+                pub from: #kind_enum,
+                pub event: #event_enum,
+                pub to: #kind_enum,
+            }
+
+            impl #step_struct {
+                /// The description of the transition given with
+                /// `Coin "..." { ... }`, if any.
+                #inline
+                pub fn label(&self) -> Option<&'static str> {
+                    #label
+                }
+            }
+
+            /// The error returned when the machine rejects an event.
+            pub type #error = ::sad_machine::InvalidTransition<#kind_enum, #event_enum>;
+
+            impl #name {
+                /// Apply `event` to the machine, returning the next state, or
+                /// `None` if the current state does not accept it.
+                #must_use
+                #inline
+                pub fn transition(&self, event: #event_enum) -> Option<#name> {
+                    #body
+                }
+
+                /// Like `transition`, but return an error carrying the current
+                /// state, the rejected event and the location of the call.
+                #inline
+                #[track_caller]
+                pub fn try_transition(&self, event: #event_enum) -> Result<#name, #error> {
+                    self.transition(event).ok_or(#error::new(self.kind(), event))
+                }
+
+                /// Like `transition`, but also return a record of the
+                /// transition that was taken.
+                #must_use
+                #inline
+                pub fn step(&self, event: #event_enum) -> Option<(#name, #step_struct)> {
+                    let next = self.transition(event)?;
+                    let step = #step_struct {
+                        from: self.kind(),
+                        event,
+                        to: next.kind(),
+                    };
+
+                    Some((next, step))
+                }
+
+                /// Apply `events` in order, stopping at the first one which is
+                /// not accepted. On failure, returns the state reached so far
+                /// and the rejected event.
+                pub fn apply_all(
+                    self,
+                    events: impl IntoIterator<Item = #event_enum>,
+                ) -> Result<#name, (#name, #event_enum)> {
+                    let mut machine = self;
+
+                    for event in events {
+                        machine = match machine.transition(event) {
+                            Some(next) => next,
+                            None => return Err((machine, event)),
+                        };
+                    }
+
+                    Ok(machine)
+                }
+            }
+        });
+    }
+}
+
+/// `replay`, reconstructing the machine from an event log.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Replay<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Replay<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+        let kind_enum = kind_enum_name(name);
+        let replay_error = replay_error_name(name);
+
+        let initial_states = &self.machine.initial_states.0;
+
+        let arms = initial_states.iter().map(|s| {
+            let state = &s.name;
+            let value = self.machine.value(state, &Provenance::init());
+
+            quote! { #kind_enum::#state => #value }
+        });
+
+        // Only add a catch-all when some state is not initial, otherwise it
+        // triggers an unreachable pattern warning.
+        let catch_all = if initial_states.len() < self.machine.states().0.len() {
+            quote! { _ => return Err(#replay_error::NotInitial(initial)) }
+        } else {
+            quote! {}
+        };
+
+        tokens.extend(quote! {
+            /// The error returned when an event log cannot be replayed.
+            pub type #replay_error = ::sad_machine::ReplayError<#kind_enum, #event_enum>;
+
+            impl #name {
+                /// Reconstruct the machine from an event log, starting in the
+                /// initial state `initial` and applying `events` in order.
+                /// Fails if `initial` is not an initial state, or with the
+                /// index of the first event which is not accepted.
+                pub fn replay(
+                    initial: #kind_enum,
+                    events: &[#event_enum],
+                ) -> Result<#name, #replay_error> {
+                    let mut machine = match initial {
+                        #(#arms,)*
+                        #catch_all
+                    };
+
+                    for (index, &event) in events.iter().enumerate() {
+                        machine = match machine.transition(event) {
+                            Some(next) => next,
+                            None => {
+                                return Err(#replay_error::InvalidEvent {
+                                    index,
+                                    kind: machine.kind(),
+                                    event,
+                                })
+                            }
+                        };
+                    }
+
+                    Ok(machine)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_dynamic_transitions_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            /// A transition taken by the machine.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileStep {
+                pub from: TurnStileKind,
+                pub event: TurnStileEvent,
+                pub to: TurnStileKind,
+            }
+
+            impl TurnStileStep {
+                /// The description of the transition given with
+                /// `Coin "..." { ... }`, if any.
+                pub fn label(&self) -> Option<&'static str> {
+                    None
+                }
+            }
+
+            /// The error returned when the machine rejects an event.
+            pub type TurnStileError = ::sad_machine::InvalidTransition<TurnStileKind, TurnStileEvent>;
+
+            impl TurnStile {
+                /// Apply `event` to the machine, returning the next state, or
+                /// `None` if the current state does not accept it.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn transition(&self, event: TurnStileEvent) -> Option<TurnStile> {
+                    match (self, event) {
+                        (TurnStile::Locked(state), TurnStileEvent::Coin) => Some(state.coin()),
+                        _ => None
+                    }
+                }
+
+                /// Like `transition`, but return an error carrying the current
+                /// state, the rejected event and the location of the call.
+                #[track_caller]
+                pub fn try_transition(&self, event: TurnStileEvent) -> Result<TurnStile, TurnStileError> {
+                    self.transition(event).ok_or(TurnStileError::new(self.kind(), event))
+                }
+
+                /// Like `transition`, but also return a record of the
+                /// transition that was taken.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn step(&self, event: TurnStileEvent) -> Option<(TurnStile, TurnStileStep)> {
+                    let next = self.transition(event)?;
+                    let step = TurnStileStep {
+                        from: self.kind(),
+                        event,
+                        to: next.kind(),
+                    };
+
+                    Some((next, step))
+                }
+
+                /// Apply `events` in order, stopping at the first one which is
+                /// not accepted. On failure, returns the state reached so far
+                /// and the rejected event.
+                pub fn apply_all(
+                    self,
+                    events: impl IntoIterator<Item = TurnStileEvent>,
+                ) -> Result<TurnStile, (TurnStile, TurnStileEvent)> {
+                    let mut machine = self;
+
+                    for event in events {
+                        machine = match machine.transition(event) {
+                            Some(next) => next,
+                            None => return Err((machine, event)),
+                        };
+                    }
+
+                    Ok(machine)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        DynamicTransitions { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_dynamic_transitions_to_tokens_table() {
+        let mut machine: Machine = syn::parse2(quote! {
+            #[dispatch(table)]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+
+                Hooks = Counter
+            }
+        })
+        .unwrap();
+        machine.options.tracing = true;
+
+        let mut tokens = TokenStream::new();
+        DynamicTransitions { machine: &machine }.to_tokens(&mut tokens);
+
+        let left = quote! {
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn transition(&self, event: TurnStileEvent) -> Option<TurnStile> {
+                let (kind, provenance) = TurnStile::TRANSITION_TABLE[self.kind() as usize][event as usize]?;
+                let to = TurnStile::from_table(kind, provenance);
+                ::tracing::debug!(
+                    machine = "TurnStile",
+                    from = self.name(),
+                    event = event.name(),
+                    to = to.name(),
+                    "transition"
+                );
+                <Counter as TurnStileHooks>::on_transition(self.kind(), event, &to);
+                Some(to)
+            }
+        };
+
+        assert!(format!("{}", tokens).contains(&format!("{}", left)));
+    }
+
+    #[test]
+    fn test_dynamic_transitions_to_tokens_without_provenance() {
+        let machine: Machine = syn::parse2(quote! {
+            #[provenance(none)]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let mut tokens = TokenStream::new();
+        DynamicTransitions { machine: &machine }.to_tokens(&mut tokens);
+
+        let left = quote! {
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn transition(&self, event: TurnStileEvent) -> Option<TurnStile> {
+                let to = match (self, event) {
+                    (TurnStile::Locked, TurnStileEvent::Coin) => TurnStile::Unlocked,
+                    (TurnStile::Unlocked, TurnStileEvent::Push) => TurnStile::Locked,
+                    _ => return None
+                };
+                Some(to)
+            }
+        };
+
+        assert!(format!("{}", tokens).contains(&format!("{}", left)));
+    }
+
+    #[test]
+    fn test_dynamic_transitions_to_tokens_labels() {
+        let machine: Machine = syn::parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin "customer inserted a coin" { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let mut tokens = TokenStream::new();
+        DynamicTransitions { machine: &machine }.to_tokens(&mut tokens);
+
+        let left = quote! {
+            impl TurnStileStep {
+                /// The description of the transition given with
+                /// `Coin "..." { ... }`, if any.
+                pub fn label(&self) -> Option<&'static str> {
+                    match (self.from, self.event) {
+                        (TurnStileKind::Locked, TurnStileEvent::Coin) => Some("customer inserted a coin"),
+                        _ => None
+                    }
+                }
+            }
+        };
+
+        assert!(format!("{}", tokens).contains(&format!("{}", left)));
+    }
+
+    #[test]
+    fn test_replay_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            /// The error returned when an event log cannot be replayed.
+            pub type TurnStileReplayError = ::sad_machine::ReplayError<TurnStileKind, TurnStileEvent>;
+
+            impl TurnStile {
+                /// Reconstruct the machine from an event log, starting in the
+                /// initial state `initial` and applying `events` in order.
+                /// Fails if `initial` is not an initial state, or with the
+                /// index of the first event which is not accepted.
+                pub fn replay(
+                    initial: TurnStileKind,
+                    events: &[TurnStileEvent],
+                ) -> Result<TurnStile, TurnStileReplayError> {
+                    let mut machine = match initial {
+                        TurnStileKind::Locked => TurnStile::Locked(LockedState::FromInit),
+                        _ => return Err(TurnStileReplayError::NotInitial(initial))
+                    };
+
+                    for (index, &event) in events.iter().enumerate() {
+                        machine = match machine.transition(event) {
+                            Some(next) => next,
+                            None => {
+                                return Err(TurnStileReplayError::InvalidEvent {
+                                    index,
+                                    kind: machine.kind(),
+                                    event,
+                                })
+                            }
+                        };
+                    }
+
+                    Ok(machine)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Replay { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
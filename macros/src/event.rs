@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    Error, Ident, LitStr, Token,
+};
+
+use crate::{
+    ident::{snake_case, stem, unraw},
+    label::{parse_entry, Labels},
+    options::InlineMode,
+};
+
+/// The complete vocabulary of events of a machine, if declared.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Events(pub Vec<Event>);
+
+impl Events {
+    /// Whether the next tokens are an `Events { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "Events")
+    }
+
+    /// Parse the block, recording the names of the renamed events in
+    /// `labels`.
+    ///
+    /// example events tokens:
+    ///
+    /// ```text
+    /// Events { Coin, Push, Refund }
+    /// Events { "wait-for-ack" as WaitForAck, Done }
+    /// ```
+    pub fn parse_labeled(
+        input: ParseStream<'_>,
+        labels: &mut HashMap<String, String>,
+    ) -> Result<Self> {
+        // `Events { ... }`
+        //  ^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "Events" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `Events { ... }` block",
+            ));
+        }
+
+        // `Events { ... }`
+        //           ^^^
+        let block_events;
+        braced!(block_events in input);
+
+        // `Events { Coin, Push, Refund }`
+        //           ^^^^  ^^^^  ^^^^^^
+        let punctuated_events: Punctuated<(Ident, Option<LitStr>), Token![,]> =
+            block_events.parse_terminated(parse_entry)?;
+
+        let mut events: Vec<Event> = Vec::new();
+
+        for (name, label) in punctuated_events {
+            let event = Event { name };
+
+            if events.contains(&event) {
+                return Err(Error::new(
+                    event.name.span(),
+                    format!("duplicate event `{}`", event.name),
+                ));
+            }
+
+            if let Some(label) = label {
+                let _ = labels.insert(unraw(&event.name), label.value());
+            }
+
+            events.push(event);
+        }
+
+        Ok(Events(events))
+    }
+}
+
+impl Parse for Events {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        Events::parse_labeled(input, &mut HashMap::new())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Event {
+    pub name: Ident,
+}
+
+impl Parse for Event {
+    /// example event tokens:
+    ///
+    /// ```text
+    /// Push
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let name = input.parse()?;
+
+        Ok(Event { name })
+    }
+}
+
+/// The name of the transition method taking `event`, e.g. `turn_key`.
+pub(crate) fn event_fn_name(event: &Ident) -> Ident {
+    snake_case(event)
+}
+
+/// The name of the enum listing the events accepted by a machine.
+pub(crate) fn event_enum_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Event", unraw(machine)), machine.span())
+}
+
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct EventKinds<'a> {
+    pub enum_name: &'a Ident,
+    pub labels: &'a Labels,
+    pub events: &'a [Event],
+    pub inline: InlineMode,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for EventKinds<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let event_enum = event_enum_name(self.enum_name);
+        let names = self.events.iter().map(|e| &e.name);
+
+        let name_arms = self.events.iter().map(|e| {
+            let name = &e.name;
+            let string = self.labels.event(name);
+
+            quote! { #event_enum::#name => #string }
+        });
+
+        let inline = self.inline;
+
+        tokens.extend(quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum #event_enum {
+                #(#names),*
+            }
+
+            impl #event_enum {
+                #inline
+                pub fn name(self) -> &'static str {
+                    match self {
+                        #(#name_arms),*
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// The name of the zero-sized type standing for a single event.
+pub(crate) fn event_marker_name(event: &Ident) -> Ident {
+    Ident::new(&format!("{}Event", stem(event)), event.span())
+}
+
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct EventMarkers<'a> {
+    pub enum_name: &'a Ident,
+    pub events: &'a [Event],
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for EventMarkers<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let event_enum = event_enum_name(self.enum_name);
+
+        for e in self.events {
+            let name = &e.name;
+            let marker = event_marker_name(name);
+
+            tokens.extend(quote! {
+                #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+                pub struct #marker;
+
+                impl From<#marker> for #event_enum {
+                    fn from(_: #marker) -> #event_enum {
+                        #event_enum::#name
+                    }
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_event_parse() {
+        let left: Event = syn::parse2(quote! { Push }).unwrap();
+        let right = Event {
+            name: parse_quote! { Push },
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_events_parse() {
+        let left: Events = syn::parse2(quote! { Events { Coin, Push } }).unwrap();
+        let right = Events(vec![
+            Event {
+                name: parse_quote! { Coin },
+            },
+            Event {
+                name: parse_quote! { Push },
+            },
+        ]);
+
+        assert_eq!(left, right);
+
+        let result: Result<Events> = syn::parse2(quote! { Events { Coin, Coin } });
+
+        assert_eq!(result.unwrap_err().to_string(), "duplicate event `Coin`");
+    }
+
+    #[test]
+    fn test_event_kinds_to_tokens() {
+        let events = vec![
+            Event {
+                name: parse_quote! { Coin },
+            },
+            Event {
+                name: parse_quote! { Push },
+            },
+        ];
+
+        let event_kinds = EventKinds {
+            enum_name: &parse_quote! { TurnStile },
+            labels: &Labels::default(),
+            events: &events,
+            inline: InlineMode::Auto,
+        };
+
+        let left = quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum TurnStileEvent {
+                Coin,
+                Push
+            }
+
+            impl TurnStileEvent {
+                pub fn name(self) -> &'static str {
+                    match self {
+                        TurnStileEvent::Coin => "Coin",
+                        TurnStileEvent::Push => "Push"
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        event_kinds.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_event_markers_to_tokens() {
+        let events = vec![Event {
+            name: parse_quote! { Coin },
+        }];
+
+        let event_markers = EventMarkers {
+            enum_name: &parse_quote! { TurnStile },
+            events: &events,
+        };
+
+        let left = quote! {
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct CoinEvent;
+
+            impl From<CoinEvent> for TurnStileEvent {
+                fn from(_: CoinEvent) -> TurnStileEvent {
+                    TurnStileEvent::Coin
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        event_markers.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    token::Brace,
+    Error, Ident, Token,
+};
+
+use crate::{
+    event::{event_enum_name, Event},
+    ident::unraw,
+    machine::Machine,
+    options::InlineMode,
+    transition::{Transition, Transitions},
+};
+
+/// The name of the enum listing the event groups of a machine.
+pub(crate) fn event_group_enum_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}EventGroup", unraw(machine)), machine.span())
+}
+
+/// A group of events declared with `Group UserInput { ... }`, whose name can
+/// be used in place of an event to declare the same transitions for each of
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EventGroup {
+    pub name: Ident,
+    pub events: Vec<Event>,
+}
+
+impl EventGroup {
+    /// Whether the next tokens are a `Group ... { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "Group")
+            && fork.parse::<Ident>().is_ok()
+            && fork.peek(Brace)
+    }
+}
+
+impl Parse for EventGroup {
+    /// example event group tokens:
+    ///
+    /// ```text
+    /// Group UserInput { Push, Coin }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Group UserInput { Push, Coin }`
+        //  ^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "Group" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `Group ... { ... }` block",
+            ));
+        }
+
+        // `Group UserInput { Push, Coin }`
+        //        ^^^^^^^^^
+        let name: Ident = input.parse()?;
+
+        // `Group UserInput { Push, Coin }`
+        //                    ^^^^^^^^^^
+        let block_events;
+        braced!(block_events in input);
+
+        let punctuated_events: Punctuated<Event, Token![,]> =
+            block_events.parse_terminated(Event::parse)?;
+
+        let mut events: Vec<Event> = Vec::new();
+
+        for event in punctuated_events {
+            if events.contains(&event) {
+                return Err(Error::new(
+                    event.name.span(),
+                    format!("duplicate event `{}` in group `{}`", event.name, name),
+                ));
+            }
+
+            events.push(event);
+        }
+
+        if events.is_empty() {
+            return Err(Error::new(
+                name.span(),
+                format!("group `{}` is empty", name),
+            ));
+        }
+
+        Ok(EventGroup { name, events })
+    }
+}
+
+/// Replace the transitions declared for a group of `machine` with the same
+/// transitions for each event of the group, in the order the events are
+/// listed in.
+pub(crate) fn expand(machine: &mut Machine) -> Result<()> {
+    let groups = &machine.event_groups;
+
+    let mut by_name: HashMap<String, &EventGroup> = HashMap::new();
+    let mut by_event: HashMap<String, &EventGroup> = HashMap::new();
+
+    for group in groups {
+        if by_name.insert(unraw(&group.name), group).is_some() {
+            return Err(Error::new(
+                group.name.span(),
+                format!("duplicate group `{}`", group.name),
+            ));
+        }
+
+        for e in &group.events {
+            if let Some(previous) = by_event.insert(unraw(&e.name), group) {
+                return Err(Error::new(
+                    e.name.span(),
+                    format!(
+                        "event `{}` is in both groups `{}` and `{}`",
+                        e.name, previous.name, group.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(declared_events) = &machine.declared_events {
+        if let Some(e) = declared_events
+            .0
+            .iter()
+            .find(|e| by_name.contains_key(&unraw(&e.name)))
+        {
+            return Err(Error::new(
+                e.name.span(),
+                format!("event `{}` has the same name as a group", e.name),
+            ));
+        }
+    }
+
+    // The transitions declared so far, by event and source state.
+    let mut declared: HashMap<(String, String), usize> = HashMap::new();
+    let mut transitions: Vec<Transition> = Vec::new();
+
+    for t in &machine.transitions.0 {
+        let events = match by_name.get(&unraw(&t.event.name)) {
+            Some(group) => group
+                .events
+                .iter()
+                .map(|e| {
+                    // Point at the transition rather than at the group.
+                    let mut name = e.name.clone();
+                    name.set_span(t.event.name.span());
+
+                    Event { name }
+                })
+                .collect(),
+            None => vec![t.event.clone()],
+        };
+
+        for event in events {
+            let key = (unraw(&event.name), t.from.name.to_string());
+            let expanded = Transition { event, ..t.clone() };
+
+            if let Some(&previous) = declared.get(&key) {
+                return Err(expanded.clash(&transitions[previous]));
+            }
+
+            let _ = declared.insert(key, transitions.len());
+            transitions.push(expanded);
+        }
+    }
+
+    machine.transitions = Transitions(transitions);
+
+    let events = machine.events();
+
+    for group in groups {
+        for e in &group.events {
+            if !events.iter().any(|event| event.name == e.name) {
+                return Err(Error::new(
+                    e.name.span(),
+                    format!(
+                        "event `{}` of group `{}` is not an event of `{}`",
+                        e.name, group.name, machine.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `{Machine}EventGroup`, listing the groups declared with
+/// `Group ... { ... }`, and `{Machine}Event::group`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct EventGroups<'a> {
+    pub enum_name: &'a Ident,
+    pub groups: &'a [EventGroup],
+    pub events: &'a [Event],
+    pub inline: InlineMode,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for EventGroups<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let event_enum = event_enum_name(self.enum_name);
+        let group_enum = event_group_enum_name(self.enum_name);
+        let names = self.groups.iter().map(|g| &g.name);
+
+        let arms = self
+            .groups
+            .iter()
+            .flat_map(|g| g.events.iter().map(move |e| (g, e)))
+            .map(|(g, e)| {
+                let group = &g.name;
+                let event = &e.name;
+
+                quote! { #event_enum::#event => Some(#group_enum::#group) }
+            })
+            .collect::<Vec<_>>();
+
+        // Only add a catch-all when some event is in no group, otherwise it
+        // triggers an unreachable pattern warning.
+        let catch_all = if arms.len() < self.events.len() {
+            quote! { _ => None }
+        } else {
+            quote! {}
+        };
+
+        let inline = self.inline;
+
+        tokens.extend(quote! {
+            /// The groups of events declared with `Group ... { ... }`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum #group_enum {
+                #(#names),*
+            }
+
+            impl #event_enum {
+                /// The group the event belongs to, if any.
+                #inline
+                pub fn group(self) -> Option<#group_enum> {
+                    match self {
+                        #(#arms,)*
+                        #catch_all
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_event_group_parse() {
+        let left: EventGroup = parse2(quote! { Group UserInput { Push, Coin } }).unwrap();
+        let right = EventGroup {
+            name: parse_quote! { UserInput },
+            events: vec![
+                Event {
+                    name: parse_quote! { Push },
+                },
+                Event {
+                    name: parse_quote! { Coin },
+                },
+            ],
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_expand() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                Group UserInput { Push, Coin }
+
+                UserInput { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let transitions = machine
+            .transitions
+            .0
+            .iter()
+            .map(|t| format!("{} {} => {}", t.event.name, t.from.name, t.to.name))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            transitions,
+            vec![
+                "Push Locked => Unlocked",
+                "Coin Locked => Unlocked",
+                "Push Unlocked => Locked",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let cases = vec![
+            (
+                quote! {
+                    TurnStile {
+                        InitialStates { Locked }
+                        Group UserInput { Push, Coin }
+                        Group UserInput { Kick }
+
+                        UserInput { Locked => Unlocked }
+                    }
+                },
+                "duplicate group `UserInput`",
+            ),
+            (
+                quote! {
+                    TurnStile {
+                        InitialStates { Locked }
+                        Group UserInput { Push, Coin }
+                        Group Force { Push }
+
+                        UserInput { Locked => Unlocked }
+                    }
+                },
+                "event `Push` is in both groups `UserInput` and `Force`",
+            ),
+            (
+                quote! {
+                    TurnStile {
+                        InitialStates { Locked }
+                        Group UserInput { Push, Coin }
+
+                        UserInput { Locked => Unlocked }
+                        Push { Locked => Locked }
+                    }
+                },
+                "nondeterministic transition: `Push` from `Locked` leads to both `Unlocked` and `Locked`",
+            ),
+            (
+                quote! {
+                    TurnStile {
+                        InitialStates { Locked }
+                        Group UserInput { Push, Coin }
+
+                        Push { Locked => Unlocked }
+                    }
+                },
+                "event `Coin` of group `UserInput` is not an event of `TurnStile`",
+            ),
+            (
+                quote! {
+                    TurnStile {
+                        InitialStates { Locked }
+                        Group UserInput { }
+
+                        Push { Locked => Unlocked }
+                    }
+                },
+                "group `UserInput` is empty",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_event_groups_to_tokens() {
+        let groups = vec![EventGroup {
+            name: parse_quote! { UserInput },
+            events: vec![
+                Event {
+                    name: parse_quote! { Push },
+                },
+                Event {
+                    name: parse_quote! { Coin },
+                },
+            ],
+        }];
+        let events = vec![
+            Event {
+                name: parse_quote! { Push },
+            },
+            Event {
+                name: parse_quote! { Coin },
+            },
+            Event {
+                name: parse_quote! { Reset },
+            },
+        ];
+
+        let left = quote! {
+            /// The groups of events declared with `Group ... { ... }`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum TurnStileEventGroup {
+                UserInput
+            }
+
+            impl TurnStileEvent {
+                /// The group the event belongs to, if any.
+                pub fn group(self) -> Option<TurnStileEventGroup> {
+                    match self {
+                        TurnStileEvent::Push => Some(TurnStileEventGroup::UserInput),
+                        TurnStileEvent::Coin => Some(TurnStileEventGroup::UserInput),
+                        _ => None
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        EventGroups {
+            enum_name: &parse_quote! { TurnStile },
+            groups: &groups,
+            events: &events,
+            inline: InlineMode::Auto,
+        }
+        .to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,196 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{event::event_enum_name, ident::unraw, machine::Machine};
+
+/// The name of the map keyed by the events of a machine.
+pub(crate) fn event_map_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}EventMap", unraw(machine)), machine.span())
+}
+
+/// `{Machine}EventMap<T>`, holding a value for each event of the machine in
+/// an array indexed by `{Machine}Event`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct EventMap<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for EventMap<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+        let map = event_map_name(name);
+        let inline = self.machine.options.inline;
+
+        let events = self.machine.events();
+        let events = events.iter().map(|e| &e.name).collect::<Vec<_>>();
+
+        let doc = format!(
+            "A value for each event of `{}`, stored in an array indexed by `{}`.",
+            unraw(name),
+            event_enum
+        );
+
+        tokens.extend(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct #map<T>([T; #name::EVENT_COUNT]);
+
+            impl<T> #map<T> {
+                /// Every event, in the order of the values.
+                const EVENTS: [#event_enum; #name::EVENT_COUNT] = [#(#event_enum::#events),*];
+
+                /// The map holding `f(event)` for each event, called in
+                /// declaration order.
+                #inline
+                pub fn from_fn(mut f: impl FnMut(#event_enum) -> T) -> Self {
+                    #map([#(f(#event_enum::#events)),*])
+                }
+
+                /// The value for `event`.
+                #inline
+                pub fn get(&self, event: #event_enum) -> &T {
+                    &self.0[event as usize]
+                }
+
+                /// The value for `event`, mutably.
+                #inline
+                pub fn get_mut(&mut self, event: #event_enum) -> &mut T {
+                    &mut self.0[event as usize]
+                }
+
+                /// The events and their values, in declaration order.
+                #inline
+                pub fn iter(&self) -> impl Iterator<Item = (#event_enum, &T)> {
+                    Self::EVENTS.iter().copied().zip(self.0.iter())
+                }
+
+                /// The events and their values, mutably, in declaration
+                /// order.
+                #inline
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (#event_enum, &mut T)> {
+                    Self::EVENTS.iter().copied().zip(self.0.iter_mut())
+                }
+
+                /// The values, in the declaration order of their events.
+                #inline
+                pub fn values(&self) -> &[T] {
+                    &self.0
+                }
+            }
+
+            impl<T: Default> Default for #map<T> {
+                #inline
+                fn default() -> Self {
+                    #map::from_fn(|_| T::default())
+                }
+            }
+
+            impl<T> ::core::ops::Index<#event_enum> for #map<T> {
+                type Output = T;
+
+                #inline
+                fn index(&self, event: #event_enum) -> &T {
+                    self.get(event)
+                }
+            }
+
+            impl<T> ::core::ops::IndexMut<#event_enum> for #map<T> {
+                #inline
+                fn index_mut(&mut self, event: #event_enum) -> &mut T {
+                    self.get_mut(event)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_event_map_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = "A value for each event of `TurnStile`, stored in an array indexed by `TurnStileEvent`."]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileEventMap<T>([T; TurnStile::EVENT_COUNT]);
+
+            impl<T> TurnStileEventMap<T> {
+                /// Every event, in the order of the values.
+                const EVENTS: [TurnStileEvent; TurnStile::EVENT_COUNT] = [TurnStileEvent::Coin, TurnStileEvent::Push];
+
+                /// The map holding `f(event)` for each event, called in
+                /// declaration order.
+                pub fn from_fn(mut f: impl FnMut(TurnStileEvent) -> T) -> Self {
+                    TurnStileEventMap([f(TurnStileEvent::Coin), f(TurnStileEvent::Push)])
+                }
+
+                /// The value for `event`.
+                pub fn get(&self, event: TurnStileEvent) -> &T {
+                    &self.0[event as usize]
+                }
+
+                /// The value for `event`, mutably.
+                pub fn get_mut(&mut self, event: TurnStileEvent) -> &mut T {
+                    &mut self.0[event as usize]
+                }
+
+                /// The events and their values, in declaration order.
+                pub fn iter(&self) -> impl Iterator<Item = (TurnStileEvent, &T)> {
+                    Self::EVENTS.iter().copied().zip(self.0.iter())
+                }
+
+                /// The events and their values, mutably, in declaration
+                /// order.
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (TurnStileEvent, &mut T)> {
+                    Self::EVENTS.iter().copied().zip(self.0.iter_mut())
+                }
+
+                /// The values, in the declaration order of their events.
+                pub fn values(&self) -> &[T] {
+                    &self.0
+                }
+            }
+
+            impl<T: Default> Default for TurnStileEventMap<T> {
+                fn default() -> Self {
+                    TurnStileEventMap::from_fn(|_| T::default())
+                }
+            }
+
+            impl<T> ::core::ops::Index<TurnStileEvent> for TurnStileEventMap<T> {
+                type Output = T;
+
+                fn index(&self, event: TurnStileEvent) -> &T {
+                    self.get(event)
+                }
+            }
+
+            impl<T> ::core::ops::IndexMut<TurnStileEvent> for TurnStileEventMap<T> {
+                fn index_mut(&mut self, event: TurnStileEvent) -> &mut T {
+                    self.get_mut(event)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        EventMap { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
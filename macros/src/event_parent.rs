@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream, Result},
+    Error, Ident, Token,
+};
+
+use crate::{
+    event::{event_enum_name, Event},
+    ident::unraw,
+    machine::Machine,
+    options::InlineMode,
+    transition::Transition,
+};
+
+/// An event declared as a child of another with `KeypadPress : UserInput`,
+/// which is accepted wherever its parent is, unless it has a transition of
+/// its own from the same state.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EventParent {
+    pub child: Event,
+    pub parent: Event,
+}
+
+impl EventParent {
+    /// Whether the next tokens are a `... : ...` declaration.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok()
+            && fork.peek(Token![:])
+            && !fork.peek(Token![::])
+            && fork.parse::<Token![:]>().is_ok()
+            && fork.peek(Ident)
+    }
+}
+
+impl Parse for EventParent {
+    /// example event parent tokens:
+    ///
+    /// ```text
+    /// KeypadPress : UserInput
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `KeypadPress : UserInput`
+        //  ^^^^^^^^^^^
+        let child = input.parse()?;
+
+        // `KeypadPress : UserInput`
+        //              ^
+        let _: Token![:] = input.parse()?;
+
+        // `KeypadPress : UserInput`
+        //                ^^^^^^^^^
+        let parent = input.parse()?;
+
+        Ok(EventParent { child, parent })
+    }
+}
+
+/// Add to `machine` a transition for each event with a parent from every
+/// state where one of its ancestors has a transition and the event itself
+/// does not, leading to the same state as the closest ancestor's.
+pub(crate) fn expand(machine: &mut Machine) -> Result<()> {
+    let mut parents: HashMap<String, &EventParent> = HashMap::new();
+
+    for p in &machine.event_parents {
+        if let Some(previous) = parents.insert(unraw(&p.child.name), p) {
+            return Err(Error::new(
+                p.child.name.span(),
+                format!(
+                    "event `{}` already has the parent `{}`",
+                    p.child.name, previous.parent.name
+                ),
+            ));
+        }
+    }
+
+    if parents.is_empty() {
+        return Ok(());
+    }
+
+    // The ancestors of each event, from its parent up.
+    let mut ancestors: Vec<(&EventParent, Vec<String>)> = Vec::new();
+
+    for p in &machine.event_parents {
+        let child = unraw(&p.child.name);
+        let mut chain = Vec::new();
+        let mut event = unraw(&p.parent.name);
+
+        loop {
+            if event == child {
+                return Err(Error::new(
+                    p.child.name.span(),
+                    format!("event `{}` cannot be its own ancestor", p.child.name),
+                ));
+            }
+
+            chain.push(event.clone());
+
+            match parents.get(&event) {
+                Some(next) => event = unraw(&next.parent.name),
+                None => break,
+            }
+        }
+
+        ancestors.push((p, chain));
+    }
+
+    let mut inherited: Vec<Transition> = Vec::new();
+
+    for (p, chain) in &ancestors {
+        let child = unraw(&p.child.name);
+
+        // The states the event already has a transition from.
+        let mut handled: HashSet<String> = machine
+            .transitions
+            .0
+            .iter()
+            .filter(|t| unraw(&t.event.name) == child)
+            .map(|t| t.from.name.to_string())
+            .collect();
+
+        for ancestor in chain {
+            for t in &machine.transitions.0 {
+                if unraw(&t.event.name) == *ancestor && handled.insert(t.from.name.to_string()) {
+                    inherited.push(Transition {
+                        event: p.child.clone(),
+                        ..t.clone()
+                    });
+                }
+            }
+        }
+    }
+
+    machine.transitions.0.extend(inherited);
+
+    let events = machine.events();
+
+    for p in &machine.event_parents {
+        for e in [&p.child, &p.parent] {
+            if !events.iter().any(|event| event.name == e.name) {
+                return Err(Error::new(
+                    e.name.span(),
+                    format!(
+                        "event `{}` of `{} : {}` is not an event of `{}`",
+                        e.name, p.child.name, p.parent.name, machine.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `{Machine}Event::parent` and `{Machine}Event::is_a`, for the machines
+/// declaring parents of events with `... : ...`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct EventParents<'a> {
+    pub enum_name: &'a Ident,
+    pub parents: &'a [EventParent],
+    pub events: &'a [Event],
+    pub inline: InlineMode,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for EventParents<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let event_enum = event_enum_name(self.enum_name);
+
+        let arms = self
+            .parents
+            .iter()
+            .map(|p| {
+                let child = &p.child.name;
+                let parent = &p.parent.name;
+
+                quote! { #event_enum::#child => Some(#event_enum::#parent) }
+            })
+            .collect::<Vec<_>>();
+
+        // Only add a catch-all when some event has no parent, otherwise it
+        // triggers an unreachable pattern warning.
+        let catch_all = if arms.len() < self.events.len() {
+            quote! { _ => None }
+        } else {
+            quote! {}
+        };
+
+        let inline = self.inline;
+
+        tokens.extend(quote! {
+            impl #event_enum {
+                /// The event declared as the parent of this one with
+                /// `... : ...`, if any.
+                #inline
+                pub fn parent(self) -> Option<#event_enum> {
+                    match self {
+                        #(#arms,)*
+                        #catch_all
+                    }
+                }
+
+                /// Whether the event is `ancestor` or one of its
+                /// descendants.
+                #inline
+                pub fn is_a(self, ancestor: #event_enum) -> bool {
+                    let mut event = Some(self);
+
+                    while let Some(e) = event {
+                        if e == ancestor {
+                            return true;
+                        }
+
+                        event = e.parent();
+                    }
+
+                    false
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_event_parent_parse() {
+        let left: EventParent = parse2(quote! { KeypadPress : UserInput }).unwrap();
+        let right = EventParent {
+            child: Event {
+                name: parse_quote! { KeypadPress },
+            },
+            parent: Event {
+                name: parse_quote! { UserInput },
+            },
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_expand() {
+        let machine: Machine = parse2(quote! {
+            Kiosk {
+                InitialStates { Idle }
+                KeypadPress : UserInput
+                Enter : KeypadPress
+
+                UserInput { Idle => Active  Active => Active }
+                KeypadPress { Active => Typing }
+                Enter { Typing => Idle }
+                Timeout { Active, Typing => Idle }
+            }
+        })
+        .unwrap();
+
+        let transitions = machine
+            .transitions
+            .0
+            .iter()
+            .map(|t| format!("{} {} => {}", t.event.name, t.from.name, t.to.name))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            transitions,
+            vec![
+                "UserInput Idle => Active",
+                "UserInput Active => Active",
+                "KeypadPress Active => Typing",
+                "Enter Typing => Idle",
+                "Timeout Active => Idle",
+                "Timeout Typing => Idle",
+                "KeypadPress Idle => Active",
+                "Enter Active => Typing",
+                "Enter Idle => Active",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let cases = vec![
+            (
+                quote! {
+                    Kiosk {
+                        InitialStates { Idle }
+                        KeypadPress : UserInput
+                        KeypadPress : Touch
+
+                        UserInput { Idle => Active }
+                        Touch { Active => Idle }
+                    }
+                },
+                "event `KeypadPress` already has the parent `UserInput`",
+            ),
+            (
+                quote! {
+                    Kiosk {
+                        InitialStates { Idle }
+                        KeypadPress : UserInput
+                        UserInput : KeypadPress
+
+                        UserInput { Idle => Active }
+                        KeypadPress { Active => Idle }
+                    }
+                },
+                "event `KeypadPress` cannot be its own ancestor",
+            ),
+            (
+                quote! {
+                    Kiosk {
+                        InitialStates { Idle }
+                        KeypadPress : Touch
+
+                        UserInput { Idle => Active }
+                        KeypadPress { Active => Idle }
+                    }
+                },
+                "event `Touch` of `KeypadPress : Touch` is not an event of `Kiosk`",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_event_parents_to_tokens() {
+        let parents = vec![EventParent {
+            child: Event {
+                name: parse_quote! { KeypadPress },
+            },
+            parent: Event {
+                name: parse_quote! { UserInput },
+            },
+        }];
+        let events = vec![
+            Event {
+                name: parse_quote! { UserInput },
+            },
+            Event {
+                name: parse_quote! { KeypadPress },
+            },
+        ];
+
+        let left = quote! {
+            impl KioskEvent {
+                /// The event declared as the parent of this one with
+                /// `... : ...`, if any.
+                pub fn parent(self) -> Option<KioskEvent> {
+                    match self {
+                        KioskEvent::KeypadPress => Some(KioskEvent::UserInput),
+                        _ => None
+                    }
+                }
+
+                /// Whether the event is `ancestor` or one of its
+                /// descendants.
+                pub fn is_a(self, ancestor: KioskEvent) -> bool {
+                    let mut event = Some(self);
+
+                    while let Some(e) = event {
+                        if e == ancestor {
+                            return true;
+                        }
+
+                        event = e.parent();
+                    }
+
+                    false
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        EventParents {
+            enum_name: &parse_quote! { Kiosk },
+            parents: &parents,
+            events: &events,
+            inline: InlineMode::Auto,
+        }
+        .to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,81 @@
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    Error, Ident, Token,
+};
+
+use crate::state::State;
+
+/// The states in which the machine is allowed to stop, which don't need any
+/// outgoing transition.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct FinalStates(pub Vec<State>);
+
+impl FinalStates {
+    /// Whether the next tokens are a `FinalStates { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "FinalStates")
+    }
+}
+
+impl Parse for FinalStates {
+    /// example final states tokens:
+    ///
+    /// ```text
+    /// FinalStates { Broken }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `FinalStates { ... }`
+        //  ^^^^^^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "FinalStates" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `FinalStates { ... }` block",
+            ));
+        }
+
+        // `FinalStates { ... }`
+        //                ^^^
+        let block_final_states;
+        braced!(block_final_states in input);
+
+        // `FinalStates { Broken }`
+        //                ^^^^^^
+        let punctuated_final_states: Punctuated<State, Token![,]> =
+            block_final_states.parse_terminated(State::parse)?;
+
+        Ok(FinalStates(punctuated_final_states.into_iter().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_final_states_parse() {
+        let left: FinalStates = syn::parse2(quote! {
+            FinalStates { Broken, Open }
+        })
+        .unwrap();
+
+        let right = FinalStates(vec![
+            State {
+                name: parse_quote! { Broken },
+            },
+            State {
+                name: parse_quote! { Open },
+            },
+        ]);
+
+        assert_eq!(left, right);
+    }
+}
@@ -0,0 +1,105 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use std::{env, fs, path::PathBuf};
+use syn::{
+    parse::{Parse, ParseStream, Result},
+    Error, LitStr,
+};
+
+use crate::machine::Machines;
+
+/// The machines declared in a file, for `state_machine_from_file!`.
+#[derive(Debug)]
+pub(crate) struct MachinesFromFile {
+    /// The absolute path of the file, so that the crate is rebuilt when it
+    /// changes.
+    pub path: String,
+    pub machines: Machines,
+}
+
+impl Parse for MachinesFromFile {
+    /// example file tokens:
+    ///
+    /// ```text
+    /// "machines/lock.sm"
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let lit: LitStr = input.parse()?;
+
+        let path = env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(lit.value());
+
+        let source = fs::read_to_string(&path).map_err(|e| {
+            Error::new(
+                lit.span(),
+                format!("cannot read `{}`: {}", path.display(), e),
+            )
+        })?;
+
+        let tokens: TokenStream = source.parse().map_err(|e| {
+            Error::new(
+                lit.span(),
+                format!("cannot tokenize `{}`: {}", path.display(), e),
+            )
+        })?;
+
+        // Errors in the file can only point at the path.
+        let machines: Machines = syn::parse2(tokens)
+            .map_err(|e| Error::new(lit.span(), format!("in `{}`: {}", path.display(), e)))?;
+
+        Ok(MachinesFromFile {
+            path: path.display().to_string(),
+            machines,
+        })
+    }
+}
+
+impl ToTokens for MachinesFromFile {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let path = &self.path;
+        let machines = &self.machines;
+
+        tokens.extend(quote! {
+            const _: &str = include_str!(#path);
+
+            #machines
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_machines_from_file_parse() {
+        let dir = env::temp_dir().join("sad_machine_test_machines_from_file_parse");
+        fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("turn_stile.sm");
+        fs::write(
+            &file,
+            "TurnStile {\n    InitialStates { Locked }\n    Coin { Locked => Unlocked }\n    Push { Unlocked => Locked }\n}\n",
+        )
+        .unwrap();
+
+        let path = file.to_str().unwrap();
+        let left: MachinesFromFile = parse2(quote! { #path }).unwrap();
+
+        assert_eq!(left.path, path);
+        assert_eq!(left.machines.0[0].name, "TurnStile");
+
+        fs::write(&file, "TurnStile { }").unwrap();
+        let result: Result<MachinesFromFile> = parse2(quote! { #path });
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with(&format!("in `{}`: machine `TurnStile` is empty", path)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
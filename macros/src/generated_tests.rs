@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{
+    event::{event_enum_name, event_fn_name},
+    ident::{snake_case, unraw},
+    machine::Machine,
+    options::ProvenanceMode,
+    provenance::{provenance_enum_name, Provenance},
+    state::{kind_enum_name, state_enum_name},
+};
+
+/// The name of the test module generated for a machine by
+/// `#[generated_tests]`.
+pub(crate) fn generated_tests_name(machine: &Ident) -> Ident {
+    Ident::new(
+        &format!("{}_generated_tests", unraw(&snake_case(machine))),
+        machine.span(),
+    )
+}
+
+/// `#[cfg(test)] mod {machine}_generated_tests`, generated with
+/// `#[generated_tests]`, which checks at test time that the generated code
+/// agrees with the diagram: the constructors build the initial states, every
+/// transition method and `transition` land on the declared target, the other
+/// events are rejected and snapshots, as well as packing with `#[packed]`,
+/// restore every state. With `#[dispatch(table)]` or `#[provenance(none)]`
+/// there are no transition methods to check, and neither are there for the
+/// transitions taken with `OnInvalid => ...`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct GeneratedTests<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for GeneratedTests<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let module = generated_tests_name(name);
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+        let provenance_enum = provenance_enum_name(name);
+        let init = Provenance::init().variant();
+        let provenance = self.machine.options.provenance != ProvenanceMode::None;
+
+        let initial_states = self.machine.initial_states.0.iter().map(|s| {
+            let state = &s.name;
+            let fn_name = snake_case(&s.name);
+
+            let provenance = if provenance {
+                Some(quote! {
+                    assert_eq!(#name::#fn_name().provenance(), #provenance_enum::#init);
+                })
+            } else {
+                None
+            };
+
+            quote! {
+                assert_eq!(#name::#fn_name().kind(), #kind_enum::#state);
+                #provenance
+            }
+        });
+
+        let provenances = self
+            .machine
+            .state_provenances()
+            .into_iter()
+            .map(|(s, provenances)| (s.name.to_string(), provenances))
+            .collect::<HashMap<_, _>>();
+
+        let methods = self.machine.has_transition_methods();
+        let invalid_transitions = self.machine.invalid_transitions();
+
+        // The `OnInvalid => ...` transitions have no methods.
+        let declared = self.machine.transitions.0.iter().map(|t| (t, methods));
+        let invalid = invalid_transitions.iter().map(|t| (t, false));
+
+        let transitions = declared.chain(invalid).flat_map(|(t, methods)| {
+            let from = &t.from.name;
+            let from_enum = state_enum_name(&t.from.name);
+            let machine = self.machine;
+            let event = &t.event.name;
+            let event_fn = event_fn_name(&t.event.name);
+            let to = &t.to.name;
+            let completed = machine.completed(&t.to).name;
+            let kind_enum = &kind_enum;
+            let event_enum = &event_enum;
+
+            // `transition` goes on to take the completion transitions, which
+            // the transition methods leave to `settle`.
+            let settle = if completed == *to {
+                None
+            } else {
+                Some(quote! { .settle() })
+            };
+
+            provenances[&t.from.name.to_string()].iter().map(move |p| {
+                let variant = p.variant();
+
+                // Without transition methods, only `transition` is checked.
+                if !methods {
+                    let value = machine.value(from, p);
+
+                    return quote! {
+                        assert_eq!(
+                            #value
+                                .transition(#event_enum::#event)
+                                .map(|to| to.kind()),
+                            Some(#kind_enum::#completed)
+                        );
+                    };
+                }
+
+                quote! {
+                    assert_eq!(#from_enum::#variant.#event_fn().kind(), #kind_enum::#to);
+                    assert_eq!(
+                        #name::#from(#from_enum::#variant).transition(#event_enum::#event),
+                        Some(#from_enum::#variant.#event_fn()#settle)
+                    );
+                }
+            })
+        });
+
+        let dynamic_transitions = self.machine.dynamic_transitions();
+        let accepted = dynamic_transitions.0.iter().map(|t| {
+            let from = &t.from.name;
+            let event = &t.event.name;
+
+            quote! { (#kind_enum::#from, #event_enum::#event) }
+        });
+
+        let packed = if self.machine.options.packed {
+            Some(quote! {
+                #[test]
+                fn packed() {
+                    for (i, machine) in #name::ALL.iter().enumerate() {
+                        assert_eq!(usize::from(machine.pack().to_bits()), i);
+                        assert_eq!(&machine.pack().unpack(), machine);
+                    }
+                }
+            })
+        } else {
+            None
+        };
+
+        // The tests call the methods of the deprecated transitions too.
+        let allow = if self
+            .machine
+            .transitions
+            .0
+            .iter()
+            .any(|t| t.deprecated.is_some())
+        {
+            Some(quote! { #[allow(deprecated)] })
+        } else {
+            None
+        };
+
+        tokens.extend(quote! {
+            #[cfg(test)]
+            mod #module {
+                use super::*;
+
+                #[test]
+                fn initial_states() {
+                    #(#initial_states)*
+                }
+
+                #[test]
+                #allow
+                fn transitions() {
+                    #(#transitions)*
+                }
+
+                #[test]
+                fn rejected_events() {
+                    let accepted: &[(#kind_enum, #event_enum)] = &[#(#accepted),*];
+
+                    for machine in #name::ALL.iter() {
+                        for &event in <#name as ::sad_machine::StateMachine>::EVENTS {
+                            assert_eq!(
+                                machine.transition(event).is_some(),
+                                accepted.contains(&(machine.kind(), event)),
+                            );
+                        }
+                    }
+                }
+
+                #[test]
+                fn snapshots() {
+                    for machine in #name::ALL.iter() {
+                        assert_eq!(#name::restore(machine.snapshot()).as_ref(), Ok(machine));
+                    }
+                }
+
+                #packed
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_generated_tests_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[cfg(test)]
+            mod turn_stile_generated_tests {
+                use super::*;
+
+                #[test]
+                fn initial_states() {
+                    assert_eq!(TurnStile::locked().kind(), TurnStileKind::Locked);
+                    assert_eq!(TurnStile::locked().provenance(), TurnStileProvenance::FromInit);
+                }
+
+                #[test]
+                fn transitions() {
+                    assert_eq!(LockedState::FromPush.coin().kind(), TurnStileKind::Unlocked);
+                    assert_eq!(
+                        TurnStile::Locked(LockedState::FromPush).transition(TurnStileEvent::Coin),
+                        Some(LockedState::FromPush.coin())
+                    );
+                    assert_eq!(LockedState::FromInit.coin().kind(), TurnStileKind::Unlocked);
+                    assert_eq!(
+                        TurnStile::Locked(LockedState::FromInit).transition(TurnStileEvent::Coin),
+                        Some(LockedState::FromInit.coin())
+                    );
+                    assert_eq!(UnlockedState::FromCoin.push().kind(), TurnStileKind::Locked);
+                    assert_eq!(
+                        TurnStile::Unlocked(UnlockedState::FromCoin).transition(TurnStileEvent::Push),
+                        Some(UnlockedState::FromCoin.push())
+                    );
+                }
+
+                #[test]
+                fn rejected_events() {
+                    let accepted: &[(TurnStileKind, TurnStileEvent)] = &[
+                        (TurnStileKind::Locked, TurnStileEvent::Coin),
+                        (TurnStileKind::Unlocked, TurnStileEvent::Push)
+                    ];
+
+                    for machine in TurnStile::ALL.iter() {
+                        for &event in <TurnStile as ::sad_machine::StateMachine>::EVENTS {
+                            assert_eq!(
+                                machine.transition(event).is_some(),
+                                accepted.contains(&(machine.kind(), event)),
+                            );
+                        }
+                    }
+                }
+
+                #[test]
+                fn snapshots() {
+                    for machine in TurnStile::ALL.iter() {
+                        assert_eq!(TurnStile::restore(machine.snapshot()).as_ref(), Ok(machine));
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        GeneratedTests { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,256 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream, Result},
+    Error, Ident, Path, Token,
+};
+
+use crate::{event::event_enum_name, ident::unraw, state::kind_enum_name};
+
+/// The name of the trait implemented by the hooks of a machine.
+pub(crate) fn hooks_trait_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Hooks", unraw(machine)), machine.span())
+}
+
+/// The name of the trait implemented by the async hooks of a machine.
+pub(crate) fn async_hooks_trait_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}AsyncHooks", unraw(machine)), machine.span())
+}
+
+/// The type named by `Hooks = ...`, whose hooks are called by the
+/// transitions.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Hooks(pub Path);
+
+impl Hooks {
+    /// Whether the next tokens are a `Hooks = ...` declaration.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "Hooks") && fork.peek(Token![=])
+    }
+}
+
+impl Parse for Hooks {
+    /// example hooks tokens:
+    ///
+    /// ```text
+    /// Hooks = LockHooks
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Hooks = MyHooks`
+        //  ^^^^^
+        let name: Ident = input.parse()?;
+
+        if name != "Hooks" {
+            return Err(Error::new(name.span(), "expected `Hooks = ...`"));
+        }
+
+        // `Hooks = MyHooks`
+        //        ^
+        let _: Token![=] = input.parse()?;
+
+        // `Hooks = MyHooks`
+        //          ^^^^^^^
+        Ok(Hooks(input.parse()?))
+    }
+}
+
+/// The `{Machine}Hooks` trait, whose methods the transitions call on the type
+/// named by `Hooks = ...`. The methods do nothing by default, so that the
+/// calls compile to nothing unless they are overridden.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct HooksTrait<'a> {
+    pub enum_name: &'a Ident,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for HooksTrait<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = self.enum_name;
+        let trait_name = hooks_trait_name(name);
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+
+        tokens.extend(quote! {
+            /// Hooks called by the transitions of the machine on the type
+            /// declared with `Hooks = ...`.
+            pub trait #trait_name {
+                /// Called on every transition, with the state left, the event
+                /// and the state entered.
+                fn on_transition(_from: #kind_enum, _event: #event_enum, _to: &#name) {}
+            }
+        });
+    }
+}
+
+/// The `{Machine}AsyncHooks` trait and `transition_async`, generated for
+/// machines with `#[async_hooks]` so that the actions taken on transitions can
+/// be awaited.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct AsyncHooks<'a> {
+    pub enum_name: &'a Ident,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for AsyncHooks<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = self.enum_name;
+        let trait_name = async_hooks_trait_name(name);
+        let event_enum = event_enum_name(name);
+
+        tokens.extend(quote! {
+            /// Async actions awaited by `transition_async`, in the order
+            /// `on_exit`, `on_transition`, `on_entry`.
+            pub trait #trait_name {
+                /// Called before leaving the state `from` on `event`.
+                fn on_exit(
+                    &mut self,
+                    _from: &#name,
+                    _event: #event_enum,
+                ) -> impl ::core::future::Future<Output = ()> {
+                    async {}
+                }
+
+                /// Called between leaving `from` and entering `to`.
+                fn on_transition(
+                    &mut self,
+                    _from: &#name,
+                    _event: #event_enum,
+                    _to: &#name,
+                ) -> impl ::core::future::Future<Output = ()> {
+                    async {}
+                }
+
+                /// Called after entering the state `to` on `event`.
+                fn on_entry(
+                    &mut self,
+                    _event: #event_enum,
+                    _to: &#name,
+                ) -> impl ::core::future::Future<Output = ()> {
+                    async {}
+                }
+            }
+
+            impl #name {
+                /// Like `transition`, but awaiting the actions of `hooks`.
+                /// Returns `None` without calling them if the current state
+                /// does not accept `event`.
+                pub async fn transition_async<H: #trait_name>(
+                    &self,
+                    event: #event_enum,
+                    hooks: &mut H,
+                ) -> Option<#name> {
+                    let to = self.transition(event)?;
+
+                    hooks.on_exit(self, event).await;
+                    hooks.on_transition(self, event, &to).await;
+                    hooks.on_entry(event, &to).await;
+
+                    Some(to)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_hooks_parse() {
+        let left: Hooks = syn::parse2(quote! { Hooks = hooks::LockHooks }).unwrap();
+        let right = Hooks(parse_quote! { hooks::LockHooks });
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_hooks_trait_to_tokens() {
+        let left = quote! {
+            /// Hooks called by the transitions of the machine on the type
+            /// declared with `Hooks = ...`.
+            pub trait TurnStileHooks {
+                /// Called on every transition, with the state left, the event
+                /// and the state entered.
+                fn on_transition(_from: TurnStileKind, _event: TurnStileEvent, _to: &TurnStile) {}
+            }
+        };
+
+        let mut right = TokenStream::new();
+        HooksTrait {
+            enum_name: &parse_quote! { TurnStile },
+        }
+        .to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_async_hooks_to_tokens() {
+        let left = quote! {
+            /// Async actions awaited by `transition_async`, in the order
+            /// `on_exit`, `on_transition`, `on_entry`.
+            pub trait TurnStileAsyncHooks {
+                /// Called before leaving the state `from` on `event`.
+                fn on_exit(
+                    &mut self,
+                    _from: &TurnStile,
+                    _event: TurnStileEvent,
+                ) -> impl ::core::future::Future<Output = ()> {
+                    async {}
+                }
+
+                /// Called between leaving `from` and entering `to`.
+                fn on_transition(
+                    &mut self,
+                    _from: &TurnStile,
+                    _event: TurnStileEvent,
+                    _to: &TurnStile,
+                ) -> impl ::core::future::Future<Output = ()> {
+                    async {}
+                }
+
+                /// Called after entering the state `to` on `event`.
+                fn on_entry(
+                    &mut self,
+                    _event: TurnStileEvent,
+                    _to: &TurnStile,
+                ) -> impl ::core::future::Future<Output = ()> {
+                    async {}
+                }
+            }
+
+            impl TurnStile {
+                /// Like `transition`, but awaiting the actions of `hooks`.
+                /// Returns `None` without calling them if the current state
+                /// does not accept `event`.
+                pub async fn transition_async<H: TurnStileAsyncHooks>(
+                    &self,
+                    event: TurnStileEvent,
+                    hooks: &mut H,
+                ) -> Option<TurnStile> {
+                    let to = self.transition(event)?;
+
+                    hooks.on_exit(self, event).await;
+                    hooks.on_transition(self, event, &to).await;
+                    hooks.on_entry(event, &to).await;
+
+                    Some(to)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        AsyncHooks {
+            enum_name: &parse_quote! { TurnStile },
+        }
+        .to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,137 @@
+use convert_case::{Case, Casing};
+use proc_macro2::{Ident, Span};
+use syn::ext::IdentExt;
+
+/// The keywords of every edition, which can only be used as identifiers in
+/// their raw form.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// The name of `ident` without its `r#` prefix, to build other identifiers
+/// and strings from.
+pub(crate) fn unraw(ident: &Ident) -> String {
+    ident.unraw().to_string()
+}
+
+/// The name of `ident` to derive the names of other items from: without its
+/// `r#` prefix, nor the trailing underscore of the keywords which cannot be
+/// raw identifiers, so that the state `Self_` gets the `SelfState` enum and
+/// the `as_self` accessor rather than `Self_State` and `as_self_`.
+pub(crate) fn stem(ident: &Ident) -> String {
+    let name = unraw(ident);
+    let stem = name.trim_end_matches('_');
+
+    if stem.is_empty() {
+        name
+    } else {
+        stem.to_owned()
+    }
+}
+
+/// An identifier for `name`, escaped if it is a keyword. The keywords which
+/// cannot be raw identifiers get a trailing underscore instead.
+pub(crate) fn escaped(name: &str, span: Span) -> Ident {
+    match name {
+        "crate" | "self" | "Self" | "super" => Ident::new(&format!("{}_", name), span),
+        _ if KEYWORDS.contains(&name) => Ident::new_raw(name, span),
+        _ => Ident::new(name, span),
+    }
+}
+
+/// The snake_case method name derived from `ident`, e.g. `turn_key` for
+/// `TurnKey` or `r#loop` for `Loop`.
+pub(crate) fn snake_case(ident: &Ident) -> Ident {
+    escaped(&unraw(ident).to_case(Case::Snake), ident.span())
+}
+
+/// Whether `ident` is already in UpperCamelCase, the way Rust names types and
+/// enum variants: it starts with an uppercase letter and has no underscores.
+pub(crate) fn is_camel_case(ident: &Ident) -> bool {
+    let name = unraw(ident);
+
+    !name.starts_with(|c: char| c.is_lowercase()) && !name.contains('_')
+}
+
+/// The UpperCamelCase type name derived from `ident`, e.g. `WaitForAck` for
+/// `wait_for_ack` or `WAIT_FOR_ACK`.
+pub(crate) fn camel_case(ident: &Ident) -> Ident {
+    escaped(&unraw(ident).to_case(Case::UpperCamel), ident.span())
+}
+
+/// The SCREAMING_SNAKE_CASE constant name derived from `ident`, e.g.
+/// `WAIT_FOR_ACK` for `WaitForAck`. No keyword is in uppercase, so it never
+/// needs escaping.
+pub(crate) fn upper_snake_case(ident: &Ident) -> Ident {
+    Ident::new(&unraw(ident).to_case(Case::UpperSnake), ident.span())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_snake_case() {
+        let cases: Vec<(Ident, &str)> = vec![
+            (parse_quote! { TurnKey }, "turn_key"),
+            (parse_quote! { Move }, "r#move"),
+            (parse_quote! { r#Loop }, "r#loop"),
+            (parse_quote! { r#type }, "r#type"),
+            (parse_quote! { SelfType }, "self_type"),
+            (parse_quote! { Super }, "super_"),
+        ];
+
+        for (ident, expected) in cases {
+            assert_eq!(snake_case(&ident).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_camel_case() {
+        let cases: Vec<(Ident, bool, &str)> = vec![
+            (parse_quote! { WaitForAck }, true, "WaitForAck"),
+            (parse_quote! { wait_for_ack }, false, "WaitForAck"),
+            (parse_quote! { WAIT_FOR_ACK }, false, "WaitForAck"),
+            (parse_quote! { waitForAck }, false, "WaitForAck"),
+            (parse_quote! { r#loop }, false, "Loop"),
+            (parse_quote! { HTTPRequest }, true, "HttpRequest"),
+        ];
+
+        for (ident, is_camel, expected) in cases {
+            assert_eq!(is_camel_case(&ident), is_camel);
+            assert_eq!(camel_case(&ident).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_stem() {
+        let cases: Vec<(Ident, &str)> = vec![
+            (parse_quote! { WaitForAck }, "WaitForAck"),
+            (parse_quote! { r#loop }, "loop"),
+            (parse_quote! { Self_ }, "Self"),
+            (parse_quote! { self_ }, "self"),
+        ];
+
+        for (ident, expected) in cases {
+            assert_eq!(stem(&ident), expected);
+        }
+    }
+
+    #[test]
+    fn test_upper_snake_case() {
+        let cases: Vec<(Ident, &str)> = vec![
+            (parse_quote! { WaitForAck }, "WAIT_FOR_ACK"),
+            (parse_quote! { r#Loop }, "LOOP"),
+            (parse_quote! { Self_ }, "SELF"),
+        ];
+
+        for (ident, expected) in cases {
+            assert_eq!(upper_snake_case(&ident).to_string(), expected);
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    Error, Ident, Token,
+};
+
+use crate::{
+    ident::snake_case,
+    options::{InlineMode, ProvenanceMode},
+    state::{kind_enum_name, state_enum_name},
+};
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct InitialStates(pub Vec<InitialState>);
+
+impl InitialStates {
+    /// Whether the next tokens are an `InitialStates { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "InitialStates")
+    }
+}
+
+impl Parse for InitialStates {
+    /// example initial states tokens:
+    ///
+    /// ```text
+    /// InitialStates { Locked, Unlocked }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let mut initial_states: Vec<InitialState> = Vec::new();
+
+        // `InitialStates { ... }`
+        //  ^^^^^^^^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "InitialStates" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `InitialStates { ... }` block",
+            ));
+        }
+
+        // `InitialStates { ... }`
+        //                  ^^^
+        let block_initial_states;
+        braced!(block_initial_states in input);
+
+        // `InitialStates { Locked, Unlocked }`
+        //                  ^^^^^^  ^^^^^^^^
+        let punctuated_initial_states: Punctuated<Ident, Token![,]> =
+            block_initial_states.parse_terminated(Ident::parse)?;
+
+        if punctuated_initial_states.is_empty() {
+            return Err(Error::new(
+                block_name.span(),
+                "`InitialStates` must list at least one state, \
+                 otherwise the machine cannot be constructed",
+            ));
+        }
+
+        for name in punctuated_initial_states {
+            initial_states.push(InitialState { name });
+        }
+
+        Ok(InitialStates(initial_states))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InitialState {
+    pub name: Ident,
+}
+
+impl Parse for InitialState {
+    /// example initial state tokens:
+    ///
+    /// ```text
+    /// Locked
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let name = input.parse()?;
+
+        Ok(InitialState { name })
+    }
+}
+
+/// `#[must_use]` for the constructors building a machine in one of its
+/// initial states.
+pub(crate) fn must_use_constructor() -> TokenStream {
+    quote! {
+        #[must_use = "this returns a new machine, which does nothing unless it is used"]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct InitialStateFns {
+    pub enum_name: Ident,
+    pub initial_states: Vec<InitialState>,
+    pub provenance: ProvenanceMode,
+}
+
+impl InitialStates {
+    pub fn to_fn(&self, enum_name: &Ident, provenance: ProvenanceMode) -> InitialStateFns {
+        InitialStateFns {
+            enum_name: enum_name.clone(),
+            initial_states: self.0.clone(),
+            provenance,
+        }
+    }
+}
+
+impl ToTokens for InitialStateFns {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for s in &self.initial_states {
+            let fn_name = snake_case(&s.name);
+            let variant_name = &s.name;
+            let struct_name = state_enum_name(&s.name);
+
+            let enum_name = &self.enum_name;
+
+            let value = if self.provenance == ProvenanceMode::None {
+                quote! { #enum_name::#variant_name }
+            } else {
+                quote! { #enum_name::#variant_name(#struct_name::FromInit) }
+            };
+
+            let must_use = must_use_constructor();
+
+            tokens.extend(quote! {
+                #must_use
+                pub fn #fn_name() -> #enum_name {
+                    #value
+                }
+            })
+        }
+    }
+}
+
+/// `initial_kinds` and `initial_states`, iterating over the initial states
+/// of the machine in the order of `InitialStates { ... }`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct InitialStateIters<'a> {
+    pub enum_name: &'a Ident,
+    pub initial_states: &'a InitialStates,
+    pub inline: InlineMode,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for InitialStateIters<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let enum_name = self.enum_name;
+        let kind_enum = kind_enum_name(enum_name);
+        let kinds = self.initial_states.0.iter().map(|s| &s.name);
+        let fns = self.initial_states.0.iter().map(|s| snake_case(&s.name));
+        let inline = self.inline;
+
+        tokens.extend(quote! {
+            /// The states the machine can start in.
+            #inline
+            pub fn initial_kinds() -> impl Iterator<Item = #kind_enum> {
+                ::core::iter::IntoIterator::into_iter([#(#kind_enum::#kinds),*])
+            }
+
+            /// The machine in each of the states it can start in, as built by
+            /// their constructors.
+            #inline
+            pub fn initial_states() -> impl Iterator<Item = #enum_name> {
+                ::core::iter::IntoIterator::into_iter([#(#enum_name::#fns()),*])
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_initial_state_parse() {
+        let left: InitialState = parse2(quote! { Unlocked }).unwrap();
+        let right = InitialState {
+            name: parse_quote! { Unlocked },
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_initial_states_parse() {
+        let left: InitialStates = parse2(quote! {
+            InitialStates { Locked, Unlocked }
+        })
+        .unwrap();
+
+        let right = InitialStates(vec![
+            InitialState {
+                name: parse_quote! { Locked },
+            },
+            InitialState {
+                name: parse_quote! { Unlocked },
+            },
+        ]);
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_initial_states_parse_empty() {
+        let result: Result<InitialStates> = parse2(quote! {
+            InitialStates { }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "`InitialStates` must list at least one state, \
+             otherwise the machine cannot be constructed"
+        );
+    }
+
+    #[test]
+    fn test_initial_states_to_tokens() {
+        let initial_states = InitialStates(vec![
+            InitialState {
+                name: parse_quote! { Locked },
+            },
+            InitialState {
+                name: parse_quote! { Unlocked },
+            },
+        ])
+        .to_fn(&parse_quote! { Door }, ProvenanceMode::Event);
+
+        let left = quote! {
+            #[must_use = "this returns a new machine, which does nothing unless it is used"]
+            pub fn locked() -> Door {
+                Door::Locked(LockedState::FromInit)
+            }
+
+            #[must_use = "this returns a new machine, which does nothing unless it is used"]
+            pub fn unlocked() -> Door {
+                Door::Unlocked(UnlockedState::FromInit)
+            }
+        };
+
+        let mut right = TokenStream::new();
+        initial_states.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_initial_states_to_tokens_without_provenance() {
+        let initial_states = InitialStates(vec![InitialState {
+            name: parse_quote! { Locked },
+        }])
+        .to_fn(&parse_quote! { Door }, ProvenanceMode::None);
+
+        let left = quote! {
+            #[must_use = "this returns a new machine, which does nothing unless it is used"]
+            pub fn locked() -> Door {
+                Door::Locked
+            }
+        };
+
+        let mut right = TokenStream::new();
+        initial_states.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_initial_state_iters_to_tokens() {
+        let initial_states = InitialStates(vec![
+            InitialState {
+                name: parse_quote! { Locked },
+            },
+            InitialState {
+                name: parse_quote! { Unlocked },
+            },
+        ]);
+
+        let left = quote! {
+            /// The states the machine can start in.
+            pub fn initial_kinds() -> impl Iterator<Item = DoorKind> {
+                ::core::iter::IntoIterator::into_iter([DoorKind::Locked, DoorKind::Unlocked])
+            }
+
+            /// The machine in each of the states it can start in, as built by
+            /// their constructors.
+            pub fn initial_states() -> impl Iterator<Item = Door> {
+                ::core::iter::IntoIterator::into_iter([Door::locked(), Door::unlocked()])
+            }
+        };
+
+        let mut right = TokenStream::new();
+        InitialStateIters {
+            enum_name: &parse_quote! { Door },
+            initial_states: &initial_states,
+            inline: InlineMode::Auto,
+        }
+        .to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
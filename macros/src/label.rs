@@ -0,0 +1,372 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::Ident;
+use syn::{
+    parse::{ParseStream, Result},
+    Error, LitStr, Token,
+};
+
+use crate::{
+    ident::{camel_case, is_camel_case, unraw},
+    machine::Machine,
+};
+
+/// The names of the states and events of a machine which differ from their
+/// Rust identifiers, because they were declared with a rename or in another
+/// casing. They are used wherever the names leave Rust: `name()`, the
+/// diagrams, the JavaScript bindings and the logs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Labels {
+    pub states: HashMap<String, String>,
+    pub events: HashMap<String, String>,
+}
+
+impl Labels {
+    /// The name of the state `state`.
+    pub fn state(&self, state: &Ident) -> String {
+        label(&self.states, state)
+    }
+
+    /// The name of the event `event`.
+    pub fn event(&self, event: &Ident) -> String {
+        label(&self.events, event)
+    }
+}
+
+fn label(labels: &HashMap<String, String>, ident: &Ident) -> String {
+    let name = unraw(ident);
+
+    labels.get(&name).cloned().unwrap_or(name)
+}
+
+/// An entry of a `States { ... }` or `Events { ... }` block, which may rename
+/// the identifier it declares.
+///
+/// example entry tokens:
+///
+/// ```text
+/// Locked
+/// "wait-for-ack" as WaitForAck
+/// ```
+pub(crate) fn parse_entry(input: ParseStream<'_>) -> Result<(Ident, Option<LitStr>)> {
+    if !input.peek(LitStr) {
+        return Ok((input.parse()?, None));
+    }
+
+    // `"wait-for-ack" as WaitForAck`
+    //  ^^^^^^^^^^^^^^
+    let label: LitStr = input.parse()?;
+
+    if label.value().is_empty() {
+        return Err(Error::new(label.span(), "names cannot be empty"));
+    }
+
+    // `"wait-for-ack" as WaitForAck`
+    //                 ^^
+    let _: Token![as] = input.parse()?;
+
+    // `"wait-for-ack" as WaitForAck`
+    //                    ^^^^^^^^^^
+    let ident = input.parse()?;
+
+    Ok((ident, Some(label)))
+}
+
+/// Rename the states and events of `machine` which are not in UpperCamelCase,
+/// such as `wait_for_ack`, to the identifiers their types and variants get in
+/// Rust, such as `WaitForAck`, keeping their original spelling as their name.
+pub(crate) fn normalize(machine: &mut Machine) -> Result<()> {
+    let mut states = Vec::new();
+
+    for t in &mut machine.transitions.0 {
+        states.push(&mut t.from.name);
+        states.push(&mut t.to.name);
+    }
+
+    for i in &mut machine.initial_states.0 {
+        states.push(&mut i.name);
+    }
+
+    for f in &mut machine.final_states.0 {
+        states.push(&mut f.name);
+    }
+
+    if let Some(declared_states) = &mut machine.declared_states {
+        for s in &mut declared_states.0 {
+            states.push(&mut s.name);
+        }
+    }
+
+    if let Some(on_invalid) = &mut machine.on_invalid {
+        states.push(&mut on_invalid.0.name);
+    }
+
+    for c in &mut machine.completions {
+        states.push(&mut c.from.name);
+        states.push(&mut c.to.name);
+    }
+
+    for set in &mut machine.state_sets {
+        for s in &mut set.states {
+            states.push(&mut s.name);
+        }
+    }
+
+    for r in &mut machine.regions {
+        for s in &mut r.states {
+            states.push(&mut s.name);
+        }
+    }
+
+    if let Some(properties) = &mut machine.properties {
+        for p in &mut properties.0 {
+            states.push(&mut p.from.name);
+            states.push(&mut p.to.name);
+        }
+    }
+
+    rename(states, &mut machine.labels.states, "state")?;
+
+    let mut events = Vec::new();
+
+    for t in &mut machine.transitions.0 {
+        events.push(&mut t.event.name);
+    }
+
+    if let Some(declared_events) = &mut machine.declared_events {
+        for e in &mut declared_events.0 {
+            events.push(&mut e.name);
+        }
+    }
+
+    for g in &mut machine.event_groups {
+        for e in &mut g.events {
+            events.push(&mut e.name);
+        }
+    }
+
+    for p in &mut machine.event_parents {
+        events.push(&mut p.child.name);
+        events.push(&mut p.parent.name);
+    }
+
+    rename(events, &mut machine.labels.events, "event")?;
+
+    // Two states or events with the same name could not be told apart once
+    // exported.
+    let states = machine.states().0.into_iter().map(|s| s.name);
+    check_unique(states, |s| machine.labels.state(s), "state")?;
+
+    let events = machine.events().into_iter().map(|e| e.name);
+    check_unique(events, |e| machine.labels.event(e), "event")
+}
+
+fn rename(idents: Vec<&mut Ident>, labels: &mut HashMap<String, String>, kind: &str) -> Result<()> {
+    let camel_cased = idents
+        .iter()
+        .filter(|i| is_camel_case(i))
+        .map(|i| unraw(i))
+        .collect::<HashSet<_>>();
+
+    let mut renamed: HashMap<String, Ident> = HashMap::new();
+
+    for ident in idents {
+        if is_camel_case(ident) {
+            continue;
+        }
+
+        let original = unraw(ident);
+        let camel = camel_case(ident);
+
+        if camel_cased.contains(&unraw(&camel)) {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "{0} `{1}` would be named `{2}` in Rust, like the {0} `{2}`",
+                    kind, ident, camel
+                ),
+            ));
+        }
+
+        if let Some(previous) = renamed.get(&unraw(&camel)) {
+            if unraw(previous) != original {
+                return Err(Error::new(
+                    ident.span(),
+                    format!(
+                        "{}s `{}` and `{}` would both be named `{}` in Rust",
+                        kind, previous, ident, camel
+                    ),
+                ));
+            }
+        }
+
+        // A rename in the `States` or `Events` block takes precedence over
+        // the original spelling.
+        let label = labels.get(&original).cloned().unwrap_or(original);
+        let _ = labels.insert(unraw(&camel), label);
+        let _ = renamed.insert(unraw(&camel), ident.clone());
+
+        *ident = camel;
+    }
+
+    for original in renamed.values() {
+        let _ = labels.remove(&unraw(original));
+    }
+
+    Ok(())
+}
+
+fn check_unique(
+    idents: impl Iterator<Item = Ident>,
+    label: impl Fn(&Ident) -> String,
+    kind: &str,
+) -> Result<()> {
+    let mut seen: HashMap<String, Ident> = HashMap::new();
+
+    for ident in idents {
+        if let Some(previous) = seen.insert(label(&ident), ident.clone()) {
+            return Err(Error::new(
+                ident.span(),
+                format!(
+                    "{}s `{}` and `{}` are both named `{}`",
+                    kind,
+                    previous,
+                    ident,
+                    label(&ident)
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::parse2;
+
+    #[test]
+    fn test_normalize_renames() {
+        let machine: Machine = parse2(quote! {
+            Link {
+                InitialStates { idle }
+                States { idle, "wait-for-ack" as WaitForAck, done }
+                Events { "send!" as Send, ack, reset_link }
+
+                Send { idle => WaitForAck }
+                ack { WaitForAck => done }
+                reset_link { WaitForAck, done => idle }
+            }
+        })
+        .unwrap();
+
+        let states = machine
+            .states()
+            .0
+            .into_iter()
+            .map(|s| (s.name.to_string(), machine.labels.state(&s.name)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                ("Idle".to_string(), "idle".to_string()),
+                ("WaitForAck".to_string(), "wait-for-ack".to_string()),
+                ("Done".to_string(), "done".to_string()),
+            ]
+        );
+
+        let events = machine
+            .events()
+            .into_iter()
+            .map(|e| (e.name.to_string(), machine.labels.event(&e.name)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                ("Send".to_string(), "send!".to_string()),
+                ("Ack".to_string(), "ack".to_string()),
+                ("ResetLink".to_string(), "reset_link".to_string()),
+            ]
+        );
+
+        assert_eq!(machine.initial_states.0[0].name, "Idle");
+        assert_eq!(machine.transitions.0[1].to.name, "Done");
+    }
+
+    #[test]
+    fn test_normalize_renamed_identifier() {
+        let machine: Machine = parse2(quote! {
+            Link {
+                InitialStates { wait_for_ack }
+                FinalStates { Done }
+                States { "wait-for-ack" as wait_for_ack, Done }
+
+                Ack { wait_for_ack => Done }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(machine.transitions.0[0].from.name, "WaitForAck");
+        assert_eq!(
+            machine.labels.state(&machine.transitions.0[0].from.name),
+            "wait-for-ack"
+        );
+        assert!(!machine.labels.states.contains_key("wait_for_ack"));
+    }
+
+    #[test]
+    fn test_normalize_conflicts() {
+        let cases = vec![
+            (
+                quote! {
+                    Link {
+                        InitialStates { wait_for_ack }
+
+                        Ack { WaitForAck => Done }
+                    }
+                },
+                "state `wait_for_ack` would be named `WaitForAck` in Rust, like the state `WaitForAck`",
+            ),
+            (
+                quote! {
+                    Link {
+                        InitialStates { wait_for_ack }
+
+                        Ack { WAIT_FOR_ACK => Done }
+                    }
+                },
+                "states `WAIT_FOR_ACK` and `wait_for_ack` would both be named `WaitForAck` in Rust",
+            ),
+            (
+                quote! {
+                    Link {
+                        InitialStates { Idle }
+                        States { Idle, "Idle" as Waiting }
+
+                        Send { Idle => Waiting }
+                    }
+                },
+                "states `Idle` and `Waiting` are both named `Idle`",
+            ),
+            (
+                quote! {
+                    Link {
+                        InitialStates { Idle }
+                        States { Idle, "" as Waiting }
+
+                        Send { Idle => Waiting }
+                    }
+                },
+                "names cannot be empty",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+}
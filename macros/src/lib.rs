@@ -0,0 +1,97 @@
+// quote! macro needs a higher recursion limit
+#![recursion_limit = "512"]
+#![forbid(
+    future_incompatible,
+    macro_use_extern_crate,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_compatibility,
+    trivial_casts,
+    trivial_numeric_casts,
+    unsafe_code,
+    variant_size_differences
+)]
+#![warn(
+    rust_2018_idioms,
+    single_use_lifetimes,
+    unused_import_braces,
+    unused_lifetimes,
+    unused_qualifications,
+    unused_results,
+    unused
+)]
+
+use crate::{from_file::MachinesFromFile, machine::Machines};
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::parse_macro_input;
+
+mod actix;
+mod arbitrary;
+mod completion;
+mod defmt;
+mod diagram;
+mod dynamic;
+mod event;
+mod event_group;
+mod event_map;
+mod event_parent;
+mod final_state;
+mod from_file;
+mod generated_tests;
+mod hooks;
+mod ident;
+mod initial_state;
+mod label;
+mod lints;
+mod machine;
+mod metrics;
+mod on_invalid;
+mod options;
+mod packed;
+mod parts;
+mod paths;
+mod petgraph;
+mod prelude;
+mod property;
+mod proptest;
+mod provenance;
+mod random_walk;
+mod region;
+mod runtime;
+mod simulate;
+mod snapshot;
+mod state;
+mod state_bitset;
+mod state_map;
+mod state_set;
+mod state_transition;
+mod summary;
+mod table;
+mod template;
+mod timeout;
+mod tokio;
+mod trace;
+mod transition;
+mod typestate;
+mod undo;
+mod wasm;
+
+/// Generate the declaratively described state machine diagram.
+#[proc_macro]
+pub fn state_machine(input: TokenStream) -> TokenStream {
+    let machines: Machines = parse_macro_input!(input as Machines);
+
+    quote!(#machines).into()
+}
+
+/// Generate the state machine diagrams declared in a file, with the same
+/// syntax as [`state_machine!`]. The path is relative to the manifest of the
+/// crate, and the crate is rebuilt when the file changes.
+#[proc_macro]
+pub fn state_machine_from_file(input: TokenStream) -> TokenStream {
+    let machines: MachinesFromFile = parse_macro_input!(input as MachinesFromFile);
+
+    quote!(#machines).into()
+}
@@ -0,0 +1,910 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{Error, Ident, Result};
+
+use crate::{
+    event::{event_fn_name, event_marker_name, Event},
+    ident::unraw,
+    machine::Machine,
+    options::ProvenanceMode,
+    property::{describe_path, shortest_path, Quantifier},
+    state::{state_enum_name, State},
+    state_bitset::state_bitset_repr,
+    transition::Transitions,
+};
+
+/// How a problem found in the diagram is reported, mirroring the lint levels
+/// of rustc.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum LintLevel {
+    Allow,
+    Warn,
+    #[default]
+    Deny,
+}
+
+/// A problem found in the diagram, pointing at the offending identifier.
+#[derive(Debug)]
+pub(crate) struct Finding {
+    pub span: Span,
+    pub message: String,
+}
+
+/// The states which cannot be reached from any initial state.
+fn unreachable_states(machine: &Machine) -> Vec<State> {
+    // The `OnInvalid => ...` state is entered by the invalid transitions.
+    let mut transitions = machine.transitions.0.clone();
+    transitions.extend(machine.invalid_transitions());
+    let transitions = Transitions(transitions);
+    let index = transitions.index();
+
+    let mut reachable: HashSet<String> = HashSet::new();
+    let mut queue: Vec<&Ident> = Vec::new();
+
+    for i in &machine.initial_states.0 {
+        if reachable.insert(i.name.to_string()) {
+            queue.push(&i.name);
+        }
+    }
+
+    while let Some(state) = queue.pop() {
+        // The states left by the completion transitions are still entered.
+        let completed = machine
+            .completions
+            .iter()
+            .filter(|c| c.from.name == *state)
+            .map(|c| &c.to);
+
+        for to in index.from(state).iter().map(|t| &t.to).chain(completed) {
+            if reachable.insert(to.name.to_string()) {
+                queue.push(&to.name);
+            }
+        }
+    }
+
+    machine
+        .states()
+        .0
+        .into_iter()
+        .filter(|s| !reachable.contains(&s.name.to_string()))
+        .collect()
+}
+
+/// The states which have no outgoing transition, not even a completion
+/// transition, but are not declared final.
+fn dead_end_states(machine: &Machine) -> Vec<State> {
+    let index = machine.transitions.index();
+
+    machine
+        .states()
+        .0
+        .into_iter()
+        .filter(|s| index.from(&s.name).is_empty())
+        .filter(|s| !machine.completions.iter().any(|c| c.from.name == s.name))
+        .filter(|s| !machine.final_states.0.iter().any(|f| f.name == s.name))
+        .collect()
+}
+
+/// The events declared in the `Events` block which no transition uses.
+fn unused_events(machine: &Machine) -> Vec<Event> {
+    match &machine.declared_events {
+        None => Vec::new(),
+        Some(declared_events) => {
+            let used = machine
+                .transitions
+                .0
+                .iter()
+                .map(|t| t.event.name.to_string())
+                .collect::<HashSet<_>>();
+
+            declared_events
+                .0
+                .iter()
+                .filter(|e| !used.contains(&e.name.to_string()))
+                .cloned()
+                .collect()
+        }
+    }
+}
+
+/// A type generated for the machine: its name, and the kind and identifier of
+/// the machine, state or event it is named after.
+struct GeneratedType<'a> {
+    name: String,
+    kind: &'static str,
+    origin: &'a Ident,
+}
+
+/// Every type the expansion of `machine` declares.
+fn generated_types<'a>(
+    machine: &'a Machine,
+    states: &'a [State],
+    events: &'a [Event],
+) -> Vec<GeneratedType<'a>> {
+    let generated = |suffix: &str, kind, origin: &'a Ident| GeneratedType {
+        name: format!("{}{}", unraw(origin), suffix),
+        kind,
+        origin,
+    };
+
+    let mut types = vec![generated("", "machine", &machine.name)];
+
+    if machine.options.typestate {
+        types.extend(states.iter().map(|s| generated("", "state", &s.name)));
+    } else {
+        for suffix in [
+            "Kind",
+            "Event",
+            "Step",
+            "Error",
+            "ReplayError",
+            "Runner",
+            "Snapshot",
+            "StateMap",
+            "EventMap",
+        ] {
+            types.push(generated(suffix, "machine", &machine.name));
+        }
+
+        if machine.options.provenance != ProvenanceMode::None {
+            types.push(generated("Provenance", "machine", &machine.name));
+        }
+
+        if state_bitset_repr(states.len()).is_some() {
+            types.push(generated("StateSet", "machine", &machine.name));
+        }
+
+        if machine.options.packed {
+            types.push(generated("Packed", "machine", &machine.name));
+        }
+
+        if !machine.event_groups.is_empty() {
+            types.push(generated("EventGroup", "machine", &machine.name));
+        }
+
+        if !machine.regions.is_empty() {
+            types.push(generated("Region", "machine", &machine.name));
+        }
+
+        if machine.hooks.is_some() {
+            types.push(generated("Hooks", "machine", &machine.name));
+        }
+
+        if machine.options.actix {
+            types.push(generated("Actor", "machine", &machine.name));
+        }
+
+        if machine.options.critical_section {
+            types.push(generated("InterruptRunner", "machine", &machine.name));
+        }
+
+        if machine.options.tokio {
+            types.push(generated("Sender", "machine", &machine.name));
+        }
+
+        if machine.options.wasm {
+            types.push(generated("Wasm", "machine", &machine.name));
+        }
+
+        if machine.options.async_hooks {
+            types.push(generated("AsyncHooks", "machine", &machine.name));
+        }
+
+        if machine.options.provenance != ProvenanceMode::None {
+            types.extend(states.iter().map(|s| GeneratedType {
+                name: state_enum_name(&s.name).to_string(),
+                kind: "state",
+                origin: &s.name,
+            }));
+        }
+        types.extend(events.iter().map(|e| GeneratedType {
+            name: event_marker_name(&e.name).to_string(),
+            kind: "event",
+            origin: &e.name,
+        }));
+    }
+
+    types
+}
+
+/// The findings of the lints enabled at `level`.
+pub(crate) fn findings(machine: &Machine, level: LintLevel) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    if machine.options.unreachable_states == level {
+        for s in unreachable_states(machine) {
+            findings.push(Finding {
+                span: s.name.span(),
+                message: format!("state `{}` is unreachable from the initial states", s.name),
+            });
+        }
+    }
+
+    if machine.options.dead_end_states == level {
+        for s in dead_end_states(machine) {
+            findings.push(Finding {
+                span: s.name.span(),
+                message: format!(
+                    "state `{}` has no outgoing transitions; \
+                     declare it in `FinalStates {{ ... }}` if it is meant to be terminal",
+                    s.name
+                ),
+            });
+        }
+    }
+
+    if machine.options.unused_events == level {
+        for e in unused_events(machine) {
+            findings.push(Finding {
+                span: e.name.span(),
+                message: format!("event `{}` is declared but has no transitions", e.name),
+            });
+        }
+    }
+
+    findings
+}
+
+/// Fail with every denied finding.
+pub(crate) fn check(machine: &Machine) -> Result<()> {
+    let states = machine.states();
+    let state_names = states
+        .0
+        .iter()
+        .map(|s| s.name.to_string())
+        .collect::<HashSet<_>>();
+
+    let unknown_final_states = machine
+        .final_states
+        .0
+        .iter()
+        .filter(|f| !state_names.contains(&f.name.to_string()))
+        .map(|f| Error::new(f.name.span(), format!("unknown state `{}`", f.name)));
+
+    // With a `States` block, every state used by a transition or a
+    // completion transition, declared initial or named by `OnInvalid => ...`
+    // must be in it.
+    let mut undeclared_states: Vec<&Ident> = Vec::new();
+    if let Some(declared_states) = &machine.declared_states {
+        let mut seen = declared_states
+            .0
+            .iter()
+            .map(|s| s.name.to_string())
+            .collect::<HashSet<_>>();
+
+        let used = machine
+            .transitions
+            .0
+            .iter()
+            .flat_map(|t| [&t.from.name, &t.to.name])
+            .chain(machine.initial_states.0.iter().map(|i| &i.name))
+            .chain(machine.on_invalid.iter().map(|o| &o.0.name))
+            .chain(
+                machine
+                    .completions
+                    .iter()
+                    .flat_map(|c| [&c.from.name, &c.to.name]),
+            );
+
+        for name in used {
+            if seen.insert(name.to_string()) {
+                undeclared_states.push(name);
+            }
+        }
+    }
+    let undeclared_states = undeclared_states.into_iter().map(|s| {
+        Error::new(
+            s.span(),
+            format!(
+                "undeclared state `{}`; add it to the `States {{ ... }}` block",
+                s
+            ),
+        )
+    });
+
+    // With an `Events` block, every event used by a transition must be in it.
+    let mut undeclared_events: Vec<&Ident> = Vec::new();
+    if let Some(declared_events) = &machine.declared_events {
+        let mut seen = declared_events
+            .0
+            .iter()
+            .map(|e| e.name.to_string())
+            .collect::<HashSet<_>>();
+
+        for t in &machine.transitions.0 {
+            if seen.insert(t.event.name.to_string()) {
+                undeclared_events.push(&t.event.name);
+            }
+        }
+    }
+    let undeclared_events = undeclared_events.into_iter().map(|e| {
+        Error::new(
+            e.span(),
+            format!(
+                "undeclared event `{}`; add it to the `Events {{ ... }}` block",
+                e
+            ),
+        )
+    });
+
+    // The `Properties` block is checked against the transitions.
+    let mut property_violations = Vec::new();
+    for p in machine.properties.iter().flat_map(|p| &p.0) {
+        let unknown = [&p.from, &p.to]
+            .iter()
+            .filter(|s| !state_names.contains(&s.name.to_string()))
+            .map(|s| Error::new(s.name.span(), format!("unknown state `{}`", s.name)))
+            .collect::<Vec<_>>();
+
+        if !unknown.is_empty() {
+            property_violations.extend(unknown);
+            continue;
+        }
+
+        let path = shortest_path(&machine.transitions, &p.from.name, &p.to.name);
+
+        match (p.quantifier, path) {
+            (Quantifier::Never, Some(path)) => property_violations.push(Error::new(
+                p.keyword.span(),
+                format!(
+                    "property violated: `{}` is reachable from `{}` through {}",
+                    p.to.name,
+                    p.from.name,
+                    describe_path(&path)
+                ),
+            )),
+            (Quantifier::Always, None) => property_violations.push(Error::new(
+                p.keyword.span(),
+                format!(
+                    "property violated: `{}` is not reachable from `{}`",
+                    p.to.name, p.from.name
+                ),
+            )),
+            _ => {}
+        }
+    }
+
+    // Events whose names only differ in case or underscores would get the
+    // same transition method.
+    let events = machine.events();
+    let mut methods: HashMap<String, &Event> = HashMap::new();
+    let mut method_collisions = Vec::new();
+    for e in &events {
+        let method = event_fn_name(&e.name);
+
+        match methods.entry(method.to_string()) {
+            Entry::Vacant(entry) => {
+                let _ = entry.insert(e);
+            }
+            Entry::Occupied(previous) => method_collisions.push(Error::new(
+                e.name.span(),
+                format!(
+                    "events `{}` and `{}` would both become the method `{}`",
+                    previous.get().name,
+                    e.name,
+                    method
+                ),
+            )),
+        }
+    }
+
+    // The types generated for the machine, its states and its events are
+    // named after them, so they must not share names.
+    let mut name_collisions = Vec::new();
+    let mut collide = |first: &Ident, first_kind: &str, second: &Ident, second_kind: &str| {
+        if unraw(first) == unraw(second) {
+            let mut error = Error::new(
+                second.span(),
+                format!(
+                    "{} `{}` clashes with the {} of the same name",
+                    second_kind, second, first_kind
+                ),
+            );
+            error.combine(Error::new(
+                first.span(),
+                format!("{} `{}` declared here", first_kind, first),
+            ));
+            name_collisions.push(error);
+        }
+    };
+
+    for s in &states.0 {
+        collide(&machine.name, "machine", &s.name, "state");
+    }
+
+    let states_by_name = states
+        .0
+        .iter()
+        .map(|s| (unraw(&s.name), &s.name))
+        .collect::<HashMap<_, _>>();
+
+    for e in &events {
+        collide(&machine.name, "machine", &e.name, "event");
+
+        if let Some(s) = states_by_name.get(&unraw(&e.name)) {
+            collide(s, "state", &e.name, "event");
+        }
+    }
+
+    // Types generated for different names can still clash, like the
+    // `LockedState` enum of the state `Locked` in a machine named
+    // `LockedState`. Those named after the same identifier are reported
+    // above.
+    let types = generated_types(machine, &states.0, &events);
+    let mut types_by_name: HashMap<&str, Vec<&GeneratedType<'_>>> = HashMap::new();
+    let type_collisions = types.iter().flat_map(|t| {
+        let same_name = types_by_name.entry(&t.name).or_default();
+
+        let collision = same_name
+            .iter()
+            .find(|previous| unraw(previous.origin) != unraw(t.origin))
+            .copied();
+        same_name.push(t);
+
+        collision.map(|previous| {
+            let mut error = Error::new(
+                t.origin.span(),
+                format!(
+                    "{} `{}` generates the type `{}`, which clashes with the {} `{}`",
+                    t.kind, t.origin, t.name, previous.kind, previous.origin
+                ),
+            );
+            error.combine(Error::new(
+                previous.origin.span(),
+                format!("{} `{}` declared here", previous.kind, previous.origin),
+            ));
+            error
+        })
+    });
+
+    let mut errors = unknown_final_states
+        .chain(undeclared_states)
+        .chain(undeclared_events)
+        .chain(method_collisions)
+        .chain(name_collisions)
+        .chain(type_collisions)
+        .chain(property_violations)
+        .chain(
+            findings(machine, LintLevel::Deny)
+                .into_iter()
+                .map(|f| Error::new(f.span, f.message)),
+        );
+
+    match errors.next() {
+        None => Ok(()),
+        Some(mut error) => {
+            for e in errors {
+                error.combine(e);
+            }
+
+            Err(error)
+        }
+    }
+}
+
+/// Check that the machines declared in the same invocation generate no type
+/// twice, like the `ClosedState` enums of two machines with a `Closed` state,
+/// or the `LockKind` enums of the machines `Lock` and `LockKind`.
+pub(crate) fn check_machines(machines: &[Machine]) -> Result<()> {
+    let parts = machines
+        .iter()
+        .map(|m| (m, m.states(), m.events()))
+        .collect::<Vec<_>>();
+
+    let describe = |t: &GeneratedType<'_>, machine: &Ident| {
+        if t.kind == "machine" {
+            format!("machine `{}`", t.origin)
+        } else {
+            format!("{} `{}` of the machine `{}`", t.kind, t.origin, machine)
+        }
+    };
+
+    let mut owners: HashMap<String, (&Ident, GeneratedType<'_>)> = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (machine, states, events) in &parts {
+        // The types of a single machine are checked not to clash by `check`.
+        for t in generated_types(machine, &states.0, events) {
+            match owners.entry(t.name.clone()) {
+                Entry::Vacant(entry) => {
+                    let _ = entry.insert((&machine.name, t));
+                }
+                Entry::Occupied(entry) => {
+                    let (previous_machine, previous) = entry.get();
+                    let mut error = Error::new(
+                        t.origin.span(),
+                        format!(
+                            "{} generates the type `{}`, which clashes with the {}",
+                            describe(&t, &machine.name),
+                            t.name,
+                            describe(previous, previous_machine)
+                        ),
+                    );
+                    error.combine(Error::new(
+                        previous.origin.span(),
+                        format!("{} declared here", describe(previous, previous_machine)),
+                    ));
+                    errors.push(error);
+                }
+            }
+        }
+    }
+
+    let mut errors = errors.into_iter();
+
+    match errors.next() {
+        None => Ok(()),
+        Some(mut error) => {
+            for e in errors {
+                error.combine(e);
+            }
+
+            Err(error)
+        }
+    }
+}
+
+/// Warnings for the findings of the lints set to `warn`. There is no way to
+/// emit a warning from a proc macro on stable, so each one is the use of a
+/// deprecated item carrying the message, spanned on the offending identifier.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Warnings<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Warnings<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for f in findings(self.machine, LintLevel::Warn) {
+            let message = &f.message;
+            let usage = quote_spanned! {f.span=> let _ = Warning; };
+
+            tokens.extend(quote! {
+                const _: () = {
+                    #[deprecated(note = #message)]
+                    struct Warning;
+
+                    #usage
+                };
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_unreachable_states() {
+        let machine: Machine = parse2(quote! {
+            #[allow(unreachable_states)]
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+                Push { Broken => Locked }
+            }
+        })
+        .unwrap();
+
+        let names = unreachable_states(&machine)
+            .into_iter()
+            .map(|s| s.name.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["Broken"]);
+    }
+
+    #[test]
+    fn test_check_unreachable_states() {
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+                Push { Broken => Locked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "state `Broken` is unreachable from the initial states"
+        );
+    }
+
+    #[test]
+    fn test_check_dead_end_states() {
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "state `Unlocked` has no outgoing transitions; \
+             declare it in `FinalStates { ... }` if it is meant to be terminal"
+        );
+
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin { Locked => Unlocked }
+            }
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_check_unknown_final_states() {
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked, Open }
+
+                Coin { Locked => Unlocked }
+            }
+        });
+
+        assert_eq!(result.unwrap_err().to_string(), "unknown state `Open`");
+    }
+
+    #[test]
+    fn test_check_event_method_collisions() {
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                TurnKey { Locked => Unlocked }
+                TurnKEY { Unlocked => Locked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "events `TurnKey` and `TurnKEY` would both become the method `turn_key`"
+        );
+    }
+
+    #[test]
+    fn test_check_name_collisions() {
+        let error = parse2::<Machine>(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Coin }
+                Push { Coin => Locked }
+            }
+        })
+        .unwrap_err();
+
+        let messages = error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "event `Coin` clashes with the state of the same name",
+                "state `Coin` declared here",
+            ]
+        );
+
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { TurnStile }
+
+                Coin { TurnStile => TurnStile }
+            }
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_check_undeclared_states() {
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                States { Locked, Unlocked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlcoked => Locked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "undeclared state `Unlcoked`; add it to the `States { ... }` block"
+        );
+
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                States { Locked, Unlocked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_check_undeclared_events() {
+        let result: Result<Machine> = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                Events { Coin }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "undeclared event `Push`; add it to the `Events { ... }` block"
+        );
+    }
+
+    #[test]
+    fn test_unused_events() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                Events { Coin, Push, Refund }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let names = unused_events(&machine)
+            .into_iter()
+            .map(|e| e.name.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["Refund"]);
+
+        let result: Result<Machine> = parse2(quote! {
+            #[deny(unused_events)]
+            TurnStile {
+                InitialStates { Locked }
+                Events { Coin, Push, Refund }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "event `Refund` is declared but has no transitions"
+        );
+    }
+
+    #[test]
+    fn test_check_type_collisions() {
+        let error = parse2::<Machine>(quote! {
+            LockedState {
+                InitialStates { Locked }
+                FinalStates { Locked }
+
+                TurnKey { Locked => Locked }
+            }
+        })
+        .unwrap_err();
+
+        let messages = error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "state `Locked` generates the type `LockedState`, \
+                 which clashes with the machine `LockedState`",
+                "machine `LockedState` declared here",
+            ]
+        );
+
+        let result: Result<Machine> = parse2(quote! {
+            #[typestate]
+            LockedState {
+                InitialStates { Locked }
+                FinalStates { Locked }
+
+                TurnKey { Locked => Locked }
+            }
+        });
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_check_properties() {
+        let result: Result<Machine> = parse2(quote! {
+            Lock {
+                InitialStates { Locked }
+                Properties {
+                    Never reachable(Broken => Unlocked);
+                    Always reachable(Locked => Broken);
+                }
+
+                TurnKey {
+                    Locked => Unlocked
+                    Unlocked => Locked
+                }
+                BreakKeyhole { Locked, Unlocked => Broken }
+                Repair { Broken => Locked }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "property violated: `Unlocked` is reachable from `Broken` through \
+             `Broken` --`Repair`--> `Locked` --`TurnKey`--> `Unlocked`"
+        );
+
+        let result: Result<Machine> = parse2(quote! {
+            Lock {
+                InitialStates { Locked }
+                FinalStates { Broken }
+                Properties {
+                    Always reachable(Broken => Locked);
+                    Never reachable(Broken => Unlocked)
+                }
+
+                TurnKey {
+                    Locked => Unlocked
+                    Unlocked => Locked
+                }
+                BreakKeyhole { Locked, Unlocked => Broken }
+            }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "property violated: `Locked` is not reachable from `Broken`"
+        );
+    }
+
+    #[test]
+    fn test_warnings_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            #[warn(unreachable_states)]
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Locked }
+
+                Push { Broken => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            const _: () = {
+                #[deprecated(note = "state `Broken` is unreachable from the initial states")]
+                struct Warning;
+
+                let _ = Warning;
+            };
+        };
+
+        let mut right = TokenStream::new();
+        Warnings { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,2795 @@
+use std::collections::HashSet;
+
+use proc_macro2::{Delimiter, Group, Literal, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    Attribute, Error, Ident, Token,
+};
+
+use crate::{
+    actix::ActixImpls,
+    arbitrary::ArbitraryImpls,
+    completion::{self, Completion, Settle},
+    defmt::DefmtImpls,
+    diagram,
+    dynamic::{DynamicTransitions, Replay},
+    event::{Event, EventKinds, EventMarkers, Events},
+    event_group::{self, EventGroup, EventGroups},
+    event_map::EventMap,
+    event_parent::{self, EventParent, EventParents},
+    final_state::FinalStates,
+    generated_tests::GeneratedTests,
+    hooks::{AsyncHooks, Hooks, HooksTrait},
+    ident::unraw,
+    initial_state::{InitialStateIters, InitialStates},
+    label::{self, Labels},
+    lints::{self, Warnings},
+    on_invalid::OnInvalid,
+    options::{DispatchMode, Options, ProvenanceMode},
+    packed::{packed_repr, Packed},
+    parts::MachineParts,
+    paths::{Paths, Reachable, ShortestPath},
+    petgraph::PetgraphImpls,
+    prelude::Prelude,
+    property::Properties,
+    proptest::ProptestStrategies,
+    provenance::{provenance_enum_name, Provenance, ProvenanceAccessors, ProvenanceKinds},
+    random_walk::{RandomWalk, SimulateStep},
+    region::{self, Region, Regions},
+    runtime::{InterruptRunnerAlias, RunnerAlias, StateMachineImpl, TransitionImpls},
+    simulate::Simulator,
+    snapshot::Snapshot,
+    state::{state_accessor_name, state_enum_name, State, StateAccessors, StateKinds, States},
+    state_bitset::{self, StateBitset},
+    state_map::StateMap,
+    state_set::{self, StateSet, StateSets},
+    state_transition::StateTransitions,
+    summary::Summary,
+    table::TransitionTable,
+    template::{self, Template},
+    timeout::{self, Timeouts},
+    tokio::TokioRunner,
+    transition::{Transition, Transitions},
+    typestate::Typestate,
+    undo::Undo,
+    wasm::WasmBindings,
+};
+
+/// The machines declared in one `state_machine!` invocation.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Machines(pub Vec<Machine>);
+
+impl Parse for Machines {
+    /// example machines tokens:
+    ///
+    /// ```text
+    /// Template Retry(Start, Done) { ... }
+    /// TurnStile { ... }
+    /// Lock { ... }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let mut templates: Vec<Template> = Vec::new();
+        let mut bodies: Vec<TokenStream> = Vec::new();
+
+        // The templates can be declared after the machines including them,
+        // so the machines are only parsed once all of them are known.
+        loop {
+            if Template::peek(input) {
+                let template: Template = input.parse()?;
+
+                if templates
+                    .iter()
+                    .any(|t| unraw(&t.name) == unraw(&template.name))
+                {
+                    return Err(Error::new(
+                        template.name.span(),
+                        format!("duplicate template `{}`", template.name),
+                    ));
+                }
+
+                templates.push(template);
+            } else {
+                let attrs = input.call(Attribute::parse_outer)?;
+                let name: Ident = input.parse()?;
+                let body: TokenTree = input.parse()?;
+
+                bodies.push(quote! { #(#attrs)* #name #body });
+            }
+
+            if input.is_empty() {
+                break;
+            }
+        }
+
+        let mut machines: Vec<Machine> = Vec::new();
+
+        for body in bodies {
+            let machine: Machine = if templates.is_empty() {
+                syn::parse2(body)?
+            } else {
+                syn::parse2(expand_templates(body, &templates)?)?
+            };
+
+            // The types generated for both machines would clash.
+            if let Some(previous) = machines
+                .iter()
+                .find(|m| unraw(&m.name) == unraw(&machine.name))
+            {
+                let mut error = Error::new(
+                    machine.name.span(),
+                    format!("duplicate machine `{}`", machine.name),
+                );
+                error.combine(Error::new(
+                    previous.name.span(),
+                    format!("machine `{}` first declared here", previous.name),
+                ));
+
+                return Err(error);
+            }
+
+            machines.push(machine);
+        }
+
+        if machines.is_empty() {
+            return Err(input.error("expected a machine after the templates"));
+        }
+
+        lints::check_machines(&machines)?;
+
+        // Only once every machine of the invocation is known to be valid, so
+        // that a rejected invocation leaves the previous diagrams in place.
+        for machine in &machines {
+            diagram::write_files(machine)?;
+        }
+
+        Ok(Machines(machines))
+    }
+}
+
+/// Expand the templates included in the body of `machine`, whose tokens are
+/// its attributes, name and body.
+fn expand_templates(machine: TokenStream, templates: &[Template]) -> Result<TokenStream> {
+    machine
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                let body = template::expand(group.stream(), templates, &mut Vec::new())?;
+                let mut expanded = Group::new(Delimiter::Brace, body);
+                expanded.set_span(group.span());
+
+                Ok(TokenTree::Group(expanded))
+            }
+            tree => Ok(tree),
+        })
+        .collect()
+}
+
+impl ToTokens for Machines {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for machine in &self.0 {
+            machine.to_tokens(tokens);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Machine {
+    pub name: Ident,
+    pub options: Options,
+    pub initial_states: InitialStates,
+    pub final_states: FinalStates,
+    pub declared_states: Option<States>,
+    pub declared_events: Option<Events>,
+    pub event_groups: Vec<EventGroup>,
+    pub event_parents: Vec<EventParent>,
+    pub state_sets: Vec<StateSet>,
+    pub regions: Vec<Region>,
+    pub properties: Option<Properties>,
+    pub hooks: Option<Hooks>,
+    pub on_invalid: Option<OnInvalid>,
+    pub completions: Vec<Completion>,
+    pub labels: Labels,
+    pub transitions: Transitions,
+}
+
+impl Machine {
+    /// The states declared in the `States` block, or else the ones used by the
+    /// transitions, the initial states, `OnInvalid => ...` and the completion
+    /// transitions.
+    pub fn states(&self) -> States {
+        if let Some(declared_states) = &self.declared_states {
+            return States(declared_states.0.clone());
+        }
+
+        let mut seen = HashSet::new();
+        let mut states: Vec<State> = Vec::new();
+
+        for t in &self.transitions.0 {
+            if seen.insert(t.from.name.to_string()) {
+                states.push(t.from.clone());
+            }
+
+            if seen.insert(t.to.name.to_string()) {
+                states.push(t.to.clone());
+            }
+        }
+
+        for i in &self.initial_states.0 {
+            if seen.insert(i.name.to_string()) {
+                states.push(State {
+                    name: i.name.clone(),
+                });
+            }
+        }
+
+        if let Some(on_invalid) = &self.on_invalid {
+            if seen.insert(on_invalid.0.name.to_string()) {
+                states.push(on_invalid.0.clone());
+            }
+        }
+
+        for c in &self.completions {
+            for s in [&c.from, &c.to] {
+                if seen.insert(s.name.to_string()) {
+                    states.push(s.clone());
+                }
+            }
+        }
+
+        States(states)
+    }
+
+    /// The events declared in the `Events` block, or else the ones used by
+    /// the transitions.
+    pub fn events(&self) -> Vec<Event> {
+        if let Some(declared_events) = &self.declared_events {
+            return declared_events.0.clone();
+        }
+
+        let mut seen = HashSet::new();
+        let mut events: Vec<Event> = Vec::new();
+
+        for t in &self.transitions.0 {
+            if seen.insert(t.event.name.to_string()) {
+                events.push(t.event.clone());
+            }
+        }
+
+        events
+    }
+
+    /// Every state along with all the ways in which it can be entered, which
+    /// become the cases of its `{State}State` enum.
+    ///
+    /// They are listed in the order the transitions are declared in, so that
+    /// the generated code is the same across compilations. With
+    /// `#[provenance(none)]` nothing is recorded, so every state has a single
+    /// provenance, which is never generated.
+    pub fn state_provenances(&self) -> Vec<(State, Vec<Provenance>)> {
+        if self.options.provenance == ProvenanceMode::None {
+            return self
+                .states()
+                .0
+                .into_iter()
+                .map(|s| (s, vec![Provenance::init()]))
+                .collect();
+        }
+
+        // The completion transitions taken after a transition keep its
+        // provenance, and `complete_once` stops at each of the states they
+        // go through.
+        let mut transitions = Vec::new();
+
+        for t in self.transitions.0.iter().chain(&self.invalid_transitions()) {
+            let mut to = &t.to;
+            transitions.push(t.clone());
+
+            while let Some(c) = self.completions.iter().find(|c| c.from.name == to.name) {
+                to = &c.to;
+                transitions.push(Transition {
+                    to: to.clone(),
+                    ..t.clone()
+                });
+            }
+        }
+
+        let transitions = Transitions(transitions);
+        let index = transitions.index();
+
+        self.states()
+            .0
+            .into_iter()
+            .map(|s| {
+                let mut seen = HashSet::new();
+                let mut provenances: Vec<Provenance> = Vec::new();
+
+                for t in index.to(&s.name) {
+                    let provenance = t.provenance(self.options.provenance);
+
+                    if seen.insert(provenance.clone()) {
+                        provenances.push(provenance);
+                    }
+                }
+
+                if self.initial_states.0.iter().any(|is| is.name == s.name) {
+                    provenances.push(Provenance::init());
+                }
+
+                (s, provenances)
+            })
+            .collect()
+    }
+
+    /// The machine in `state`, entered with `provenance`, as an expression or
+    /// a pattern: `Lock::Locked(LockedState::FromInit)`, or `Lock::Locked`
+    /// with `#[provenance(none)]`.
+    pub fn value(&self, state: &Ident, provenance: &Provenance) -> TokenStream {
+        let name = &self.name;
+
+        if self.options.provenance == ProvenanceMode::None {
+            return quote! { #name::#state };
+        }
+
+        let state_enum = state_enum_name(state);
+        let variant = provenance.variant();
+
+        quote! { #name::#state(#state_enum::#variant) }
+    }
+
+    /// A pattern matching the machine in `state` with any provenance, which
+    /// it binds to `binding`: `Lock::Locked(state)`, or `Lock::Locked` with
+    /// `#[provenance(none)]`.
+    pub fn state_pattern(&self, state: &Ident, binding: TokenStream) -> TokenStream {
+        let name = &self.name;
+
+        if self.options.provenance == ProvenanceMode::None {
+            quote! { #name::#state }
+        } else {
+            quote! { #name::#state(#binding) }
+        }
+    }
+
+    /// The type of the provenance of the machine: `LockProvenance`, or `()`
+    /// with `#[provenance(none)]`.
+    pub fn provenance_type(&self) -> TokenStream {
+        if self.options.provenance == ProvenanceMode::None {
+            quote! { () }
+        } else {
+            let provenance_enum = provenance_enum_name(&self.name);
+
+            quote! { #provenance_enum }
+        }
+    }
+
+    /// The value of `provenance`: `LockProvenance::FromInit`, or `()` with
+    /// `#[provenance(none)]`.
+    pub fn provenance_value(&self, provenance: &Provenance) -> TokenStream {
+        if self.options.provenance == ProvenanceMode::None {
+            quote! { () }
+        } else {
+            let provenance_enum = provenance_enum_name(&self.name);
+            let variant = provenance.variant();
+
+            quote! { #provenance_enum::#variant }
+        }
+    }
+
+    /// Whether each state gets a transition method for every event it
+    /// accepts, such as `LockedState::turn_key`. With `#[dispatch(table)]`
+    /// or `#[provenance(none)]`, `transition` is the only way to take a
+    /// transition.
+    pub fn has_transition_methods(&self) -> bool {
+        self.options.dispatch == DispatchMode::Methods
+            && self.options.provenance != ProvenanceMode::None
+    }
+
+    /// The transitions into the state named by `OnInvalid => ...` which
+    /// `transition` takes on the events a state does not accept, for every
+    /// state and every such event.
+    pub fn invalid_transitions(&self) -> Vec<Transition> {
+        let to = match &self.on_invalid {
+            Some(on_invalid) => &on_invalid.0,
+            None => return Vec::new(),
+        };
+
+        let index = self.transitions.index();
+        let events = self.events();
+        let mut transitions = Vec::new();
+
+        for from in &self.states() {
+            let accepted = index
+                .from(&from.name)
+                .iter()
+                .map(|t| t.event.name.to_string())
+                .collect::<HashSet<_>>();
+
+            for event in events
+                .iter()
+                .filter(|e| !accepted.contains(&e.name.to_string()))
+            {
+                transitions.push(Transition {
+                    event: event.clone(),
+                    from: from.clone(),
+                    to: to.clone(),
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                });
+            }
+        }
+
+        transitions
+    }
+
+    /// Whether some transition is declared with `Timeout(30s) { ... }`.
+    pub fn has_timeouts(&self) -> bool {
+        self.transitions.0.iter().any(|t| t.timeout.is_some())
+    }
+
+    /// The state reached from `state` by taking the completion transitions
+    /// declared with `on entry ... => ...`, or `state` itself.
+    pub fn completed(&self, state: &State) -> State {
+        let mut state = state;
+
+        // The completion transitions are checked not to loop.
+        while let Some(c) = self.completions.iter().find(|c| c.from.name == state.name) {
+            state = &c.to;
+        }
+
+        state.clone()
+    }
+
+    /// The declared transitions, leading to the state reached once the
+    /// completion transitions of their target are taken.
+    pub fn completed_transitions(&self) -> Transitions {
+        Transitions(
+            self.transitions
+                .0
+                .iter()
+                .map(|t| Transition {
+                    to: self.completed(&t.to),
+                    ..t.clone()
+                })
+                .collect(),
+        )
+    }
+
+    /// The transitions `transition` can take: the `completed_transitions`,
+    /// followed by the `invalid_transitions`.
+    pub fn dynamic_transitions(&self) -> Transitions {
+        let mut transitions = self.completed_transitions().0;
+        transitions.extend(self.invalid_transitions().into_iter().map(|t| Transition {
+            to: self.completed(&t.to),
+            ..t
+        }));
+
+        Transitions(transitions)
+    }
+
+    /// Every legal combination of state and provenance.
+    pub fn values(&self) -> Vec<(State, Provenance)> {
+        let mut values = Vec::new();
+
+        for (s, provenances) in self.state_provenances() {
+            for p in provenances {
+                values.push((s.clone(), p));
+            }
+        }
+
+        values
+    }
+}
+
+/// The block `name` was probably meant to be, if it looks like a misspelled
+/// `InitialStates` or `FinalStates`.
+fn misspelled_block(name: &Ident) -> Option<&'static str> {
+    let normalized = name.to_string().to_lowercase().replace('_', "");
+
+    match normalized.as_str() {
+        "initialstates" | "initialstate" | "initial" => Some("InitialStates"),
+        "finalstates" | "finalstate" | "final" => Some("FinalStates"),
+        _ => None,
+    }
+}
+
+impl Parse for Machine {
+    /// example machine tokens:
+    ///
+    /// ```text
+    /// #[provenance(full)]
+    /// TurnStile {
+    ///     InitialStates { ... }
+    ///     FinalStates { ... }
+    ///     States { ... }
+    ///     Events { ... }
+    ///     Group UserInput { ... }
+    ///     KeypadPress : UserInput
+    ///     Set Operational = { ... }
+    ///     Region Operational { ... }
+    ///     Properties { ... }
+    ///     Hooks = MyHooks
+    ///     OnInvalid => Faulted
+    ///     on entry Validating => Validated
+    ///
+    ///     Push { ... }
+    ///     Coin { ... }
+    /// }
+    /// ```
+    ///
+    /// The blocks can come in any order, but `InitialStates` is required:
+    ///
+    /// ```text
+    /// TurnStile {
+    ///     Push { ... }
+    ///     InitialStates { ... }
+    /// }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `#[provenance(full)]`
+        //  ^^^^^^^^^^^^^^^^^^^
+        let options = Options::parse(input)?;
+
+        // `TurnStile { ... }`
+        //  ^^^^^^^^^
+        let name: Ident = input.parse()?;
+
+        // `TurnStile { ... }`
+        //              ^^^
+        let block_machine;
+        braced!(block_machine in input);
+
+        if block_machine.is_empty() {
+            return Err(Error::new(
+                name.span(),
+                format!(
+                    "machine `{0}` is empty: it needs an `InitialStates {{ ... }}` block and \
+                     transitions, e.g. `{0} {{ InitialStates {{ Locked }} \
+                     TurnKey {{ Locked => Unlocked }} }}`, or initial states which are also \
+                     final, e.g. `{0} {{ InitialStates {{ Done }} FinalStates {{ Done }} }}`",
+                    name
+                ),
+            ));
+        }
+
+        let mut initial_states = None;
+        let mut final_states = None;
+        let mut declared_states = None;
+        let mut declared_events = None;
+        let mut event_groups = Vec::new();
+        let mut event_parents = Vec::new();
+        let mut state_sets = Vec::new();
+        let mut regions = Vec::new();
+        let mut properties = None;
+        let mut hooks = None;
+        let mut on_invalid = None;
+        let mut completions = Vec::new();
+        let mut transitions = Transitions(Vec::new());
+        let mut labels = Labels::default();
+
+        // The blocks can come in any order.
+        while !block_machine.is_empty() {
+            // `#[deprecated] Push { ... }`
+            //  ^^^^^^^^^^^^^^^^^^^^^^^^^^^
+            if block_machine.peek(Token![#]) {
+                transitions.parse_event(&block_machine)?;
+                continue;
+            }
+
+            let block_name = block_machine.fork().parse::<Ident>()?;
+
+            if InitialStates::peek(&block_machine) {
+                // `InitialStates { ... }`
+                //  ^^^^^^^^^^^^^^^^^^^^^
+                if initial_states.is_some() {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "duplicate `InitialStates { ... }` block",
+                    ));
+                }
+
+                initial_states = Some(InitialStates::parse(&block_machine)?);
+            } else if FinalStates::peek(&block_machine) {
+                // `FinalStates { ... }`
+                //  ^^^^^^^^^^^^^^^^^^^
+                if final_states.is_some() {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "duplicate `FinalStates { ... }` block",
+                    ));
+                }
+
+                final_states = Some(FinalStates::parse(&block_machine)?);
+            } else if States::peek(&block_machine) {
+                // `States { ... }`
+                //  ^^^^^^^^^^^^^^
+                if declared_states.is_some() {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "duplicate `States { ... }` block",
+                    ));
+                }
+
+                declared_states = Some(States::parse_labeled(&block_machine, &mut labels.states)?);
+            } else if Events::peek(&block_machine) {
+                // `Events { ... }`
+                //  ^^^^^^^^^^^^^^
+                if declared_events.is_some() {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "duplicate `Events { ... }` block",
+                    ));
+                }
+
+                declared_events = Some(Events::parse_labeled(&block_machine, &mut labels.events)?);
+            } else if Properties::peek(&block_machine) {
+                // `Properties { ... }`
+                //  ^^^^^^^^^^^^^^^^^^
+                if properties.is_some() {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "duplicate `Properties { ... }` block",
+                    ));
+                }
+
+                properties = Some(Properties::parse(&block_machine)?);
+            } else if Hooks::peek(&block_machine) {
+                // `Hooks = MyHooks`
+                //  ^^^^^^^^^^^^^^^
+                if hooks.is_some() {
+                    return Err(Error::new(block_name.span(), "duplicate `Hooks = ...`"));
+                }
+
+                if options.typestate {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "typestate machines do not support hooks",
+                    ));
+                }
+
+                hooks = Some(Hooks::parse(&block_machine)?);
+            } else if OnInvalid::peek(&block_machine) {
+                // `OnInvalid => Faulted`
+                //  ^^^^^^^^^^^^^^^^^^^^
+                if on_invalid.is_some() {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "duplicate `OnInvalid => ...`",
+                    ));
+                }
+
+                if options.typestate {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "typestate machines reject invalid events at compile time, \
+                         so they cannot fall back to a state with `OnInvalid => ...`",
+                    ));
+                }
+
+                on_invalid = Some(OnInvalid::parse(&block_machine)?);
+            } else if Completion::peek(&block_machine) {
+                // `on entry Validating => Validated`
+                //  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                if options.typestate {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "typestate machines do not support completion transitions",
+                    ));
+                }
+
+                completions.push(Completion::parse(&block_machine)?);
+            } else if EventGroup::peek(&block_machine) {
+                // `Group UserInput { ... }`
+                //  ^^^^^^^^^^^^^^^^^^^^^^^
+                event_groups.push(EventGroup::parse(&block_machine)?);
+            } else if EventParent::peek(&block_machine) {
+                // `KeypadPress : UserInput`
+                //  ^^^^^^^^^^^^^^^^^^^^^^^
+                event_parents.push(EventParent::parse(&block_machine)?);
+            } else if StateSet::peek(&block_machine) {
+                // `Set Operational = { ... }`
+                //  ^^^^^^^^^^^^^^^^^^^^^^^^^
+                state_sets.push(StateSet::parse(&block_machine)?);
+            } else if Region::peek(&block_machine) {
+                // `Region Operational { ... }`
+                //  ^^^^^^^^^^^^^^^^^^^^^^^^^^
+                if options.typestate {
+                    return Err(Error::new(
+                        block_name.span(),
+                        "typestate machines do not support regions",
+                    ));
+                }
+
+                regions.push(Region::parse(&block_machine)?);
+            } else {
+                if let Some(expected) = misspelled_block(&block_name) {
+                    return Err(Error::new(
+                        block_name.span(),
+                        format!(
+                            "unknown block `{}`, did you mean `{}`?",
+                            block_name, expected
+                        ),
+                    ));
+                }
+
+                // `Push { ... }`
+                //  ^^^^^^^^^^^^
+                transitions.parse_event(&block_machine)?;
+            }
+        }
+
+        let initial_states = initial_states.ok_or_else(|| {
+            Error::new(
+                name.span(),
+                format!(
+                    "missing `InitialStates {{ ... }}` block: \
+                     declare the states `{}` can start in, e.g. `InitialStates {{ Locked }}`",
+                    name
+                ),
+            )
+        })?;
+        let final_states = final_states.unwrap_or_default();
+
+        let mut machine = Machine {
+            name,
+            options,
+            initial_states,
+            final_states,
+            declared_states,
+            declared_events,
+            event_groups,
+            event_parents,
+            state_sets,
+            regions,
+            properties,
+            hooks,
+            on_invalid,
+            completions,
+            labels,
+            transitions,
+        };
+
+        state_set::expand(&mut machine)?;
+        event_group::expand(&mut machine)?;
+        event_parent::expand(&mut machine)?;
+        label::normalize(&mut machine)?;
+        region::check(&machine)?;
+        state_bitset::check(&machine)?;
+        completion::check(&machine)?;
+        timeout::check(&machine)?;
+
+        // `as_graph` returns the diagram as a graph.
+        if machine.options.petgraph && machine.options.provenance != ProvenanceMode::None {
+            if let Some(s) = machine
+                .states()
+                .0
+                .iter()
+                .find(|s| state_accessor_name(&s.name) == "as_graph")
+            {
+                return Err(Error::new(
+                    s.name.span(),
+                    format!("state `{}` would clash with the method `as_graph`", s.name),
+                ));
+            }
+        }
+
+        if machine.options.packed && packed_repr(machine.values().len()).is_none() {
+            return Err(Error::new(
+                machine.name.span(),
+                format!(
+                    "`#[packed]` machines fit in a `u16`, but `{}` has {} combinations \
+                     of state and provenance",
+                    machine.name,
+                    machine.values().len()
+                ),
+            ));
+        }
+
+        lints::check(&machine)?;
+
+        Ok(machine)
+    }
+}
+
+impl ToTokens for Machine {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        Warnings { machine: self }.to_tokens(tokens);
+
+        let prelude = if self.options.prelude {
+            Some(Prelude { machine: self })
+        } else {
+            None
+        };
+
+        if self.options.typestate {
+            Typestate { machine: self }.to_tokens(tokens);
+            prelude.to_tokens(tokens);
+            return;
+        }
+
+        let name = &self.name;
+        let docs = &self.options.docs;
+
+        // With `#[provenance(none)]`, the states of the machine enum are unit
+        // variants and nothing about provenance is generated.
+        let provenance = self.options.provenance != ProvenanceMode::None;
+
+        let machine_enum = if provenance {
+            Some(MachineEnum { machine: self })
+        } else {
+            None
+        };
+
+        let summary = Summary { machine: self };
+
+        let states = &self.states();
+
+        let state_kinds = StateKinds {
+            enum_name: name,
+            labels: &self.labels,
+            states,
+            inline: self.options.inline,
+        };
+
+        let events = &self.events();
+
+        let state_map = StateMap { machine: self };
+        let state_bitset = StateBitset { machine: self };
+
+        let event_kinds = EventKinds {
+            enum_name: name,
+            labels: &self.labels,
+            events,
+            inline: self.options.inline,
+        };
+
+        let event_map = EventMap { machine: self };
+
+        let event_markers = EventMarkers {
+            enum_name: name,
+            events,
+        };
+
+        let regions = if self.regions.is_empty() {
+            None
+        } else {
+            Some(Regions { machine: self })
+        };
+
+        let state_sets = if self.state_sets.is_empty() {
+            None
+        } else {
+            Some(StateSets { machine: self })
+        };
+
+        let settle = if self.completions.is_empty() {
+            None
+        } else {
+            Some(Settle { machine: self })
+        };
+
+        let event_groups = if self.event_groups.is_empty() {
+            None
+        } else {
+            Some(EventGroups {
+                enum_name: name,
+                groups: &self.event_groups,
+                events,
+                inline: self.options.inline,
+            })
+        };
+
+        let event_parents = if self.event_parents.is_empty() {
+            None
+        } else {
+            Some(EventParents {
+                enum_name: name,
+                parents: &self.event_parents,
+                events,
+                inline: self.options.inline,
+            })
+        };
+
+        let variants = if provenance {
+            quote! { #states }
+        } else {
+            let states = states.0.iter().map(|s| &s.name);
+
+            quote! { #(#states),* }
+        };
+
+        let provenance_kinds = if provenance {
+            Some(ProvenanceKinds { machine: self })
+        } else {
+            None
+        };
+
+        let state_count = Literal::usize_unsuffixed(states.0.len());
+        let event_count = Literal::usize_unsuffixed(events.len());
+
+        let values = self.values();
+        let value_count = Literal::usize_unsuffixed(values.len());
+        let all_values = values.iter().map(|(s, p)| self.value(&s.name, p));
+
+        let initial_states = &self.initial_states.to_fn(name, self.options.provenance);
+        let initial_state_iters = InitialStateIters {
+            enum_name: name,
+            initial_states: &self.initial_states,
+            inline: self.options.inline,
+        };
+
+        let dot = diagram::dot(self);
+        let mermaid = diagram::mermaid(self);
+        let description = diagram::description(self);
+
+        let machine_parts = MachineParts { machine: self };
+
+        let dynamic_transitions = DynamicTransitions { machine: self };
+        let replay = Replay { machine: self };
+        let snapshot = Snapshot { machine: self };
+
+        let undo = Undo { machine: self };
+
+        let packed = if self.options.packed {
+            Some(Packed { machine: self })
+        } else {
+            None
+        };
+        let paths = Paths { machine: self };
+
+        let reachable = Reachable { machine: self };
+
+        let state_machine_impl = StateMachineImpl { machine: self };
+        let runner_alias = RunnerAlias { machine: self };
+        let interrupt_runner_alias = if self.options.critical_section {
+            Some(InterruptRunnerAlias { machine: self })
+        } else {
+            None
+        };
+
+        let arbitrary_impls = if self.options.arbitrary {
+            Some(ArbitraryImpls { machine: self })
+        } else {
+            None
+        };
+
+        let defmt_impls = if self.options.defmt {
+            Some(DefmtImpls { machine: self })
+        } else {
+            None
+        };
+
+        let petgraph_impls = if self.options.petgraph {
+            Some(PetgraphImpls { machine: self })
+        } else {
+            None
+        };
+
+        let actix_impls = if self.options.actix {
+            Some(ActixImpls { machine: self })
+        } else {
+            None
+        };
+
+        let tokio_runner = if self.options.tokio {
+            Some(TokioRunner { machine: self })
+        } else {
+            None
+        };
+
+        let proptest_strategies = if self.options.proptest {
+            Some(ProptestStrategies { machine: self })
+        } else {
+            None
+        };
+
+        let generated_tests = if self.options.generated_tests {
+            Some(GeneratedTests { machine: self })
+        } else {
+            None
+        };
+
+        let shortest_path = if self.options.shortest_path {
+            Some(ShortestPath { machine: self })
+        } else {
+            None
+        };
+
+        let timeouts = if self.has_timeouts() {
+            Some(Timeouts { machine: self })
+        } else {
+            None
+        };
+
+        let random_walk = if self.options.rand {
+            Some(RandomWalk { machine: self })
+        } else {
+            None
+        };
+
+        let simulate_step = if self.options.rand {
+            Some(SimulateStep { machine: self })
+        } else {
+            None
+        };
+
+        let wasm_bindings = if self.options.wasm {
+            Some(WasmBindings { machine: self })
+        } else {
+            None
+        };
+
+        let simulator = if self.options.simulate {
+            Some(Simulator { machine: self })
+        } else {
+            None
+        };
+
+        let hooks_trait = self.hooks.as_ref().map(|_| HooksTrait { enum_name: name });
+
+        let async_hooks = if self.options.async_hooks {
+            Some(AsyncHooks { enum_name: name })
+        } else {
+            None
+        };
+
+        let provenance_accessors = if provenance {
+            Some(ProvenanceAccessors { machine: self })
+        } else {
+            None
+        };
+
+        let state_accessors = if provenance {
+            Some(StateAccessors {
+                enum_name: name,
+                states,
+                inline: self.options.inline,
+            })
+        } else {
+            None
+        };
+
+        // With `#[dispatch(table)]`, the transition methods and the
+        // `Transition` impls calling them give way to a single table. With
+        // `#[provenance(none)]` there are no state types to put them on.
+        let (state_transitions, transition_impls, transition_table) =
+            if self.options.dispatch == DispatchMode::Table {
+                (None, None, Some(TransitionTable { machine: self }))
+            } else if !self.has_transition_methods() {
+                (None, None, None)
+            } else {
+                (
+                    Some(StateTransitions {
+                        enum_name: name,
+                        provenance: self.options.provenance,
+                        hooks: self.hooks.as_ref().map(|h| &h.0),
+                        tracing: self.options.tracing,
+                        metrics: self.options.metrics,
+                        inline: self.options.inline,
+                        labels: &self.labels,
+                        states,
+                        transitions: &self.transitions,
+                    }),
+                    Some(TransitionImpls { machine: self }),
+                    None,
+                )
+            };
+
+        tokens.extend(quote! {
+            #machine_enum
+
+            #(#docs)*
+            #summary
+            #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+            pub enum #name {
+                #variants
+            }
+
+            #state_kinds
+
+            #state_map
+
+            #state_bitset
+
+            #event_kinds
+
+            #event_map
+
+            #event_groups
+
+            #event_parents
+
+            #event_markers
+
+            #regions
+
+            #state_sets
+
+            #settle
+
+            #provenance_kinds
+
+            impl #name {
+                pub const STATE_COUNT: usize = #state_count;
+                pub const EVENT_COUNT: usize = #event_count;
+
+                /// Every state the machine can be in, with every provenance
+                /// it can be entered with.
+                pub const ALL: [#name; #value_count] = [#(#all_values),*];
+
+                /// A Graphviz description of the diagram.
+                pub const DOT: &'static str = #dot;
+
+                /// A Mermaid `stateDiagram-v2` description of the diagram.
+                pub const MERMAID: &'static str = #mermaid;
+
+                /// A JSON description of the states, events and transitions
+                /// of the machine.
+                pub const DESCRIPTION: &'static str = #description;
+
+                #initial_states
+
+                #initial_state_iters
+            }
+
+            #machine_parts
+
+            #transition_table
+
+            #dynamic_transitions
+
+            #replay
+
+            #snapshot
+
+            #undo
+
+            #packed
+
+            #paths
+
+            #reachable
+
+            #state_machine_impl
+
+            #runner_alias
+
+            #interrupt_runner_alias
+
+            #provenance_accessors
+
+            #state_accessors
+
+            #hooks_trait
+
+            #async_hooks
+
+            #state_transitions
+
+            #transition_impls
+
+            #arbitrary_impls
+
+            #proptest_strategies
+
+            #timeouts
+
+            #random_walk
+
+            #simulate_step
+
+            #shortest_path
+
+            #defmt_impls
+
+            #petgraph_impls
+
+            #simulator
+
+            #actix_impls
+
+            #tokio_runner
+
+            #wasm_bindings
+
+            #generated_tests
+
+            #prelude
+        });
+    }
+}
+
+#[cfg(test)]
+mod machines_tests {
+    use super::*;
+    use crate::{event::Event, initial_state::InitialState, transition::Transition};
+    use proc_macro2::{Literal, Span, TokenStream};
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_machine_parse() {
+        let left: Machine = syn::parse2(quote! {
+           TurnStile {
+               InitialStates { Locked, Unlocked }
+
+               Coin { Locked => Unlocked }
+               Push { Unlocked => Locked }
+           }
+        })
+        .unwrap();
+
+        let right = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![
+                InitialState {
+                    name: parse_quote! { Locked },
+                },
+                InitialState {
+                    name: parse_quote! { Unlocked },
+                },
+            ]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Coin },
+                    },
+                    from: State {
+                        name: parse_quote! { Locked },
+                    },
+                    to: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Push },
+                    },
+                    from: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    to: State {
+                        name: parse_quote! { Locked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+            ]),
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_machine_provenances_order() {
+        let machine: Machine = syn::parse2(quote! {
+           TurnStile {
+               InitialStates { Locked }
+
+               Push { Unlocked => Locked }
+               Kick { Unlocked => Locked }
+               Coin { Locked => Unlocked }
+               Bump { Unlocked => Locked }
+           }
+        })
+        .unwrap();
+
+        let (_, provenances) = machine
+            .state_provenances()
+            .into_iter()
+            .find(|(s, _)| s.name == "Locked")
+            .unwrap();
+
+        let variants = provenances
+            .iter()
+            .map(|p| p.variant().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            variants,
+            vec!["FromPush", "FromKick", "FromBump", "FromInit"]
+        );
+    }
+
+    #[test]
+    fn test_machine_parse_block_order() {
+        let left: Machine = syn::parse2(quote! {
+           TurnStile {
+               Coin { Locked => Unlocked }
+               InitialStates { Locked, Unlocked }
+               Push { Unlocked => Locked }
+           }
+        })
+        .unwrap();
+
+        let right: Machine = syn::parse2(quote! {
+           TurnStile {
+               InitialStates { Locked, Unlocked }
+
+               Coin { Locked => Unlocked }
+               Push { Unlocked => Locked }
+           }
+        })
+        .unwrap();
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_machine_parse_missing_initial_states() {
+        let result: Result<Machine> = syn::parse2(quote! {
+           TurnStile {
+               Coin { Locked => Unlocked }
+           }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "missing `InitialStates { ... }` block: \
+             declare the states `TurnStile` can start in, e.g. `InitialStates { Locked }`"
+        );
+    }
+
+    #[test]
+    fn test_machine_parse_empty() {
+        let result: Result<Machine> = syn::parse2(quote! {
+           Lock { }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "machine `Lock` is empty: it needs an `InitialStates { ... }` block and \
+             transitions, e.g. `Lock { InitialStates { Locked } TurnKey { Locked => Unlocked } }`, \
+             or initial states which are also final, \
+             e.g. `Lock { InitialStates { Done } FinalStates { Done } }`"
+        );
+    }
+
+    #[test]
+    fn test_machine_parse_misspelled_block() {
+        let result: Result<Machine> = syn::parse2(quote! {
+           TurnStile {
+               Initial_State { Locked }
+               Coin { Locked => Unlocked }
+           }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unknown block `Initial_State`, did you mean `InitialStates`?"
+        );
+    }
+
+    #[test]
+    fn test_machine_parse_hooks() {
+        let machine: Machine = syn::parse2(quote! {
+           TurnStile {
+               InitialStates { Locked }
+               Hooks = hooks::TurnStileCounter
+               Coin { Locked => Unlocked }
+               Push { Unlocked => Locked }
+           }
+        })
+        .unwrap();
+
+        assert_eq!(
+            machine.hooks,
+            Some(Hooks(parse_quote! { hooks::TurnStileCounter }))
+        );
+
+        let result: Result<Machine> = syn::parse2(quote! {
+           #[typestate]
+           TurnStile {
+               InitialStates { Locked }
+               Hooks = TurnStileCounter
+               Coin { Locked => Unlocked }
+           }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "typestate machines do not support hooks"
+        );
+    }
+
+    #[test]
+    fn test_machine_parse_on_invalid() {
+        let machine: Machine = syn::parse2(quote! {
+           Modem {
+               InitialStates { Idle }
+               FinalStates { Faulted }
+               OnInvalid => Faulted
+               Dial { Idle => Connected }
+               HangUp { Connected => Idle }
+           }
+        })
+        .unwrap();
+
+        let invalid = machine
+            .invalid_transitions()
+            .iter()
+            .map(|t| format!("{} {} => {}", t.event.name, t.from.name, t.to.name))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            invalid,
+            vec![
+                "HangUp Idle => Faulted",
+                "Dial Connected => Faulted",
+                "Dial Faulted => Faulted",
+                "HangUp Faulted => Faulted",
+            ]
+        );
+
+        let (_, provenances) = machine
+            .state_provenances()
+            .into_iter()
+            .find(|(s, _)| s.name == "Faulted")
+            .unwrap();
+
+        let variants = provenances
+            .iter()
+            .map(|p| p.variant().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(variants, vec!["FromHangUp", "FromDial"]);
+
+        let cases = vec![
+            (
+                quote! {
+                   Modem {
+                       InitialStates { Idle }
+                       OnInvalid => Faulted
+                       OnInvalid => Idle
+                       Dial { Idle => Faulted }
+                   }
+                },
+                "duplicate `OnInvalid => ...`",
+            ),
+            (
+                quote! {
+                   #[typestate]
+                   Modem {
+                       InitialStates { Idle }
+                       OnInvalid => Faulted
+                       Dial { Idle => Faulted }
+                   }
+                },
+                "typestate machines reject invalid events at compile time, \
+                 so they cannot fall back to a state with `OnInvalid => ...`",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let result: Result<Machine> = syn::parse2(tokens);
+
+            assert_eq!(result.unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_machines_parse() {
+        let machines: Machines = syn::parse2(quote! {
+           TurnStile {
+               InitialStates { Locked }
+               Coin { Locked => Locked }
+           }
+
+           Lock {
+               InitialStates { Shut }
+               TurnKey { Shut => Shut }
+           }
+        })
+        .unwrap();
+
+        let names = machines
+            .0
+            .iter()
+            .map(|m| m.name.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["TurnStile", "Lock"]);
+    }
+
+    #[test]
+    fn test_machines_parse_duplicate_names() {
+        let error = syn::parse2::<Machines>(quote! {
+           TurnStile {
+               InitialStates { Locked }
+               Coin { Locked => Locked }
+           }
+
+           TurnStile {
+               InitialStates { Unlocked }
+               Push { Unlocked => Unlocked }
+           }
+        })
+        .unwrap_err();
+
+        let messages = error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "duplicate machine `TurnStile`",
+                "machine `TurnStile` first declared here",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_machines_parse_generated_type_clashes() {
+        let error = syn::parse2::<Machines>(quote! {
+           Door {
+               InitialStates { Closed }
+               Open { Closed => Opened }
+               Close { Opened => Closed }
+           }
+
+           Window {
+               InitialStates { Closed }
+               Open { Closed => Ajar }
+               Close { Ajar => Closed }
+           }
+        })
+        .unwrap_err();
+
+        let messages = error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "state `Closed` of the machine `Window` generates the type `ClosedState`, \
+                 which clashes with the state `Closed` of the machine `Door`",
+                "state `Closed` of the machine `Door` declared here",
+                "event `Open` of the machine `Window` generates the type `OpenEvent`, \
+                 which clashes with the event `Open` of the machine `Door`",
+                "event `Open` of the machine `Door` declared here",
+                "event `Close` of the machine `Window` generates the type `CloseEvent`, \
+                 which clashes with the event `Close` of the machine `Door`",
+                "event `Close` of the machine `Door` declared here",
+            ]
+        );
+
+        let error = syn::parse2::<Machines>(quote! {
+           Lock {
+               InitialStates { Locked }
+               TurnKey { Locked => Locked }
+           }
+
+           LockKind {
+               InitialStates { Pin }
+               Cut { Pin => Pin }
+           }
+        })
+        .unwrap_err();
+
+        let messages = error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "machine `LockKind` generates the type `LockKind`, \
+                 which clashes with the machine `Lock`",
+                "machine `Lock` declared here",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_machines_parse_templates() {
+        let machines: Machines = syn::parse2(quote! {
+            Client {
+                InitialStates { Idle }
+                FinalStates { Connected }
+                Retry(Idle, Connected, ClientBackoff, ClientFail, ClientTimer, Connect)
+            }
+
+            Template Retry(Start, Done, Backoff, Fail, Timer, Succeed) {
+                Fail { Start => Backoff }
+                Timer { Backoff => Start }
+                Succeed { Start => Done }
+            }
+
+            Server {
+                InitialStates { Binding }
+                FinalStates { Listening }
+                Retry(Binding, Listening, ServerBackoff, ServerFail, ServerTimer, Listen)
+            }
+        })
+        .unwrap();
+
+        let transitions = machines
+            .0
+            .iter()
+            .map(|m| {
+                m.transitions
+                    .0
+                    .iter()
+                    .map(|t| format!("{} {} => {}", t.event.name, t.from.name, t.to.name))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            transitions,
+            vec![
+                vec![
+                    "ClientFail Idle => ClientBackoff",
+                    "ClientTimer ClientBackoff => Idle",
+                    "Connect Idle => Connected",
+                ],
+                vec![
+                    "ServerFail Binding => ServerBackoff",
+                    "ServerTimer ServerBackoff => Binding",
+                    "Listen Binding => Listening",
+                ],
+            ]
+        );
+
+        let error = syn::parse2::<Machines>(quote! {
+            Template Retry(Start) { }
+            Template Retry(Start) { }
+        })
+        .unwrap_err();
+
+        assert_eq!(error.to_string(), "duplicate template `Retry`");
+    }
+
+    #[test]
+    fn test_machine_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![
+                InitialState {
+                    name: parse_quote! { Unlocked },
+                },
+                InitialState {
+                    name: parse_quote! { Locked },
+                },
+            ]),
+            final_states: FinalStates(vec![State {
+                name: parse_quote! { Locked },
+            }]),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Push },
+                },
+                from: State {
+                    name: parse_quote! { Unlocked },
+                },
+                to: State {
+                    name: parse_quote! { Locked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let fingerprint = Literal::u64_suffixed(crate::snapshot::fingerprint(&machine));
+
+        let left = quote! {
+            #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+            #[allow(clippy::enum_variant_names)]
+            pub enum UnlockedState {
+                FromInit
+            }
+
+            #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+
+            #[allow(clippy::enum_variant_names)]
+            pub enum LockedState {
+                FromPush,
+                FromInit
+            }
+
+            #[doc = " # Diagram"]
+            #[doc = ""]
+            #[doc = " States: `Unlocked`, `Locked`."]
+            #[doc = ""]
+            #[doc = " Initial states: `Unlocked`, `Locked`."]
+            #[doc = ""]
+            #[doc = " Final states: `Locked`."]
+            #[doc = ""]
+            #[doc = " Events: `Push`."]
+            #[doc = ""]
+            #[doc = " Transitions:"]
+            #[doc = ""]
+            #[doc = " - `Unlocked` --`Push`--> `Locked`"]
+            #[doc = ""]
+            #[doc = " | | `Push` |"]
+            #[doc = " |---|---|"]
+            #[doc = " | `Unlocked` | `Locked` |"]
+            #[doc = " | `Locked` |  |"]
+            #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+            pub enum TurnStile {
+                Unlocked(UnlockedState),
+                Locked(LockedState)
+            }
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum TurnStileKind {
+                Unlocked,
+                Locked
+            }
+
+            impl TurnStileKind {
+                pub fn name(self) -> &'static str {
+                    match self {
+                        TurnStileKind::Unlocked => "Unlocked",
+                        TurnStileKind::Locked => "Locked"
+                    }
+                }
+            }
+
+            #[doc = "A value for each state of `TurnStile`, stored in an array indexed by `TurnStileKind`."]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileStateMap<T>([T; TurnStile::STATE_COUNT]);
+
+            impl<T> TurnStileStateMap<T> {
+                /// Every state, in the order of the values.
+                const KINDS: [TurnStileKind; TurnStile::STATE_COUNT] = [TurnStileKind::Unlocked, TurnStileKind::Locked];
+
+                /// The map holding `f(kind)` for each state `kind`, called in
+                /// declaration order.
+                pub fn from_fn(mut f: impl FnMut(TurnStileKind) -> T) -> Self {
+                    TurnStileStateMap([f(TurnStileKind::Unlocked), f(TurnStileKind::Locked)])
+                }
+
+                /// The value of the state `kind`.
+                pub fn get(&self, kind: TurnStileKind) -> &T {
+                    &self.0[kind as usize]
+                }
+
+                /// The value of the state `kind`, mutably.
+                pub fn get_mut(&mut self, kind: TurnStileKind) -> &mut T {
+                    &mut self.0[kind as usize]
+                }
+
+                /// The states and their values, in declaration order.
+                pub fn iter(&self) -> impl Iterator<Item = (TurnStileKind, &T)> {
+                    Self::KINDS.iter().copied().zip(self.0.iter())
+                }
+
+                /// The states and their values, mutably, in declaration
+                /// order.
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (TurnStileKind, &mut T)> {
+                    Self::KINDS.iter().copied().zip(self.0.iter_mut())
+                }
+
+                /// The values, in the declaration order of their states.
+                pub fn values(&self) -> &[T] {
+                    &self.0
+                }
+            }
+
+            impl<T: Default> Default for TurnStileStateMap<T> {
+                fn default() -> Self {
+                    TurnStileStateMap::from_fn(|_| T::default())
+                }
+            }
+
+            impl<T> ::core::ops::Index<TurnStileKind> for TurnStileStateMap<T> {
+                type Output = T;
+
+                fn index(&self, kind: TurnStileKind) -> &T {
+                    self.get(kind)
+                }
+            }
+
+            impl<T> ::core::ops::IndexMut<TurnStileKind> for TurnStileStateMap<T> {
+                fn index_mut(&mut self, kind: TurnStileKind) -> &mut T {
+                    self.get_mut(kind)
+                }
+            }
+
+            #[doc = "A set of states of `TurnStile`, stored as the bits of a `u32`."]
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct TurnStileStateSet(u32);
+
+            impl TurnStileStateSet {
+                /// The set without any state.
+                pub const EMPTY: Self = TurnStileStateSet(0);
+
+                /// The set of every state.
+                pub const ALL: Self = TurnStileStateSet(3);
+
+                /// The set holding only `kind`.
+                pub const fn of(kind: TurnStileKind) -> Self {
+                    TurnStileStateSet(1 << (kind as u32))
+                }
+
+                /// Whether `kind` is in the set.
+                pub const fn contains(self, kind: TurnStileKind) -> bool {
+                    self.0 & Self::of(kind).0 != 0
+                }
+
+                /// Add `kind` to the set, returning whether it was not in it
+                /// yet.
+                pub fn insert(&mut self, kind: TurnStileKind) -> bool {
+                    let absent = !self.contains(kind);
+                    self.0 |= Self::of(kind).0;
+                    absent
+                }
+
+                /// Remove `kind` from the set, returning whether it was in it.
+                pub fn remove(&mut self, kind: TurnStileKind) -> bool {
+                    let present = self.contains(kind);
+                    self.0 &= !Self::of(kind).0;
+                    present
+                }
+
+                /// The states in either set.
+                pub const fn union(self, other: Self) -> Self {
+                    TurnStileStateSet(self.0 | other.0)
+                }
+
+                /// The states in both sets.
+                pub const fn intersection(self, other: Self) -> Self {
+                    TurnStileStateSet(self.0 & other.0)
+                }
+
+                /// The states of `self` which are not in `other`.
+                pub const fn difference(self, other: Self) -> Self {
+                    TurnStileStateSet(self.0 & !other.0)
+                }
+
+                /// The states which are not in the set.
+                pub const fn complement(self) -> Self {
+                    TurnStileStateSet(!self.0 & Self::ALL.0)
+                }
+
+                /// Whether every state of `self` is in `other`.
+                pub const fn is_subset(self, other: Self) -> bool {
+                    self.0 & !other.0 == 0
+                }
+
+                /// Whether the set has no state.
+                pub const fn is_empty(self) -> bool {
+                    self.0 == 0
+                }
+
+                /// The number of states in the set.
+                pub const fn len(self) -> usize {
+                    self.0.count_ones() as usize
+                }
+
+                /// The states in the set, in declaration order.
+                pub fn iter(self) -> impl Iterator<Item = TurnStileKind> {
+                    ::core::iter::IntoIterator::into_iter([TurnStileKind::Unlocked, TurnStileKind::Locked])
+                        .filter(move |&kind| self.contains(kind))
+                }
+
+                /// The bits of the set, where bit `i` stands for the state
+                /// whose index in the kind enum is `i`.
+                pub const fn to_bits(self) -> u32 {
+                    self.0
+                }
+
+                /// The set with the given bits, or `None` if a bit standing
+                /// for no state is set.
+                pub const fn from_bits(bits: u32) -> Option<Self> {
+                    if bits & !Self::ALL.0 == 0 {
+                        Some(TurnStileStateSet(bits))
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            impl From<TurnStileKind> for TurnStileStateSet {
+                fn from(kind: TurnStileKind) -> Self {
+                    TurnStileStateSet::of(kind)
+                }
+            }
+
+            impl ::core::iter::FromIterator<TurnStileKind> for TurnStileStateSet {
+                fn from_iter<I: IntoIterator<Item = TurnStileKind>>(kinds: I) -> Self {
+                    let mut set = TurnStileStateSet::EMPTY;
+                    set.extend(kinds);
+                    set
+                }
+            }
+
+            impl ::core::iter::Extend<TurnStileKind> for TurnStileStateSet {
+                fn extend<I: IntoIterator<Item = TurnStileKind>>(&mut self, kinds: I) {
+                    for kind in kinds {
+                        let _ = self.insert(kind);
+                    }
+                }
+            }
+
+            impl ::core::ops::BitOr for TurnStileStateSet {
+                type Output = Self;
+
+                fn bitor(self, other: Self) -> Self {
+                    self.union(other)
+                }
+            }
+
+            impl ::core::ops::BitAnd for TurnStileStateSet {
+                type Output = Self;
+
+                fn bitand(self, other: Self) -> Self {
+                    self.intersection(other)
+                }
+            }
+
+            impl ::core::ops::Sub for TurnStileStateSet {
+                type Output = Self;
+
+                fn sub(self, other: Self) -> Self {
+                    self.difference(other)
+                }
+            }
+
+            impl ::core::ops::Not for TurnStileStateSet {
+                type Output = Self;
+
+                fn not(self) -> Self {
+                    self.complement()
+                }
+            }
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum TurnStileEvent {
+                Push
+            }
+
+            impl TurnStileEvent {
+                pub fn name(self) -> &'static str {
+                    match self {
+                        TurnStileEvent::Push => "Push"
+                    }
+                }
+            }
+
+            #[doc = "A value for each event of `TurnStile`, stored in an array indexed by `TurnStileEvent`."]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileEventMap<T>([T; TurnStile::EVENT_COUNT]);
+
+            impl<T> TurnStileEventMap<T> {
+                /// Every event, in the order of the values.
+                const EVENTS: [TurnStileEvent; TurnStile::EVENT_COUNT] = [TurnStileEvent::Push];
+
+                /// The map holding `f(event)` for each event, called in
+                /// declaration order.
+                pub fn from_fn(mut f: impl FnMut(TurnStileEvent) -> T) -> Self {
+                    TurnStileEventMap([f(TurnStileEvent::Push)])
+                }
+
+                /// The value for `event`.
+                pub fn get(&self, event: TurnStileEvent) -> &T {
+                    &self.0[event as usize]
+                }
+
+                /// The value for `event`, mutably.
+                pub fn get_mut(&mut self, event: TurnStileEvent) -> &mut T {
+                    &mut self.0[event as usize]
+                }
+
+                /// The events and their values, in declaration order.
+                pub fn iter(&self) -> impl Iterator<Item = (TurnStileEvent, &T)> {
+                    Self::EVENTS.iter().copied().zip(self.0.iter())
+                }
+
+                /// The events and their values, mutably, in declaration
+                /// order.
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (TurnStileEvent, &mut T)> {
+                    Self::EVENTS.iter().copied().zip(self.0.iter_mut())
+                }
+
+                /// The values, in the declaration order of their events.
+                pub fn values(&self) -> &[T] {
+                    &self.0
+                }
+            }
+
+            impl<T: Default> Default for TurnStileEventMap<T> {
+                fn default() -> Self {
+                    TurnStileEventMap::from_fn(|_| T::default())
+                }
+            }
+
+            impl<T> ::core::ops::Index<TurnStileEvent> for TurnStileEventMap<T> {
+                type Output = T;
+
+                fn index(&self, event: TurnStileEvent) -> &T {
+                    self.get(event)
+                }
+            }
+
+            impl<T> ::core::ops::IndexMut<TurnStileEvent> for TurnStileEventMap<T> {
+                fn index_mut(&mut self, event: TurnStileEvent) -> &mut T {
+                    self.get_mut(event)
+                }
+            }
+
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct PushEvent;
+
+            impl From<PushEvent> for TurnStileEvent {
+                fn from(_: PushEvent) -> TurnStileEvent {
+                    TurnStileEvent::Push
+                }
+            }
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[allow(clippy::enum_variant_names)]
+            pub enum TurnStileProvenance {
+                FromInit,
+                FromPush
+            }
+
+            impl TurnStile {
+                pub const STATE_COUNT: usize = 2;
+                pub const EVENT_COUNT: usize = 1;
+
+                /// Every state the machine can be in, with every provenance
+                /// it can be entered with.
+                pub const ALL: [TurnStile; 3] = [
+                    TurnStile::Unlocked(UnlockedState::FromInit),
+                    TurnStile::Locked(LockedState::FromPush),
+                    TurnStile::Locked(LockedState::FromInit)
+                ];
+
+                /// A Graphviz description of the diagram.
+                pub const DOT: &'static str = "digraph TurnStile {\n    __start [shape=point];\n    Unlocked [shape=circle];\n    Locked [shape=doublecircle];\n    __start -> Unlocked;\n    __start -> Locked;\n    Unlocked -> Locked [label=\"Push\"];\n}";
+
+                /// A Mermaid `stateDiagram-v2` description of the diagram.
+                pub const MERMAID: &'static str = "stateDiagram-v2\n    [*] --> Unlocked\n    [*] --> Locked\n    Unlocked --> Locked : Push\n    Locked --> [*]";
+
+                /// A JSON description of the states, events and transitions
+                /// of the machine.
+                pub const DESCRIPTION: &'static str = "{\"name\":\"TurnStile\",\"states\":[\"Unlocked\",\"Locked\"],\"events\":[\"Push\"],\"initial_states\":[\"Unlocked\",\"Locked\"],\"final_states\":[\"Locked\"],\"transitions\":[{\"event\":\"Push\",\"from\":\"Unlocked\",\"to\":\"Locked\"}]}";
+
+                #[must_use = "this returns a new machine, which does nothing unless it is used"]
+                pub fn unlocked() -> TurnStile {
+                    TurnStile::Unlocked(UnlockedState::FromInit)
+                }
+
+                #[must_use = "this returns a new machine, which does nothing unless it is used"]
+                pub fn locked() -> TurnStile {
+                    TurnStile::Locked(LockedState::FromInit)
+                }
+
+                /// The states the machine can start in.
+                pub fn initial_kinds() -> impl Iterator<Item = TurnStileKind> {
+                    ::core::iter::IntoIterator::into_iter([TurnStileKind::Unlocked, TurnStileKind::Locked])
+                }
+
+                /// The machine in each of the states it can start in, as built by
+                /// their constructors.
+                pub fn initial_states() -> impl Iterator<Item = TurnStile> {
+                    ::core::iter::IntoIterator::into_iter([TurnStile::unlocked(), TurnStile::locked()])
+                }
+            }
+
+            impl TurnStile {
+                pub fn kind(&self) -> TurnStileKind {
+                    match self {
+                        TurnStile::Unlocked(_) => TurnStileKind::Unlocked,
+                        TurnStile::Locked(_) => TurnStileKind::Locked
+                    }
+                }
+
+                pub fn name(&self) -> &'static str {
+                    self.kind().name()
+                }
+
+                /// Whether the machine is in one of its final states.
+                pub fn is_final(&self) -> bool {
+                    matches!(self.kind(), TurnStileKind::Locked)
+                }
+
+                /// Whether the machine is in the same kind of state as
+                /// `other`, however either of them entered it.
+                pub fn same_kind(&self, other: &TurnStile) -> bool {
+                    self.kind() == other.kind()
+                }
+
+                pub fn provenance(&self) -> TurnStileProvenance {
+                    match self {
+                        TurnStile::Unlocked(UnlockedState::FromInit) => TurnStileProvenance::FromInit,
+                        TurnStile::Locked(LockedState::FromPush) => TurnStileProvenance::FromPush,
+                        TurnStile::Locked(LockedState::FromInit) => TurnStileProvenance::FromInit,
+                    }
+                }
+
+                /// Build the machine in an arbitrary state, without going
+                /// through its transitions. Returns `None` if the state
+                /// cannot be entered with this provenance.
+                #[cfg(test)]
+                pub fn at(kind: TurnStileKind, provenance: TurnStileProvenance) -> Option<TurnStile> {
+                    match (kind, provenance) {
+                        (TurnStileKind::Unlocked, TurnStileProvenance::FromInit) => {
+                            Some(TurnStile::Unlocked(UnlockedState::FromInit))
+                        }
+                        (TurnStileKind::Locked, TurnStileProvenance::FromPush) => {
+                            Some(TurnStile::Locked(LockedState::FromPush))
+                        }
+                        (TurnStileKind::Locked, TurnStileProvenance::FromInit) => {
+                            Some(TurnStile::Locked(LockedState::FromInit))
+                        }
+                        _ => None
+                    }
+                }
+
+                /// Save the kind and provenance of the machine to `persist`.
+                pub fn save<P: ::sad_machine::Persist<TurnStile>>(&self, persist: &P) -> Result<(), P::Error> {
+                    persist.save(self.kind(), self.provenance())
+                }
+
+                /// Resume the machine saved in `persist`, or start from `init`
+                /// if nothing was saved yet.
+                pub fn load_or_init<P: ::sad_machine::Persist<TurnStile>>(
+                    persist: &P,
+                    init: TurnStile,
+                ) -> Result<TurnStile, ::sad_machine::LoadError<TurnStileKind, TurnStileProvenance, P::Error>> {
+                    let (kind, provenance) = match persist.load() {
+                        Ok(Some(saved)) => saved,
+                        Ok(None) => return Ok(init),
+                        Err(error) => return Err(::sad_machine::LoadError::Persist(error)),
+                    };
+
+                    let machine = match (kind, provenance) {
+                        (TurnStileKind::Unlocked, TurnStileProvenance::FromInit) => {
+                        Some(TurnStile::Unlocked(UnlockedState::FromInit))
+                        }
+                        (TurnStileKind::Locked, TurnStileProvenance::FromPush) => {
+                        Some(TurnStile::Locked(LockedState::FromPush))
+                        }
+                        (TurnStileKind::Locked, TurnStileProvenance::FromInit) => {
+                        Some(TurnStile::Locked(LockedState::FromInit))
+                        }
+                        _ => None
+                    };
+
+                    machine.ok_or(::sad_machine::LoadError::InvalidState(kind, provenance))
+                }
+            }
+
+            impl From<&TurnStile> for TurnStileKind {
+                fn from(machine: &TurnStile) -> TurnStileKind {
+                    machine.kind()
+                }
+            }
+
+            impl From<&TurnStile> for &'static str {
+                fn from(machine: &TurnStile) -> &'static str {
+                    machine.name()
+                }
+            }
+
+            impl PartialEq<TurnStileKind> for TurnStile {
+                fn eq(&self, kind: &TurnStileKind) -> bool {
+                    self.kind() == *kind
+                }
+            }
+
+            impl PartialEq<TurnStile> for TurnStileKind {
+                fn eq(&self, machine: &TurnStile) -> bool {
+                    *self == machine.kind()
+                }
+            }
+
+            impl From<UnlockedState> for TurnStile {
+                fn from(state: UnlockedState) -> TurnStile {
+                    TurnStile::Unlocked(state)
+                }
+            }
+
+            impl ::core::convert::TryFrom<TurnStile> for UnlockedState {
+                type Error = ::sad_machine::WrongState<TurnStileKind>;
+
+                fn try_from(machine: TurnStile) -> Result<UnlockedState, Self::Error> {
+                    match machine {
+                        TurnStile::Unlocked(state) => Ok(state),
+                        other => Err(::sad_machine::WrongState {
+                            expected: TurnStileKind::Unlocked,
+                            actual: other.kind(),
+                        })
+                    }
+                }
+            }
+
+            impl From<LockedState> for TurnStile {
+                fn from(state: LockedState) -> TurnStile {
+                    TurnStile::Locked(state)
+                }
+            }
+
+            impl ::core::convert::TryFrom<TurnStile> for LockedState {
+                type Error = ::sad_machine::WrongState<TurnStileKind>;
+
+                fn try_from(machine: TurnStile) -> Result<LockedState, Self::Error> {
+                    match machine {
+                        TurnStile::Locked(state) => Ok(state),
+                        other => Err(::sad_machine::WrongState {
+                            expected: TurnStileKind::Locked,
+                            actual: other.kind(),
+                        })
+                    }
+                }
+            }
+
+            /// A transition taken by the machine.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileStep {
+                pub from: TurnStileKind,
+                pub event: TurnStileEvent,
+                pub to: TurnStileKind,
+            }
+
+            impl TurnStileStep {
+                /// The description of the transition given with
+                /// `Coin "..." { ... }`, if any.
+                pub fn label(&self) -> Option<&'static str> {
+                    None
+                }
+            }
+
+            /// The error returned when the machine rejects an event.
+            pub type TurnStileError = ::sad_machine::InvalidTransition<TurnStileKind, TurnStileEvent>;
+
+            impl TurnStile {
+                /// Apply `event` to the machine, returning the next state, or
+                /// `None` if the current state does not accept it.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn transition(&self, event: TurnStileEvent) -> Option<TurnStile> {
+                    match (self, event) {
+                        (TurnStile::Unlocked(state), TurnStileEvent::Push) => Some(state.push()),
+                        _ => None
+                    }
+                }
+
+                /// Like `transition`, but return an error carrying the current
+                /// state, the rejected event and the location of the call.
+                #[track_caller]
+                pub fn try_transition(&self, event: TurnStileEvent) -> Result<TurnStile, TurnStileError> {
+                    self.transition(event).ok_or(TurnStileError::new(self.kind(), event))
+                }
+
+                /// Like `transition`, but also return a record of the
+                /// transition that was taken.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn step(&self, event: TurnStileEvent) -> Option<(TurnStile, TurnStileStep)> {
+                    let next = self.transition(event)?;
+                    let step = TurnStileStep {
+                        from: self.kind(),
+                        event,
+                        to: next.kind(),
+                    };
+
+                    Some((next, step))
+                }
+
+                /// Apply `events` in order, stopping at the first one which is
+                /// not accepted. On failure, returns the state reached so far
+                /// and the rejected event.
+                pub fn apply_all(
+                    self,
+                    events: impl IntoIterator<Item = TurnStileEvent>,
+                ) -> Result<TurnStile, (TurnStile, TurnStileEvent)> {
+                    let mut machine = self;
+
+                    for event in events {
+                        machine = match machine.transition(event) {
+                            Some(next) => next,
+                            None => return Err((machine, event)),
+                        };
+                    }
+
+                    Ok(machine)
+                }
+            }
+
+            /// The error returned when an event log cannot be replayed.
+            pub type TurnStileReplayError = ::sad_machine::ReplayError<TurnStileKind, TurnStileEvent>;
+
+            impl TurnStile {
+                /// Reconstruct the machine from an event log, starting in the
+                /// initial state `initial` and applying `events` in order.
+                /// Fails if `initial` is not an initial state, or with the
+                /// index of the first event which is not accepted.
+                pub fn replay(
+                    initial: TurnStileKind,
+                    events: &[TurnStileEvent],
+                ) -> Result<TurnStile, TurnStileReplayError> {
+                    let mut machine = match initial {
+                        TurnStileKind::Unlocked => TurnStile::Unlocked(UnlockedState::FromInit),
+                        TurnStileKind::Locked => TurnStile::Locked(LockedState::FromInit),
+                    };
+
+                    for (index, &event) in events.iter().enumerate() {
+                        machine = match machine.transition(event) {
+                            Some(next) => next,
+                            None => {
+                                return Err(TurnStileReplayError::InvalidEvent {
+                                    index,
+                                    kind: machine.kind(),
+                                    event,
+                                })
+                            }
+                        };
+                    }
+
+                    Ok(machine)
+                }
+            }
+
+            /// The state of the machine, as saved by `snapshot`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileSnapshot {
+                /// The `FINGERPRINT` of the diagram the snapshot was taken
+                /// with.
+                pub fingerprint: u64,
+                /// The index of the state and provenance of the machine.
+                pub state: u32,
+            }
+
+            impl TurnStile {
+                /// A hash of the diagram, which changes whenever the states,
+                /// events, initial states, transitions, completion
+                /// transitions, fallback state or provenance mode of the
+                /// machine do, but not with its labels, weights or final
+                /// states.
+                pub const FINGERPRINT: u64 = #fingerprint;
+
+                /// Save the state of the machine along with the fingerprint
+                /// of the diagram.
+                pub fn snapshot(&self) -> TurnStileSnapshot {
+                    let state = match self {
+                        TurnStile::Unlocked(UnlockedState::FromInit) => 0,
+                        TurnStile::Locked(LockedState::FromPush) => 1,
+                        TurnStile::Locked(LockedState::FromInit) => 2,
+                    };
+
+                    TurnStileSnapshot {
+                        fingerprint: TurnStile::FINGERPRINT,
+                        state,
+                    }
+                }
+
+                /// Restore a machine saved by `snapshot`. Fails if the
+                /// snapshot was taken with a different diagram.
+                pub fn restore(
+                    snapshot: TurnStileSnapshot,
+                ) -> Result<TurnStile, ::sad_machine::RestoreError> {
+                    if snapshot.fingerprint != TurnStile::FINGERPRINT {
+                        return Err(::sad_machine::RestoreError::Fingerprint(snapshot.fingerprint));
+                    }
+
+                    match snapshot.state {
+                        0 => Ok(TurnStile::Unlocked(UnlockedState::FromInit)),
+                        1 => Ok(TurnStile::Locked(LockedState::FromPush)),
+                        2 => Ok(TurnStile::Locked(LockedState::FromInit)),
+                        state => Err(::sad_machine::RestoreError::UnknownState(state)),
+                    }
+                }
+
+                /// Like `restore`, but hand the snapshots taken with a
+                /// different diagram to `migrate`, which fails the restore by
+                /// returning `None`.
+                pub fn restore_or_migrate(
+                    snapshot: TurnStileSnapshot,
+                    migrate: impl FnOnce(TurnStileSnapshot) -> Option<TurnStile>,
+                ) -> Result<TurnStile, ::sad_machine::RestoreError> {
+                    match TurnStile::restore(snapshot) {
+                        Err(::sad_machine::RestoreError::Fingerprint(fingerprint)) => {
+                            migrate(snapshot).ok_or(::sad_machine::RestoreError::Fingerprint(fingerprint))
+                        }
+                        result => result,
+                    }
+                }
+            }
+
+            impl TurnStile {
+                /// Revert the transition recorded in `step` by taking the edge
+                /// leading back to its source. Returns `None` if the machine
+                /// is not in the state the step led to, or if there is not
+                /// exactly one edge leading back.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn undo(&self, step: TurnStileStep) -> Option<TurnStile> {
+                    match (self, step) {
+                        _ => None,
+                    }
+                }
+            }
+
+            #[cfg(test)]
+            impl TurnStile {
+                /// Every simple path through the diagram, which enters no
+                /// state twice, starting from an initial state and taking at
+                /// least one transition, shortest first, up to the first 1024.
+                pub const PATHS: &'static [::sad_machine::Path<TurnStileKind, TurnStileEvent>] = &[
+                    ::sad_machine::Path {
+                        from: TurnStileKind::Unlocked,
+                        events: &[TurnStileEvent::Push],
+                        to: TurnStileKind::Locked,
+                    }
+                ];
+
+                /// Whether the diagram has more than 1024 simple paths, so
+                /// that `PATHS` leaves out some of the longest ones.
+                pub const PATHS_TRUNCATED: bool = false;
+
+                /// The `PATHS` taking at most `max_len` transitions and, with
+                /// `terminal_only`, ending in a final state or in a state
+                /// without outgoing transitions. If `PATHS_TRUNCATED`, only
+                /// the paths shorter than the last one in `PATHS` are all
+                /// there.
+                pub fn paths(
+                    max_len: usize,
+                    terminal_only: bool,
+                ) -> impl Iterator<Item = ::sad_machine::Path<TurnStileKind, TurnStileEvent>> {
+                    TurnStile::PATHS.iter().copied().filter(move |path| {
+                        path.events.len() <= max_len && (!terminal_only || matches!(path.to, TurnStileKind::Locked))
+                    })
+                }
+            }
+
+            impl TurnStile {
+                /// The states the machine can reach from the state `kind` by
+                /// taking one or more transitions, in declaration order. A
+                /// state the machine cannot leave reaches none.
+                pub fn reachable_from(kind: TurnStileKind) -> &'static [TurnStileKind] {
+                    match kind {
+                        TurnStileKind::Unlocked => &[TurnStileKind::Locked],
+                        TurnStileKind::Locked => &[]
+                    }
+                }
+            }
+
+            impl ::sad_machine::StateMachine for TurnStile {
+                type Kind = TurnStileKind;
+                type Event = TurnStileEvent;
+                type Provenance = TurnStileProvenance;
+
+                const STATE_COUNT: usize = TurnStile::STATE_COUNT;
+                const EVENT_COUNT: usize = TurnStile::EVENT_COUNT;
+                const EVENTS: &'static [TurnStileEvent] = &[TurnStileEvent::Push];
+                const TRANSITIONS: &'static [::sad_machine::Edge<TurnStileKind, TurnStileEvent>] = &[
+                    ::sad_machine::Edge {
+                        from: TurnStileKind::Unlocked,
+                        event: TurnStileEvent::Push,
+                        to: TurnStileKind::Locked,
+                        label: None,
+                    }
+                ];
+
+                fn kind(&self) -> TurnStileKind {
+                    TurnStile::kind(self)
+                }
+
+                fn provenance(&self) -> TurnStileProvenance {
+                    TurnStile::provenance(self)
+                }
+
+                fn transition(&self, event: TurnStileEvent) -> Option<TurnStile> {
+                    TurnStile::transition(self, event)
+                }
+            }
+
+            /// The machine driven by a queue of at most `N` events, processed
+            /// one at a time to completion.
+            pub type TurnStileRunner<const N: usize = 16> = ::sad_machine::Runner<TurnStile, N>;
+
+            impl UnlockedState {
+                pub fn event(&self) -> Option<TurnStileEvent> {
+                    match self {
+                        UnlockedState::FromInit => None
+                    }
+                }
+            }
+
+            impl LockedState {
+                pub fn event(&self) -> Option<TurnStileEvent> {
+                    match self {
+                        LockedState::FromPush => Some(TurnStileEvent::Push),
+                        LockedState::FromInit => None
+                    }
+                }
+            }
+
+            impl TurnStile {
+                #[doc = "The `UnlockedState` of the machine, if it is in the state `Unlocked`."]
+                pub fn as_unlocked(&self) -> Option<&UnlockedState> {
+                    match self {
+                        TurnStile::Unlocked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `UnlockedState` of the machine, if it is in the state `Unlocked`."]
+                pub fn as_unlocked_mut(&mut self) -> Option<&mut UnlockedState> {
+                    match self {
+                        TurnStile::Unlocked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `UnlockedState` of the machine, if it is in the state `Unlocked`."]
+                pub fn into_unlocked(self) -> Option<UnlockedState> {
+                    match self {
+                        TurnStile::Unlocked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `LockedState` of the machine, if it is in the state `Locked`."]
+                pub fn as_locked(&self) -> Option<&LockedState> {
+                    match self {
+                        TurnStile::Locked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `LockedState` of the machine, if it is in the state `Locked`."]
+                pub fn as_locked_mut(&mut self) -> Option<&mut LockedState> {
+                    match self {
+                        TurnStile::Locked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `LockedState` of the machine, if it is in the state `Locked`."]
+                pub fn into_locked(self) -> Option<LockedState> {
+                    match self {
+                        TurnStile::Locked(state) => Some(state),
+                        _ => None
+                    }
+                }
+            }
+
+            impl UnlockedState {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn push(&self) -> TurnStile {
+                    TurnStile::Locked(LockedState::FromPush)
+                }
+            }
+
+            impl ::sad_machine::Transition<PushEvent> for UnlockedState {
+                type Machine = TurnStile;
+
+                fn transition(&self, _: PushEvent) -> TurnStile {
+                    self.push()
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        machine.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_machine_expand_large() {
+        // A protocol-sized machine: 400 states in a ring, each accepting 8 of
+        // 30 events.
+        let states = (0..400)
+            .map(|i| Ident::new(&format!("S{}", i), Span::call_site()))
+            .collect::<Vec<_>>();
+
+        let blocks = (0..30).map(|e| {
+            let event = Ident::new(&format!("E{}", e), Span::call_site());
+            let transitions = (0..400).filter(|i| (i + e) % 30 < 8).map(|i| {
+                let from = &states[i];
+                let to = &states[(i + e + 1) % 400];
+
+                quote! { #from => #to }
+            });
+
+            quote! { #event { #(#transitions)* } }
+        });
+
+        let machine: Machine = syn::parse2(quote! {
+            #[allow(unreachable_states, dead_end_states)]
+            Protocol {
+                InitialStates { S0 }
+
+                #(#blocks)*
+            }
+        })
+        .unwrap();
+
+        assert_eq!(machine.states().0.len(), 400);
+        assert_eq!(machine.transitions.0.len(), 3200);
+        let paths = crate::paths::simple_paths(&machine);
+        assert_eq!(paths.paths.len(), 1024);
+        assert!(paths.truncated);
+        assert!(paths.paths.windows(2).all(|w| w[0].1.len() <= w[1].1.len()));
+        assert!(!machine.to_token_stream().is_empty());
+    }
+
+    #[test]
+    fn test_machine_parse_packed_too_large() {
+        // Every state is entered from every state by its own event, which
+        // makes 257 * 257 combinations of state and provenance, plus the
+        // initial one.
+        let states = (0..257)
+            .map(|i| Ident::new(&format!("S{}", i), Span::call_site()))
+            .collect::<Vec<_>>();
+
+        let blocks = states.iter().enumerate().map(|(e, to)| {
+            let event = Ident::new(&format!("E{}", e), Span::call_site());
+
+            quote! { #event { #(#states),* => #to } }
+        });
+
+        let error = syn::parse2::<Machine>(quote! {
+            #[packed]
+            #[provenance(full)]
+            Protocol {
+                InitialStates { S0 }
+
+                #(#blocks)*
+            }
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`#[packed]` machines fit in a `u16`, but `Protocol` has 66050 combinations \
+             of state and provenance"
+        );
+    }
+}
+
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+struct MachineEnum<'a> {
+    machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for MachineEnum<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        for (s, provenances) in self.machine.state_provenances() {
+            let state_enum = state_enum_name(&s.name);
+
+            let events = provenances
+                .iter()
+                .map(Provenance::variant)
+                .collect::<Vec<_>>();
+
+            let state_enum = &state_enum;
+            let events = &events;
+
+            tokens.extend(quote! {
+                #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+                #[allow(clippy::enum_variant_names)]
+                pub enum #state_enum {
+                    #(#events),*
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod machine_enum_tests {
+    use super::*;
+    use crate::{event::Event, initial_state::InitialState, transition::Transition};
+    use proc_macro2::TokenStream;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_machine_enum_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { turn_stile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![
+                InitialState {
+                    name: parse_quote! { Locked },
+                },
+                InitialState {
+                    name: parse_quote! { Unlocked },
+                },
+            ]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Coin },
+                    },
+                    from: State {
+                        name: parse_quote! { Locked },
+                    },
+                    to: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Push },
+                    },
+                    from: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    to: State {
+                        name: parse_quote! { Locked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+            ]),
+        };
+
+        let machine_enum = MachineEnum { machine: &machine };
+
+        let left = quote! {
+            #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+            #[allow(clippy::enum_variant_names)]
+            pub enum LockedState {
+                FromPush,
+                FromInit
+            }
+
+            #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+
+            #[allow(clippy::enum_variant_names)]
+            pub enum UnlockedState {
+                FromCoin,
+                FromInit
+            }
+        };
+
+        let mut right = TokenStream::new();
+        machine_enum.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
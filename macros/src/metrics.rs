@@ -0,0 +1,97 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use crate::{ident::unraw, label::Labels, transition::Transition};
+
+/// The `metrics` counter incremented by the method taking the transition `t` of
+/// the machine `machine`, generated for machines with `#[metrics]`. `from`
+/// evaluates to the name of the state left.
+pub(crate) fn transition_counter(
+    machine: &Ident,
+    labels: &Labels,
+    from: &TokenStream,
+    t: &Transition,
+) -> TokenStream {
+    let event = labels.event(&t.event.name);
+    let to = labels.state(&t.to.name);
+
+    increment_counter(machine, from, &quote! { #event }, &quote! { #to })
+}
+
+/// The `metrics` counter incremented by `transition` with
+/// `#[dispatch(table)]`, labelled with names only known at run time.
+pub(crate) fn dispatched_transition_counter(machine: &Ident) -> TokenStream {
+    increment_counter(
+        machine,
+        &quote! { self.name() },
+        &quote! { event.name() },
+        &quote! { to.name() },
+    )
+}
+
+fn increment_counter(
+    machine: &Ident,
+    from: &TokenStream,
+    event: &TokenStream,
+    to: &TokenStream,
+) -> TokenStream {
+    let machine = unraw(machine);
+
+    quote! {
+        ::metrics::counter!(
+            "sad_machine_transitions_total",
+            "machine" => #machine,
+            "from" => #from,
+            "event" => #event,
+            "to" => #to
+        )
+        .increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event::Event, state::State};
+    use syn::parse_quote;
+
+    #[test]
+    fn test_transition_counter() {
+        let transition = Transition {
+            event: Event {
+                name: parse_quote! { Coin },
+            },
+            from: State {
+                name: parse_quote! { Locked },
+            },
+            to: State {
+                name: parse_quote! { Unlocked },
+            },
+            label: None,
+            weight: None,
+            deprecated: None,
+            timeout: None,
+        };
+
+        let left = quote! {
+            ::metrics::counter!(
+                "sad_machine_transitions_total",
+                "machine" => "TurnStile",
+                "from" => "Locked",
+                "event" => "Coin",
+                "to" => "Unlocked"
+            )
+            .increment(1);
+        };
+
+        let right = transition_counter(
+            &parse_quote! { TurnStile },
+            &Labels::default(),
+            &quote! { "Locked" },
+            &transition,
+        );
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,64 @@
+use syn::{
+    parse::{Parse, ParseStream, Result},
+    Error, Ident, Token,
+};
+
+use crate::state::State;
+
+/// The state named by `OnInvalid => ...`, which `transition` moves to on the
+/// events the current state does not accept, instead of returning `None`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct OnInvalid(pub State);
+
+impl OnInvalid {
+    /// Whether the next tokens are an `OnInvalid => ...` declaration.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>()
+            .is_ok_and(|ident| ident == "OnInvalid")
+            && fork.peek(Token![=>])
+    }
+}
+
+impl Parse for OnInvalid {
+    /// example on invalid tokens:
+    ///
+    /// ```text
+    /// OnInvalid => Faulted
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `OnInvalid => Faulted`
+        //  ^^^^^^^^^
+        let name: Ident = input.parse()?;
+
+        if name != "OnInvalid" {
+            return Err(Error::new(name.span(), "expected `OnInvalid => ...`"));
+        }
+
+        // `OnInvalid => Faulted`
+        //            ^^
+        let _: Token![=>] = input.parse()?;
+
+        // `OnInvalid => Faulted`
+        //               ^^^^^^^
+        Ok(OnInvalid(input.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_on_invalid_parse() {
+        let left: OnInvalid = parse2(quote! { OnInvalid => Faulted }).unwrap();
+        let right = OnInvalid(State {
+            name: parse_quote! { Faulted },
+        });
+
+        assert_eq!(left, right);
+    }
+}
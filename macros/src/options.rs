@@ -0,0 +1,918 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{Parse, ParseStream, Result},
+    Attribute, Error, Lit, Meta, NestedMeta,
+};
+
+use crate::lints::LintLevel;
+
+/// How much information the `{State}State` enums record about the way a
+/// state was entered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum ProvenanceMode {
+    /// `FromCoin`: the event that caused the transition.
+    #[default]
+    Event,
+    /// `FromCoinAtLocked`: the event and the state it was received in.
+    Full,
+    /// No `{State}State` enums at all: the states of the machine enum are
+    /// unit variants.
+    None,
+}
+
+/// How the machine applies an event to its current state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum DispatchMode {
+    /// A transition method for every state and event it accepts, which
+    /// `transition` matches on.
+    #[default]
+    Methods,
+    /// A single `transition` looking the next state up in a static table.
+    Table,
+}
+
+/// The inline hint put on the transition methods and accessors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum InlineMode {
+    /// No hint: the compiler decides.
+    #[default]
+    Auto,
+    /// `#[inline]`
+    Inline,
+    /// `#[inline(always)]`
+    Always,
+}
+
+impl ToTokens for InlineMode {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            InlineMode::Auto => {}
+            InlineMode::Inline => tokens.extend(quote! { #[inline] }),
+            InlineMode::Always => tokens.extend(quote! { #[inline(always)] }),
+        }
+    }
+}
+
+/// Where `#[write_diagrams]` writes the rendered diagrams.
+#[derive(Debug, PartialEq)]
+pub(crate) enum DiagramDir {
+    /// `#[write_diagrams]`: the `OUT_DIR` of the build script.
+    OutDir,
+    /// `#[write_diagrams("docs/diagrams")]`: a directory relative to the
+    /// manifest of the crate.
+    Path(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Options {
+    pub docs: Vec<Attribute>,
+    pub provenance: ProvenanceMode,
+    /// `#[dispatch(methods)]` or `#[dispatch(table)]`.
+    pub dispatch: DispatchMode,
+    /// `#[typestate]`: generate a zero-sized type per state and consuming
+    /// transition methods instead of the machine enum.
+    pub typestate: bool,
+    /// `#[packed]`: generate `{Machine}Packed`, storing the machine in a
+    /// single `u8` or `u16`.
+    pub packed: bool,
+    /// `#[inline]` or `#[inline(always)]`.
+    pub inline: InlineMode,
+    /// `#[prelude]`: generate `{machine}_prelude`, re-exporting the types
+    /// generated for the machine.
+    pub prelude: bool,
+    /// Set by the `test-util` feature: test helpers are generated outside of
+    /// `#[cfg(test)]`, so that integration tests can use them too.
+    pub test_util: bool,
+    /// `#[arbitrary]`: implement `arbitrary::Arbitrary` for the machine, its
+    /// kinds and its events.
+    pub arbitrary: bool,
+    /// `#[defmt]`: implement `defmt::Format` for every generated type.
+    pub defmt: bool,
+    /// `#[petgraph]`: generate `as_graph`, returning the diagram as a
+    /// `petgraph` graph.
+    pub petgraph: bool,
+    /// `#[simulate]`: generate `simulate` helpers reading events from standard
+    /// input.
+    pub simulate: bool,
+    /// `#[async_hooks]`: generate the `{Machine}AsyncHooks` trait and
+    /// `transition_async`.
+    pub async_hooks: bool,
+    /// `#[tracing]`: emit a `tracing` event from every transition method.
+    pub tracing: bool,
+    /// `#[metrics]`: increment a `metrics` counter from every transition
+    /// method.
+    pub metrics: bool,
+    /// `#[actix]`: generate `{Machine}Actor`, hosting the machine as an `actix`
+    /// actor.
+    pub actix: bool,
+    /// `#[tokio]`: generate `spawn_runner`, driving the machine in a `tokio`
+    /// task.
+    pub tokio: bool,
+    /// Set by the `critical-section` feature: generate
+    /// `{Machine}InterruptRunner`.
+    pub critical_section: bool,
+    /// `#[wasm]`: generate `{Machine}Wasm`, exporting the machine to JavaScript
+    /// with `wasm-bindgen`.
+    pub wasm: bool,
+    /// `#[proptest]`: generate `valid_sequences` and `invalid_sequences`,
+    /// building `proptest` strategies.
+    pub proptest: bool,
+    /// `#[rand]`: generate `random_walk` and `simulate_step`.
+    pub rand: bool,
+    /// Set by the `alloc` feature: the search called by `path` is provided by
+    /// `sad_machine`, so `#[shortest_path]` is accepted.
+    pub alloc: bool,
+    /// `#[shortest_path]`: generate `path`, finding a shortest sequence of
+    /// events between two states.
+    pub shortest_path: bool,
+    /// `#[no_std]`: fail the expansion if another option would make the
+    /// machine need `std` or `alloc`.
+    pub no_std: bool,
+    /// `#[generated_tests]`: emit a `#[cfg(test)]` module checking that the
+    /// generated code agrees with the diagram.
+    pub generated_tests: bool,
+    /// `#[allow/warn/deny(unreachable_states)]`: how states which cannot be
+    /// reached from any initial state are reported.
+    pub unreachable_states: LintLevel,
+    /// `#[allow/warn/deny(dead_end_states)]`: how states without outgoing
+    /// transitions which are not declared final are reported.
+    pub dead_end_states: LintLevel,
+    /// `#[allow/warn/deny(unused_events)]`: how events declared in the
+    /// `Events` block without any transition are reported. Warns by default.
+    pub unused_events: LintLevel,
+    /// `#[write_diagrams]`: write the DOT and Mermaid diagrams to files during
+    /// expansion.
+    pub write_diagrams: Option<DiagramDir>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            docs: Vec::new(),
+            provenance: ProvenanceMode::default(),
+            dispatch: DispatchMode::default(),
+            typestate: false,
+            packed: false,
+            inline: InlineMode::default(),
+            prelude: false,
+            test_util: false,
+            arbitrary: false,
+            defmt: false,
+            petgraph: false,
+            simulate: false,
+            async_hooks: false,
+            tracing: false,
+            metrics: false,
+            actix: false,
+            tokio: false,
+            critical_section: false,
+            wasm: false,
+            proptest: false,
+            rand: false,
+            alloc: false,
+            shortest_path: false,
+            no_std: false,
+            generated_tests: false,
+            unreachable_states: LintLevel::Deny,
+            dead_end_states: LintLevel::Deny,
+            unused_events: LintLevel::Warn,
+            write_diagrams: None,
+        }
+    }
+}
+
+impl Parse for Options {
+    /// example options tokens:
+    ///
+    /// ```text
+    /// /// A lock with a key.
+    /// #[provenance(full)]
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let mut options = Options {
+            test_util: cfg!(feature = "test-util"),
+            critical_section: cfg!(feature = "critical-section"),
+            alloc: cfg!(feature = "alloc"),
+            ..Options::default()
+        };
+
+        let mut provenance_attr = None;
+        let mut dispatch_attr = None;
+        let mut no_std_attr = None;
+        let mut generated_tests_attr = None;
+        let mut packed_attr = None;
+        let mut shortest_path_attr = None;
+        // The options generating code for the machine enum, which typestate
+        // machines do not have.
+        let mut integration_attrs = Vec::new();
+
+        for attr in input.call(Attribute::parse_outer)? {
+            // `/// A lock with a key.`
+            //  ^^^^^^^^^^^^^^^^^^^^^^
+            if attr.path.is_ident("doc") {
+                options.docs.push(attr);
+                continue;
+            }
+
+            // `#[provenance(full)]`
+            //    ^^^^^^^^^^^^^^^^
+            match attr.parse_meta()? {
+                Meta::List(list) if list.path.is_ident("provenance") => {
+                    let mode = match list.nested.iter().collect::<Vec<_>>().as_slice() {
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("event") => {
+                            ProvenanceMode::Event
+                        }
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("full") => {
+                            ProvenanceMode::Full
+                        }
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("none") => {
+                            ProvenanceMode::None
+                        }
+                        _ => {
+                            return Err(Error::new_spanned(
+                                list,
+                                "expected `#[provenance(event)]`, `#[provenance(full)]` or \
+                                 `#[provenance(none)]`",
+                            ))
+                        }
+                    };
+
+                    options.provenance = mode;
+                    provenance_attr = Some(list);
+                }
+
+                // `#[dispatch(table)]`
+                //    ^^^^^^^^^^^^^^^
+                Meta::List(list) if list.path.is_ident("dispatch") => {
+                    let mode = match list.nested.iter().collect::<Vec<_>>().as_slice() {
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("methods") => {
+                            DispatchMode::Methods
+                        }
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("table") => {
+                            DispatchMode::Table
+                        }
+                        _ => {
+                            return Err(Error::new_spanned(
+                                list,
+                                "expected `#[dispatch(methods)]` or `#[dispatch(table)]`",
+                            ))
+                        }
+                    };
+
+                    options.dispatch = mode;
+                    dispatch_attr = Some(list);
+                }
+
+                // `#[typestate]`
+                //    ^^^^^^^^^
+                Meta::Path(path) if path.is_ident("typestate") => {
+                    options.typestate = true;
+                }
+
+                // `#[packed]`
+                //    ^^^^^^
+                Meta::Path(path) if path.is_ident("packed") => {
+                    options.packed = true;
+                    packed_attr = Some(path);
+                }
+
+                // `#[inline]`
+                //    ^^^^^^
+                Meta::Path(path) if path.is_ident("inline") => {
+                    options.inline = InlineMode::Inline;
+                }
+
+                // `#[inline(always)]`
+                //    ^^^^^^^^^^^^^^
+                Meta::List(list) if list.path.is_ident("inline") => {
+                    match list.nested.iter().collect::<Vec<_>>().as_slice() {
+                        [NestedMeta::Meta(Meta::Path(path))] if path.is_ident("always") => {
+                            options.inline = InlineMode::Always;
+                        }
+                        _ => {
+                            return Err(Error::new_spanned(
+                                list,
+                                "expected `#[inline]` or `#[inline(always)]`",
+                            ))
+                        }
+                    }
+                }
+
+                // `#[arbitrary]`
+                //    ^^^^^^^^^
+                Meta::Path(path) if path.is_ident("arbitrary") => {
+                    options.arbitrary = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[defmt]`
+                //    ^^^^^
+                Meta::Path(path) if path.is_ident("defmt") => {
+                    options.defmt = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[simulate]`
+                //    ^^^^^^^^
+                Meta::Path(path) if path.is_ident("simulate") => {
+                    options.simulate = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[petgraph]`
+                //    ^^^^^^^^
+                Meta::Path(path) if path.is_ident("petgraph") => {
+                    options.petgraph = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[async_hooks]`
+                //    ^^^^^^^^^^^
+                Meta::Path(path) if path.is_ident("async_hooks") => {
+                    options.async_hooks = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[tracing]`
+                //    ^^^^^^^
+                Meta::Path(path) if path.is_ident("tracing") => {
+                    options.tracing = true;
+                }
+
+                // `#[metrics]`
+                //    ^^^^^^^
+                Meta::Path(path) if path.is_ident("metrics") => {
+                    options.metrics = true;
+                }
+
+                // `#[actix]`
+                //    ^^^^^
+                Meta::Path(path) if path.is_ident("actix") => {
+                    options.actix = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[tokio]`
+                //    ^^^^^
+                Meta::Path(path) if path.is_ident("tokio") => {
+                    options.tokio = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[wasm]`
+                //    ^^^^
+                Meta::Path(path) if path.is_ident("wasm") => {
+                    options.wasm = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[proptest]`
+                //    ^^^^^^^^
+                Meta::Path(path) if path.is_ident("proptest") => {
+                    options.proptest = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[rand]`
+                //    ^^^^
+                Meta::Path(path) if path.is_ident("rand") => {
+                    options.rand = true;
+                    integration_attrs.push(path);
+                }
+
+                // `#[shortest_path]`
+                //    ^^^^^^^^^^^^^
+                Meta::Path(path) if path.is_ident("shortest_path") => {
+                    options.shortest_path = true;
+                    shortest_path_attr = Some(path.clone());
+                    integration_attrs.push(path);
+                }
+
+                // `#[prelude]`
+                //    ^^^^^^^
+                Meta::Path(path) if path.is_ident("prelude") => {
+                    options.prelude = true;
+                }
+
+                // `#[no_std]`
+                //    ^^^^^^
+                Meta::Path(path) if path.is_ident("no_std") => {
+                    options.no_std = true;
+                    no_std_attr = Some(path);
+                }
+
+                // `#[generated_tests]`
+                //    ^^^^^^^^^^^^^^^
+                Meta::Path(path) if path.is_ident("generated_tests") => {
+                    options.generated_tests = true;
+                    generated_tests_attr = Some(path);
+                }
+
+                // `#[write_diagrams]`
+                //    ^^^^^^^^^^^^^^
+                Meta::Path(path) if path.is_ident("write_diagrams") => {
+                    options.write_diagrams = Some(DiagramDir::OutDir);
+                }
+
+                // `#[write_diagrams("docs/diagrams")]`
+                //    ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                Meta::List(list) if list.path.is_ident("write_diagrams") => {
+                    match list.nested.iter().collect::<Vec<_>>().as_slice() {
+                        [NestedMeta::Lit(Lit::Str(dir))] => {
+                            options.write_diagrams = Some(DiagramDir::Path(dir.value()));
+                        }
+                        _ => {
+                            return Err(Error::new_spanned(
+                                list,
+                                "expected `#[write_diagrams]` or `#[write_diagrams(\"path\")]`",
+                            ))
+                        }
+                    }
+                }
+
+                // `#[warn(unreachable_states)]`
+                //    ^^^^^^^^^^^^^^^^^^^^^^^^
+                Meta::List(list)
+                    if list.path.is_ident("allow")
+                        || list.path.is_ident("warn")
+                        || list.path.is_ident("deny") =>
+                {
+                    let level = if list.path.is_ident("allow") {
+                        LintLevel::Allow
+                    } else if list.path.is_ident("warn") {
+                        LintLevel::Warn
+                    } else {
+                        LintLevel::Deny
+                    };
+
+                    for nested in &list.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::Path(path))
+                                if path.is_ident("unreachable_states") =>
+                            {
+                                options.unreachable_states = level;
+                            }
+                            NestedMeta::Meta(Meta::Path(path))
+                                if path.is_ident("dead_end_states") =>
+                            {
+                                options.dead_end_states = level;
+                            }
+                            NestedMeta::Meta(Meta::Path(path))
+                                if path.is_ident("unused_events") =>
+                            {
+                                options.unused_events = level;
+                            }
+                            _ => return Err(Error::new_spanned(nested, "unknown lint")),
+                        }
+                    }
+                }
+
+                meta => return Err(Error::new_spanned(meta, "unknown state machine option")),
+            }
+        }
+
+        if let (true, Some(attr)) = (options.typestate, provenance_attr) {
+            return Err(Error::new_spanned(
+                attr,
+                "typestate machines do not record provenance",
+            ));
+        }
+
+        if let (true, Some(attr)) = (options.typestate, dispatch_attr) {
+            return Err(Error::new_spanned(
+                attr,
+                "typestate machines always dispatch through methods",
+            ));
+        }
+
+        if let (true, Some(attr)) = (options.typestate, generated_tests_attr) {
+            return Err(Error::new_spanned(
+                attr,
+                "typestate machines do not support generated tests",
+            ));
+        }
+
+        if let (true, Some(attr)) = (options.typestate, packed_attr) {
+            return Err(Error::new_spanned(
+                attr,
+                "typestate machines have no state to pack",
+            ));
+        }
+
+        if let (true, Some(attr)) = (options.typestate, integration_attrs.first()) {
+            return Err(Error::new_spanned(
+                attr,
+                format!(
+                    "typestate machines do not support `#[{}]`",
+                    attr.to_token_stream()
+                ),
+            ));
+        }
+
+        if let (false, Some(attr)) = (options.alloc, shortest_path_attr) {
+            return Err(Error::new_spanned(
+                attr,
+                "`#[shortest_path]` needs the `alloc` feature of sad_machine",
+            ));
+        }
+
+        if let Some(attr) = no_std_attr {
+            let std_options = options.std_options();
+
+            if !std_options.is_empty() {
+                return Err(Error::new_spanned(
+                    attr,
+                    format!(
+                        "`#[no_std]` machines cannot be generated with {}, whose code \
+                         needs `std` or `alloc`",
+                        std_options.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+impl Options {
+    /// The enabled options whose generated code needs `std` or `alloc`.
+    pub fn std_options(&self) -> Vec<&'static str> {
+        [
+            ("`#[arbitrary]`", self.arbitrary),
+            ("`#[petgraph]`", self.petgraph),
+            ("`#[simulate]`", self.simulate),
+            ("`#[metrics]`", self.metrics),
+            ("`#[actix]`", self.actix),
+            ("`#[tokio]`", self.tokio),
+            ("`#[wasm]`", self.wasm),
+            ("`#[proptest]`", self.proptest),
+            ("`#[shortest_path]`", self.shortest_path),
+        ]
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(option, _)| *option)
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::parse2;
+
+    #[test]
+    fn test_options_parse() {
+        let options: Options = parse2(quote! {
+            /// A lock with a key.
+            #[provenance(full)]
+        })
+        .unwrap();
+
+        assert_eq!(options.docs.len(), 1);
+        assert_eq!(options.provenance, ProvenanceMode::Full);
+
+        let options: Options = parse2(quote! { #[provenance(none)] }).unwrap();
+
+        assert_eq!(options.provenance, ProvenanceMode::None);
+    }
+
+    #[test]
+    fn test_options_parse_inline() {
+        let options: Options = parse2(quote! {}).unwrap();
+        assert_eq!(options.inline, InlineMode::Auto);
+
+        let options: Options = parse2(quote! { #[inline] }).unwrap();
+        assert_eq!(options.inline, InlineMode::Inline);
+
+        let options: Options = parse2(quote! { #[inline(always)] }).unwrap();
+        assert_eq!(options.inline, InlineMode::Always);
+
+        let error = parse2::<Options>(quote! { #[inline(never)] }).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected `#[inline]` or `#[inline(always)]`"
+        );
+    }
+
+    #[test]
+    fn test_options_parse_typestate_provenance() {
+        let result: Result<Options> = parse2(quote! {
+            #[typestate]
+            #[provenance(full)]
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_options_parse_typestate_integrations() {
+        let attrs = vec![
+            quote! { #[arbitrary] },
+            quote! { #[petgraph] },
+            quote! { #[rand] },
+            quote! { #[proptest] },
+            quote! { #[tokio] },
+            quote! { #[actix] },
+            quote! { #[simulate] },
+            quote! { #[wasm] },
+            quote! { #[shortest_path] },
+            quote! { #[async_hooks] },
+            quote! { #[defmt] },
+        ];
+
+        for attr in attrs {
+            let error = parse2::<Options>(quote! {
+                #[typestate]
+                #attr
+            })
+            .unwrap_err();
+            let name = attr.to_string();
+            let name = name.trim_start_matches("# [").trim_end_matches(']').trim();
+
+            assert_eq!(
+                error.to_string(),
+                format!("typestate machines do not support `#[{}]`", name)
+            );
+        }
+
+        let options: Options = parse2(quote! {
+            #[typestate]
+            #[tracing]
+            #[metrics]
+        })
+        .unwrap();
+
+        assert!(options.typestate);
+    }
+
+    #[test]
+    fn test_options_parse_lint_levels() {
+        let options: Options = parse2(quote! {
+            #[warn(unreachable_states)]
+        })
+        .unwrap();
+
+        assert_eq!(options.unreachable_states, LintLevel::Warn);
+        assert_eq!(options.unused_events, LintLevel::Warn);
+
+        let options: Options = parse2(quote! {
+            #[allow(unused_events)]
+        })
+        .unwrap();
+
+        assert_eq!(options.unused_events, LintLevel::Allow);
+
+        let result: Result<Options> = parse2(quote! {
+            #[allow(unused_states)]
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_options_parse_dispatch() {
+        let options: Options = parse2(quote! { #[dispatch(table)] }).unwrap();
+
+        assert_eq!(options.dispatch, DispatchMode::Table);
+
+        let options: Options = parse2(quote! { #[dispatch(methods)] }).unwrap();
+
+        assert_eq!(options.dispatch, DispatchMode::Methods);
+
+        let error = parse2::<Options>(quote! { #[dispatch(jump)] }).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "expected `#[dispatch(methods)]` or `#[dispatch(table)]`"
+        );
+
+        let error = parse2::<Options>(quote! {
+            #[typestate]
+            #[dispatch(table)]
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "typestate machines always dispatch through methods"
+        );
+    }
+
+    #[test]
+    fn test_options_parse_no_std() {
+        let options: Options = parse2(quote! { #[no_std] }).unwrap();
+
+        assert!(options.no_std);
+    }
+
+    #[test]
+    fn test_options_parse_generated_tests() {
+        let options: Options = parse2(quote! { #[generated_tests] }).unwrap();
+
+        assert!(options.generated_tests);
+
+        let error = parse2::<Options>(quote! {
+            #[typestate]
+            #[generated_tests]
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "typestate machines do not support generated tests"
+        );
+    }
+
+    #[test]
+    fn test_options_parse_packed() {
+        let options: Options = parse2(quote! { #[packed] }).unwrap();
+
+        assert!(options.packed);
+
+        let error = parse2::<Options>(quote! {
+            #[typestate]
+            #[packed]
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "typestate machines have no state to pack"
+        );
+    }
+
+    #[test]
+    fn test_options_parse_prelude() {
+        let options: Options = parse2(quote! { #[prelude] }).unwrap();
+
+        assert!(options.prelude);
+    }
+
+    #[test]
+    fn test_options_parse_integrations() {
+        let options: Options = parse2(quote! {}).unwrap();
+        assert!(!options.arbitrary);
+        assert!(!options.shortest_path);
+        assert!(!options.rand);
+        assert!(!options.proptest);
+        assert!(!options.wasm);
+        assert!(!options.tokio);
+        assert!(!options.actix);
+        assert!(!options.metrics);
+        assert!(!options.tracing);
+        assert!(!options.async_hooks);
+        assert!(!options.petgraph);
+        assert!(!options.simulate);
+        assert!(!options.defmt);
+
+        let options: Options = parse2(quote! {
+            #[arbitrary]
+        })
+        .unwrap();
+        assert!(options.arbitrary);
+
+        let options: Options = parse2(quote! {
+            #[defmt]
+        })
+        .unwrap();
+        assert!(options.defmt);
+
+        let options: Options = parse2(quote! {
+            #[simulate]
+        })
+        .unwrap();
+        assert!(options.simulate);
+
+        let options: Options = parse2(quote! {
+            #[petgraph]
+        })
+        .unwrap();
+        assert!(options.petgraph);
+
+        let options: Options = parse2(quote! {
+            #[async_hooks]
+        })
+        .unwrap();
+        assert!(options.async_hooks);
+
+        let options: Options = parse2(quote! {
+            #[tracing]
+        })
+        .unwrap();
+        assert!(options.tracing);
+
+        let options: Options = parse2(quote! {
+            #[metrics]
+        })
+        .unwrap();
+        assert!(options.metrics);
+
+        let options: Options = parse2(quote! {
+            #[actix]
+        })
+        .unwrap();
+        assert!(options.actix);
+
+        let options: Options = parse2(quote! {
+            #[tokio]
+        })
+        .unwrap();
+        assert!(options.tokio);
+
+        let options: Options = parse2(quote! {
+            #[wasm]
+        })
+        .unwrap();
+        assert!(options.wasm);
+
+        let options: Options = parse2(quote! {
+            #[proptest]
+        })
+        .unwrap();
+        assert!(options.proptest);
+
+        let options: Options = parse2(quote! {
+            #[rand]
+        })
+        .unwrap();
+        assert!(options.rand);
+
+        let result = parse2::<Options>(quote! {
+            #[shortest_path]
+        });
+        if cfg!(feature = "alloc") {
+            assert!(result.unwrap().shortest_path);
+        } else {
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                "`#[shortest_path]` needs the `alloc` feature of sad_machine"
+            );
+        }
+
+        let error = parse2::<Options>(quote! {
+            #[no_std]
+            #[arbitrary]
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`#[no_std]` machines cannot be generated with `#[arbitrary]`, whose code \
+             needs `std` or `alloc`"
+        );
+    }
+
+    #[test]
+    fn test_options_std_options() {
+        let options = Options {
+            arbitrary: true,
+            simulate: true,
+            defmt: true,
+            tokio: true,
+            ..Options::default()
+        };
+
+        assert_eq!(
+            options.std_options(),
+            vec!["`#[arbitrary]`", "`#[simulate]`", "`#[tokio]`"]
+        );
+        assert!(Options::default().std_options().is_empty());
+    }
+
+    #[test]
+    fn test_options_parse_write_diagrams() {
+        let options: Options = parse2(quote! {
+            #[write_diagrams]
+        })
+        .unwrap();
+
+        assert_eq!(options.write_diagrams, Some(DiagramDir::OutDir));
+
+        let options: Options = parse2(quote! {
+            #[write_diagrams("docs/diagrams")]
+        })
+        .unwrap();
+
+        assert_eq!(
+            options.write_diagrams,
+            Some(DiagramDir::Path("docs/diagrams".to_owned()))
+        );
+
+        let result: Result<Options> = parse2(quote! {
+            #[write_diagrams(docs)]
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_options_parse_unknown() {
+        let result: Result<Options> = parse2(quote! {
+            #[provenance(sideways)]
+        });
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashSet;
+
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{ident::unraw, machine::Machine, state::kind_enum_name};
+
+/// The name of the packed representation of a machine.
+pub(crate) fn packed_struct_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Packed", unraw(machine)), machine.span())
+}
+
+/// The smallest unsigned integer holding an index into `ALL` for a machine
+/// with `value_count` legal states, if any.
+pub(crate) fn packed_repr(value_count: usize) -> Option<Ident> {
+    let repr = if value_count <= 1 << 8 {
+        "u8"
+    } else if value_count <= 1 << 16 {
+        "u16"
+    } else {
+        return None;
+    };
+
+    Some(Ident::new(repr, Span::call_site()))
+}
+
+/// `{Machine}Packed`, generated with `#[packed]`, which stores the kind and
+/// provenance of the machine as their index into `ALL` in a single `u8` or
+/// `u16`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Packed<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Packed<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let packed_struct = packed_struct_name(name);
+        let kind_enum = kind_enum_name(name);
+        let provenance_type = self.machine.provenance_type();
+
+        let states = self.machine.states();
+        let values = self.machine.values();
+        let repr = packed_repr(values.len()).expect("the size of packed machines is checked");
+        let doc = format!(
+            "The machine packed into a single `{}`, for storing many of them.",
+            repr
+        );
+
+        let pack_arms = values.iter().enumerate().map(|(i, (s, p))| {
+            let value = self.machine.value(&s.name, p);
+            let i = Literal::usize_unsuffixed(i);
+
+            quote! { #value => #i }
+        });
+
+        // A state which cannot be entered has an empty enum, so it is never
+        // packed.
+        let entered = values
+            .iter()
+            .map(|(s, _)| s.name.to_string())
+            .collect::<HashSet<_>>();
+        let empty_arms = states
+            .0
+            .iter()
+            .filter(|s| !entered.contains(&s.name.to_string()))
+            .map(|s| {
+                let state = &s.name;
+
+                quote! { #name::#state(state) => match *state {} }
+            });
+
+        let unpack_arms = values.iter().enumerate().map(|(i, (s, p))| {
+            let value = self.machine.value(&s.name, p);
+            let i = Literal::usize_unsuffixed(i);
+
+            quote! { #i => #value }
+        });
+
+        // Only add a catch-all when some bit pattern is left over, otherwise
+        // it triggers an unreachable pattern warning.
+        let catch_all = if values.len() == 1 << 8 || values.len() == 1 << 16 {
+            quote! {}
+        } else {
+            quote! { _ => unreachable!("packed machines only hold legal states") }
+        };
+
+        let value_count = Literal::usize_unsuffixed(values.len());
+        let inline = self.machine.options.inline;
+
+        tokens.extend(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[repr(transparent)]
+            pub struct #packed_struct(#repr);
+
+            impl #packed_struct {
+                /// Pack the kind and provenance of `machine`.
+                #inline
+                pub fn new(machine: &#name) -> #packed_struct {
+                    #packed_struct(match machine {
+                        #(#pack_arms,)*
+                        #(#empty_arms,)*
+                    })
+                }
+
+                /// The machine packed in `self`.
+                #inline
+                pub fn unpack(self) -> #name {
+                    match self.0 {
+                        #(#unpack_arms,)*
+                        #catch_all
+                    }
+                }
+
+                #inline
+                pub fn kind(self) -> #kind_enum {
+                    self.unpack().kind()
+                }
+
+                #inline
+                pub fn provenance(self) -> #provenance_type {
+                    self.unpack().provenance()
+                }
+
+                /// The index of the packed state in `ALL`.
+                #inline
+                pub fn to_bits(self) -> #repr {
+                    self.0
+                }
+
+                /// Read back the bits returned by `to_bits`, or `None` if
+                /// they are not the index of a state in `ALL`.
+                pub fn from_bits(bits: #repr) -> Option<#packed_struct> {
+                    if usize::from(bits) < #value_count {
+                        Some(#packed_struct(bits))
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            impl #name {
+                /// Pack the kind and provenance of the machine into a single
+                /// integer.
+                #inline
+                pub fn pack(&self) -> #packed_struct {
+                    #packed_struct::new(self)
+                }
+            }
+
+            impl From<#name> for #packed_struct {
+                fn from(machine: #name) -> #packed_struct {
+                    #packed_struct::new(&machine)
+                }
+            }
+
+            impl From<#packed_struct> for #name {
+                fn from(packed: #packed_struct) -> #name {
+                    packed.unpack()
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_packed_repr() {
+        assert_eq!(packed_repr(3).unwrap(), "u8");
+        assert_eq!(packed_repr(256).unwrap(), "u8");
+        assert_eq!(packed_repr(257).unwrap(), "u16");
+        assert_eq!(packed_repr(65_536).unwrap(), "u16");
+        assert!(packed_repr(65_537).is_none());
+    }
+
+    #[test]
+    fn test_packed_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            #[packed]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = "The machine packed into a single `u8`, for storing many of them."]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[repr(transparent)]
+            pub struct TurnStilePacked(u8);
+
+            impl TurnStilePacked {
+                /// Pack the kind and provenance of `machine`.
+                pub fn new(machine: &TurnStile) -> TurnStilePacked {
+                    TurnStilePacked(match machine {
+                        TurnStile::Locked(LockedState::FromPush) => 0,
+                        TurnStile::Locked(LockedState::FromInit) => 1,
+                        TurnStile::Unlocked(UnlockedState::FromCoin) => 2,
+                    })
+                }
+
+                /// The machine packed in `self`.
+                pub fn unpack(self) -> TurnStile {
+                    match self.0 {
+                        0 => TurnStile::Locked(LockedState::FromPush),
+                        1 => TurnStile::Locked(LockedState::FromInit),
+                        2 => TurnStile::Unlocked(UnlockedState::FromCoin),
+                        _ => unreachable!("packed machines only hold legal states")
+                    }
+                }
+
+                pub fn kind(self) -> TurnStileKind {
+                    self.unpack().kind()
+                }
+
+                pub fn provenance(self) -> TurnStileProvenance {
+                    self.unpack().provenance()
+                }
+
+                /// The index of the packed state in `ALL`.
+                pub fn to_bits(self) -> u8 {
+                    self.0
+                }
+
+                /// Read back the bits returned by `to_bits`, or `None` if
+                /// they are not the index of a state in `ALL`.
+                pub fn from_bits(bits: u8) -> Option<TurnStilePacked> {
+                    if usize::from(bits) < 3 {
+                        Some(TurnStilePacked(bits))
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            impl TurnStile {
+                /// Pack the kind and provenance of the machine into a single
+                /// integer.
+                pub fn pack(&self) -> TurnStilePacked {
+                    TurnStilePacked::new(self)
+                }
+            }
+
+            impl From<TurnStile> for TurnStilePacked {
+                fn from(machine: TurnStile) -> TurnStilePacked {
+                    TurnStilePacked::new(&machine)
+                }
+            }
+
+            impl From<TurnStilePacked> for TurnStile {
+                fn from(packed: TurnStilePacked) -> TurnStile {
+                    packed.unpack()
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Packed { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashSet;
+
+use crate::{
+    machine::Machine,
+    options::ProvenanceMode,
+    state::{kind_enum_name, state_enum_name},
+};
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+/// Conversions between a machine and its `(kind, provenance)` pair.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct MachineParts<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for MachineParts<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let provenance_type = self.machine.provenance_type();
+        let provenance = self.machine.options.provenance != ProvenanceMode::None;
+
+        let states = self.machine.states();
+        let values = self.machine.values();
+
+        let kind_arms = states.0.iter().map(|s| {
+            let state = &s.name;
+            let pattern = self.machine.state_pattern(&s.name, quote! { _ });
+
+            quote! { #pattern => #kind_enum::#state }
+        });
+
+        let provenance_arms = values.iter().map(|(s, p)| {
+            let value = self.machine.value(&s.name, p);
+            let provenance = self.machine.provenance_value(p);
+
+            quote! { #value => #provenance }
+        });
+
+        // A state which cannot be entered has an empty enum, so it has no
+        // provenance to report.
+        let entered = values
+            .iter()
+            .map(|(s, _)| s.name.to_string())
+            .collect::<HashSet<_>>();
+        let empty_arms = states
+            .0
+            .iter()
+            .filter(|s| !entered.contains(&s.name.to_string()))
+            .map(|s| {
+                let state = &s.name;
+
+                quote! { #name::#state(state) => match *state {} }
+            });
+
+        let at_arms = values
+            .iter()
+            .map(|(s, p)| {
+                let state = &s.name;
+                let value = self.machine.value(&s.name, p);
+                let provenance = self.machine.provenance_value(p);
+
+                quote! {
+                    (#kind_enum::#state, #provenance) => {
+                        Some(#value)
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Only add a catch-all when some combination is actually illegal,
+        // otherwise it triggers an unreachable pattern warning.
+        let provenance_count = values
+            .iter()
+            .map(|(_, p)| p.variant())
+            .collect::<HashSet<_>>()
+            .len();
+        let catch_all = if values.len() < states.0.len() * provenance_count {
+            quote! { _ => None }
+        } else {
+            quote! {}
+        };
+
+        // Without provenance there are no state enums to convert from.
+        let state_conversions = states.0.iter().filter(|_| provenance).map(|s| {
+            let state = &s.name;
+            let state_enum = state_enum_name(&s.name);
+
+            // A machine with a single state is always in it.
+            let wrong_state = if states.0.len() > 1 {
+                quote! {
+                    other => Err(::sad_machine::WrongState {
+                        expected: #kind_enum::#state,
+                        actual: other.kind(),
+                    })
+                }
+            } else {
+                quote! {}
+            };
+
+            quote! {
+                impl From<#state_enum> for #name {
+                    fn from(state: #state_enum) -> #name {
+                        #name::#state(state)
+                    }
+                }
+
+                impl ::core::convert::TryFrom<#name> for #state_enum {
+                    type Error = ::sad_machine::WrongState<#kind_enum>;
+
+                    fn try_from(machine: #name) -> Result<#state_enum, Self::Error> {
+                        match machine {
+                            #name::#state(state) => Ok(state),
+                            #wrong_state
+                        }
+                    }
+                }
+            }
+        });
+
+        let final_states = self.machine.final_states.0.iter().map(|s| {
+            let state = &s.name;
+
+            quote! { #kind_enum::#state }
+        });
+
+        let is_final = if self.machine.final_states.0.is_empty() {
+            quote! { false }
+        } else {
+            quote! { matches!(self.kind(), #(#final_states)|*) }
+        };
+
+        let at_cfg = if self.machine.options.test_util {
+            quote! {}
+        } else {
+            quote! { #[cfg(test)] }
+        };
+
+        let inline = self.machine.options.inline;
+
+        tokens.extend(quote! {
+            impl #name {
+                #inline
+                pub fn kind(&self) -> #kind_enum {
+                    match self {
+                        #(#kind_arms),*
+                    }
+                }
+
+                #inline
+                pub fn name(&self) -> &'static str {
+                    self.kind().name()
+                }
+
+                /// Whether the machine is in one of its final states.
+                #inline
+                pub fn is_final(&self) -> bool {
+                    #is_final
+                }
+
+                /// Whether the machine is in the same kind of state as
+                /// `other`, however either of them entered it.
+                #inline
+                pub fn same_kind(&self, other: &#name) -> bool {
+                    self.kind() == other.kind()
+                }
+
+                #inline
+                pub fn provenance(&self) -> #provenance_type {
+                    match self {
+                        #(#provenance_arms,)*
+                        #(#empty_arms,)*
+                    }
+                }
+
+                /// Build the machine in an arbitrary state, without going
+                /// through its transitions. Returns `None` if the state
+                /// cannot be entered with this provenance.
+                #at_cfg
+                pub fn at(kind: #kind_enum, provenance: #provenance_type) -> Option<#name> {
+                    match (kind, provenance) {
+                        #(#at_arms)*
+                        #catch_all
+                    }
+                }
+
+                /// Save the kind and provenance of the machine to `persist`.
+                pub fn save<P: ::sad_machine::Persist<#name>>(&self, persist: &P) -> Result<(), P::Error> {
+                    persist.save(self.kind(), self.provenance())
+                }
+
+                /// Resume the machine saved in `persist`, or start from `init`
+                /// if nothing was saved yet.
+                pub fn load_or_init<P: ::sad_machine::Persist<#name>>(
+                    persist: &P,
+                    init: #name,
+                ) -> Result<#name, ::sad_machine::LoadError<#kind_enum, #provenance_type, P::Error>> {
+                    let (kind, provenance) = match persist.load() {
+                        Ok(Some(saved)) => saved,
+                        Ok(None) => return Ok(init),
+                        Err(error) => return Err(::sad_machine::LoadError::Persist(error)),
+                    };
+
+                    let machine = match (kind, provenance) {
+                        #(#at_arms)*
+                        #catch_all
+                    };
+
+                    machine.ok_or(::sad_machine::LoadError::InvalidState(kind, provenance))
+                }
+            }
+
+            impl From<&#name> for #kind_enum {
+                fn from(machine: &#name) -> #kind_enum {
+                    machine.kind()
+                }
+            }
+
+            impl From<&#name> for &'static str {
+                fn from(machine: &#name) -> &'static str {
+                    machine.name()
+                }
+            }
+
+            impl PartialEq<#kind_enum> for #name {
+                fn eq(&self, kind: &#kind_enum) -> bool {
+                    self.kind() == *kind
+                }
+            }
+
+            impl PartialEq<#name> for #kind_enum {
+                fn eq(&self, machine: &#name) -> bool {
+                    *self == machine.kind()
+                }
+            }
+
+            #(#state_conversions)*
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_machine_parts_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            impl TurnStile {
+                pub fn kind(&self) -> TurnStileKind {
+                    match self {
+                        TurnStile::Locked(_) => TurnStileKind::Locked,
+                        TurnStile::Unlocked(_) => TurnStileKind::Unlocked
+                    }
+                }
+
+                pub fn name(&self) -> &'static str {
+                    self.kind().name()
+                }
+
+                /// Whether the machine is in one of its final states.
+                pub fn is_final(&self) -> bool {
+                    false
+                }
+
+                /// Whether the machine is in the same kind of state as
+                /// `other`, however either of them entered it.
+                pub fn same_kind(&self, other: &TurnStile) -> bool {
+                    self.kind() == other.kind()
+                }
+
+                pub fn provenance(&self) -> TurnStileProvenance {
+                    match self {
+                        TurnStile::Locked(LockedState::FromInit) => TurnStileProvenance::FromInit,
+                        TurnStile::Unlocked(UnlockedState::FromCoin) => TurnStileProvenance::FromCoin,
+                    }
+                }
+
+                /// Build the machine in an arbitrary state, without going
+                /// through its transitions. Returns `None` if the state
+                /// cannot be entered with this provenance.
+                #[cfg(test)]
+                pub fn at(kind: TurnStileKind, provenance: TurnStileProvenance) -> Option<TurnStile> {
+                    match (kind, provenance) {
+                        (TurnStileKind::Locked, TurnStileProvenance::FromInit) => {
+                            Some(TurnStile::Locked(LockedState::FromInit))
+                        }
+                        (TurnStileKind::Unlocked, TurnStileProvenance::FromCoin) => {
+                            Some(TurnStile::Unlocked(UnlockedState::FromCoin))
+                        }
+                        _ => None
+                    }
+                }
+
+                /// Save the kind and provenance of the machine to `persist`.
+                pub fn save<P: ::sad_machine::Persist<TurnStile>>(&self, persist: &P) -> Result<(), P::Error> {
+                    persist.save(self.kind(), self.provenance())
+                }
+
+                /// Resume the machine saved in `persist`, or start from `init`
+                /// if nothing was saved yet.
+                pub fn load_or_init<P: ::sad_machine::Persist<TurnStile>>(
+                    persist: &P,
+                    init: TurnStile,
+                ) -> Result<TurnStile, ::sad_machine::LoadError<TurnStileKind, TurnStileProvenance, P::Error>> {
+                    let (kind, provenance) = match persist.load() {
+                        Ok(Some(saved)) => saved,
+                        Ok(None) => return Ok(init),
+                        Err(error) => return Err(::sad_machine::LoadError::Persist(error)),
+                    };
+
+                    let machine = match (kind, provenance) {
+                        (TurnStileKind::Locked, TurnStileProvenance::FromInit) => {
+                        Some(TurnStile::Locked(LockedState::FromInit))
+                        }
+                        (TurnStileKind::Unlocked, TurnStileProvenance::FromCoin) => {
+                        Some(TurnStile::Unlocked(UnlockedState::FromCoin))
+                        }
+                        _ => None
+                    };
+
+                    machine.ok_or(::sad_machine::LoadError::InvalidState(kind, provenance))
+                }
+            }
+
+            impl From<&TurnStile> for TurnStileKind {
+                fn from(machine: &TurnStile) -> TurnStileKind {
+                    machine.kind()
+                }
+            }
+
+            impl From<&TurnStile> for &'static str {
+                fn from(machine: &TurnStile) -> &'static str {
+                    machine.name()
+                }
+            }
+
+            impl PartialEq<TurnStileKind> for TurnStile {
+                fn eq(&self, kind: &TurnStileKind) -> bool {
+                    self.kind() == *kind
+                }
+            }
+
+            impl PartialEq<TurnStile> for TurnStileKind {
+                fn eq(&self, machine: &TurnStile) -> bool {
+                    *self == machine.kind()
+                }
+            }
+
+            impl From<LockedState> for TurnStile {
+                fn from(state: LockedState) -> TurnStile {
+                    TurnStile::Locked(state)
+                }
+            }
+
+            impl ::core::convert::TryFrom<TurnStile> for LockedState {
+                type Error = ::sad_machine::WrongState<TurnStileKind>;
+
+                fn try_from(machine: TurnStile) -> Result<LockedState, Self::Error> {
+                    match machine {
+                        TurnStile::Locked(state) => Ok(state),
+                        other => Err(::sad_machine::WrongState {
+                            expected: TurnStileKind::Locked,
+                            actual: other.kind(),
+                        })
+                    }
+                }
+            }
+
+            impl From<UnlockedState> for TurnStile {
+                fn from(state: UnlockedState) -> TurnStile {
+                    TurnStile::Unlocked(state)
+                }
+            }
+
+            impl ::core::convert::TryFrom<TurnStile> for UnlockedState {
+                type Error = ::sad_machine::WrongState<TurnStileKind>;
+
+                fn try_from(machine: TurnStile) -> Result<UnlockedState, Self::Error> {
+                    match machine {
+                        TurnStile::Unlocked(state) => Ok(state),
+                        other => Err(::sad_machine::WrongState {
+                            expected: TurnStileKind::Unlocked,
+                            actual: other.kind(),
+                        })
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        MachineParts { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
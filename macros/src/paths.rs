@@ -0,0 +1,507 @@
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{
+    event::event_enum_name, machine::Machine, state::kind_enum_name, transition::Transition,
+};
+
+/// The most paths listed by `simple_paths`. Their number grows exponentially
+/// with the size of a diagram with cycles, so larger diagrams only get the
+/// shortest ones.
+const MAX_PATHS: usize = 1024;
+
+/// The paths listed by `simple_paths`.
+#[derive(Debug)]
+pub(crate) struct SimplePaths {
+    /// The initial state each path starts from and the transitions it takes.
+    pub paths: Vec<(Ident, Vec<Transition>)>,
+    /// Whether the diagram has more than `MAX_PATHS` paths, so that some of
+    /// the longest ones are left out.
+    pub truncated: bool,
+}
+
+/// Every simple path through the diagram, which enters no state twice, as
+/// the initial state it starts from and the transitions it takes. Paths
+/// taking no transition are left out.
+///
+/// The paths are listed breadth first, shortest first and then following the
+/// initial states and the transitions in the order they are declared in, up
+/// to `MAX_PATHS`, so that a bound on their length never misses a path which
+/// a longer one pushed out. Like `transition`, they take the completion
+/// transitions.
+pub(crate) fn simple_paths(machine: &Machine) -> SimplePaths {
+    let transitions = machine.completed_transitions();
+    let index = transitions.index();
+
+    // The paths found last, which the next ones extend by one transition,
+    // with the states they entered.
+    let mut frontier: Vec<(&Ident, Vec<&Ident>, Vec<&Transition>)> = machine
+        .initial_states
+        .0
+        .iter()
+        .map(|i| (&i.name, vec![&i.name], Vec::new()))
+        .collect();
+    let mut paths: Vec<(&Ident, Vec<&Transition>)> = Vec::new();
+    let mut truncated = false;
+
+    'walk: while !frontier.is_empty() {
+        let mut next = Vec::new();
+
+        for (from, visited, taken) in &frontier {
+            let current = visited[visited.len() - 1];
+
+            for &t in index.from(current) {
+                if visited.contains(&&t.to.name) {
+                    continue;
+                }
+
+                if paths.len() == MAX_PATHS {
+                    truncated = true;
+                    break 'walk;
+                }
+
+                let mut visited = visited.clone();
+                let mut taken = taken.clone();
+                visited.push(&t.to.name);
+                taken.push(t);
+
+                paths.push((from, taken.clone()));
+                next.push((*from, visited, taken));
+            }
+        }
+
+        frontier = next;
+    }
+
+    SimplePaths {
+        paths: paths
+            .into_iter()
+            .map(|(from, taken)| (from.clone(), taken.into_iter().cloned().collect()))
+            .collect(),
+        truncated,
+    }
+}
+
+/// The states `machine` can reach from `from` by taking one or more
+/// transitions, including the fallbacks of `OnInvalid => ...`, in the order of
+/// `Machine::states`. Like `transition`, the transitions take the completion
+/// transitions, so the states they leave are not listed.
+pub(crate) fn reachable_from(machine: &Machine, from: &Ident) -> Vec<Ident> {
+    let transitions = machine.dynamic_transitions();
+    let index = transitions.index();
+
+    let mut reachable: HashSet<String> = HashSet::new();
+    let mut queue: Vec<&Ident> = vec![from];
+
+    while let Some(state) = queue.pop() {
+        let completed = machine
+            .completions
+            .iter()
+            .filter(|c| c.from.name == *state)
+            .map(|c| &c.to);
+
+        for to in index.from(state).iter().map(|t| &t.to).chain(completed) {
+            if reachable.insert(to.name.to_string()) {
+                queue.push(&to.name);
+            }
+        }
+    }
+
+    machine
+        .states()
+        .0
+        .into_iter()
+        .filter(|s| reachable.contains(&s.name.to_string()))
+        .map(|s| s.name)
+        .collect()
+}
+
+/// `reachable_from`, listing the states reachable from each state, computed
+/// from the diagram during expansion.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Reachable<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Reachable<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let inline = self.machine.options.inline;
+
+        let arms = self.machine.states().0.into_iter().map(|s| {
+            let state = &s.name;
+            let reachable = reachable_from(self.machine, state);
+
+            quote! { #kind_enum::#state => &[#(#kind_enum::#reachable),*] }
+        });
+
+        tokens.extend(quote! {
+            impl #name {
+                /// The states the machine can reach from the state `kind` by
+                /// taking one or more transitions, in declaration order. A
+                /// state the machine cannot leave reaches none.
+                #inline
+                pub fn reachable_from(kind: #kind_enum) -> &'static [#kind_enum] {
+                    match kind {
+                        #(#arms),*
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// `PATHS` and `paths`, which list the simple paths through the diagram so
+/// that test scenarios can be derived from it. Like `at`, they are only
+/// generated under `#[cfg(test)]` unless the `test-util` feature is enabled.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Paths<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Paths<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+
+        let SimplePaths { paths, truncated } = simple_paths(self.machine);
+
+        let paths = paths.into_iter().map(|(from, taken)| {
+            let events = taken.iter().map(|t| &t.event.name);
+            let to = &taken[taken.len() - 1].to.name;
+
+            quote! {
+                ::sad_machine::Path {
+                    from: #kind_enum::#from,
+                    events: &[#(#event_enum::#events),*],
+                    to: #kind_enum::#to,
+                }
+            }
+        });
+
+        let index = self.machine.transitions.index();
+
+        let terminal_states = self
+            .machine
+            .states()
+            .0
+            .into_iter()
+            .filter(|s| {
+                self.machine.final_states.0.iter().any(|f| f.name == s.name)
+                    || index.from(&s.name).is_empty()
+            })
+            .filter(|s| self.machine.completed(s).name == s.name)
+            .map(|s| {
+                let state = s.name;
+
+                quote! { #kind_enum::#state }
+            })
+            .collect::<Vec<_>>();
+
+        let terminal = if terminal_states.is_empty() {
+            quote! { !terminal_only }
+        } else {
+            quote! { (!terminal_only || matches!(path.to, #(#terminal_states)|*)) }
+        };
+
+        let paths_cfg = if self.machine.options.test_util {
+            quote! {}
+        } else {
+            quote! { #[cfg(test)] }
+        };
+
+        tokens.extend(quote! {
+            #paths_cfg
+            impl #name {
+                /// Every simple path through the diagram, which enters no
+                /// state twice, starting from an initial state and taking at
+                /// least one transition, shortest first, up to the first 1024.
+                pub const PATHS: &'static [::sad_machine::Path<#kind_enum, #event_enum>] = &[
+                    #(#paths),*
+                ];
+
+                /// Whether the diagram has more than 1024 simple paths, so
+                /// that `PATHS` leaves out some of the longest ones.
+                pub const PATHS_TRUNCATED: bool = #truncated;
+
+                /// The `PATHS` taking at most `max_len` transitions and, with
+                /// `terminal_only`, ending in a final state or in a state
+                /// without outgoing transitions. If `PATHS_TRUNCATED`, only
+                /// the paths shorter than the last one in `PATHS` are all
+                /// there.
+                pub fn paths(
+                    max_len: usize,
+                    terminal_only: bool,
+                ) -> impl Iterator<Item = ::sad_machine::Path<#kind_enum, #event_enum>> {
+                    #name::PATHS.iter().copied().filter(move |path| {
+                        path.events.len() <= max_len && #terminal
+                    })
+                }
+            }
+        });
+    }
+}
+
+/// `path`, finding a shortest sequence of events between two states, for the
+/// machines with `#[shortest_path]`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct ShortestPath<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for ShortestPath<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+        let inline = self.machine.options.inline;
+
+        // Like `transition`, the edges take the completion transitions.
+        let transitions = self.machine.completed_transitions();
+        let edges = transitions.0.iter().map(|t| {
+            let from = &t.from.name;
+            let event = &t.event.name;
+            let to = &t.to.name;
+
+            quote! { (#kind_enum::#from, #event_enum::#event, #kind_enum::#to) }
+        });
+
+        tokens.extend(quote! {
+            impl #name {
+                /// A shortest sequence of events leading from the state `from`
+                /// to the state `to`, or `None` if `to` cannot be reached from
+                /// `from`. The sequence is empty if they are the same state.
+                #inline
+                pub fn path(
+                    from: #kind_enum,
+                    to: #kind_enum,
+                ) -> Option<::sad_machine::__private::Vec<#event_enum>> {
+                    ::sad_machine::__private::shortest_path(
+                        &[#(#edges),*],
+                        from,
+                        to,
+                    )
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_simple_paths() {
+        let machine: Machine = parse2(quote! {
+            Lock {
+                InitialStates { Locked }
+                FinalStates { Broken }
+
+                TurnKey {
+                    Locked => Unlocked
+                    Unlocked => Locked
+                }
+
+                BreakKeyhole {
+                    Locked, Unlocked => Broken
+                }
+            }
+        })
+        .unwrap();
+
+        let SimplePaths { paths, truncated } = simple_paths(&machine);
+        let paths = paths
+            .into_iter()
+            .map(|(from, taken)| {
+                let events = taken
+                    .iter()
+                    .map(|t| t.event.name.to_string())
+                    .collect::<Vec<_>>();
+
+                format!("{}: {}", from, events.join(" "))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            paths,
+            vec![
+                "Locked: TurnKey",
+                "Locked: BreakKeyhole",
+                "Locked: TurnKey BreakKeyhole",
+            ]
+        );
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn test_paths_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[cfg(test)]
+            impl TurnStile {
+                /// Every simple path through the diagram, which enters no
+                /// state twice, starting from an initial state and taking at
+                /// least one transition, shortest first, up to the first 1024.
+                pub const PATHS: &'static [::sad_machine::Path<TurnStileKind, TurnStileEvent>] = &[
+                    ::sad_machine::Path {
+                        from: TurnStileKind::Locked,
+                        events: &[TurnStileEvent::Coin],
+                        to: TurnStileKind::Unlocked,
+                    }
+                ];
+
+                /// Whether the diagram has more than 1024 simple paths, so
+                /// that `PATHS` leaves out some of the longest ones.
+                pub const PATHS_TRUNCATED: bool = false;
+
+                /// The `PATHS` taking at most `max_len` transitions and, with
+                /// `terminal_only`, ending in a final state or in a state
+                /// without outgoing transitions. If `PATHS_TRUNCATED`, only
+                /// the paths shorter than the last one in `PATHS` are all
+                /// there.
+                pub fn paths(
+                    max_len: usize,
+                    terminal_only: bool,
+                ) -> impl Iterator<Item = ::sad_machine::Path<TurnStileKind, TurnStileEvent>> {
+                    TurnStile::PATHS.iter().copied().filter(move |path| {
+                        path.events.len() <= max_len && !terminal_only
+                    })
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Paths { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_shortest_path_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// A shortest sequence of events leading from the state `from`
+                /// to the state `to`, or `None` if `to` cannot be reached from
+                /// `from`. The sequence is empty if they are the same state.
+                pub fn path(
+                    from: TurnStileKind,
+                    to: TurnStileKind,
+                ) -> Option<::sad_machine::__private::Vec<TurnStileEvent>> {
+                    ::sad_machine::__private::shortest_path(
+                        &[
+                            (TurnStileKind::Locked, TurnStileEvent::Coin, TurnStileKind::Unlocked),
+                            (TurnStileKind::Unlocked, TurnStileEvent::Push, TurnStileKind::Locked)
+                        ],
+                        from,
+                        to,
+                    )
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        ShortestPath { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_reachable_from() {
+        let machine: Machine = parse2(quote! {
+            Service {
+                InitialStates { Starting }
+                FinalStates { Stopped }
+                OnInvalid => Faulted
+                on entry Draining => Idle
+
+                Ready { Starting => Running }
+                Overload { Running => Degraded }
+                Shed { Degraded => Draining }
+                Serve { Idle => Running }
+                Stop { Idle => Stopped }
+                Reset { Faulted => Starting }
+            }
+        })
+        .unwrap();
+
+        let reachable = |state: &str| {
+            reachable_from(&machine, &Ident::new(state, proc_macro2::Span::call_site()))
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            reachable("Degraded"),
+            vec!["Starting", "Running", "Degraded", "Idle", "Stopped", "Faulted"]
+        );
+        // The final state falls back to `Faulted` on every event.
+        assert_eq!(reachable("Stopped"), reachable("Degraded"));
+    }
+
+    #[test]
+    fn test_reachable_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            Lock {
+                InitialStates { Locked }
+                FinalStates { Broken }
+
+                TurnKey { Locked => Unlocked  Unlocked => Locked }
+                BreakKeyhole { Locked, Unlocked => Broken }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl Lock {
+                /// The states the machine can reach from the state `kind` by
+                /// taking one or more transitions, in declaration order. A
+                /// state the machine cannot leave reaches none.
+                pub fn reachable_from(kind: LockKind) -> &'static [LockKind] {
+                    match kind {
+                        LockKind::Locked => &[LockKind::Locked, LockKind::Unlocked, LockKind::Broken],
+                        LockKind::Unlocked => &[LockKind::Locked, LockKind::Unlocked, LockKind::Broken],
+                        LockKind::Broken => &[]
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Reachable { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{event::event_enum_name, machine::Machine, state::kind_enum_name};
+
+/// `as_graph`, generated for machines with `#[petgraph]` so that the diagram
+/// can be analysed with the algorithms of `petgraph`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct PetgraphImpls<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for PetgraphImpls<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+        let states = self.machine.states();
+
+        let nodes = states.0.iter().map(|s| {
+            let state = &s.name;
+
+            quote! { graph.add_node(#kind_enum::#state) }
+        });
+
+        let indices = states
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.name.to_string(), i))
+            .collect::<HashMap<_, _>>();
+
+        let index = |state: &Ident| Literal::usize_unsuffixed(indices[&state.to_string()]);
+
+        let edges = self.machine.transitions.0.iter().map(|t| {
+            let from = index(&t.from.name);
+            let to = index(&t.to.name);
+            let event = &t.event.name;
+
+            quote! { let _ = graph.add_edge(nodes[#from], nodes[#to], #event_enum::#event); }
+        });
+
+        tokens.extend(quote! {
+            impl #name {
+                /// The diagram as a `petgraph` graph, with a node per kind of
+                /// state in declaration order and an edge per transition.
+                pub fn as_graph() -> ::petgraph::graph::DiGraph<#kind_enum, #event_enum> {
+                    let mut graph = ::petgraph::graph::DiGraph::new();
+                    let nodes = [#(#nodes),*];
+
+                    #(#edges)*
+
+                    graph
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_petgraph_impls_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// The diagram as a `petgraph` graph, with a node per kind of
+                /// state in declaration order and an edge per transition.
+                pub fn as_graph() -> ::petgraph::graph::DiGraph<TurnStileKind, TurnStileEvent> {
+                    let mut graph = ::petgraph::graph::DiGraph::new();
+                    let nodes = [
+                        graph.add_node(TurnStileKind::Locked),
+                        graph.add_node(TurnStileKind::Unlocked)
+                    ];
+
+                    let _ = graph.add_edge(nodes[0], nodes[1], TurnStileEvent::Coin);
+                    let _ = graph.add_edge(nodes[1], nodes[0], TurnStileEvent::Push);
+
+                    graph
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        PetgraphImpls { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,164 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{
+    event::{event_enum_name, event_marker_name},
+    event_group::event_group_enum_name,
+    event_map::event_map_name,
+    ident::{snake_case, unraw},
+    machine::Machine,
+    options::ProvenanceMode,
+    provenance::provenance_enum_name,
+    region::region_enum_name,
+    state::{kind_enum_name, state_enum_name},
+    state_bitset::{state_bitset_name, state_bitset_repr},
+    state_map::state_map_name,
+};
+
+/// The name of the prelude module generated for a machine by `#[prelude]`.
+pub(crate) fn prelude_name(machine: &Ident) -> Ident {
+    Ident::new(
+        &format!("{}_prelude", unraw(&snake_case(machine))),
+        machine.span(),
+    )
+}
+
+/// `pub mod {machine}_prelude`, generated with `#[prelude]`, which re-exports
+/// the machine along with the types of its states, kinds, provenances and
+/// events, so that they can all be imported with a single glob.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Prelude<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Prelude<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let module = prelude_name(name);
+        let states = self.machine.states();
+
+        let mut types = vec![name.clone()];
+
+        if self.machine.options.typestate {
+            types.extend(states.0.into_iter().map(|s| s.name));
+        } else {
+            types.push(kind_enum_name(name));
+            types.push(event_enum_name(name));
+            types.push(state_map_name(name));
+            types.push(event_map_name(name));
+
+            if state_bitset_repr(states.0.len()).is_some() {
+                types.push(state_bitset_name(name));
+            }
+
+            if !self.machine.event_groups.is_empty() {
+                types.push(event_group_enum_name(name));
+            }
+
+            if !self.machine.regions.is_empty() {
+                types.push(region_enum_name(name));
+            }
+
+            if self.machine.options.provenance != ProvenanceMode::None {
+                types.push(provenance_enum_name(name));
+                types.extend(states.0.iter().map(|s| state_enum_name(&s.name)));
+            }
+
+            types.extend(
+                self.machine
+                    .events()
+                    .iter()
+                    .map(|e| event_marker_name(&e.name)),
+            );
+        }
+
+        let doc = format!(
+            "The types generated for `{}`, to be imported with `use {}::*`.",
+            unraw(name),
+            module
+        );
+
+        tokens.extend(quote! {
+            #[doc = #doc]
+            pub mod #module {
+                #[allow(unused_imports)]
+                pub use super::{#(#types),*};
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_prelude_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            #[prelude]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = "The types generated for `TurnStile`, to be imported with `use turn_stile_prelude::*`."]
+            pub mod turn_stile_prelude {
+                #[allow(unused_imports)]
+                pub use super::{
+                    TurnStile,
+                    TurnStileKind,
+                    TurnStileEvent,
+                    TurnStileStateMap,
+                    TurnStileEventMap,
+                    TurnStileStateSet,
+                    TurnStileProvenance,
+                    LockedState,
+                    UnlockedState,
+                    CoinEvent,
+                    PushEvent
+                };
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Prelude { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_prelude_to_tokens_typestate() {
+        let machine: Machine = parse2(quote! {
+            #[typestate]
+            #[prelude]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = "The types generated for `TurnStile`, to be imported with `use turn_stile_prelude::*`."]
+            pub mod turn_stile_prelude {
+                #[allow(unused_imports)]
+                pub use super::{TurnStile, Locked, Unlocked};
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Prelude { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
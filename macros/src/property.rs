@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    Error, Ident, Token,
+};
+
+use crate::{
+    ident::unraw,
+    state::State,
+    transition::{Transition, Transitions},
+};
+
+/// Whether a property requires a path to exist or to be absent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Quantifier {
+    Always,
+    Never,
+}
+
+/// A requirement on the diagram, checked during expansion.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Property {
+    /// The `Always` or `Never` keyword, which errors point at.
+    pub keyword: Ident,
+    pub quantifier: Quantifier,
+    pub from: State,
+    pub to: State,
+}
+
+impl Parse for Property {
+    /// example property tokens:
+    ///
+    /// ```text
+    /// Never reachable(Broken => Unlocked)
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Never reachable(Broken => Unlocked)`
+        //  ^^^^^
+        let keyword: Ident = input.parse()?;
+
+        let quantifier = if keyword == "Always" {
+            Quantifier::Always
+        } else if keyword == "Never" {
+            Quantifier::Never
+        } else {
+            return Err(Error::new(keyword.span(), "expected `Always` or `Never`"));
+        };
+
+        // `Never reachable(Broken => Unlocked)`
+        //        ^^^^^^^^^
+        let predicate: Ident = input.parse()?;
+
+        if predicate != "reachable" {
+            return Err(Error::new(
+                predicate.span(),
+                "expected `reachable(From => To)`",
+            ));
+        }
+
+        // `Never reachable(Broken => Unlocked)`
+        //                 ^^^^^^^^^^^^^^^^^^^^
+        let content;
+        parenthesized!(content in input);
+
+        let from = State::parse(&content)?;
+        let _: Token![=>] = content.parse()?;
+        let to = State::parse(&content)?;
+
+        Ok(Property {
+            keyword,
+            quantifier,
+            from,
+            to,
+        })
+    }
+}
+
+/// The properties the diagram must satisfy.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Properties(pub Vec<Property>);
+
+impl Properties {
+    /// Whether the next tokens are a `Properties { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "Properties")
+    }
+}
+
+impl Parse for Properties {
+    /// example properties tokens:
+    ///
+    /// ```text
+    /// Properties {
+    ///     Never reachable(Broken => Unlocked);
+    ///     Always reachable(Locked => Broken);
+    /// }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Properties { ... }`
+        //  ^^^^^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "Properties" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `Properties { ... }` block",
+            ));
+        }
+
+        // `Properties { ... }`
+        //               ^^^
+        let block_properties;
+        braced!(block_properties in input);
+
+        let properties: Punctuated<Property, Token![;]> =
+            block_properties.parse_terminated(Property::parse)?;
+
+        Ok(Properties(properties.into_iter().collect()))
+    }
+}
+
+/// The shortest path of at least one transition from `from` to `to`.
+pub(crate) fn shortest_path<'a>(
+    transitions: &'a Transitions,
+    from: &Ident,
+    to: &Ident,
+) -> Option<Vec<&'a Transition>> {
+    let index = transitions.index();
+
+    // The transition through which each state was first reached.
+    let mut reached: HashMap<String, &Transition> = HashMap::new();
+    let mut frontier: Vec<&Ident> = vec![from];
+
+    while !frontier.is_empty() {
+        let mut next = Vec::new();
+
+        for state in frontier {
+            for &t in index.from(state) {
+                if reached.contains_key(&t.to.name.to_string()) {
+                    continue;
+                }
+
+                let _ = reached.insert(t.to.name.to_string(), t);
+                next.push(&t.to.name);
+
+                if &t.to.name == to {
+                    let mut path = vec![t];
+
+                    while &path[0].from.name != from {
+                        let previous = reached
+                            .get(&path[0].from.name.to_string())
+                            .expect("every state on the frontier was reached");
+                        path.insert(0, previous);
+                    }
+
+                    return Some(path);
+                }
+            }
+        }
+
+        frontier = next;
+    }
+
+    None
+}
+
+/// `` `A` --`Event`--> `B` --`Event`--> `C` `` for a path.
+pub(crate) fn describe_path(path: &[&Transition]) -> String {
+    let mut description = format!("`{}`", unraw(&path[0].from.name));
+
+    for t in path {
+        description.push_str(&format!(
+            " --`{}`--> `{}`",
+            unraw(&t.event.name),
+            unraw(&t.to.name)
+        ));
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_properties_parse() {
+        let left: Properties = syn::parse2(quote! {
+            Properties {
+                Never reachable(Broken => Unlocked);
+                Always reachable(Locked => Broken);
+            }
+        })
+        .unwrap();
+
+        let right = Properties(vec![
+            Property {
+                keyword: parse_quote! { Never },
+                quantifier: Quantifier::Never,
+                from: State {
+                    name: parse_quote! { Broken },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+            },
+            Property {
+                keyword: parse_quote! { Always },
+                quantifier: Quantifier::Always,
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Broken },
+                },
+            },
+        ]);
+
+        assert_eq!(left, right);
+
+        let result: Result<Properties> = syn::parse2(quote! {
+            Properties { Sometimes reachable(Locked => Broken) }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "expected `Always` or `Never`"
+        );
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let transitions: Transitions = syn::parse2(quote! {
+            TurnKey { Locked => Unlocked Unlocked => Locked }
+            BreakKeyhole { Locked, Unlocked => Broken }
+            Repair { Broken => Locked }
+        })
+        .unwrap();
+
+        let path = shortest_path(
+            &transitions,
+            &parse_quote! { Broken },
+            &parse_quote! { Unlocked },
+        )
+        .unwrap();
+
+        assert_eq!(
+            describe_path(&path),
+            "`Broken` --`Repair`--> `Locked` --`TurnKey`--> `Unlocked`"
+        );
+
+        let path = shortest_path(
+            &transitions,
+            &parse_quote! { Locked },
+            &parse_quote! { Locked },
+        )
+        .unwrap();
+
+        assert_eq!(
+            describe_path(&path),
+            "`Locked` --`TurnKey`--> `Unlocked` --`TurnKey`--> `Locked`"
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+use crate::{event::event_enum_name, machine::Machine};
+
+/// `valid_sequences` and `invalid_sequences`, generated for machines with
+/// `#[proptest]`, which build `proptest` strategies producing event sequences
+/// walking the diagram from a given state.
+///
+/// The sequences are drawn as a list of indices into the events accepted at
+/// each step, so that they shrink towards shorter sequences and towards the
+/// events declared first while staying valid.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct ProptestStrategies<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for ProptestStrategies<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+
+        tokens.extend(quote! {
+            impl #name {
+                /// A strategy producing sequences of at most `max_len` events
+                /// which are accepted one after the other starting from this
+                /// state. A sequence ends early if it reaches a state which
+                /// accepts no event.
+                pub fn valid_sequences(
+                    self,
+                    max_len: usize,
+                ) -> impl ::proptest::strategy::Strategy<Value = ::std::vec::Vec<#event_enum>> {
+                    ::proptest::strategy::Strategy::prop_map(
+                        ::proptest::collection::vec(
+                            ::proptest::arbitrary::any::<::proptest::sample::Index>(),
+                            0..=max_len,
+                        ),
+                        move |picks| self.proptest_walk(picks).0,
+                    )
+                }
+
+                /// Like `valid_sequences`, but followed by one event which
+                /// the state reached does not accept, for negative tests.
+                /// Sequences reaching a state which accepts every event are
+                /// rejected.
+                pub fn invalid_sequences(
+                    self,
+                    max_len: usize,
+                ) -> impl ::proptest::strategy::Strategy<Value = ::std::vec::Vec<#event_enum>> {
+                    ::proptest::strategy::Strategy::prop_filter_map(
+                        (
+                            ::proptest::collection::vec(
+                                ::proptest::arbitrary::any::<::proptest::sample::Index>(),
+                                0..=max_len,
+                            ),
+                            ::proptest::arbitrary::any::<::proptest::sample::Index>(),
+                        ),
+                        "the state reached accepts every event",
+                        move |(picks, pick)| {
+                            let (mut events, machine) = self.proptest_walk(picks);
+
+                            let rejected = <#name as ::sad_machine::StateMachine>::EVENTS
+                                .iter()
+                                .copied()
+                                .filter(|&event| !::sad_machine::StateMachine::accepts(&machine, event))
+                                .collect::<::std::vec::Vec<_>>();
+
+                            if rejected.is_empty() {
+                                return None;
+                            }
+
+                            events.push(*pick.get(&rejected));
+
+                            Some(events)
+                        },
+                    )
+                }
+
+                /// Take the event at each of `picks` among the events accepted
+                /// by the current state, returning the events taken and the
+                /// state reached.
+                fn proptest_walk(
+                    &self,
+                    picks: ::std::vec::Vec<::proptest::sample::Index>,
+                ) -> (::std::vec::Vec<#event_enum>, #name) {
+                    let mut machine = self.clone();
+                    let mut events = ::std::vec::Vec::with_capacity(picks.len());
+
+                    for pick in picks {
+                        let accepted = <#name as ::sad_machine::StateMachine>::EVENTS
+                            .iter()
+                            .copied()
+                            .filter(|&event| ::sad_machine::StateMachine::accepts(&machine, event))
+                            .filter_map(|event| machine.transition(event).map(|next| (event, next)))
+                            .collect::<::std::vec::Vec<_>>();
+
+                        if accepted.is_empty() {
+                            break;
+                        }
+
+                        let (event, next) = pick.get(&accepted).clone();
+                        events.push(event);
+                        machine = next;
+                    }
+
+                    (events, machine)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_proptest_strategies_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// A strategy producing sequences of at most `max_len` events
+                /// which are accepted one after the other starting from this
+                /// state. A sequence ends early if it reaches a state which
+                /// accepts no event.
+                pub fn valid_sequences(
+                    self,
+                    max_len: usize,
+                ) -> impl ::proptest::strategy::Strategy<Value = ::std::vec::Vec<TurnStileEvent>> {
+                    ::proptest::strategy::Strategy::prop_map(
+                        ::proptest::collection::vec(
+                            ::proptest::arbitrary::any::<::proptest::sample::Index>(),
+                            0..=max_len,
+                        ),
+                        move |picks| self.proptest_walk(picks).0,
+                    )
+                }
+
+                /// Like `valid_sequences`, but followed by one event which
+                /// the state reached does not accept, for negative tests.
+                /// Sequences reaching a state which accepts every event are
+                /// rejected.
+                pub fn invalid_sequences(
+                    self,
+                    max_len: usize,
+                ) -> impl ::proptest::strategy::Strategy<Value = ::std::vec::Vec<TurnStileEvent>> {
+                    ::proptest::strategy::Strategy::prop_filter_map(
+                        (
+                            ::proptest::collection::vec(
+                                ::proptest::arbitrary::any::<::proptest::sample::Index>(),
+                                0..=max_len,
+                            ),
+                            ::proptest::arbitrary::any::<::proptest::sample::Index>(),
+                        ),
+                        "the state reached accepts every event",
+                        move |(picks, pick)| {
+                            let (mut events, machine) = self.proptest_walk(picks);
+
+                            let rejected = <TurnStile as ::sad_machine::StateMachine>::EVENTS
+                                .iter()
+                                .copied()
+                                .filter(|&event| !::sad_machine::StateMachine::accepts(&machine, event))
+                                .collect::<::std::vec::Vec<_>>();
+
+                            if rejected.is_empty() {
+                                return None;
+                            }
+
+                            events.push(*pick.get(&rejected));
+
+                            Some(events)
+                        },
+                    )
+                }
+
+                /// Take the event at each of `picks` among the events accepted
+                /// by the current state, returning the events taken and the
+                /// state reached.
+                fn proptest_walk(
+                    &self,
+                    picks: ::std::vec::Vec<::proptest::sample::Index>,
+                ) -> (::std::vec::Vec<TurnStileEvent>, TurnStile) {
+                    let mut machine = self.clone();
+                    let mut events = ::std::vec::Vec::with_capacity(picks.len());
+
+                    for pick in picks {
+                        let accepted = <TurnStile as ::sad_machine::StateMachine>::EVENTS
+                            .iter()
+                            .copied()
+                            .filter(|&event| ::sad_machine::StateMachine::accepts(&machine, event))
+                            .filter_map(|event| machine.transition(event).map(|next| (event, next)))
+                            .collect::<::std::vec::Vec<_>>();
+
+                        if accepted.is_empty() {
+                            break;
+                        }
+
+                        let (event, next) = pick.get(&accepted).clone();
+                        events.push(event);
+                        machine = next;
+                    }
+
+                    (events, machine)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        ProptestStrategies { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
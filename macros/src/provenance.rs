@@ -0,0 +1,293 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{
+    event::event_enum_name,
+    ident::{stem, unraw},
+    machine::Machine,
+    options::ProvenanceMode,
+    state::{kind_enum_name, state_enum_name},
+};
+
+/// The way a state was entered: either one of the initial states, or through
+/// an event. In full provenance mode the source state is recorded as well.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Provenance {
+    pub event: Option<Ident>,
+    pub source: Option<Ident>,
+}
+
+impl Provenance {
+    pub fn init() -> Self {
+        Provenance {
+            event: None,
+            source: None,
+        }
+    }
+
+    /// The name of the case in the `{State}State` enum, e.g. `FromInit`,
+    /// `FromCoin` or `FromCoinAtLocked`.
+    pub fn variant(&self) -> Ident {
+        match (&self.event, &self.source) {
+            (None, _) => Ident::new("FromInit", Span::call_site()),
+            (Some(event), None) => Ident::new(&format!("From{}", stem(event)), event.span()),
+            (Some(event), Some(source)) => Ident::new(
+                &format!("From{}At{}", stem(event), stem(source)),
+                event.span(),
+            ),
+        }
+    }
+}
+
+/// The name of the enum listing every provenance of every state of a machine.
+pub(crate) fn provenance_enum_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Provenance", unraw(machine)), machine.span())
+}
+
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct ProvenanceKinds<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for ProvenanceKinds<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let provenance_enum = provenance_enum_name(&self.machine.name);
+
+        let mut variants: Vec<Ident> = Vec::new();
+        for (_, p) in self.machine.values() {
+            let variant = p.variant();
+
+            if !variants.contains(&variant) {
+                variants.push(variant);
+            }
+        }
+
+        tokens.extend(quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[allow(clippy::enum_variant_names)]
+            pub enum #provenance_enum {
+                #(#variants),*
+            }
+        });
+    }
+}
+
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct ProvenanceAccessors<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for ProvenanceAccessors<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let event_enum = event_enum_name(&self.machine.name);
+        let kind_enum = kind_enum_name(&self.machine.name);
+
+        let inline = self.machine.options.inline;
+
+        for (s, provenances) in self.machine.state_provenances() {
+            let state_enum = state_enum_name(&s.name);
+
+            let event_arms = provenances.iter().map(|p| {
+                let variant = p.variant();
+
+                match &p.event {
+                    Some(event) => quote! { #state_enum::#variant => Some(#event_enum::#event) },
+                    None => quote! { #state_enum::#variant => None },
+                }
+            });
+
+            // A state which cannot be entered has an empty enum, which can
+            // only be matched on by value.
+            let scrutinee = if provenances.is_empty() {
+                quote! { *self }
+            } else {
+                quote! { self }
+            };
+
+            let source_fn = if self.machine.options.provenance == ProvenanceMode::Full {
+                let source_arms = provenances.iter().map(|p| {
+                    let variant = p.variant();
+
+                    match &p.source {
+                        Some(source) => {
+                            quote! { #state_enum::#variant => Some(#kind_enum::#source) }
+                        }
+                        None => quote! { #state_enum::#variant => None },
+                    }
+                });
+
+                quote! {
+                    #inline
+                    pub fn source(&self) -> Option<#kind_enum> {
+                        match #scrutinee {
+                            #(#source_arms),*
+                        }
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            tokens.extend(quote! {
+                impl #state_enum {
+                    #inline
+                    pub fn event(&self) -> Option<#event_enum> {
+                        match #scrutinee {
+                            #(#event_arms),*
+                        }
+                    }
+
+                    #source_fn
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_provenance_kinds_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Locked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            #[allow(clippy::enum_variant_names)]
+            pub enum TurnStileProvenance {
+                FromCoin,
+                FromInit
+            }
+        };
+
+        let mut right = TokenStream::new();
+        ProvenanceKinds { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_provenance_accessors_full_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options {
+                provenance: ProvenanceMode::Full,
+                ..Options::default()
+            },
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            impl LockedState {
+                pub fn event(&self) -> Option<TurnStileEvent> {
+                    match self {
+                        LockedState::FromInit => None
+                    }
+                }
+
+                pub fn source(&self) -> Option<TurnStileKind> {
+                    match self {
+                        LockedState::FromInit => None
+                    }
+                }
+            }
+
+            impl UnlockedState {
+                pub fn event(&self) -> Option<TurnStileEvent> {
+                    match self {
+                        UnlockedState::FromCoinAtLocked => Some(TurnStileEvent::Coin)
+                    }
+                }
+
+                pub fn source(&self) -> Option<TurnStileKind> {
+                    match self {
+                        UnlockedState::FromCoinAtLocked => Some(TurnStileKind::Locked)
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        ProvenanceAccessors { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,258 @@
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{event::event_enum_name, machine::Machine, state::kind_enum_name};
+
+/// `random_walk`, generated for machines with `#[rand]`, which walks the
+/// diagram by picking one of the events accepted by the current state at
+/// random.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct RandomWalk<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for RandomWalk<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+
+        tokens.extend(quote! {
+            impl #name {
+                /// A walk from this state, which repeatedly applies
+                /// an event picked by `rng` among the ones the current state
+                /// accepts and yields it with the state entered. The walk
+                /// ends when it reaches a state which accepts no event.
+                pub fn random_walk<R: ::rand::RngCore>(
+                    self,
+                    mut rng: R,
+                ) -> impl Iterator<Item = (#event_enum, #name)> {
+                    let mut machine = self;
+
+                    ::core::iter::from_fn(move || {
+                        let accepted = || {
+                            <#name as ::sad_machine::StateMachine>::EVENTS
+                                .iter()
+                                .copied()
+                                .filter(|&event| ::sad_machine::StateMachine::accepts(&machine, event))
+                        };
+
+                        let count = accepted().count();
+
+                        if count == 0 {
+                            return None;
+                        }
+
+                        let pick = ::rand::RngCore::next_u32(&mut rng) as usize % count;
+                        let event = accepted().nth(pick)?;
+
+                        machine = machine.transition(event)?;
+
+                        Some((event, machine.clone()))
+                    })
+                }
+            }
+        });
+    }
+}
+
+/// `simulate_step`, generated for machines with `#[rand]`, which picks the
+/// next event according to the weights given with `Coin * 3 { ... }`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct SimulateStep<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for SimulateStep<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+        let kind_enum = kind_enum_name(name);
+        let index = self.machine.transitions.index();
+        let states = self.machine.states();
+
+        let arms = states
+            .0
+            .iter()
+            .filter(|s| !index.from(&s.name).is_empty())
+            .map(|s| {
+                let state = &s.name;
+                let weights = index.from(&s.name).iter().map(|t| {
+                    let event = &t.event.name;
+                    let weight = Literal::u64_unsuffixed(t.weight.unwrap_or(1).into());
+
+                    quote! { (#event_enum::#event, #weight) }
+                });
+
+                quote! { #kind_enum::#state => &[#(#weights),*] }
+            })
+            .collect::<Vec<_>>();
+
+        // Only add a catch-all when some state accepts no event, otherwise it
+        // triggers an unreachable pattern warning.
+        let catch_all = if arms.len() < states.0.len() {
+            quote! { _ => &[] }
+        } else {
+            quote! {}
+        };
+
+        tokens.extend(quote! {
+            impl #name {
+                /// Apply an event picked by `rng` among the ones the current
+                /// state accepts, each as likely as the weight given to its
+                /// transition with `Coin * 3 { ... }`, and return it with the
+                /// state entered. Returns `None` in a state which accepts no
+                /// event.
+                pub fn simulate_step<R: ::rand::RngCore>(
+                    &self,
+                    rng: &mut R,
+                ) -> Option<(#event_enum, #name)> {
+                    let weights: &[(#event_enum, u64)] = match self.kind() {
+                        #(#arms,)*
+                        #catch_all
+                    };
+
+                    let total: u64 = weights.iter().map(|&(_, weight)| weight).sum();
+
+                    if total == 0 {
+                        return None;
+                    }
+
+                    let mut pick = ::rand::RngCore::next_u64(rng) % total;
+
+                    for &(event, weight) in weights {
+                        if pick < weight {
+                            return Some((event, self.transition(event)?));
+                        }
+
+                        pick -= weight;
+                    }
+
+                    None
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_random_walk_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// A walk from this state, which repeatedly applies
+                /// an event picked by `rng` among the ones the current state
+                /// accepts and yields it with the state entered. The walk
+                /// ends when it reaches a state which accepts no event.
+                pub fn random_walk<R: ::rand::RngCore>(
+                    self,
+                    mut rng: R,
+                ) -> impl Iterator<Item = (TurnStileEvent, TurnStile)> {
+                    let mut machine = self;
+
+                    ::core::iter::from_fn(move || {
+                        let accepted = || {
+                            <TurnStile as ::sad_machine::StateMachine>::EVENTS
+                                .iter()
+                                .copied()
+                                .filter(|&event| ::sad_machine::StateMachine::accepts(&machine, event))
+                        };
+
+                        let count = accepted().count();
+
+                        if count == 0 {
+                            return None;
+                        }
+
+                        let pick = ::rand::RngCore::next_u32(&mut rng) as usize % count;
+                        let event = accepted().nth(pick)?;
+
+                        machine = machine.transition(event)?;
+
+                        Some((event, machine.clone()))
+                    })
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        RandomWalk { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_simulate_step_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Broken }
+
+                Coin * 3 { Locked => Unlocked }
+                Push { Unlocked => Locked }
+                Kick * 2 { Locked, Unlocked => Broken }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// Apply an event picked by `rng` among the ones the current
+                /// state accepts, each as likely as the weight given to its
+                /// transition with `Coin * 3 { ... }`, and return it with the
+                /// state entered. Returns `None` in a state which accepts no
+                /// event.
+                pub fn simulate_step<R: ::rand::RngCore>(
+                    &self,
+                    rng: &mut R,
+                ) -> Option<(TurnStileEvent, TurnStile)> {
+                    let weights: &[(TurnStileEvent, u64)] = match self.kind() {
+                        TurnStileKind::Locked => &[(TurnStileEvent::Coin, 3), (TurnStileEvent::Kick, 2)],
+                        TurnStileKind::Unlocked => &[(TurnStileEvent::Push, 1), (TurnStileEvent::Kick, 2)],
+                        _ => &[]
+                    };
+
+                    let total: u64 = weights.iter().map(|&(_, weight)| weight).sum();
+
+                    if total == 0 {
+                        return None;
+                    }
+
+                    let mut pick = ::rand::RngCore::next_u64(rng) % total;
+
+                    for &(event, weight) in weights {
+                        if pick < weight {
+                            return Some((event, self.transition(event)?));
+                        }
+
+                        pick -= weight;
+                    }
+
+                    None
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        SimulateStep { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
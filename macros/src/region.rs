@@ -0,0 +1,333 @@
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    token::Brace,
+    Error, Ident, Token,
+};
+
+use crate::{
+    ident::{snake_case, unraw},
+    machine::Machine,
+    state::{kind_enum_name, State},
+};
+
+/// The name of the enum listing the regions of a machine.
+pub(crate) fn region_enum_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Region", unraw(machine)), machine.span())
+}
+
+/// The name of the method checking whether a machine is in `region`, e.g.
+/// `in_operational`.
+pub(crate) fn region_fn_name(region: &Ident) -> Ident {
+    Ident::new(&format!("in_{}", unraw(&snake_case(region))), region.span())
+}
+
+/// A set of states declared with `Region Operational { ... }`, which the
+/// machine can be queried for being in.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Region {
+    pub name: Ident,
+    pub states: Vec<State>,
+}
+
+impl Region {
+    /// Whether the next tokens are a `Region ... { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "Region")
+            && fork.parse::<Ident>().is_ok()
+            && fork.peek(Brace)
+    }
+}
+
+impl Parse for Region {
+    /// example region tokens:
+    ///
+    /// ```text
+    /// Region Operational { Running, Paused }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Region Operational { Running, Paused }`
+        //  ^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "Region" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `Region ... { ... }` block",
+            ));
+        }
+
+        // `Region Operational { Running, Paused }`
+        //         ^^^^^^^^^^^
+        let name: Ident = input.parse()?;
+
+        // `Region Operational { Running, Paused }`
+        //                       ^^^^^^^^^^^^^^^
+        let block_states;
+        braced!(block_states in input);
+
+        let punctuated_states: Punctuated<State, Token![,]> =
+            block_states.parse_terminated(State::parse)?;
+
+        let mut states: Vec<State> = Vec::new();
+
+        for state in punctuated_states {
+            if states.contains(&state) {
+                return Err(Error::new(
+                    state.name.span(),
+                    format!("duplicate state `{}` in region `{}`", state.name, name),
+                ));
+            }
+
+            states.push(state);
+        }
+
+        if states.is_empty() {
+            return Err(Error::new(
+                name.span(),
+                format!("region `{}` is empty", name),
+            ));
+        }
+
+        Ok(Region { name, states })
+    }
+}
+
+/// Check that the regions of `machine` have distinct names and only list its
+/// states.
+pub(crate) fn check(machine: &Machine) -> Result<()> {
+    let states = machine.states();
+    let mut names = HashSet::new();
+
+    for region in &machine.regions {
+        if !names.insert(unraw(&region.name)) {
+            return Err(Error::new(
+                region.name.span(),
+                format!("duplicate region `{}`", region.name),
+            ));
+        }
+
+        // `in_region` takes the region as an argument.
+        if region_fn_name(&region.name) == "in_region" {
+            return Err(Error::new(
+                region.name.span(),
+                format!(
+                    "region `{}` would clash with the method `in_region`",
+                    region.name
+                ),
+            ));
+        }
+
+        for s in &region.states {
+            if !states.0.iter().any(|state| state.name == s.name) {
+                return Err(Error::new(
+                    s.name.span(),
+                    format!(
+                        "state `{}` of region `{}` is not a state of `{}`",
+                        s.name, region.name, machine.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `{Machine}Region`, listing the regions declared with
+/// `Region ... { ... }`, and the methods checking whether the machine is in
+/// each of them.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Regions<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Regions<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let region_enum = region_enum_name(name);
+        let regions = &self.machine.regions;
+        let inline = self.machine.options.inline;
+
+        let names = regions.iter().map(|r| &r.name);
+
+        let fns = regions.iter().map(|r| {
+            let fn_name = region_fn_name(&r.name);
+            let states = r.states.iter().map(|s| &s.name);
+            let doc = format!(
+                "Whether the machine is in one of the states of the region `{}`.",
+                unraw(&r.name)
+            );
+
+            quote! {
+                #[doc = #doc]
+                #inline
+                pub fn #fn_name(&self) -> bool {
+                    matches!(self.kind(), #(#kind_enum::#states)|*)
+                }
+            }
+        });
+
+        let arms = regions.iter().map(|r| {
+            let region = &r.name;
+            let fn_name = region_fn_name(&r.name);
+
+            quote! { #region_enum::#region => self.#fn_name() }
+        });
+
+        tokens.extend(quote! {
+            /// The regions of states declared with `Region ... { ... }`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum #region_enum {
+                #(#names),*
+            }
+
+            impl #name {
+                #(#fns)*
+
+                /// Whether the machine is in one of the states of `region`.
+                #inline
+                pub fn in_region(&self, region: #region_enum) -> bool {
+                    match region {
+                        #(#arms),*
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_region_parse() {
+        let left: Region = parse2(quote! { Region Operational { Running, Paused } }).unwrap();
+        let right = Region {
+            name: parse_quote! { Operational },
+            states: vec![
+                State {
+                    name: parse_quote! { Running },
+                },
+                State {
+                    name: parse_quote! { Paused },
+                },
+            ],
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_check() {
+        let cases = vec![
+            (
+                quote! {
+                    Player {
+                        InitialStates { Stopped }
+                        Region Operational { Running }
+                        Region Operational { Paused }
+
+                        Play { Stopped, Paused => Running }
+                        Pause { Running => Paused }
+                    }
+                },
+                "duplicate region `Operational`",
+            ),
+            (
+                quote! {
+                    Player {
+                        InitialStates { Stopped }
+                        Region Region { Running }
+
+                        Play { Stopped => Running }
+                    }
+                },
+                "region `Region` would clash with the method `in_region`",
+            ),
+            (
+                quote! {
+                    Player {
+                        InitialStates { Stopped }
+                        Region Operational { Running, Seeking }
+
+                        Play { Stopped => Running }
+                    }
+                },
+                "state `Seeking` of region `Operational` is not a state of `Player`",
+            ),
+            (
+                quote! {
+                    Player {
+                        InitialStates { Stopped }
+                        Region Operational { }
+
+                        Play { Stopped => Running }
+                    }
+                },
+                "region `Operational` is empty",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_regions_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            Player {
+                InitialStates { Stopped }
+                FinalStates { Stopped }
+                Region Operational { Running, Paused }
+
+                Play { Stopped, Paused => Running }
+                Pause { Running => Paused }
+                Stop { Running, Paused => Stopped }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            /// The regions of states declared with `Region ... { ... }`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum PlayerRegion {
+                Operational
+            }
+
+            impl Player {
+                #[doc = "Whether the machine is in one of the states of the region `Operational`."]
+                pub fn in_operational(&self) -> bool {
+                    matches!(self.kind(), PlayerKind::Running | PlayerKind::Paused)
+                }
+
+                /// Whether the machine is in one of the states of `region`.
+                pub fn in_region(&self, region: PlayerRegion) -> bool {
+                    match region {
+                        PlayerRegion::Operational => self.in_operational()
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Regions { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
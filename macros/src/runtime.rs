@@ -0,0 +1,370 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{
+    event::{event_enum_name, event_fn_name, event_marker_name},
+    ident::unraw,
+    machine::Machine,
+    state::{kind_enum_name, state_enum_name},
+};
+
+/// Implementation of the `sad_machine::StateMachine` trait, forwarding to the
+/// inherent methods of the machine.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateMachineImpl<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateMachineImpl<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+        let provenance_type = self.machine.provenance_type();
+        let events = self.machine.events().into_iter().map(|e| e.name);
+        let transitions = self.machine.transitions.0.iter().map(|t| {
+            let from = &t.from.name;
+            let event = &t.event.name;
+            let to = &t.to.name;
+            let label = match &t.label {
+                Some(label) => quote! { Some(#label) },
+                None => quote! { None },
+            };
+
+            quote! {
+                ::sad_machine::Edge {
+                    from: #kind_enum::#from,
+                    event: #event_enum::#event,
+                    to: #kind_enum::#to,
+                    label: #label,
+                }
+            }
+        });
+
+        let complete_once = if self.machine.completions.is_empty() {
+            None
+        } else {
+            Some(quote! {
+                fn complete_once(&self) -> Option<#name> {
+                    #name::complete_once(self)
+                }
+            })
+        };
+
+        tokens.extend(quote! {
+            impl ::sad_machine::StateMachine for #name {
+                type Kind = #kind_enum;
+                type Event = #event_enum;
+                type Provenance = #provenance_type;
+
+                const STATE_COUNT: usize = #name::STATE_COUNT;
+                const EVENT_COUNT: usize = #name::EVENT_COUNT;
+                const EVENTS: &'static [#event_enum] = &[#(#event_enum::#events),*];
+                const TRANSITIONS: &'static [::sad_machine::Edge<#kind_enum, #event_enum>] = &[
+                    #(#transitions),*
+                ];
+
+                fn kind(&self) -> #kind_enum {
+                    #name::kind(self)
+                }
+
+                fn provenance(&self) -> #provenance_type {
+                    #name::provenance(self)
+                }
+
+                fn transition(&self, event: #event_enum) -> Option<#name> {
+                    #name::transition(self, event)
+                }
+
+                #complete_once
+            }
+        });
+    }
+}
+
+/// The name of the event queue runner of a machine.
+pub(crate) fn runner_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Runner", unraw(machine)), machine.span())
+}
+
+/// `{Machine}Runner`, the `sad_machine::Runner` driving the machine.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct RunnerAlias<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for RunnerAlias<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let runner = runner_name(name);
+
+        tokens.extend(quote! {
+            /// The machine driven by a queue of at most `N` events, processed
+            /// one at a time to completion.
+            pub type #runner<const N: usize = 16> = ::sad_machine::Runner<#name, N>;
+        });
+    }
+}
+
+/// The name of the interrupt-safe runner of a machine.
+pub(crate) fn interrupt_runner_name(machine: &Ident) -> Ident {
+    Ident::new(
+        &format!("{}InterruptRunner", unraw(machine)),
+        machine.span(),
+    )
+}
+
+/// `{Machine}InterruptRunner`, the `sad_machine::InterruptRunner` driving the
+/// machine, generated with the `critical-section` feature.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct InterruptRunnerAlias<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for InterruptRunnerAlias<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let runner = interrupt_runner_name(name);
+
+        tokens.extend(quote! {
+            /// The machine driven by a queue of at most `N` events, which
+            /// interrupt handlers can post to.
+            pub type #runner<const N: usize = 16> = ::sad_machine::InterruptRunner<#name, N>;
+        });
+    }
+}
+
+/// Implementations of `sad_machine::Transition<{Event}Event>` for every state
+/// which accepts the event.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct TransitionImpls<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for TransitionImpls<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+
+        for t in &self.machine.transitions.0 {
+            let state_enum = state_enum_name(&t.from.name);
+            let marker = event_marker_name(&t.event.name);
+            let event_fn = event_fn_name(&t.event.name);
+            let allow = t
+                .deprecated
+                .as_ref()
+                .map(|_| quote! { #[allow(deprecated)] });
+
+            tokens.extend(quote! {
+                impl ::sad_machine::Transition<#marker> for #state_enum {
+                    type Machine = #name;
+
+                    #allow
+                    fn transition(&self, _: #marker) -> #name {
+                        self.#event_fn()
+                    }
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_state_machine_impl_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![]),
+        };
+
+        let left = quote! {
+            impl ::sad_machine::StateMachine for TurnStile {
+                type Kind = TurnStileKind;
+                type Event = TurnStileEvent;
+                type Provenance = TurnStileProvenance;
+
+                const STATE_COUNT: usize = TurnStile::STATE_COUNT;
+                const EVENT_COUNT: usize = TurnStile::EVENT_COUNT;
+                const EVENTS: &'static [TurnStileEvent] = &[];
+                const TRANSITIONS: &'static [::sad_machine::Edge<TurnStileKind, TurnStileEvent>] = &[];
+
+                fn kind(&self) -> TurnStileKind {
+                    TurnStile::kind(self)
+                }
+
+                fn provenance(&self) -> TurnStileProvenance {
+                    TurnStile::provenance(self)
+                }
+
+                fn transition(&self, event: TurnStileEvent) -> Option<TurnStile> {
+                    TurnStile::transition(self, event)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        StateMachineImpl { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_runner_alias_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![]),
+        };
+
+        let left = quote! {
+            /// The machine driven by a queue of at most `N` events, processed
+            /// one at a time to completion.
+            pub type TurnStileRunner<const N: usize = 16> = ::sad_machine::Runner<TurnStile, N>;
+        };
+
+        let mut right = TokenStream::new();
+        RunnerAlias { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_interrupt_runner_alias_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![]),
+        };
+
+        let left = quote! {
+            /// The machine driven by a queue of at most `N` events, which
+            /// interrupt handlers can post to.
+            pub type TurnStileInterruptRunner<const N: usize = 16> = ::sad_machine::InterruptRunner<TurnStile, N>;
+        };
+
+        let mut right = TokenStream::new();
+        InterruptRunnerAlias { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_transition_impls_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }]),
+        };
+
+        let left = quote! {
+            impl ::sad_machine::Transition<CoinEvent> for LockedState {
+                type Machine = TurnStile;
+
+                fn transition(&self, _: CoinEvent) -> TurnStile {
+                    self.coin()
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        TransitionImpls { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,164 @@
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{event::event_enum_name, machine::Machine};
+
+/// `simulate` and `simulate_with`, generated for machines with `#[simulate]` so
+/// that the machine can be walked by typing event names in a terminal.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Simulator<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Simulator<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let event_enum = event_enum_name(name);
+
+        let events = self.machine.events();
+        let event_count = Literal::usize_unsuffixed(events.len());
+        let events = events.into_iter().map(|e| {
+            let event = e.name;
+
+            quote! { #event_enum::#event }
+        });
+
+        tokens.extend(quote! {
+            impl #name {
+                /// Walk the machine, reading one event name per line from
+                /// `input` and writing the current state and the events it
+                /// accepts to `output`. Returns the state reached when
+                /// `input` ends.
+                pub fn simulate_with(
+                    self,
+                    input: impl ::std::io::BufRead,
+                    mut output: impl ::std::io::Write,
+                ) -> ::std::io::Result<#name> {
+                    const EVENTS: [#event_enum; #event_count] = [#(#events),*];
+
+                    let mut machine = self;
+                    let mut lines = input.lines();
+
+                    loop {
+                        let accepted = EVENTS
+                            .iter()
+                            .filter(|event| ::sad_machine::StateMachine::accepts(&machine, **event))
+                            .map(|event| event.name())
+                            .collect::<::std::vec::Vec<_>>();
+
+                        ::std::writeln!(output, "state: {:?}", machine)?;
+                        ::std::writeln!(output, "events: {}", accepted.join(", "))?;
+
+                        let line = match lines.next() {
+                            Some(line) => line?,
+                            None => return Ok(machine),
+                        };
+                        let event_name = line.trim();
+
+                        match EVENTS.iter().find(|event| event.name() == event_name) {
+                            None => ::std::writeln!(output, "unknown event `{}`", event_name)?,
+                            Some(&event) => match machine.transition(event) {
+                                Some(next) => machine = next,
+                                None => ::std::writeln!(
+                                    output,
+                                    "event `{}` is not accepted in state `{}`",
+                                    event_name,
+                                    machine.name()
+                                )?,
+                            },
+                        }
+                    }
+                }
+
+                /// `simulate_with` on the standard input and output.
+                pub fn simulate(self) -> ::std::io::Result<#name> {
+                    let stdin = ::std::io::stdin();
+
+                    self.simulate_with(stdin.lock(), ::std::io::stdout())
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_simulator_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// Walk the machine, reading one event name per line from
+                /// `input` and writing the current state and the events it
+                /// accepts to `output`. Returns the state reached when
+                /// `input` ends.
+                pub fn simulate_with(
+                    self,
+                    input: impl ::std::io::BufRead,
+                    mut output: impl ::std::io::Write,
+                ) -> ::std::io::Result<TurnStile> {
+                    const EVENTS: [TurnStileEvent; 2] = [TurnStileEvent::Coin, TurnStileEvent::Push];
+
+                    let mut machine = self;
+                    let mut lines = input.lines();
+
+                    loop {
+                        let accepted = EVENTS
+                            .iter()
+                            .filter(|event| ::sad_machine::StateMachine::accepts(&machine, **event))
+                            .map(|event| event.name())
+                            .collect::<::std::vec::Vec<_>>();
+
+                        ::std::writeln!(output, "state: {:?}", machine)?;
+                        ::std::writeln!(output, "events: {}", accepted.join(", "))?;
+
+                        let line = match lines.next() {
+                            Some(line) => line?,
+                            None => return Ok(machine),
+                        };
+                        let event_name = line.trim();
+
+                        match EVENTS.iter().find(|event| event.name() == event_name) {
+                            None => ::std::writeln!(output, "unknown event `{}`", event_name)?,
+                            Some(&event) => match machine.transition(event) {
+                                Some(next) => machine = next,
+                                None => ::std::writeln!(
+                                    output,
+                                    "event `{}` is not accepted in state `{}`",
+                                    event_name,
+                                    machine.name()
+                                )?,
+                            },
+                        }
+                    }
+                }
+
+                /// `simulate_with` on the standard input and output.
+                pub fn simulate(self) -> ::std::io::Result<TurnStile> {
+                    let stdin = ::std::io::stdin();
+
+                    self.simulate_with(stdin.lock(), ::std::io::stdout())
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Simulator { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{ident::unraw, machine::Machine};
+
+/// The name of the snapshot struct of a machine.
+pub(crate) fn snapshot_struct_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Snapshot", unraw(machine)), machine.span())
+}
+
+/// A 64-bit FNV-1a hash of what decides the meaning of the state indices
+/// stored in snapshots: the states, events, initial states, transitions,
+/// completion transitions, the fallback state of `OnInvalid => ...` and the
+/// provenance mode. Labels, weights, final states and the name of the machine
+/// leave the indices alone, so they do not change it.
+pub(crate) fn fingerprint(machine: &Machine) -> u64 {
+    let names = |names: Vec<String>| names.join(",");
+
+    let states = names(
+        machine
+            .states()
+            .0
+            .iter()
+            .map(|s| s.name.to_string())
+            .collect(),
+    );
+    let events = names(
+        machine
+            .events()
+            .iter()
+            .map(|e| e.name.to_string())
+            .collect(),
+    );
+    let initial_states = names(
+        machine
+            .initial_states
+            .0
+            .iter()
+            .map(|i| i.name.to_string())
+            .collect(),
+    );
+    let transitions = names(
+        machine
+            .transitions
+            .0
+            .iter()
+            .map(|t| format!("{}:{}>{}", t.event.name, t.from.name, t.to.name))
+            .collect(),
+    );
+    let completions = names(
+        machine
+            .completions
+            .iter()
+            .map(|c| format!("{}>{}", c.from.name, c.to.name))
+            .collect(),
+    );
+    let on_invalid = machine
+        .on_invalid
+        .as_ref()
+        .map(|on_invalid| on_invalid.0.name.to_string())
+        .unwrap_or_default();
+
+    let input = format!(
+        "{};{};{};{};{};{};{:?}",
+        states,
+        events,
+        initial_states,
+        transitions,
+        completions,
+        on_invalid,
+        machine.options.provenance
+    );
+
+    input.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// `{Machine}Snapshot`, `snapshot` and `restore`, which persist the machine as
+/// a pair of integers that can only be restored by the same diagram.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Snapshot<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Snapshot<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let snapshot_struct = snapshot_struct_name(name);
+        let fingerprint = Literal::u64_suffixed(fingerprint(self.machine));
+
+        let states = self.machine.states();
+        let values = self.machine.values();
+
+        let snapshot_arms = values.iter().enumerate().map(|(i, (s, p))| {
+            let value = self.machine.value(&s.name, p);
+            let i = Literal::u32_unsuffixed(i as u32);
+
+            quote! { #value => #i }
+        });
+
+        // A state which cannot be entered has an empty enum, so it is never
+        // in a snapshot.
+        let entered = values
+            .iter()
+            .map(|(s, _)| s.name.to_string())
+            .collect::<HashSet<_>>();
+        let empty_arms = states
+            .0
+            .iter()
+            .filter(|s| !entered.contains(&s.name.to_string()))
+            .map(|s| {
+                let state = &s.name;
+
+                quote! { #name::#state(state) => match *state {} }
+            });
+
+        let restore_arms = values.iter().enumerate().map(|(i, (s, p))| {
+            let value = self.machine.value(&s.name, p);
+            let i = Literal::u32_unsuffixed(i as u32);
+
+            quote! { #i => Ok(#value) }
+        });
+
+        tokens.extend(quote! {
+            /// The state of the machine, as saved by `snapshot`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct #snapshot_struct {
+                /// The `FINGERPRINT` of the diagram the snapshot was taken
+                /// with.
+                pub fingerprint: u64,
+                /// The index of the state and provenance of the machine.
+                pub state: u32,
+            }
+
+            impl #name {
+                /// A hash of the diagram, which changes whenever the states,
+                /// events, initial states, transitions, completion
+                /// transitions, fallback state or provenance mode of the
+                /// machine do, but not with its labels, weights or final
+                /// states.
+                pub const FINGERPRINT: u64 = #fingerprint;
+
+                /// Save the state of the machine along with the fingerprint
+                /// of the diagram.
+                pub fn snapshot(&self) -> #snapshot_struct {
+                    let state = match self {
+                        #(#snapshot_arms,)*
+                        #(#empty_arms,)*
+                    };
+
+                    #snapshot_struct {
+                        fingerprint: #name::FINGERPRINT,
+                        state,
+                    }
+                }
+
+                /// Restore a machine saved by `snapshot`. Fails if the
+                /// snapshot was taken with a different diagram.
+                pub fn restore(
+                    snapshot: #snapshot_struct,
+                ) -> Result<#name, ::sad_machine::RestoreError> {
+                    if snapshot.fingerprint != #name::FINGERPRINT {
+                        return Err(::sad_machine::RestoreError::Fingerprint(snapshot.fingerprint));
+                    }
+
+                    match snapshot.state {
+                        #(#restore_arms,)*
+                        state => Err(::sad_machine::RestoreError::UnknownState(state)),
+                    }
+                }
+
+                /// Like `restore`, but hand the snapshots taken with a
+                /// different diagram to `migrate`, which fails the restore by
+                /// returning `None`.
+                pub fn restore_or_migrate(
+                    snapshot: #snapshot_struct,
+                    migrate: impl FnOnce(#snapshot_struct) -> Option<#name>,
+                ) -> Result<#name, ::sad_machine::RestoreError> {
+                    match #name::restore(snapshot) {
+                        Err(::sad_machine::RestoreError::Fingerprint(fingerprint)) => {
+                            migrate(snapshot).ok_or(::sad_machine::RestoreError::Fingerprint(fingerprint))
+                        }
+                        result => result,
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_fingerprint() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let renamed: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Open }
+                Push { Open => Locked }
+            }
+        })
+        .unwrap();
+
+        let full: Machine = parse2(quote! {
+            #[provenance(full)]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let on_invalid: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                OnInvalid => Unlocked
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(fingerprint(&machine), fingerprint(&machine));
+        assert_ne!(fingerprint(&machine), fingerprint(&renamed));
+        assert_ne!(fingerprint(&machine), fingerprint(&full));
+        assert_ne!(fingerprint(&machine), fingerprint(&on_invalid));
+
+        let completion: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                on entry Unlocked => Locked
+
+                Coin { Locked => Unlocked }
+            }
+        })
+        .unwrap();
+
+        let dead_end: Machine = parse2(quote! {
+            #[allow(dead_end_states)]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+            }
+        })
+        .unwrap();
+
+        assert_ne!(fingerprint(&completion), fingerprint(&dead_end));
+
+        let described: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+                FinalStates { Unlocked }
+
+                Coin "customer inserted a coin" * 3 { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(fingerprint(&machine), fingerprint(&described));
+    }
+
+    #[test]
+    fn test_snapshot_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let fingerprint = Literal::u64_suffixed(fingerprint(&machine));
+
+        let left = quote! {
+            /// The state of the machine, as saved by `snapshot`.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileSnapshot {
+                /// The `FINGERPRINT` of the diagram the snapshot was taken
+                /// with.
+                pub fingerprint: u64,
+                /// The index of the state and provenance of the machine.
+                pub state: u32,
+            }
+
+            impl TurnStile {
+                /// A hash of the diagram, which changes whenever the states,
+                /// events, initial states, transitions, completion
+                /// transitions, fallback state or provenance mode of the
+                /// machine do, but not with its labels, weights or final
+                /// states.
+                pub const FINGERPRINT: u64 = #fingerprint;
+
+                /// Save the state of the machine along with the fingerprint
+                /// of the diagram.
+                pub fn snapshot(&self) -> TurnStileSnapshot {
+                    let state = match self {
+                        TurnStile::Locked(LockedState::FromPush) => 0,
+                        TurnStile::Locked(LockedState::FromInit) => 1,
+                        TurnStile::Unlocked(UnlockedState::FromCoin) => 2,
+                    };
+
+                    TurnStileSnapshot {
+                        fingerprint: TurnStile::FINGERPRINT,
+                        state,
+                    }
+                }
+
+                /// Restore a machine saved by `snapshot`. Fails if the
+                /// snapshot was taken with a different diagram.
+                pub fn restore(
+                    snapshot: TurnStileSnapshot,
+                ) -> Result<TurnStile, ::sad_machine::RestoreError> {
+                    if snapshot.fingerprint != TurnStile::FINGERPRINT {
+                        return Err(::sad_machine::RestoreError::Fingerprint(snapshot.fingerprint));
+                    }
+
+                    match snapshot.state {
+                        0 => Ok(TurnStile::Locked(LockedState::FromPush)),
+                        1 => Ok(TurnStile::Locked(LockedState::FromInit)),
+                        2 => Ok(TurnStile::Unlocked(UnlockedState::FromCoin)),
+                        state => Err(::sad_machine::RestoreError::UnknownState(state)),
+                    }
+                }
+
+                /// Like `restore`, but hand the snapshots taken with a
+                /// different diagram to `migrate`, which fails the restore by
+                /// returning `None`.
+                pub fn restore_or_migrate(
+                    snapshot: TurnStileSnapshot,
+                    migrate: impl FnOnce(TurnStileSnapshot) -> Option<TurnStile>,
+                ) -> Result<TurnStile, ::sad_machine::RestoreError> {
+                    match TurnStile::restore(snapshot) {
+                        Err(::sad_machine::RestoreError::Fingerprint(fingerprint)) => {
+                            migrate(snapshot).ok_or(::sad_machine::RestoreError::Fingerprint(fingerprint))
+                        }
+                        result => result,
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Snapshot { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
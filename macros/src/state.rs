@@ -0,0 +1,481 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use std::{collections::HashMap, vec::IntoIter};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    Error, Ident, LitStr, Token,
+};
+
+use crate::{
+    ident::{snake_case, stem, unraw},
+    label::{parse_entry, Labels},
+    options::InlineMode,
+};
+
+/// The states of a machine, either gathered from its transitions or declared
+/// upfront in a `States { ... }` block.
+#[derive(Debug, PartialEq)]
+pub(crate) struct States(pub Vec<State>);
+
+impl States {
+    /// Whether the next tokens are a `States { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        input
+            .fork()
+            .parse::<Ident>()
+            .is_ok_and(|ident| ident == "States")
+    }
+
+    /// Parse the block, recording the names of the renamed states in
+    /// `labels`.
+    ///
+    /// example states tokens:
+    ///
+    /// ```text
+    /// States { Locked, Unlocked, Broken }
+    /// States { "wait-for-ack" as WaitForAck, Done }
+    /// ```
+    pub fn parse_labeled(
+        input: ParseStream<'_>,
+        labels: &mut HashMap<String, String>,
+    ) -> Result<Self> {
+        // `States { ... }`
+        //  ^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "States" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `States { ... }` block",
+            ));
+        }
+
+        // `States { ... }`
+        //           ^^^
+        let block_states;
+        braced!(block_states in input);
+
+        // `States { Locked, Unlocked, Broken }`
+        //           ^^^^^^  ^^^^^^^^  ^^^^^^
+        let punctuated_states: Punctuated<(Ident, Option<LitStr>), Token![,]> =
+            block_states.parse_terminated(parse_entry)?;
+
+        let mut states: Vec<State> = Vec::new();
+
+        for (name, label) in punctuated_states {
+            let state = State { name };
+
+            if states.contains(&state) {
+                return Err(Error::new(
+                    state.name.span(),
+                    format!("duplicate state `{}`", state.name),
+                ));
+            }
+
+            if let Some(label) = label {
+                let _ = labels.insert(unraw(&state.name), label.value());
+            }
+
+            states.push(state);
+        }
+
+        Ok(States(states))
+    }
+}
+
+impl Parse for States {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        States::parse_labeled(input, &mut HashMap::new())
+    }
+}
+
+impl ToTokens for States {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let states = &self.0;
+
+        tokens.extend(quote! {
+            #(#states),*
+        })
+    }
+}
+
+impl IntoIterator for &States {
+    type IntoIter = IntoIter<State>;
+    type Item = State;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.clone().into_iter()
+    }
+}
+
+/// The name of the `{State}State` enum recording how `state` was entered. It
+/// reuses the span of the state, so that errors in the generated code point
+/// at the diagram.
+pub(crate) fn state_enum_name(state: &Ident) -> Ident {
+    Ident::new(&format!("{}State", stem(state)), state.span())
+}
+
+/// The name of the enum listing the kinds of state of a machine, ignoring
+/// provenance.
+pub(crate) fn kind_enum_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Kind", unraw(machine)), machine.span())
+}
+
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateKinds<'a> {
+    pub enum_name: &'a Ident,
+    pub labels: &'a Labels,
+    pub states: &'a States,
+    pub inline: InlineMode,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateKinds<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let kind_enum = kind_enum_name(self.enum_name);
+        let names = self.states.0.iter().map(|s| &s.name);
+
+        let name_arms = self.states.0.iter().map(|s| {
+            let name = &s.name;
+            let string = self.labels.state(name);
+
+            quote! { #kind_enum::#name => #string }
+        });
+
+        let inline = self.inline;
+
+        tokens.extend(quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum #kind_enum {
+                #(#names),*
+            }
+
+            impl #kind_enum {
+                #inline
+                pub fn name(self) -> &'static str {
+                    match self {
+                        #(#name_arms),*
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// The name of the accessor borrowing the state enum of `state` from the
+/// machine, e.g. `as_locked`. The prefix keeps it from being a keyword, so
+/// the state `Self_` gets `as_self`.
+pub(crate) fn state_accessor_name(state: &Ident) -> Ident {
+    Ident::new(&format!("as_{}", stem(&snake_case(state))), state.span())
+}
+
+/// The `as_{state}()`, `as_{state}_mut()` and `into_{state}()` accessors of a
+/// machine, which return its state enum if it is in that state.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateAccessors<'a> {
+    pub enum_name: &'a Ident,
+    pub states: &'a States,
+    pub inline: InlineMode,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateAccessors<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = self.enum_name;
+        let inline = self.inline;
+
+        // A machine with a single state always matches it.
+        let catch_all = if self.states.0.len() > 1 {
+            quote! { _ => None }
+        } else {
+            quote! {}
+        };
+
+        let fns = self.states.0.iter().map(|s| {
+            let state = &s.name;
+            let state_enum = state_enum_name(state);
+            let as_fn = state_accessor_name(state);
+            let as_mut_fn = Ident::new(&format!("{}_mut", as_fn), state.span());
+            let into_fn = Ident::new(&format!("into_{}", stem(&snake_case(state))), state.span());
+
+            let doc = format!(
+                "The `{}` of the machine, if it is in the state `{}`.",
+                state_enum,
+                unraw(state)
+            );
+
+            quote! {
+                #[doc = #doc]
+                #inline
+                pub fn #as_fn(&self) -> Option<&#state_enum> {
+                    match self {
+                        #name::#state(state) => Some(state),
+                        #catch_all
+                    }
+                }
+
+                #[doc = #doc]
+                #inline
+                pub fn #as_mut_fn(&mut self) -> Option<&mut #state_enum> {
+                    match self {
+                        #name::#state(state) => Some(state),
+                        #catch_all
+                    }
+                }
+
+                #[doc = #doc]
+                #inline
+                pub fn #into_fn(self) -> Option<#state_enum> {
+                    match self {
+                        #name::#state(state) => Some(state),
+                        #catch_all
+                    }
+                }
+            }
+        });
+
+        tokens.extend(quote! {
+            impl #name {
+                #(#fns)*
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct State {
+    pub name: Ident,
+}
+
+impl Parse for State {
+    /// example state tokens:
+    ///
+    /// ```text
+    /// Locked
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let name = input.parse()?;
+
+        Ok(State { name })
+    }
+}
+
+impl ToTokens for State {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.name;
+        let struct_name = state_enum_name(&self.name);
+
+        tokens.extend(quote! {
+            #name(#struct_name)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::TokenStream;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_state_parse() {
+        let left: State = syn::parse2(quote! { Unlocked }).unwrap();
+        let right = State {
+            name: parse_quote! { Unlocked },
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_states_parse() {
+        let left: States = syn::parse2(quote! { States { Locked, Unlocked } }).unwrap();
+        let right = States(vec![
+            State {
+                name: parse_quote! { Locked },
+            },
+            State {
+                name: parse_quote! { Unlocked },
+            },
+        ]);
+
+        assert_eq!(left, right);
+
+        let result: Result<States> = syn::parse2(quote! { States { Locked, Locked } });
+
+        assert_eq!(result.unwrap_err().to_string(), "duplicate state `Locked`");
+    }
+
+    #[test]
+    fn test_state_to_tokens() {
+        let state = State {
+            name: parse_quote! { Unlocked },
+        };
+
+        let left = quote! {
+            Unlocked(UnlockedState)
+        };
+
+        let mut right = TokenStream::new();
+        state.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_states_to_tokens() {
+        let states = States(vec![
+            State {
+                name: parse_quote! { Locked },
+            },
+            State {
+                name: parse_quote! { Unlocked },
+            },
+        ]);
+
+        let left = quote! {
+            Locked(LockedState),
+            Unlocked(UnlockedState)
+        };
+
+        let mut right = TokenStream::new();
+        states.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_state_kinds_to_tokens() {
+        let states = States(vec![
+            State {
+                name: parse_quote! { Locked },
+            },
+            State {
+                name: parse_quote! { Unlocked },
+            },
+        ]);
+
+        let state_kinds = StateKinds {
+            enum_name: &parse_quote! { TurnStile },
+            labels: &Labels::default(),
+            states: &states,
+            inline: InlineMode::Always,
+        };
+
+        let left = quote! {
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum TurnStileKind {
+                Locked,
+                Unlocked
+            }
+
+            impl TurnStileKind {
+                #[inline(always)]
+                pub fn name(self) -> &'static str {
+                    match self {
+                        TurnStileKind::Locked => "Locked",
+                        TurnStileKind::Unlocked => "Unlocked"
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        state_kinds.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_state_accessor_name() {
+        let cases: Vec<(Ident, &str)> = vec![
+            (parse_quote! { Locked }, "as_locked"),
+            (parse_quote! { WaitForAck }, "as_wait_for_ack"),
+            (parse_quote! { r#Loop }, "as_loop"),
+            (parse_quote! { Self_ }, "as_self"),
+        ];
+
+        for (state, expected) in cases {
+            assert_eq!(state_accessor_name(&state).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_state_accessors_to_tokens() {
+        let states = States(vec![
+            State {
+                name: parse_quote! { Locked },
+            },
+            State {
+                name: parse_quote! { Unlocked },
+            },
+        ]);
+
+        let state_accessors = StateAccessors {
+            enum_name: &parse_quote! { TurnStile },
+            states: &states,
+            inline: InlineMode::Auto,
+        };
+
+        let left = quote! {
+            impl TurnStile {
+                #[doc = "The `LockedState` of the machine, if it is in the state `Locked`."]
+                pub fn as_locked(&self) -> Option<&LockedState> {
+                    match self {
+                        TurnStile::Locked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `LockedState` of the machine, if it is in the state `Locked`."]
+                pub fn as_locked_mut(&mut self) -> Option<&mut LockedState> {
+                    match self {
+                        TurnStile::Locked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `LockedState` of the machine, if it is in the state `Locked`."]
+                pub fn into_locked(self) -> Option<LockedState> {
+                    match self {
+                        TurnStile::Locked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `UnlockedState` of the machine, if it is in the state `Unlocked`."]
+                pub fn as_unlocked(&self) -> Option<&UnlockedState> {
+                    match self {
+                        TurnStile::Unlocked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `UnlockedState` of the machine, if it is in the state `Unlocked`."]
+                pub fn as_unlocked_mut(&mut self) -> Option<&mut UnlockedState> {
+                    match self {
+                        TurnStile::Unlocked(state) => Some(state),
+                        _ => None
+                    }
+                }
+
+                #[doc = "The `UnlockedState` of the machine, if it is in the state `Unlocked`."]
+                pub fn into_unlocked(self) -> Option<UnlockedState> {
+                    match self {
+                        TurnStile::Unlocked(state) => Some(state),
+                        _ => None
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        state_accessors.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{parse::Result, Error};
+
+use crate::{
+    ident::{unraw, upper_snake_case},
+    machine::Machine,
+    state::{kind_enum_name, State},
+};
+
+/// The name of the bitset over the states of a machine.
+pub(crate) fn state_bitset_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}StateSet", unraw(machine)), machine.span())
+}
+
+/// The smallest unsigned integer with a bit for each of `state_count`
+/// states, if any.
+pub(crate) fn state_bitset_repr(state_count: usize) -> Option<Ident> {
+    let repr = if state_count <= 32 {
+        "u32"
+    } else if state_count <= 64 {
+        "u64"
+    } else {
+        return None;
+    };
+
+    Some(Ident::new(repr, Span::call_site()))
+}
+
+/// Check that the sets and regions of `machine` can each have a constant in
+/// `{Machine}StateSet`.
+pub(crate) fn check(machine: &Machine) -> Result<()> {
+    let mut constants: HashMap<String, &Ident> = HashMap::new();
+
+    for set in &machine.state_sets {
+        let _ = constants.insert(upper_snake_case(&set.name).to_string(), &set.name);
+    }
+
+    for region in &machine.regions {
+        if let Some(set) = constants.get(&upper_snake_case(&region.name).to_string()) {
+            return Err(Error::new(
+                region.name.span(),
+                format!(
+                    "region `{}` has the same name as the set `{}`",
+                    region.name, set
+                ),
+            ));
+        }
+    }
+
+    let names = machine.state_sets.iter().map(|s| (&s.name, "set"));
+    let names = names.chain(machine.regions.iter().map(|r| (&r.name, "region")));
+
+    for (name, what) in names {
+        let constant = upper_snake_case(name);
+
+        if constant == "ALL" || constant == "EMPTY" {
+            return Err(Error::new(
+                name.span(),
+                format!(
+                    "{} `{}` would clash with the constant `{}::{}`",
+                    what,
+                    name,
+                    state_bitset_name(&machine.name),
+                    constant
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `{Machine}StateSet`, a set of states stored as the bits of a `u32` or
+/// `u64`, with a constant for each set and region of the machine. Machines
+/// with more than 64 states do not get one.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateBitset<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateBitset<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let bitset = state_bitset_name(name);
+        let inline = self.machine.options.inline;
+
+        let states = self.machine.states();
+        let repr = match state_bitset_repr(states.0.len()) {
+            Some(repr) => repr,
+            None => return,
+        };
+        let kinds = states.0.iter().map(|s| &s.name);
+
+        // The bits of `members`, by their index in `{Machine}Kind`.
+        let mask = |members: &[State]| {
+            let bits = states
+                .0
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| members.contains(s))
+                .fold(0u64, |bits, (i, _)| bits | 1 << i);
+
+            Literal::u64_unsuffixed(bits)
+        };
+
+        let all = mask(&states.0);
+
+        let sets = self
+            .machine
+            .state_sets
+            .iter()
+            .map(|s| (&s.name, &s.states, "set"));
+        let regions = self
+            .machine
+            .regions
+            .iter()
+            .map(|r| (&r.name, &r.states, "region"));
+        let constants = sets.chain(regions).map(|(name, members, what)| {
+            let constant = upper_snake_case(name);
+            let bits = mask(members);
+            let doc = format!("The states of the {} `{}`.", what, unraw(name));
+
+            quote! {
+                #[doc = #doc]
+                pub const #constant: Self = #bitset(#bits);
+            }
+        });
+
+        let doc = format!(
+            "A set of states of `{}`, stored as the bits of a `{}`.",
+            unraw(name),
+            repr
+        );
+
+        tokens.extend(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct #bitset(#repr);
+
+            impl #bitset {
+                /// The set without any state.
+                pub const EMPTY: Self = #bitset(0);
+
+                /// The set of every state.
+                pub const ALL: Self = #bitset(#all);
+
+                #(#constants)*
+
+                /// The set holding only `kind`.
+                #inline
+                pub const fn of(kind: #kind_enum) -> Self {
+                    #bitset(1 << (kind as u32))
+                }
+
+                /// Whether `kind` is in the set.
+                #inline
+                pub const fn contains(self, kind: #kind_enum) -> bool {
+                    self.0 & Self::of(kind).0 != 0
+                }
+
+                /// Add `kind` to the set, returning whether it was not in it
+                /// yet.
+                #inline
+                pub fn insert(&mut self, kind: #kind_enum) -> bool {
+                    let absent = !self.contains(kind);
+                    self.0 |= Self::of(kind).0;
+                    absent
+                }
+
+                /// Remove `kind` from the set, returning whether it was in it.
+                #inline
+                pub fn remove(&mut self, kind: #kind_enum) -> bool {
+                    let present = self.contains(kind);
+                    self.0 &= !Self::of(kind).0;
+                    present
+                }
+
+                /// The states in either set.
+                #inline
+                pub const fn union(self, other: Self) -> Self {
+                    #bitset(self.0 | other.0)
+                }
+
+                /// The states in both sets.
+                #inline
+                pub const fn intersection(self, other: Self) -> Self {
+                    #bitset(self.0 & other.0)
+                }
+
+                /// The states of `self` which are not in `other`.
+                #inline
+                pub const fn difference(self, other: Self) -> Self {
+                    #bitset(self.0 & !other.0)
+                }
+
+                /// The states which are not in the set.
+                #inline
+                pub const fn complement(self) -> Self {
+                    #bitset(!self.0 & Self::ALL.0)
+                }
+
+                /// Whether every state of `self` is in `other`.
+                #inline
+                pub const fn is_subset(self, other: Self) -> bool {
+                    self.0 & !other.0 == 0
+                }
+
+                /// Whether the set has no state.
+                #inline
+                pub const fn is_empty(self) -> bool {
+                    self.0 == 0
+                }
+
+                /// The number of states in the set.
+                #inline
+                pub const fn len(self) -> usize {
+                    self.0.count_ones() as usize
+                }
+
+                /// The states in the set, in declaration order.
+                #inline
+                pub fn iter(self) -> impl Iterator<Item = #kind_enum> {
+                    ::core::iter::IntoIterator::into_iter([#(#kind_enum::#kinds),*])
+                        .filter(move |&kind| self.contains(kind))
+                }
+
+                /// The bits of the set, where bit `i` stands for the state
+                /// whose index in the kind enum is `i`.
+                #inline
+                pub const fn to_bits(self) -> #repr {
+                    self.0
+                }
+
+                /// The set with the given bits, or `None` if a bit standing
+                /// for no state is set.
+                #inline
+                pub const fn from_bits(bits: #repr) -> Option<Self> {
+                    if bits & !Self::ALL.0 == 0 {
+                        Some(#bitset(bits))
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            impl From<#kind_enum> for #bitset {
+                #inline
+                fn from(kind: #kind_enum) -> Self {
+                    #bitset::of(kind)
+                }
+            }
+
+            impl ::core::iter::FromIterator<#kind_enum> for #bitset {
+                fn from_iter<I: IntoIterator<Item = #kind_enum>>(kinds: I) -> Self {
+                    let mut set = #bitset::EMPTY;
+                    set.extend(kinds);
+                    set
+                }
+            }
+
+            impl ::core::iter::Extend<#kind_enum> for #bitset {
+                fn extend<I: IntoIterator<Item = #kind_enum>>(&mut self, kinds: I) {
+                    for kind in kinds {
+                        let _ = self.insert(kind);
+                    }
+                }
+            }
+
+            impl ::core::ops::BitOr for #bitset {
+                type Output = Self;
+
+                #inline
+                fn bitor(self, other: Self) -> Self {
+                    self.union(other)
+                }
+            }
+
+            impl ::core::ops::BitAnd for #bitset {
+                type Output = Self;
+
+                #inline
+                fn bitand(self, other: Self) -> Self {
+                    self.intersection(other)
+                }
+            }
+
+            impl ::core::ops::Sub for #bitset {
+                type Output = Self;
+
+                #inline
+                fn sub(self, other: Self) -> Self {
+                    self.difference(other)
+                }
+            }
+
+            impl ::core::ops::Not for #bitset {
+                type Output = Self;
+
+                #inline
+                fn not(self) -> Self {
+                    self.complement()
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_state_bitset_repr() {
+        assert_eq!(state_bitset_repr(1).unwrap(), "u32");
+        assert_eq!(state_bitset_repr(32).unwrap(), "u32");
+        assert_eq!(state_bitset_repr(33).unwrap(), "u64");
+        assert_eq!(state_bitset_repr(64).unwrap(), "u64");
+        assert!(state_bitset_repr(65).is_none());
+    }
+
+    #[test]
+    fn test_check() {
+        let cases = vec![
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Operational = { Running, Paused }
+                        Region Operational { Running }
+
+                        Pause { Running => Paused }
+                    }
+                },
+                "region `Operational` has the same name as the set `Operational`",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set All = { Running, Paused }
+
+                        Pause { Running => Paused }
+                    }
+                },
+                "set `All` would clash with the constant `PumpStateSet::ALL`",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Region Empty { Paused }
+
+                        Pause { Running => Paused }
+                    }
+                },
+                "region `Empty` would clash with the constant `PumpStateSet::EMPTY`",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_state_bitset_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            Pump {
+                InitialStates { Running }
+                FinalStates { Stopped }
+                Set Operational = { Running, Paused }
+                Region Idle { Paused, Stopped }
+
+                Pause { Running => Paused }
+                Stop { Operational => Stopped }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = "A set of states of `Pump`, stored as the bits of a `u32`."]
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct PumpStateSet(u32);
+
+            impl PumpStateSet {
+                /// The set without any state.
+                pub const EMPTY: Self = PumpStateSet(0);
+
+                /// The set of every state.
+                pub const ALL: Self = PumpStateSet(7);
+
+                #[doc = "The states of the set `Operational`."]
+                pub const OPERATIONAL: Self = PumpStateSet(3);
+
+                #[doc = "The states of the region `Idle`."]
+                pub const IDLE: Self = PumpStateSet(6);
+
+                /// The set holding only `kind`.
+                pub const fn of(kind: PumpKind) -> Self {
+                    PumpStateSet(1 << (kind as u32))
+                }
+
+                /// Whether `kind` is in the set.
+                pub const fn contains(self, kind: PumpKind) -> bool {
+                    self.0 & Self::of(kind).0 != 0
+                }
+
+                /// Add `kind` to the set, returning whether it was not in it
+                /// yet.
+                pub fn insert(&mut self, kind: PumpKind) -> bool {
+                    let absent = !self.contains(kind);
+                    self.0 |= Self::of(kind).0;
+                    absent
+                }
+
+                /// Remove `kind` from the set, returning whether it was in it.
+                pub fn remove(&mut self, kind: PumpKind) -> bool {
+                    let present = self.contains(kind);
+                    self.0 &= !Self::of(kind).0;
+                    present
+                }
+
+                /// The states in either set.
+                pub const fn union(self, other: Self) -> Self {
+                    PumpStateSet(self.0 | other.0)
+                }
+
+                /// The states in both sets.
+                pub const fn intersection(self, other: Self) -> Self {
+                    PumpStateSet(self.0 & other.0)
+                }
+
+                /// The states of `self` which are not in `other`.
+                pub const fn difference(self, other: Self) -> Self {
+                    PumpStateSet(self.0 & !other.0)
+                }
+
+                /// The states which are not in the set.
+                pub const fn complement(self) -> Self {
+                    PumpStateSet(!self.0 & Self::ALL.0)
+                }
+
+                /// Whether every state of `self` is in `other`.
+                pub const fn is_subset(self, other: Self) -> bool {
+                    self.0 & !other.0 == 0
+                }
+
+                /// Whether the set has no state.
+                pub const fn is_empty(self) -> bool {
+                    self.0 == 0
+                }
+
+                /// The number of states in the set.
+                pub const fn len(self) -> usize {
+                    self.0.count_ones() as usize
+                }
+
+                /// The states in the set, in declaration order.
+                pub fn iter(self) -> impl Iterator<Item = PumpKind> {
+                    ::core::iter::IntoIterator::into_iter([PumpKind::Running, PumpKind::Paused, PumpKind::Stopped])
+                        .filter(move |&kind| self.contains(kind))
+                }
+
+                /// The bits of the set, where bit `i` stands for the state
+                /// whose index in the kind enum is `i`.
+                pub const fn to_bits(self) -> u32 {
+                    self.0
+                }
+
+                /// The set with the given bits, or `None` if a bit standing
+                /// for no state is set.
+                pub const fn from_bits(bits: u32) -> Option<Self> {
+                    if bits & !Self::ALL.0 == 0 {
+                        Some(PumpStateSet(bits))
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            impl From<PumpKind> for PumpStateSet {
+                fn from(kind: PumpKind) -> Self {
+                    PumpStateSet::of(kind)
+                }
+            }
+
+            impl ::core::iter::FromIterator<PumpKind> for PumpStateSet {
+                fn from_iter<I: IntoIterator<Item = PumpKind>>(kinds: I) -> Self {
+                    let mut set = PumpStateSet::EMPTY;
+                    set.extend(kinds);
+                    set
+                }
+            }
+
+            impl ::core::iter::Extend<PumpKind> for PumpStateSet {
+                fn extend<I: IntoIterator<Item = PumpKind>>(&mut self, kinds: I) {
+                    for kind in kinds {
+                        let _ = self.insert(kind);
+                    }
+                }
+            }
+
+            impl ::core::ops::BitOr for PumpStateSet {
+                type Output = Self;
+
+                fn bitor(self, other: Self) -> Self {
+                    self.union(other)
+                }
+            }
+
+            impl ::core::ops::BitAnd for PumpStateSet {
+                type Output = Self;
+
+                fn bitand(self, other: Self) -> Self {
+                    self.intersection(other)
+                }
+            }
+
+            impl ::core::ops::Sub for PumpStateSet {
+                type Output = Self;
+
+                fn sub(self, other: Self) -> Self {
+                    self.difference(other)
+                }
+            }
+
+            impl ::core::ops::Not for PumpStateSet {
+                type Output = Self;
+
+                fn not(self) -> Self {
+                    self.complement()
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        StateBitset { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
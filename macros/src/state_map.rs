@@ -0,0 +1,196 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{ident::unraw, machine::Machine, state::kind_enum_name};
+
+/// The name of the map keyed by the states of a machine.
+pub(crate) fn state_map_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}StateMap", unraw(machine)), machine.span())
+}
+
+/// `{Machine}StateMap<T>`, holding a value for each state of the machine in
+/// an array indexed by `{Machine}Kind`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateMap<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateMap<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let map = state_map_name(name);
+        let inline = self.machine.options.inline;
+
+        let states = self.machine.states();
+        let kinds = states.0.iter().map(|s| &s.name).collect::<Vec<_>>();
+
+        let doc = format!(
+            "A value for each state of `{}`, stored in an array indexed by `{}`.",
+            unraw(name),
+            kind_enum
+        );
+
+        tokens.extend(quote! {
+            #[doc = #doc]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct #map<T>([T; #name::STATE_COUNT]);
+
+            impl<T> #map<T> {
+                /// Every state, in the order of the values.
+                const KINDS: [#kind_enum; #name::STATE_COUNT] = [#(#kind_enum::#kinds),*];
+
+                /// The map holding `f(kind)` for each state `kind`, called in
+                /// declaration order.
+                #inline
+                pub fn from_fn(mut f: impl FnMut(#kind_enum) -> T) -> Self {
+                    #map([#(f(#kind_enum::#kinds)),*])
+                }
+
+                /// The value of the state `kind`.
+                #inline
+                pub fn get(&self, kind: #kind_enum) -> &T {
+                    &self.0[kind as usize]
+                }
+
+                /// The value of the state `kind`, mutably.
+                #inline
+                pub fn get_mut(&mut self, kind: #kind_enum) -> &mut T {
+                    &mut self.0[kind as usize]
+                }
+
+                /// The states and their values, in declaration order.
+                #inline
+                pub fn iter(&self) -> impl Iterator<Item = (#kind_enum, &T)> {
+                    Self::KINDS.iter().copied().zip(self.0.iter())
+                }
+
+                /// The states and their values, mutably, in declaration
+                /// order.
+                #inline
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (#kind_enum, &mut T)> {
+                    Self::KINDS.iter().copied().zip(self.0.iter_mut())
+                }
+
+                /// The values, in the declaration order of their states.
+                #inline
+                pub fn values(&self) -> &[T] {
+                    &self.0
+                }
+            }
+
+            impl<T: Default> Default for #map<T> {
+                #inline
+                fn default() -> Self {
+                    #map::from_fn(|_| T::default())
+                }
+            }
+
+            impl<T> ::core::ops::Index<#kind_enum> for #map<T> {
+                type Output = T;
+
+                #inline
+                fn index(&self, kind: #kind_enum) -> &T {
+                    self.get(kind)
+                }
+            }
+
+            impl<T> ::core::ops::IndexMut<#kind_enum> for #map<T> {
+                #inline
+                fn index_mut(&mut self, kind: #kind_enum) -> &mut T {
+                    self.get_mut(kind)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_state_map_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = "A value for each state of `TurnStile`, stored in an array indexed by `TurnStileKind`."]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub struct TurnStileStateMap<T>([T; TurnStile::STATE_COUNT]);
+
+            impl<T> TurnStileStateMap<T> {
+                /// Every state, in the order of the values.
+                const KINDS: [TurnStileKind; TurnStile::STATE_COUNT] = [TurnStileKind::Locked, TurnStileKind::Unlocked];
+
+                /// The map holding `f(kind)` for each state `kind`, called in
+                /// declaration order.
+                pub fn from_fn(mut f: impl FnMut(TurnStileKind) -> T) -> Self {
+                    TurnStileStateMap([f(TurnStileKind::Locked), f(TurnStileKind::Unlocked)])
+                }
+
+                /// The value of the state `kind`.
+                pub fn get(&self, kind: TurnStileKind) -> &T {
+                    &self.0[kind as usize]
+                }
+
+                /// The value of the state `kind`, mutably.
+                pub fn get_mut(&mut self, kind: TurnStileKind) -> &mut T {
+                    &mut self.0[kind as usize]
+                }
+
+                /// The states and their values, in declaration order.
+                pub fn iter(&self) -> impl Iterator<Item = (TurnStileKind, &T)> {
+                    Self::KINDS.iter().copied().zip(self.0.iter())
+                }
+
+                /// The states and their values, mutably, in declaration
+                /// order.
+                pub fn iter_mut(&mut self) -> impl Iterator<Item = (TurnStileKind, &mut T)> {
+                    Self::KINDS.iter().copied().zip(self.0.iter_mut())
+                }
+
+                /// The values, in the declaration order of their states.
+                pub fn values(&self) -> &[T] {
+                    &self.0
+                }
+            }
+
+            impl<T: Default> Default for TurnStileStateMap<T> {
+                fn default() -> Self {
+                    TurnStileStateMap::from_fn(|_| T::default())
+                }
+            }
+
+            impl<T> ::core::ops::Index<TurnStileKind> for TurnStileStateMap<T> {
+                type Output = T;
+
+                fn index(&self, kind: TurnStileKind) -> &T {
+                    self.get(kind)
+                }
+            }
+
+            impl<T> ::core::ops::IndexMut<TurnStileKind> for TurnStileStateMap<T> {
+                fn index_mut(&mut self, kind: TurnStileKind) -> &mut T {
+                    self.get_mut(kind)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        StateMap { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
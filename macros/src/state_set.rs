@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Result},
+    punctuated::Punctuated,
+    Error, Ident, Token,
+};
+
+use crate::{
+    ident::{snake_case, unraw},
+    machine::Machine,
+    state::{kind_enum_name, State},
+    transition::{Transition, Transitions},
+};
+
+/// The name of the method checking whether a machine is in one of the states
+/// of `set`, e.g. `is_operational`.
+pub(crate) fn state_set_fn_name(set: &Ident) -> Ident {
+    Ident::new(&format!("is_{}", unraw(&snake_case(set))), set.span())
+}
+
+/// A set of states declared with `Set Operational = { ... }`, whose name can
+/// be used in place of a state on the left of `=>` to declare the same
+/// transition from each of them.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StateSet {
+    pub name: Ident,
+    pub states: Vec<State>,
+}
+
+impl StateSet {
+    /// Whether the next tokens are a `Set ... = { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "Set")
+            && fork.parse::<Ident>().is_ok()
+            && fork.peek(Token![=])
+            && !fork.peek(Token![=>])
+    }
+}
+
+impl Parse for StateSet {
+    /// example state set tokens:
+    ///
+    /// ```text
+    /// Set Operational = { Running, Paused, Degraded }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Set Operational = { Running, Paused, Degraded }`
+        //  ^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "Set" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `Set ... = { ... }` block",
+            ));
+        }
+
+        // `Set Operational = { Running, Paused, Degraded }`
+        //      ^^^^^^^^^^^
+        let name: Ident = input.parse()?;
+
+        // `Set Operational = { Running, Paused, Degraded }`
+        //                  ^
+        let _: Token![=] = input.parse()?;
+
+        // `Set Operational = { Running, Paused, Degraded }`
+        //                      ^^^^^^^^^^^^^^^^^^^^^^^^^
+        let block_states;
+        braced!(block_states in input);
+
+        let punctuated_states: Punctuated<State, Token![,]> =
+            block_states.parse_terminated(State::parse)?;
+
+        let mut states: Vec<State> = Vec::new();
+
+        for state in punctuated_states {
+            if states.contains(&state) {
+                return Err(Error::new(
+                    state.name.span(),
+                    format!("duplicate state `{}` in set `{}`", state.name, name),
+                ));
+            }
+
+            states.push(state);
+        }
+
+        if states.is_empty() {
+            return Err(Error::new(name.span(), format!("set `{}` is empty", name)));
+        }
+
+        Ok(StateSet { name, states })
+    }
+}
+
+/// Replace the transitions of `machine` declared from a set with the same
+/// transition from each state of the set, in the order the states are listed
+/// in.
+pub(crate) fn expand(machine: &mut Machine) -> Result<()> {
+    let sets = &machine.state_sets;
+
+    let mut by_name: HashMap<String, &StateSet> = HashMap::new();
+
+    for set in sets {
+        if by_name.insert(unraw(&set.name), set).is_some() {
+            return Err(Error::new(
+                set.name.span(),
+                format!("duplicate set `{}`", set.name),
+            ));
+        }
+
+        // `is_final` tells whether the machine is in a final state.
+        if state_set_fn_name(&set.name) == "is_final" {
+            return Err(Error::new(
+                set.name.span(),
+                format!("set `{}` would clash with the method `is_final`", set.name),
+            ));
+        }
+    }
+
+    if sets.is_empty() {
+        return Ok(());
+    }
+
+    for t in &machine.transitions.0 {
+        if let Some(set) = by_name.get(&unraw(&t.to.name)) {
+            return Err(Error::new(
+                t.to.name.span(),
+                format!(
+                    "set `{}` cannot be the target of a transition, only its source",
+                    set.name
+                ),
+            ));
+        }
+    }
+
+    // Check the sets before they add transitions from their states, so that
+    // a misspelled state is not taken for a new one.
+    let states = machine.states();
+
+    for set in sets {
+        for s in &set.states {
+            if !states.0.iter().any(|state| state.name == s.name) {
+                return Err(Error::new(
+                    s.name.span(),
+                    format!(
+                        "state `{}` of set `{}` is not a state of `{}`",
+                        s.name, set.name, machine.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    // The transitions declared so far, by event and source state.
+    let mut declared: HashMap<(String, String), usize> = HashMap::new();
+    let mut transitions: Vec<Transition> = Vec::new();
+
+    for t in &machine.transitions.0 {
+        let froms = match by_name.get(&unraw(&t.from.name)) {
+            Some(set) => set
+                .states
+                .iter()
+                .map(|s| {
+                    // Point at the transition rather than at the set.
+                    let mut name = s.name.clone();
+                    name.set_span(t.from.name.span());
+
+                    State { name }
+                })
+                .collect(),
+            None => vec![t.from.clone()],
+        };
+
+        for from in froms {
+            let key = (unraw(&t.event.name), from.name.to_string());
+            let expanded = Transition { from, ..t.clone() };
+
+            if let Some(&previous) = declared.get(&key) {
+                return Err(expanded.clash(&transitions[previous]));
+            }
+
+            let _ = declared.insert(key, transitions.len());
+            transitions.push(expanded);
+        }
+    }
+
+    machine.transitions = Transitions(transitions);
+
+    if let Some(s) = machine
+        .states()
+        .0
+        .iter()
+        .find(|s| by_name.contains_key(&unraw(&s.name)))
+    {
+        return Err(Error::new(
+            s.name.span(),
+            format!("state `{}` has the same name as a set", s.name),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The methods checking whether the machine is in one of the states of each
+/// set declared with `Set ... = { ... }`.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateSets<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateSets<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let inline = self.machine.options.inline;
+
+        let fns = self.machine.state_sets.iter().map(|set| {
+            let fn_name = state_set_fn_name(&set.name);
+            let states = set.states.iter().map(|s| &s.name);
+            let doc = format!(
+                "Whether the machine is in one of the states of the set `{}`.",
+                unraw(&set.name)
+            );
+
+            quote! {
+                #[doc = #doc]
+                #inline
+                pub fn #fn_name(&self) -> bool {
+                    matches!(self.kind(), #(#kind_enum::#states)|*)
+                }
+            }
+        });
+
+        tokens.extend(quote! {
+            impl #name {
+                #(#fns)*
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_state_set_parse() {
+        let left: StateSet = parse2(quote! { Set Operational = { Running, Paused } }).unwrap();
+        let right = StateSet {
+            name: parse_quote! { Operational },
+            states: vec![
+                State {
+                    name: parse_quote! { Running },
+                },
+                State {
+                    name: parse_quote! { Paused },
+                },
+            ],
+        };
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_expand() {
+        let machine: Machine = parse2(quote! {
+            Pump {
+                InitialStates { Running }
+                FinalStates { Stopped }
+                Set Operational = { Running, Paused, Degraded }
+
+                Shutdown { Operational => Stopped }
+                Pause { Running => Paused }
+                Fail { Running => Degraded }
+                Resume { Paused, Degraded => Running }
+            }
+        })
+        .unwrap();
+
+        let transitions = machine
+            .transitions
+            .0
+            .iter()
+            .map(|t| format!("{} {} => {}", t.event.name, t.from.name, t.to.name))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            transitions,
+            vec![
+                "Shutdown Running => Stopped",
+                "Shutdown Paused => Stopped",
+                "Shutdown Degraded => Stopped",
+                "Pause Running => Paused",
+                "Fail Running => Degraded",
+                "Resume Paused => Running",
+                "Resume Degraded => Running",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let cases = vec![
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Operational = { Running }
+                        Set Operational = { Paused }
+
+                        Pause { Running => Paused }
+                    }
+                },
+                "duplicate set `Operational`",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Final = { Running }
+
+                        Pause { Running => Paused }
+                    }
+                },
+                "set `Final` would clash with the method `is_final`",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Operational = { Running, Paused }
+
+                        Shutdown { Operational => Stopped }
+                        Shutdown { Paused => Running }
+                    }
+                },
+                "nondeterministic transition: `Shutdown` from `Paused` leads to both `Stopped` and `Running`",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Operational = { Running, Paused }
+
+                        Pause { Running => Operational }
+                    }
+                },
+                "set `Operational` cannot be the target of a transition, only its source",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Operational }
+                        Set Operational = { Running }
+
+                        Pause { Running => Paused }
+                    }
+                },
+                "state `Operational` has the same name as a set",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Operational = { Running, Seized }
+
+                        Shutdown { Operational => Stopped }
+                    }
+                },
+                "state `Seized` of set `Operational` is not a state of `Pump`",
+            ),
+            (
+                quote! {
+                    Pump {
+                        InitialStates { Running }
+                        Set Operational = { }
+
+                        Shutdown { Running => Stopped }
+                    }
+                },
+                "set `Operational` is empty",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_state_sets_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            Pump {
+                InitialStates { Running }
+                FinalStates { Stopped }
+                Set Operational = { Running, Paused }
+
+                Shutdown { Operational => Stopped }
+                Pause { Running => Paused }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl Pump {
+                #[doc = "Whether the machine is in one of the states of the set `Operational`."]
+                pub fn is_operational(&self) -> bool {
+                    matches!(self.kind(), PumpKind::Running | PumpKind::Paused)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        StateSets { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
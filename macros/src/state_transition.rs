@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+use syn::Path;
+
+use crate::{
+    label::Labels,
+    options::{InlineMode, ProvenanceMode},
+    state::{state_enum_name, States},
+    transition::{Transition, TransitionFns, Transitions},
+};
+
+#[derive(Debug, PartialEq)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct StateTransitions<'a> {
+    pub enum_name: &'a Ident,
+    pub provenance: ProvenanceMode,
+    pub hooks: Option<&'a Path>,
+    pub tracing: bool,
+    pub metrics: bool,
+    pub inline: InlineMode,
+    pub labels: &'a Labels,
+    pub states: &'a States,
+    pub transitions: &'a Transitions,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for StateTransitions<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let index = self.transitions.index();
+        let shared = self.transitions.shared(self.provenance);
+
+        for s in self.states {
+            let struct_name = state_enum_name(&s.name);
+
+            let transitions = index
+                .from(&s.name)
+                .iter()
+                .map(|&t| t.clone())
+                .collect::<Vec<Transition>>();
+
+            let shared_here = transitions
+                .iter()
+                .filter(|t| shared.contains(&t.shared_key()))
+                .cloned()
+                .collect::<Vec<Transition>>();
+
+            let transitions = TransitionFns {
+                inline: self.inline,
+                labels: self.labels.clone(),
+                ..Transitions(transitions).to_fns(
+                    self.enum_name,
+                    self.provenance,
+                    self.hooks,
+                    self.tracing,
+                    self.metrics,
+                    &shared_here,
+                )
+            };
+
+            // The bodies shared by the transitions entering this state, once
+            // per event.
+            let mut shared_fns = Vec::new();
+
+            if transitions.is_instrumented() {
+                let mut seen = HashSet::new();
+
+                for &t in index.to(&s.name) {
+                    let key = t.shared_key();
+
+                    if shared.contains(&key) && seen.insert(key) {
+                        shared_fns.push(transitions.shared_fn(t));
+                    }
+                }
+            }
+
+            if transitions.transitions.is_empty() && shared_fns.is_empty() {
+                continue;
+            }
+
+            tokens.extend(quote! {
+                impl #struct_name {
+                    #(#shared_fns)*
+                    #transitions
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{event::Event, state::State};
+
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn state_transition_tokens() {
+        let state_transitions = StateTransitions {
+            enum_name: &parse_quote! { TurnStile },
+            provenance: ProvenanceMode::Event,
+            hooks: None,
+            tracing: false,
+            metrics: false,
+            inline: InlineMode::Auto,
+            labels: &Labels::default(),
+            states: &States(vec![parse_quote!(Locked), parse_quote!(Unlocked)]),
+            transitions: &Transitions(vec![
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Coin },
+                    },
+                    from: State {
+                        name: parse_quote! { Locked },
+                    },
+                    to: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Push },
+                    },
+                    from: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    to: State {
+                        name: parse_quote! { Locked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+            ]),
+        };
+
+        let left = quote! {
+            impl LockedState {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn coin(&self) -> TurnStile {
+                    TurnStile::Unlocked(UnlockedState::FromCoin)
+                }
+            }
+
+            impl UnlockedState {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn push(&self) -> TurnStile {
+                    TurnStile::Locked(LockedState::FromPush)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        state_transitions.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn state_transition_tokens_shared() {
+        let machine: crate::machine::Machine = syn::parse2(quote! {
+            Lock {
+                InitialStates { Locked }
+                FinalStates { Broken }
+
+                TurnKey { Locked => Unlocked }
+                BreakKeyhole { Locked, Unlocked => Broken }
+            }
+        })
+        .unwrap();
+
+        let state_transitions = StateTransitions {
+            enum_name: &machine.name,
+            provenance: ProvenanceMode::Event,
+            hooks: None,
+            tracing: true,
+            metrics: false,
+            inline: InlineMode::Auto,
+            labels: &Labels::default(),
+            states: &machine.states(),
+            transitions: &machine.transitions,
+        };
+
+        let left = quote! {
+            impl LockedState {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn turn_key(&self) -> Lock {
+                    ::tracing::debug!(
+                        machine = "Lock",
+                        from = "Locked",
+                        event = "TurnKey",
+                        to = "Unlocked",
+                        "transition"
+                    );
+                    let to = Lock::Unlocked(UnlockedState::FromTurnKey);
+                    to
+                }
+
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn break_keyhole(&self) -> Lock {
+                    BrokenState::enter_on_break_keyhole(LockKind::Locked)
+                }
+            }
+
+            impl UnlockedState {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn break_keyhole(&self) -> Lock {
+                    BrokenState::enter_on_break_keyhole(LockKind::Unlocked)
+                }
+            }
+
+            impl BrokenState {
+                fn enter_on_break_keyhole(from: LockKind) -> Lock {
+                    ::tracing::debug!(
+                        machine = "Lock",
+                        from = from.name(),
+                        event = "BreakKeyhole",
+                        to = "Broken",
+                        "transition"
+                    );
+                    let to = Lock::Broken(BrokenState::FromBreakKeyhole);
+                    to
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        state_transitions.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,167 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::Ident;
+
+use crate::{ident::unraw, machine::Machine};
+
+/// `` `A`, `B` `` for a list of identifiers.
+#[allow(single_use_lifetimes)]
+fn code_list<'a>(idents: impl IntoIterator<Item = &'a Ident>) -> String {
+    idents
+        .into_iter()
+        .map(|i| format!("`{}`", unraw(i)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Doc attributes summarizing the diagram, appended to the docs of the
+/// machine enum so that `cargo doc` and IDE hovers show the whole machine
+/// without going back to the macro invocation.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Summary<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Summary<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let machine = self.machine;
+        let states = machine.states();
+        let events = machine.events();
+
+        let mut lines = Vec::new();
+
+        if !machine.options.docs.is_empty() {
+            lines.push(String::new());
+        }
+
+        lines.push("# Diagram".to_owned());
+        lines.push(String::new());
+        lines.push(format!(
+            "States: {}.",
+            code_list(states.0.iter().map(|s| &s.name))
+        ));
+        lines.push(String::new());
+        lines.push(format!(
+            "Initial states: {}.",
+            code_list(machine.initial_states.0.iter().map(|i| &i.name))
+        ));
+
+        if !machine.final_states.0.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "Final states: {}.",
+                code_list(machine.final_states.0.iter().map(|f| &f.name))
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!(
+            "Events: {}.",
+            code_list(events.iter().map(|e| &e.name))
+        ));
+        lines.push(String::new());
+        lines.push("Transitions:".to_owned());
+        lines.push(String::new());
+
+        for t in &machine.transitions.0 {
+            lines.push(format!(
+                "- `{}` --`{}`--> `{}`",
+                unraw(&t.from.name),
+                unraw(&t.event.name),
+                unraw(&t.to.name)
+            ));
+        }
+
+        // The transition table, with a row per state and a column per event,
+        // so that missing edges stand out.
+        if !events.is_empty() {
+            lines.push(String::new());
+            lines.push(format!(
+                "| | {} |",
+                events
+                    .iter()
+                    .map(|e| format!("`{}`", unraw(&e.name)))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ));
+            lines.push(format!("|---|{}", "---|".repeat(events.len())));
+
+            let index = machine.transitions.index();
+
+            for s in &states.0 {
+                let cells = events.iter().map(|e| {
+                    index
+                        .from(&s.name)
+                        .iter()
+                        .find(|t| t.event.name == e.name)
+                        .map(|t| format!("`{}`", unraw(&t.to.name)))
+                        .unwrap_or_default()
+                });
+
+                lines.push(format!(
+                    "| `{}` | {} |",
+                    unraw(&s.name),
+                    cells.collect::<Vec<_>>().join(" | ")
+                ));
+            }
+        }
+
+        for line in lines {
+            let line = if line.is_empty() {
+                line
+            } else {
+                format!(" {}", line)
+            };
+
+            tokens.extend(quote! { #[doc = #line] });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_summary_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            /// A turnstile.
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            #[doc = ""]
+            #[doc = " # Diagram"]
+            #[doc = ""]
+            #[doc = " States: `Locked`, `Unlocked`."]
+            #[doc = ""]
+            #[doc = " Initial states: `Locked`."]
+            #[doc = ""]
+            #[doc = " Events: `Coin`, `Push`."]
+            #[doc = ""]
+            #[doc = " Transitions:"]
+            #[doc = ""]
+            #[doc = " - `Locked` --`Coin`--> `Unlocked`"]
+            #[doc = " - `Unlocked` --`Push`--> `Locked`"]
+            #[doc = ""]
+            #[doc = " | | `Coin` | `Push` |"]
+            #[doc = " |---|---|---|"]
+            #[doc = " | `Locked` | `Unlocked` |  |"]
+            #[doc = " | `Unlocked` |  | `Locked` |"]
+        };
+
+        let mut right = TokenStream::new();
+        Summary { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
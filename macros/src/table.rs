@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+use crate::{machine::Machine, state::kind_enum_name};
+
+/// `TRANSITION_TABLE`, generated with `#[dispatch(table)]`, which lists the
+/// state reached from every state on every event, and `from_table`, which
+/// builds the machine from one of its entries. Together they replace the
+/// transition method of every state and event.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct TransitionTable<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for TransitionTable<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let provenance_type = self.machine.provenance_type();
+
+        let states = self.machine.states();
+        let events = self.machine.events();
+        let values = self.machine.values();
+        let transitions = self.machine.dynamic_transitions();
+        let index = transitions.index();
+
+        let rows = states.0.iter().map(|s| {
+            let targets = index
+                .from(&s.name)
+                .iter()
+                .map(|t| (t.event.name.to_string(), *t))
+                .collect::<HashMap<_, _>>();
+
+            let cells = events
+                .iter()
+                .map(|e| match targets.get(&e.name.to_string()) {
+                    Some(t) => {
+                        let to = &t.to.name;
+                        let provenance = self
+                            .machine
+                            .provenance_value(&t.provenance(self.machine.options.provenance));
+
+                        quote! { Some((#kind_enum::#to, #provenance)) }
+                    }
+                    None => quote! { None },
+                });
+
+            quote! { [#(#cells),*] }
+        });
+
+        let arms = values.iter().map(|(s, p)| {
+            let state = &s.name;
+            let provenance = self.machine.provenance_value(p);
+            let value = self.machine.value(&s.name, p);
+
+            quote! { (#kind_enum::#state, #provenance) => #value }
+        });
+
+        // Only add a catch-all when some combination is actually illegal,
+        // otherwise it triggers an unreachable pattern warning.
+        let provenance_count = values
+            .iter()
+            .map(|(_, p)| p.variant())
+            .collect::<HashSet<_>>()
+            .len();
+        let catch_all = if values.len() < states.0.len() * provenance_count {
+            quote! { _ => unreachable!("the transition table only lists legal states") }
+        } else {
+            quote! {}
+        };
+
+        tokens.extend(quote! {
+            impl #name {
+                /// The kind and provenance of the state reached from each
+                /// state on each event, indexed by the kind of the state and
+                /// by the event, or `None` where the state does not accept
+                /// the event.
+                pub const TRANSITION_TABLE: [[Option<(#kind_enum, #provenance_type)>; #name::EVENT_COUNT]; #name::STATE_COUNT] = [
+                    #(#rows),*
+                ];
+
+                /// Build the machine from an entry of `TRANSITION_TABLE`.
+                fn from_table(kind: #kind_enum, provenance: #provenance_type) -> #name {
+                    match (kind, provenance) {
+                        #(#arms,)*
+                        #catch_all
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_transition_table_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            #[dispatch(table)]
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl TurnStile {
+                /// The kind and provenance of the state reached from each
+                /// state on each event, indexed by the kind of the state and
+                /// by the event, or `None` where the state does not accept
+                /// the event.
+                pub const TRANSITION_TABLE: [[Option<(TurnStileKind, TurnStileProvenance)>; TurnStile::EVENT_COUNT]; TurnStile::STATE_COUNT] = [
+                    [Some((TurnStileKind::Unlocked, TurnStileProvenance::FromCoin)), None],
+                    [None, Some((TurnStileKind::Locked, TurnStileProvenance::FromPush))]
+                ];
+
+                /// Build the machine from an entry of `TRANSITION_TABLE`.
+                fn from_table(kind: TurnStileKind, provenance: TurnStileProvenance) -> TurnStile {
+                    match (kind, provenance) {
+                        (TurnStileKind::Locked, TurnStileProvenance::FromPush) => TurnStile::Locked(LockedState::FromPush),
+                        (TurnStileKind::Locked, TurnStileProvenance::FromInit) => TurnStile::Locked(LockedState::FromInit),
+                        (TurnStileKind::Unlocked, TurnStileProvenance::FromCoin) => TurnStile::Unlocked(UnlockedState::FromCoin),
+                        _ => unreachable!("the transition table only lists legal states")
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        TransitionTable { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Delimiter, Group, TokenStream, TokenTree};
+use syn::{
+    parenthesized,
+    parse::{Parse, ParseStream, Parser, Result},
+    punctuated::Punctuated,
+    token::{Brace, Paren},
+    Error, Ident, Token,
+};
+
+use crate::ident::unraw;
+
+/// A part of a diagram declared with `Template Retry(Start, Done) { ... }`
+/// next to the machines of a `state_machine!` invocation, which their bodies
+/// can include with `Retry(Connecting, Connected)`.
+#[derive(Debug, Clone)]
+pub(crate) struct Template {
+    pub name: Ident,
+    pub params: Vec<Ident>,
+    pub body: TokenStream,
+}
+
+impl Template {
+    /// Whether the next tokens are a `Template ...(...) { ... }` block.
+    pub fn peek(input: ParseStream<'_>) -> bool {
+        let fork = input.fork();
+
+        fork.parse::<Ident>().is_ok_and(|ident| ident == "Template")
+            && fork.parse::<Ident>().is_ok()
+            && fork.peek(Paren)
+    }
+}
+
+impl Parse for Template {
+    /// example template tokens:
+    ///
+    /// ```text
+    /// Template Retry(Start, Done) {
+    ///     Fail { Start => Backoff }
+    ///     Timer { Backoff => Start }
+    ///     Succeed { Start => Done }
+    /// }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        // `Template Retry(Start, Done) { ... }`
+        //  ^^^^^^^^
+        let block_name: Ident = input.parse()?;
+
+        if block_name != "Template" {
+            return Err(Error::new(
+                block_name.span(),
+                "expected `Template ...(...) { ... }` block",
+            ));
+        }
+
+        // `Template Retry(Start, Done) { ... }`
+        //           ^^^^^
+        let name: Ident = input.parse()?;
+
+        // `Template Retry(Start, Done) { ... }`
+        //                 ^^^^^^^^^^^
+        let block_params;
+        parenthesized!(block_params in input);
+
+        let punctuated_params: Punctuated<Ident, Token![,]> =
+            block_params.parse_terminated(Ident::parse)?;
+
+        let mut params: Vec<Ident> = Vec::new();
+
+        for param in punctuated_params {
+            if params.contains(&param) {
+                return Err(Error::new(
+                    param.span(),
+                    format!("duplicate parameter `{}` in template `{}`", param, name),
+                ));
+            }
+
+            params.push(param);
+        }
+
+        // `Template Retry(Start, Done) { ... }`
+        //                              ^^^^^^^
+        if !input.peek(Brace) {
+            return Err(input.error("expected the body of the template in `{ ... }`"));
+        }
+
+        let body = match input.parse::<TokenTree>()? {
+            TokenTree::Group(group) => group.stream(),
+            _ => unreachable!(),
+        };
+
+        Ok(Template { name, params, body })
+    }
+}
+
+/// Replace the identifiers in `tokens` named after parameters with their
+/// arguments, in nested blocks too.
+fn substitute(tokens: TokenStream, args: &HashMap<String, TokenTree>) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Ident(ident) => match args.get(&ident.to_string()) {
+                Some(arg) => arg.clone(),
+                None => TokenTree::Ident(ident),
+            },
+            TokenTree::Group(group) => {
+                let mut substituted =
+                    Group::new(group.delimiter(), substitute(group.stream(), args));
+                substituted.set_span(group.span());
+
+                TokenTree::Group(substituted)
+            }
+            tree => tree,
+        })
+        .collect()
+}
+
+/// Replace the instantiations `Retry(...)` in the body of a machine with the
+/// bodies of their templates. `stack` lists the templates being instantiated,
+/// to reject templates which include themselves.
+pub(crate) fn expand(
+    body: TokenStream,
+    templates: &[Template],
+    stack: &mut Vec<String>,
+) -> Result<TokenStream> {
+    let mut expanded = TokenStream::new();
+    let mut trees = body.into_iter().peekable();
+
+    while let Some(tree) = trees.next() {
+        let name = match (&tree, trees.peek()) {
+            (TokenTree::Ident(name), Some(TokenTree::Group(group)))
+                if group.delimiter() == Delimiter::Parenthesis =>
+            {
+                name.clone()
+            }
+            _ => {
+                expanded.extend([tree]);
+                continue;
+            }
+        };
+
+        let args = match trees.next() {
+            Some(TokenTree::Group(group)) => group,
+            _ => unreachable!(),
+        };
+
+        let template = templates
+            .iter()
+            .find(|t| unraw(&t.name) == unraw(&name))
+            .ok_or_else(|| Error::new(name.span(), format!("unknown template `{}`", name)))?;
+
+        if stack.contains(&unraw(&template.name)) {
+            return Err(Error::new(
+                name.span(),
+                format!("template `{}` includes itself", template.name),
+            ));
+        }
+
+        let args = Punctuated::<TokenTree, Token![,]>::parse_terminated.parse2(args.stream())?;
+
+        if args.len() != template.params.len() {
+            return Err(Error::new(
+                name.span(),
+                format!(
+                    "template `{}` takes {} parameters but {} were given",
+                    template.name,
+                    template.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        let args = template
+            .params
+            .iter()
+            .map(ToString::to_string)
+            .zip(args)
+            .collect::<HashMap<_, _>>();
+
+        stack.push(unraw(&template.name));
+        expanded.extend(expand(
+            substitute(template.body.clone(), &args),
+            templates,
+            stack,
+        )?);
+        let _ = stack.pop();
+    }
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+    use syn::parse2;
+
+    fn templates() -> Vec<Template> {
+        vec![parse2(quote! {
+            Template Retry(Start, Done, Weight) {
+                Fail * Weight { Start => Backoff }
+                Timer { Backoff => Start }
+                Succeed { Start => Done }
+            }
+        })
+        .unwrap()]
+    }
+
+    #[test]
+    fn test_template_parse() {
+        let template = &templates()[0];
+
+        assert_eq!(template.name, "Retry");
+        assert_eq!(template.params, vec!["Start", "Done", "Weight"]);
+
+        let error = parse2::<Template>(quote! { Template Retry(Start, Start) { } }).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "duplicate parameter `Start` in template `Retry`"
+        );
+    }
+
+    #[test]
+    fn test_expand() {
+        let body = quote! {
+            InitialStates { Idle }
+            Retry(Connecting, Connected, 3)
+            Connect { Idle => Connecting }
+        };
+
+        let left = quote! {
+            InitialStates { Idle }
+            Fail * 3 { Connecting => Backoff }
+            Timer { Backoff => Connecting }
+            Succeed { Connecting => Connected }
+            Connect { Idle => Connecting }
+        };
+
+        let right = expand(body, &templates(), &mut Vec::new()).unwrap();
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let cases = vec![
+            (
+                quote! { Retyr(Connecting, Connected, 3) },
+                "unknown template `Retyr`",
+            ),
+            (
+                quote! { Retry(Connecting, Connected) },
+                "template `Retry` takes 3 parameters but 2 were given",
+            ),
+        ];
+
+        for (body, expected) in cases {
+            let error = expand(body, &templates(), &mut Vec::new()).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+
+        let templates = vec![parse2(quote! {
+            Template Loop(Start) {
+                Loop(Start)
+            }
+        })
+        .unwrap()];
+
+        let error = expand(quote! { Loop(Idle) }, &templates, &mut Vec::new()).unwrap_err();
+
+        assert_eq!(error.to_string(), "template `Loop` includes itself");
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{parse::Result, Error, LitInt};
+
+use crate::{
+    event::event_enum_name, machine::Machine, state::kind_enum_name, transition::Transition,
+};
+
+/// The duration given with `Timeout(30s) { ... }`, in milliseconds. It takes
+/// one of the units `ms`, `s` and `min`.
+pub(crate) fn duration_millis(timeout: &LitInt) -> Result<u64> {
+    let unit = match timeout.suffix() {
+        "ms" => 1,
+        "s" => 1_000,
+        "min" => 60_000,
+        _ => {
+            return Err(Error::new(
+                timeout.span(),
+                "timeouts take a duration such as `500ms`, `30s` or `5min`",
+            ))
+        }
+    };
+
+    let value: u64 = timeout.base10_parse()?;
+
+    if value == 0 {
+        return Err(Error::new(timeout.span(), "timeouts must be positive"));
+    }
+
+    value
+        .checked_mul(unit)
+        .ok_or_else(|| Error::new(timeout.span(), "timeout too long"))
+}
+
+/// Check that every state of `machine` is left by at most one timed
+/// transition, and that `machine` is not a typestate machine, which has no
+/// value to look the timeout up from.
+pub(crate) fn check(machine: &Machine) -> Result<()> {
+    let mut timed: HashMap<String, &Transition> = HashMap::new();
+
+    for t in &machine.transitions.0 {
+        let timeout = match &t.timeout {
+            Some(timeout) => timeout,
+            None => continue,
+        };
+
+        if machine.options.typestate {
+            return Err(Error::new(
+                timeout.span(),
+                "typestate machines do not support timeouts",
+            ));
+        }
+
+        if let Some(previous) = timed.insert(t.from.name.to_string(), t) {
+            return Err(Error::new(
+                t.from.name.span(),
+                format!(
+                    "state `{}` already times out with `{}`",
+                    t.from.name, previous.event.name
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `timeout`, generated for the machines with transitions declared with
+/// `Timeout(30s) { ... }`, which returns the event to apply once the machine
+/// has stayed in its current state for the given duration.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Timeouts<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Timeouts<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+
+        let arms = self
+            .machine
+            .transitions
+            .0
+            .iter()
+            .filter_map(|t| {
+                // Checked when parsing the transition.
+                let millis = duration_millis(t.timeout.as_ref()?).ok()?;
+                let millis = Literal::u64_unsuffixed(millis);
+                let from = &t.from.name;
+                let event = &t.event.name;
+
+                Some(quote! {
+                    #kind_enum::#from => Some((
+                        #event_enum::#event,
+                        ::core::time::Duration::from_millis(#millis),
+                    ))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Only add a catch-all when some state does not time out, otherwise
+        // it triggers an unreachable pattern warning.
+        let catch_all = if arms.len() < self.machine.states().0.len() {
+            quote! { _ => None }
+        } else {
+            quote! {}
+        };
+
+        let inline = self.machine.options.inline;
+
+        tokens.extend(quote! {
+            impl #name {
+                /// The event declared with `Timeout(30s) { ... }` to apply
+                /// once the machine has stayed in its current state for the
+                /// duration returned with it, or `None` if the current state
+                /// does not time out. Entering a state, even the one it was
+                /// in, restarts its timer.
+                #inline
+                pub fn timeout(&self) -> Option<(#event_enum, ::core::time::Duration)> {
+                    match self.kind() {
+                        #(#arms,)*
+                        #catch_all
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_check() {
+        let cases = vec![
+            (
+                quote! {
+                    Door {
+                        InitialStates { Closed }
+
+                        Open { Closed => Opened }
+                        Timeout(30h) { Opened => Closed }
+                    }
+                },
+                "timeouts take a duration such as `500ms`, `30s` or `5min`",
+            ),
+            (
+                quote! {
+                    Door {
+                        InitialStates { Closed }
+
+                        Open { Closed => Opened }
+                        Timeout(0s) { Opened => Closed }
+                    }
+                },
+                "timeouts must be positive",
+            ),
+            (
+                quote! {
+                    Door {
+                        InitialStates { Closed }
+
+                        Open { Closed => Opened }
+                        Timeout(30s) { Opened => Closed }
+                        Alarm(5min) { Opened => Ringing }
+                        Silence { Ringing => Closed }
+                    }
+                },
+                "state `Opened` already times out with `Timeout`",
+            ),
+            (
+                quote! {
+                    #[typestate]
+                    Door {
+                        InitialStates { Closed }
+
+                        Open { Closed => Opened }
+                        Timeout(30s) { Opened => Closed }
+                    }
+                },
+                "typestate machines do not support timeouts",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = parse2::<Machine>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_timeouts_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            Door {
+                InitialStates { Closed }
+
+                Open { Closed => Opened }
+                Close { Opened => Closed }
+                Timeout(30s) { Opened => Closed }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl Door {
+                /// The event declared with `Timeout(30s) { ... }` to apply
+                /// once the machine has stayed in its current state for the
+                /// duration returned with it, or `None` if the current state
+                /// does not time out. Entering a state, even the one it was
+                /// in, restarts its timer.
+                pub fn timeout(&self) -> Option<(DoorEvent, ::core::time::Duration)> {
+                    match self.kind() {
+                        DoorKind::Opened => Some((
+                            DoorEvent::Timeout,
+                            ::core::time::Duration::from_millis(30000),
+                        )),
+                        _ => None
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Timeouts { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,288 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{event::event_enum_name, ident::unraw, machine::Machine};
+
+/// The name of the sender driving a machine spawned in a task.
+pub(crate) fn sender_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Sender", unraw(machine)), machine.span())
+}
+
+/// `spawn_runner` and `{Machine}Sender`, generated for machines with
+/// `#[tokio]`, which move the machine into a task driven through channels.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct TokioRunner<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for TokioRunner<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let sender = sender_name(name);
+        let event_enum = event_enum_name(name);
+
+        // With timeouts, the timer of the current state is armed when it is
+        // entered and dropped when it is left, so that the events it ignores
+        // do not restart it.
+        let (timeout_doc, body) = if self.machine.has_timeouts() {
+            let doc = quote! {
+                ///
+                /// The task applies the event of `timeout` once the machine
+                /// has stayed in a state for its duration, which needs the
+                /// `time` feature of `tokio`.
+            };
+            let body = quote! {
+                let mut machine = self;
+                let mut deadline = machine.timeout().map(|(event, after)| {
+                    (event, ::tokio::time::Instant::now() + after)
+                });
+
+                loop {
+                    let event = match deadline {
+                        Some((timeout, at)) => {
+                            match ::tokio::time::timeout_at(at, queue.recv()).await {
+                                Ok(Some(event)) => event,
+                                Ok(None) => break,
+                                Err(_) => timeout,
+                            }
+                        }
+                        None => match queue.recv().await {
+                            Some(event) => event,
+                            None => break,
+                        },
+                    };
+
+                    if let Some(next) = machine.transition(event) {
+                        machine = next;
+                        deadline = machine.timeout().map(|(event, after)| {
+                            (event, ::tokio::time::Instant::now() + after)
+                        });
+                        let _ = notify.send_replace(machine.clone());
+                    }
+                }
+
+                machine
+            };
+
+            (Some(doc), body)
+        } else {
+            let body = quote! {
+                let mut machine = self;
+
+                while let Some(event) = queue.recv().await {
+                    if let Some(next) = machine.transition(event) {
+                        machine = next;
+                        let _ = notify.send_replace(machine.clone());
+                    }
+                }
+
+                machine
+            };
+
+            (None, body)
+        };
+
+        tokens.extend(quote! {
+            /// Sends events to a machine moved into a task by `spawn_runner`.
+            #[derive(Debug, Clone)]
+            pub struct #sender {
+                events: ::tokio::sync::mpsc::UnboundedSender<#event_enum>,
+                states: ::tokio::sync::watch::Receiver<#name>,
+            }
+
+            impl #sender {
+                /// Queue `event`, or give it back if the task has ended.
+                pub fn send(&self, event: #event_enum) -> Result<(), #event_enum> {
+                    self.events.send(event).map_err(|error| error.0)
+                }
+
+                /// A receiver holding the current state of the machine and
+                /// notified after every transition.
+                pub fn subscribe(&self) -> ::tokio::sync::watch::Receiver<#name> {
+                    self.states.clone()
+                }
+            }
+
+            impl #name {
+                /// Move the machine into a task which applies the events
+                /// queued with the returned sender one at a time, ignoring the
+                /// ones its current state does not accept. The task ends when
+                /// every clone of the sender is dropped, returning the machine.
+                #timeout_doc
+                pub fn spawn_runner(self) -> (#sender, ::tokio::task::JoinHandle<#name>) {
+                    let (events, mut queue) = ::tokio::sync::mpsc::unbounded_channel();
+                    let (notify, states) = ::tokio::sync::watch::channel(self.clone());
+
+                    let handle = ::tokio::spawn(async move { #body });
+
+                    (#sender { events, states }, handle)
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_tokio_runner_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            /// Sends events to a machine moved into a task by `spawn_runner`.
+            #[derive(Debug, Clone)]
+            pub struct TurnStileSender {
+                events: ::tokio::sync::mpsc::UnboundedSender<TurnStileEvent>,
+                states: ::tokio::sync::watch::Receiver<TurnStile>,
+            }
+
+            impl TurnStileSender {
+                /// Queue `event`, or give it back if the task has ended.
+                pub fn send(&self, event: TurnStileEvent) -> Result<(), TurnStileEvent> {
+                    self.events.send(event).map_err(|error| error.0)
+                }
+
+                /// A receiver holding the current state of the machine and
+                /// notified after every transition.
+                pub fn subscribe(&self) -> ::tokio::sync::watch::Receiver<TurnStile> {
+                    self.states.clone()
+                }
+            }
+
+            impl TurnStile {
+                /// Move the machine into a task which applies the events
+                /// queued with the returned sender one at a time, ignoring the
+                /// ones its current state does not accept. The task ends when
+                /// every clone of the sender is dropped, returning the machine.
+                pub fn spawn_runner(self) -> (TurnStileSender, ::tokio::task::JoinHandle<TurnStile>) {
+                    let (events, mut queue) = ::tokio::sync::mpsc::unbounded_channel();
+                    let (notify, states) = ::tokio::sync::watch::channel(self.clone());
+
+                    let handle = ::tokio::spawn(async move {
+                        let mut machine = self;
+
+                        while let Some(event) = queue.recv().await {
+                            if let Some(next) = machine.transition(event) {
+                                machine = next;
+                                let _ = notify.send_replace(machine.clone());
+                            }
+                        }
+
+                        machine
+                    });
+
+                    (TurnStileSender { events, states }, handle)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        TokioRunner { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_tokio_runner_to_tokens_timeouts() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+                Timeout(30s) { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            /// Sends events to a machine moved into a task by `spawn_runner`.
+            #[derive(Debug, Clone)]
+            pub struct TurnStileSender {
+                events: ::tokio::sync::mpsc::UnboundedSender<TurnStileEvent>,
+                states: ::tokio::sync::watch::Receiver<TurnStile>,
+            }
+
+            impl TurnStileSender {
+                /// Queue `event`, or give it back if the task has ended.
+                pub fn send(&self, event: TurnStileEvent) -> Result<(), TurnStileEvent> {
+                    self.events.send(event).map_err(|error| error.0)
+                }
+
+                /// A receiver holding the current state of the machine and
+                /// notified after every transition.
+                pub fn subscribe(&self) -> ::tokio::sync::watch::Receiver<TurnStile> {
+                    self.states.clone()
+                }
+            }
+
+            impl TurnStile {
+                /// Move the machine into a task which applies the events
+                /// queued with the returned sender one at a time, ignoring the
+                /// ones its current state does not accept. The task ends when
+                /// every clone of the sender is dropped, returning the machine.
+                ///
+                /// The task applies the event of `timeout` once the machine
+                /// has stayed in a state for its duration, which needs the
+                /// `time` feature of `tokio`.
+                pub fn spawn_runner(self) -> (TurnStileSender, ::tokio::task::JoinHandle<TurnStile>) {
+                    let (events, mut queue) = ::tokio::sync::mpsc::unbounded_channel();
+                    let (notify, states) = ::tokio::sync::watch::channel(self.clone());
+
+                    let handle = ::tokio::spawn(async move {
+                        let mut machine = self;
+                        let mut deadline = machine.timeout().map(|(event, after)| {
+                            (event, ::tokio::time::Instant::now() + after)
+                        });
+
+                        loop {
+                            let event = match deadline {
+                                Some((timeout, at)) => {
+                                    match ::tokio::time::timeout_at(at, queue.recv()).await {
+                                        Ok(Some(event)) => event,
+                                        Ok(None) => break,
+                                        Err(_) => timeout,
+                                    }
+                                }
+                                None => match queue.recv().await {
+                                    Some(event) => event,
+                                    None => break,
+                                },
+                            };
+
+                            if let Some(next) = machine.transition(event) {
+                                machine = next;
+                                deadline = machine.timeout().map(|(event, after)| {
+                                    (event, ::tokio::time::Instant::now() + after)
+                                });
+                                let _ = notify.send_replace(machine.clone());
+                            }
+                        }
+
+                        machine
+                    });
+
+                    (TurnStileSender { events, states }, handle)
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        TokioRunner { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,96 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::Ident;
+
+use crate::{ident::unraw, label::Labels, transition::Transition};
+
+/// The `tracing` event emitted by the method taking the transition `t` of the
+/// machine `machine`, generated for machines with `#[tracing]`. `from`
+/// evaluates to the name of the state left, which is only known at run time in
+/// the bodies shared by several transitions.
+pub(crate) fn transition_event(
+    machine: &Ident,
+    labels: &Labels,
+    from: &TokenStream,
+    t: &Transition,
+) -> TokenStream {
+    let event = labels.event(&t.event.name);
+    let to = labels.state(&t.to.name);
+
+    debug_event(machine, from, &quote! { #event }, &quote! { #to })
+}
+
+/// The `tracing` event emitted by `transition` with `#[dispatch(table)]`,
+/// which names the state left, the event and the state entered at run time.
+pub(crate) fn dispatched_transition_event(machine: &Ident) -> TokenStream {
+    debug_event(
+        machine,
+        &quote! { self.name() },
+        &quote! { event.name() },
+        &quote! { to.name() },
+    )
+}
+
+fn debug_event(
+    machine: &Ident,
+    from: &TokenStream,
+    event: &TokenStream,
+    to: &TokenStream,
+) -> TokenStream {
+    let machine = unraw(machine);
+
+    quote! {
+        ::tracing::debug!(
+            machine = #machine,
+            from = #from,
+            event = #event,
+            to = #to,
+            "transition"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event::Event, state::State};
+    use syn::parse_quote;
+
+    #[test]
+    fn test_transition_event() {
+        let transition = Transition {
+            event: Event {
+                name: parse_quote! { r#Loop },
+            },
+            from: State {
+                name: parse_quote! { Locked },
+            },
+            to: State {
+                name: parse_quote! { Unlocked },
+            },
+            label: None,
+            weight: None,
+            deprecated: None,
+            timeout: None,
+        };
+
+        let left = quote! {
+            ::tracing::debug!(
+                machine = "TurnStile",
+                from = "Locked",
+                event = "Loop",
+                to = "Unlocked",
+                "transition"
+            );
+        };
+
+        let right = transition_event(
+            &parse_quote! { TurnStile },
+            &Labels::default(),
+            &quote! { "Locked" },
+            &transition,
+        );
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
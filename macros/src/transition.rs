@@ -0,0 +1,987 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{
+    braced, parenthesized,
+    parse::{Parse, ParseStream, Result},
+    token::{Comma, Paren},
+    Attribute, Error, LitInt, LitStr, Path, Token,
+};
+
+use crate::{
+    event::{event_enum_name, event_fn_name, Event},
+    hooks::hooks_trait_name,
+    ident::unraw,
+    label::Labels,
+    metrics::transition_counter,
+    options::{InlineMode, ProvenanceMode},
+    provenance::Provenance,
+    state::{kind_enum_name, state_enum_name, State},
+    timeout::duration_millis,
+    trace::transition_event,
+};
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Transitions(pub Vec<Transition>);
+
+impl Parse for Transitions {
+    /// example transitions tokens:
+    ///
+    /// ```text
+    /// Push { ... }
+    /// Coin { ... }
+    /// ```
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let mut transitions = Transitions(Vec::new());
+
+        while !input.is_empty() {
+            transitions.parse_event(input)?;
+        }
+
+        Ok(transitions)
+    }
+}
+
+impl Transitions {
+    /// Parse the block of a single event, adding its transitions.
+    ///
+    /// ```text
+    /// Coin { Locked, Unlocked => Unlocked }
+    /// Coin "customer inserted a coin" { Locked => Unlocked }
+    /// Coin * 3 { Locked => Unlocked }
+    /// Timeout(30s) { Opened => Closed }
+    /// #[deprecated = "use `Tap` instead"] Coin { Locked => Unlocked }
+    /// ```
+    pub fn parse_event(&mut self, input: ParseStream<'_>) -> Result<()> {
+        let transitions = &mut self.0;
+
+        // `#[deprecated = "use `Tap` instead"] Coin { Locked => Unlocked }`
+        //  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+        let mut deprecated: Option<Attribute> = None;
+
+        for attr in input.call(Attribute::parse_outer)? {
+            if !attr.path.is_ident("deprecated") {
+                return Err(Error::new_spanned(
+                    attr,
+                    "transitions only support the `#[deprecated]` attribute",
+                ));
+            }
+
+            if deprecated.is_some() {
+                return Err(Error::new_spanned(attr, "duplicate `#[deprecated]`"));
+            }
+
+            deprecated = Some(attr);
+        }
+
+        // `Coin { Locked, Unlocked => Unlocked }`
+        //  ^^^^
+        let event = Event::parse(input)?;
+
+        // `Timeout(30s) { Opened => Closed }`
+        //         ^^^^^
+        let timeout = if input.peek(Paren) {
+            let block_timeout;
+            parenthesized!(block_timeout in input);
+            let timeout: LitInt = block_timeout.parse()?;
+            let _ = duration_millis(&timeout)?;
+
+            Some(timeout)
+        } else {
+            None
+        };
+
+        // `Coin "customer inserted a coin" { Locked => Unlocked }`
+        //       ^^^^^^^^^^^^^^^^^^^^^^^^^^
+        let label = if input.peek(LitStr) {
+            let label: LitStr = input.parse()?;
+
+            if label.value().is_empty() {
+                return Err(Error::new(
+                    label.span(),
+                    "transition labels cannot be empty",
+                ));
+            }
+
+            Some(label)
+        } else {
+            None
+        };
+
+        // `Coin * 3 { Locked => Unlocked }`
+        //       ^^^
+        let weight = if input.peek(Token![*]) {
+            let _: Token![*] = input.parse()?;
+            let weight: LitInt = input.parse()?;
+            let value: u32 = weight.base10_parse()?;
+
+            if value == 0 {
+                return Err(Error::new(
+                    weight.span(),
+                    "transition weights must be positive",
+                ));
+            }
+
+            Some(value)
+        } else {
+            None
+        };
+
+        // `Coin { Locked, Unlocked => Unlocked }`
+        //         ^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+        let block_transition;
+        braced!(block_transition in input);
+
+        // The transitions already declared for this event, by source state.
+        let mut declared: HashMap<String, usize> = transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.event.name == event.name)
+            .map(|(i, t)| (t.from.name.to_string(), i))
+            .collect();
+
+        while !block_transition.is_empty() {
+            let mut from_states: Vec<State> = Vec::new();
+
+            // `Coin { Locked, Unlocked => Unlocked }`
+            //                          ^^
+            while !block_transition.peek(Token![=>]) {
+                // `Coin { Locked, Unlocked => Unlocked }`
+                //               ^
+                if block_transition.peek(Token![,]) {
+                    let _: Comma = block_transition.parse()?;
+                    continue;
+                }
+
+                // `Coin { Locked, Unlocked => Unlocked }`
+                //         ^^^^^^  ^^^^^^^^
+                from_states.push(State::parse(&block_transition)?);
+            }
+
+            // `Coin { Locked, Unlocked => Unlocked }`
+            //                          ^^
+            let _: Token![=>] = block_transition.parse()?;
+
+            // `Coin { Locked, Unlocked => Unlocked }`
+            //                             ^^^^^^^^
+            let to = State::parse(&block_transition)?;
+
+            for from in from_states {
+                let transition = Transition {
+                    event: event.clone(),
+                    from,
+                    to: to.clone(),
+                    label: label.clone(),
+                    weight,
+                    deprecated: deprecated.clone(),
+                    timeout: timeout.clone(),
+                };
+
+                // Each event can only lead to one state from a given
+                // state, otherwise the transition methods would clash.
+                if let Some(&previous) = declared.get(&transition.from.name.to_string()) {
+                    return Err(transition.clash(&transitions[previous]));
+                }
+
+                let _ = declared.insert(transition.from.name.to_string(), transitions.len());
+
+                transitions.push(transition)
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The transitions of a machine grouped by the names of their source and
+/// target states, so that looking up the transitions of a state does not
+/// scan the whole diagram.
+#[derive(Debug)]
+pub(crate) struct TransitionIndex<'a> {
+    from: HashMap<String, Vec<&'a Transition>>,
+    to: HashMap<String, Vec<&'a Transition>>,
+}
+
+impl<'a> TransitionIndex<'a> {
+    /// The transitions leaving `state`, in declaration order.
+    pub fn from(&self, state: &Ident) -> &[&'a Transition] {
+        self.from.get(&state.to_string()).map_or(&[], Vec::as_slice)
+    }
+
+    /// The transitions entering `state`, in declaration order.
+    pub fn to(&self, state: &Ident) -> &[&'a Transition] {
+        self.to.get(&state.to_string()).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Transition {
+    pub event: Event,
+    pub from: State,
+    pub to: State,
+    /// The human-readable description given with `Coin "..." { ... }`, if
+    /// any.
+    pub label: Option<LitStr>,
+    /// How likely `simulate_step` is to pick this transition relative to
+    /// the others from the same state, given with `Coin * 3 { ... }`. The
+    /// transitions declared without a weight weigh 1.
+    pub weight: Option<u32>,
+    /// The `#[deprecated]` attribute given to the block declaring the
+    /// transition, put on its method.
+    pub deprecated: Option<Attribute>,
+    /// How long the machine stays in the source state before the event is
+    /// applied for it, given with `Timeout(30s) { ... }`. See
+    /// `timeout::duration_millis`.
+    pub timeout: Option<LitInt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TransitionFns {
+    pub enum_name: Ident,
+    pub provenance: ProvenanceMode,
+    /// The type declared with `Hooks = ...`, if any.
+    pub hooks: Option<Path>,
+    /// Whether to emit a `tracing` event on every transition.
+    pub tracing: bool,
+    /// Whether to increment a `metrics` counter on every transition.
+    pub metrics: bool,
+    pub transitions: Vec<Transition>,
+    /// The transitions among `transitions` whose body is shared with other
+    /// source states, see `TransitionFns::shared_fn`.
+    pub shared: Vec<Transition>,
+    pub inline: InlineMode,
+    /// The names of the states and events, for tracing and metrics.
+    pub labels: Labels,
+}
+
+impl Transition {
+    /// The error for declaring both `self` and `previous`, which take the
+    /// same event from the same state.
+    pub fn clash(&self, previous: &Transition) -> Error {
+        if previous.to.name == self.to.name {
+            return Error::new(
+                self.from.name.span(),
+                format!(
+                    "duplicate transition: `{}` from `{}` is already defined",
+                    self.event.name, self.from.name
+                ),
+            );
+        }
+
+        let mut error = Error::new(
+            self.from.name.span(),
+            format!(
+                "nondeterministic transition: `{}` from `{}` leads to both `{}` and `{}`",
+                self.event.name, self.from.name, previous.to.name, self.to.name
+            ),
+        );
+        error.combine(Error::new(
+            previous.from.name.span(),
+            format!(
+                "first transition: `{} {{ {} => {} }}`",
+                previous.event.name, previous.from.name, previous.to.name
+            ),
+        ));
+
+        error
+    }
+
+    /// The names of the event and the target state, which identify the
+    /// transitions sharing a body.
+    pub fn shared_key(&self) -> (String, String) {
+        (self.event.name.to_string(), self.to.name.to_string())
+    }
+
+    /// The provenance recorded in the target state when this transition is
+    /// taken.
+    pub fn provenance(&self, mode: ProvenanceMode) -> Provenance {
+        let source = match mode {
+            ProvenanceMode::Event | ProvenanceMode::None => None,
+            ProvenanceMode::Full => Some(self.from.name.clone()),
+        };
+
+        Provenance {
+            event: Some(self.event.name.clone()),
+            source,
+        }
+    }
+}
+
+impl Transitions {
+    /// Group the transitions by source and target state.
+    pub fn index(&self) -> TransitionIndex<'_> {
+        let mut index = TransitionIndex {
+            from: HashMap::new(),
+            to: HashMap::new(),
+        };
+
+        for t in &self.0 {
+            index
+                .from
+                .entry(t.from.name.to_string())
+                .or_default()
+                .push(t);
+            index.to.entry(t.to.name.to_string()).or_default().push(t);
+        }
+
+        index
+    }
+
+    pub fn to_fns(
+        &self,
+        enum_name: &Ident,
+        provenance: ProvenanceMode,
+        hooks: Option<&Path>,
+        tracing: bool,
+        metrics: bool,
+        shared: &[Transition],
+    ) -> TransitionFns {
+        TransitionFns {
+            enum_name: enum_name.clone(),
+            provenance,
+            hooks: hooks.cloned(),
+            tracing,
+            metrics,
+            transitions: self.0.clone(),
+            shared: shared.to_vec(),
+            inline: InlineMode::default(),
+            labels: Labels::default(),
+        }
+    }
+
+    /// The names of the events and target states of the transitions taken
+    /// from several source states, whose instrumented bodies would otherwise
+    /// be generated identically in each source state. With
+    /// `#[provenance(full)]` the bodies record their source state, so none
+    /// are shared.
+    pub fn shared(&self, provenance: ProvenanceMode) -> HashSet<(String, String)> {
+        if provenance == ProvenanceMode::Full {
+            return HashSet::new();
+        }
+
+        let mut seen = HashSet::new();
+        let mut shared = HashSet::new();
+
+        for t in &self.0 {
+            let key = t.shared_key();
+
+            if !seen.insert(key.clone()) {
+                let _ = shared.insert(key);
+            }
+        }
+
+        shared
+    }
+}
+
+/// `#[must_use]` for the methods returning the state a transition leads to,
+/// which is easy to drop by mistake since the current state is left as is.
+pub(crate) fn must_use_transition() -> TokenStream {
+    quote! {
+        #[must_use = "transitions return the next state instead of changing the current one"]
+    }
+}
+
+/// The name of the function shared by the transitions entering a state on
+/// `event`, see `TransitionFns::shared_fn`.
+pub(crate) fn shared_fn_name(event: &Ident) -> Ident {
+    Ident::new(
+        &format!("enter_on_{}", unraw(&event_fn_name(event))),
+        event.span(),
+    )
+}
+
+impl TransitionFns {
+    /// The private function entering the target state of `t` on its event,
+    /// shared by the methods taking that event from several states.
+    pub fn shared_fn(&self, t: &Transition) -> TokenStream {
+        let shared_fn = shared_fn_name(&t.event.name);
+        let enum_name = &self.enum_name;
+        let kind_enum = kind_enum_name(enum_name);
+        let body = self.instrumented_body(t, &quote! { from }, &quote! { from.name() });
+        let inline = self.inline;
+
+        quote! {
+            #inline
+            fn #shared_fn(from: #kind_enum) -> #enum_name {
+                #body
+            }
+        }
+    }
+
+    /// Whether the transition methods do more than build the next state.
+    pub fn is_instrumented(&self) -> bool {
+        self.hooks.is_some() || self.tracing || self.metrics
+    }
+
+    /// Whether the body of `t` is generated once in its target state by
+    /// `shared_fn` instead of in every method taking it. Uninstrumented
+    /// bodies are a single expression and are never shared.
+    fn is_shared(&self, t: &Transition) -> bool {
+        self.is_instrumented() && self.shared.contains(t)
+    }
+
+    /// The body of the method taking `t` with tracing, metrics or hooks.
+    /// `from_kind` evaluates to the kind of the state left and `from_name` to
+    /// its name.
+    fn instrumented_body(
+        &self,
+        t: &Transition,
+        from_kind: &TokenStream,
+        from_name: &TokenStream,
+    ) -> TokenStream {
+        let enum_name = &self.enum_name;
+        let to_enum = &t.to.name;
+        let to_struct = state_enum_name(&t.to.name);
+        let event_enum = t.provenance(self.provenance).variant();
+
+        let trace = if self.tracing {
+            Some(transition_event(enum_name, &self.labels, from_name, t))
+        } else {
+            None
+        };
+
+        let count = if self.metrics {
+            Some(transition_counter(enum_name, &self.labels, from_name, t))
+        } else {
+            None
+        };
+
+        let hook = self.hooks.as_ref().map(|hooks| {
+            let hooks_trait = hooks_trait_name(enum_name);
+            let event_kind_enum = event_enum_name(enum_name);
+            let event = &t.event.name;
+
+            quote! {
+                <#hooks as #hooks_trait>::on_transition(
+                    #from_kind,
+                    #event_kind_enum::#event,
+                    &to,
+                );
+            }
+        });
+
+        quote! {
+            #trace
+            #count
+            let to = #enum_name::#to_enum(#to_struct::#event_enum);
+            #hook
+            to
+        }
+    }
+}
+
+impl ToTokens for TransitionFns {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let enum_name = &self.enum_name;
+        let kind_enum = kind_enum_name(enum_name);
+
+        for t in &self.transitions {
+            let event_fn = event_fn_name(&t.event.name);
+            let from = &t.from.name;
+
+            let body = if self.is_shared(t) {
+                let to_struct = state_enum_name(&t.to.name);
+                let shared_fn = shared_fn_name(&t.event.name);
+
+                quote! { #to_struct::#shared_fn(#kind_enum::#from) }
+            } else if self.is_instrumented() {
+                let from_name = self.labels.state(from);
+
+                self.instrumented_body(t, &quote! { #kind_enum::#from }, &quote! { #from_name })
+            } else {
+                let to_enum = &t.to.name;
+                let to_struct = state_enum_name(&t.to.name);
+                let event_enum = t.provenance(self.provenance).variant();
+
+                quote! { #enum_name::#to_enum(#to_struct::#event_enum) }
+            };
+
+            let must_use = must_use_transition();
+            let inline = self.inline;
+            let doc = t.label.as_ref().map(|label| quote! { #[doc = #label] });
+            let deprecated = &t.deprecated;
+
+            tokens.extend(quote! {
+                #doc
+                #deprecated
+                #must_use
+                #inline
+                pub fn #event_fn(&self) -> #enum_name {
+                    #body
+                }
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macro2::TokenStream;
+    use syn::{self, parse_quote};
+
+    #[test]
+    fn test_transitions_parse() {
+        let left: Transitions = syn::parse2(quote! {
+            Push { Locked, Unlocked => Locked }
+            Coin { Locked, Unlocked => Unlocked }
+        })
+        .unwrap();
+
+        let right = Transitions(vec![
+            Transition {
+                event: Event {
+                    name: parse_quote! { Push },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Locked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+            Transition {
+                event: Event {
+                    name: parse_quote! { Push },
+                },
+                from: State {
+                    name: parse_quote! { Unlocked },
+                },
+                to: State {
+                    name: parse_quote! { Locked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+            Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+            Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Unlocked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+        ]);
+
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_transitions_parse_duplicate() {
+        let result: Result<Transitions> = syn::parse2(quote! {
+            Push { Locked => Unlocked }
+            Coin { Locked => Unlocked }
+            Push { Unlocked, Locked => Unlocked }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "duplicate transition: `Push` from `Locked` is already defined"
+        );
+    }
+
+    #[test]
+    fn test_transitions_parse_timeout() {
+        let transitions: Transitions = syn::parse2(quote! {
+            Timeout(30s) "door left open" { Opened, Ajar => Closed }
+            Close { Opened => Closed }
+        })
+        .unwrap();
+
+        let timeouts = transitions
+            .0
+            .iter()
+            .map(|t| {
+                t.timeout
+                    .as_ref()
+                    .map(|timeout| duration_millis(timeout).unwrap())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(timeouts, vec![Some(30_000), Some(30_000), None]);
+        assert!(transitions.0[0].label.is_some());
+    }
+
+    #[test]
+    fn test_transitions_parse_label() {
+        let transitions: Transitions = syn::parse2(quote! {
+            Coin "customer inserted a coin" { Locked, Unlocked => Unlocked }
+            Push { Unlocked => Locked }
+        })
+        .unwrap();
+
+        let labels = transitions
+            .0
+            .iter()
+            .map(|t| t.label.as_ref().map(LitStr::value))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            labels,
+            vec![
+                Some("customer inserted a coin".to_string()),
+                Some("customer inserted a coin".to_string()),
+                None,
+            ]
+        );
+
+        let result: Result<Transitions> = syn::parse2(quote! {
+            Coin "" { Locked => Unlocked }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "transition labels cannot be empty"
+        );
+    }
+
+    #[test]
+    fn test_transitions_parse_weight() {
+        let transitions: Transitions = syn::parse2(quote! {
+            Coin "customer inserted a coin" * 3 { Locked, Unlocked => Unlocked }
+            Push { Unlocked => Locked }
+        })
+        .unwrap();
+
+        let weights = transitions.0.iter().map(|t| t.weight).collect::<Vec<_>>();
+
+        assert_eq!(weights, vec![Some(3), Some(3), None]);
+
+        let result: Result<Transitions> = syn::parse2(quote! {
+            Coin * 0 { Locked => Unlocked }
+        });
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "transition weights must be positive"
+        );
+    }
+
+    #[test]
+    fn test_transitions_parse_deprecated() {
+        let transitions: Transitions = syn::parse2(quote! {
+            #[deprecated = "use `Tap` instead"]
+            Coin { Locked, Unlocked => Unlocked }
+            Push { Unlocked => Locked }
+        })
+        .unwrap();
+
+        let deprecated = transitions
+            .0
+            .iter()
+            .map(|t| t.deprecated.is_some())
+            .collect::<Vec<_>>();
+
+        assert_eq!(deprecated, vec![true, true, false]);
+
+        let cases = vec![
+            (
+                quote! { #[must_use] Coin { Locked => Unlocked } },
+                "transitions only support the `#[deprecated]` attribute",
+            ),
+            (
+                quote! { #[deprecated] #[deprecated] Coin { Locked => Unlocked } },
+                "duplicate `#[deprecated]`",
+            ),
+        ];
+
+        for (tokens, expected) in cases {
+            let error = syn::parse2::<Transitions>(tokens).unwrap_err();
+
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_transitions_parse_nondeterministic() {
+        let error = syn::parse2::<Transitions>(quote! {
+            Push { Locked => Locked }
+            Push { Unlocked, Locked => Unlocked }
+        })
+        .unwrap_err();
+
+        let messages = error.into_iter().map(|e| e.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                "nondeterministic transition: `Push` from `Locked` leads to both `Locked` and `Unlocked`",
+                "first transition: `Push { Locked => Locked }`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transitions_to_tokens() {
+        let transitions = Transitions(vec![
+            Transition {
+                event: Event {
+                    name: parse_quote! { Push },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Locked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+            Transition {
+                event: Event {
+                    name: parse_quote! { Push },
+                },
+                from: State {
+                    name: parse_quote! { Unlocked },
+                },
+                to: State {
+                    name: parse_quote! { Locked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+            Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+            Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Unlocked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            },
+        ])
+        .to_fns(
+            &parse_quote! { TurnStile },
+            ProvenanceMode::Event,
+            None,
+            false,
+            false,
+            &[],
+        );
+
+        let left = quote! {
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn push(&self) -> TurnStile {
+                TurnStile::Locked(LockedState::FromPush)
+            }
+
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn push(&self) -> TurnStile {
+                TurnStile::Locked(LockedState::FromPush)
+            }
+
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn coin(&self) -> TurnStile {
+                TurnStile::Unlocked(UnlockedState::FromCoin)
+            }
+
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn coin(&self) -> TurnStile {
+                TurnStile::Unlocked(UnlockedState::FromCoin)
+            }
+        };
+
+        let mut right = TokenStream::new();
+        transitions.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_transitions_to_tokens_inline() {
+        let transitions = TransitionFns {
+            inline: InlineMode::Inline,
+            ..Transitions(vec![Transition {
+                event: Event {
+                    name: parse_quote! { Coin },
+                },
+                from: State {
+                    name: parse_quote! { Locked },
+                },
+                to: State {
+                    name: parse_quote! { Unlocked },
+                },
+                label: None,
+                weight: None,
+                deprecated: None,
+                timeout: None,
+            }])
+            .to_fns(
+                &parse_quote! { TurnStile },
+                ProvenanceMode::Event,
+                None,
+                false,
+                false,
+                &[],
+            )
+        };
+
+        let left = quote! {
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            #[inline]
+            pub fn coin(&self) -> TurnStile {
+                TurnStile::Unlocked(UnlockedState::FromCoin)
+            }
+        };
+
+        let mut right = TokenStream::new();
+        transitions.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_transitions_to_tokens_deprecated() {
+        let transitions = Transitions(vec![Transition {
+            event: Event {
+                name: parse_quote! { Coin },
+            },
+            from: State {
+                name: parse_quote! { Locked },
+            },
+            to: State {
+                name: parse_quote! { Unlocked },
+            },
+            label: None,
+            weight: None,
+            deprecated: Some(parse_quote! { #[deprecated = "use `Tap` instead"] }),
+            timeout: None,
+        }])
+        .to_fns(
+            &parse_quote! { TurnStile },
+            ProvenanceMode::Event,
+            None,
+            false,
+            false,
+            &[],
+        );
+
+        let left = quote! {
+            #[deprecated = "use `Tap` instead"]
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn coin(&self) -> TurnStile {
+                TurnStile::Unlocked(UnlockedState::FromCoin)
+            }
+        };
+
+        let mut right = TokenStream::new();
+        transitions.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_transitions_to_tokens_instrumented() {
+        let transitions = Transitions(vec![Transition {
+            event: Event {
+                name: parse_quote! { Coin },
+            },
+            from: State {
+                name: parse_quote! { Locked },
+            },
+            to: State {
+                name: parse_quote! { Unlocked },
+            },
+            label: None,
+            weight: None,
+            deprecated: None,
+            timeout: None,
+        }])
+        .to_fns(
+            &parse_quote! { TurnStile },
+            ProvenanceMode::Event,
+            Some(&parse_quote! { Counter }),
+            true,
+            true,
+            &[],
+        );
+
+        let left = quote! {
+            #[must_use = "transitions return the next state instead of changing the current one"]
+            pub fn coin(&self) -> TurnStile {
+                ::tracing::debug!(
+                    machine = "TurnStile",
+                    from = "Locked",
+                    event = "Coin",
+                    to = "Unlocked",
+                    "transition"
+                );
+                ::metrics::counter!(
+                    "sad_machine_transitions_total",
+                    "machine" => "TurnStile",
+                    "from" => "Locked",
+                    "event" => "Coin",
+                    "to" => "Unlocked"
+                )
+                .increment(1);
+                let to = TurnStile::Unlocked(UnlockedState::FromCoin);
+                <Counter as TurnStileHooks>::on_transition(
+                    TurnStileKind::Locked,
+                    TurnStileEvent::Coin,
+                    &to,
+                );
+                to
+            }
+        };
+
+        let mut right = TokenStream::new();
+        transitions.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,251 @@
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{
+    diagram, event::event_fn_name, ident::snake_case, initial_state::must_use_constructor,
+    machine::Machine, metrics::transition_counter, trace::transition_event,
+    transition::must_use_transition,
+};
+
+/// The typestate representation of a machine: a zero-sized type per state,
+/// with transition methods consuming the current state and returning the
+/// next one, so that the diagram is enforced entirely at compile time.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Typestate<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Typestate<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let docs = &self.machine.options.docs;
+
+        let state_count = Literal::usize_unsuffixed(self.machine.states().0.len());
+        let event_count = Literal::usize_unsuffixed(self.machine.events().len());
+
+        let initial_fns = self.machine.initial_states.0.iter().map(|s| {
+            let fn_name = snake_case(&s.name);
+            let state = &s.name;
+
+            let must_use = must_use_constructor();
+
+            quote! {
+                #must_use
+                pub fn #fn_name() -> #state {
+                    #state(())
+                }
+            }
+        });
+
+        let dot = diagram::dot(self.machine);
+        let mermaid = diagram::mermaid(self.machine);
+        let description = diagram::description(self.machine);
+
+        tokens.extend(quote! {
+            #(#docs)*
+            #[derive(Debug)]
+            pub enum #name {}
+
+            impl #name {
+                pub const STATE_COUNT: usize = #state_count;
+                pub const EVENT_COUNT: usize = #event_count;
+
+                /// A Graphviz description of the diagram.
+                pub const DOT: &'static str = #dot;
+
+                /// A Mermaid `stateDiagram-v2` description of the diagram.
+                pub const MERMAID: &'static str = #mermaid;
+
+                /// A JSON description of the states, events and transitions
+                /// of the machine.
+                pub const DESCRIPTION: &'static str = #description;
+
+                #(#initial_fns)*
+            }
+        });
+
+        let index = self.machine.transitions.index();
+
+        for s in &self.machine.states() {
+            let state = &s.name;
+
+            let transition_fns = index.from(&s.name).iter().map(|t| {
+                let event_fn = event_fn_name(&t.event.name);
+                let to = &t.to.name;
+                let from = self.machine.labels.state(&t.from.name);
+                let from = quote! { #from };
+
+                let trace = if self.machine.options.tracing {
+                    Some(transition_event(
+                        &self.machine.name,
+                        &self.machine.labels,
+                        &from,
+                        t,
+                    ))
+                } else {
+                    None
+                };
+
+                let count = if self.machine.options.metrics {
+                    Some(transition_counter(
+                        &self.machine.name,
+                        &self.machine.labels,
+                        &from,
+                        t,
+                    ))
+                } else {
+                    None
+                };
+
+                let must_use = must_use_transition();
+                let inline = self.machine.options.inline;
+                let deprecated = &t.deprecated;
+
+                quote! {
+                    #deprecated
+                    #must_use
+                    #inline
+                    pub fn #event_fn(self) -> #to {
+                        #trace
+                        #count
+                        #to(())
+                    }
+                }
+            });
+
+            tokens.extend(quote! {
+                #[derive(Debug, PartialEq, Eq, Hash)]
+                pub struct #state(());
+
+                impl #state {
+                    #(#transition_fns)*
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::parse_quote;
+
+    #[test]
+    fn test_typestate_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options {
+                typestate: true,
+                ..Options::default()
+            },
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Coin },
+                    },
+                    from: State {
+                        name: parse_quote! { Locked },
+                    },
+                    to: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Push },
+                    },
+                    from: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    to: State {
+                        name: parse_quote! { Locked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+            ]),
+        };
+
+        let left = quote! {
+            #[derive(Debug)]
+            pub enum TurnStile {}
+
+            impl TurnStile {
+                pub const STATE_COUNT: usize = 2;
+                pub const EVENT_COUNT: usize = 2;
+
+                /// A Graphviz description of the diagram.
+                pub const DOT: &'static str = "digraph TurnStile {\n    __start [shape=point];\n    Locked [shape=circle];\n    Unlocked [shape=circle];\n    __start -> Locked;\n    Locked -> Unlocked [label=\"Coin\"];\n    Unlocked -> Locked [label=\"Push\"];\n}";
+
+                /// A Mermaid `stateDiagram-v2` description of the diagram.
+                pub const MERMAID: &'static str = "stateDiagram-v2\n    [*] --> Locked\n    Locked --> Unlocked : Coin\n    Unlocked --> Locked : Push";
+
+                /// A JSON description of the states, events and transitions
+                /// of the machine.
+                pub const DESCRIPTION: &'static str = "{\"name\":\"TurnStile\",\"states\":[\"Locked\",\"Unlocked\"],\"events\":[\"Coin\",\"Push\"],\"initial_states\":[\"Locked\"],\"final_states\":[],\"transitions\":[{\"event\":\"Coin\",\"from\":\"Locked\",\"to\":\"Unlocked\"},{\"event\":\"Push\",\"from\":\"Unlocked\",\"to\":\"Locked\"}]}";
+
+                #[must_use = "this returns a new machine, which does nothing unless it is used"]
+                pub fn locked() -> Locked {
+                    Locked(())
+                }
+            }
+
+            #[derive(Debug, PartialEq, Eq, Hash)]
+            pub struct Locked(());
+
+            impl Locked {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn coin(self) -> Unlocked {
+                    Unlocked(())
+                }
+            }
+
+            #[derive(Debug, PartialEq, Eq, Hash)]
+            pub struct Unlocked(());
+
+            impl Unlocked {
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn push(self) -> Locked {
+                    Locked(())
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Typestate { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
@@ -0,0 +1,275 @@
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+
+use crate::{
+    dynamic::step_struct_name,
+    event::{event_enum_name, event_fn_name},
+    machine::Machine,
+    state::kind_enum_name,
+    transition::must_use_transition,
+};
+
+/// `undo(step)`, which reverts a transition by taking the only edge leading
+/// back from the state it reached, after its completion transitions, to its
+/// source. Transitions without such an edge, or with several of them, cannot
+/// be undone.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct Undo<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for Undo<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let kind_enum = kind_enum_name(name);
+        let event_enum = event_enum_name(name);
+        let step_struct = step_struct_name(name);
+
+        let index = self.machine.transitions.index();
+
+        // `step` records the state reached once the completion transitions
+        // are taken, so the edge leading back leaves that state and enters
+        // the source once its own completion transitions are taken.
+        let arms = self
+            .machine
+            .completed_transitions()
+            .0
+            .into_iter()
+            .filter_map(|t| {
+                let mut reverse = index
+                    .from(&t.to.name)
+                    .iter()
+                    .filter(|r| self.machine.completed(&r.to).name == t.from.name);
+
+                let r = match (reverse.next(), reverse.next()) {
+                    (Some(r), None) => r,
+                    _ => return None,
+                };
+
+                let from = &t.from.name;
+                let event = &t.event.name;
+                let to = &t.to.name;
+                // Without transition methods, or when the edge leading back
+                // enters a state with a completion transition, which the methods
+                // do not take, the edge is taken through `transition`.
+                let (state, reverse) =
+                    if !self.machine.has_transition_methods() || r.to.name != t.from.name {
+                        let reverse_event = &r.event.name;
+
+                        (
+                            self.machine.state_pattern(to, quote! { _ }),
+                            quote! { self.transition(#event_enum::#reverse_event) },
+                        )
+                    } else {
+                        let reverse_fn = event_fn_name(&r.event.name);
+
+                        (
+                            quote! { #name::#to(state) },
+                            quote! { Some(state.#reverse_fn()) },
+                        )
+                    };
+
+                Some(quote! {
+                    (
+                        #state,
+                        #step_struct {
+                            from: #kind_enum::#from,
+                            event: #event_enum::#event,
+                            to: #kind_enum::#to,
+                        },
+                    ) => #reverse
+                })
+            });
+
+        let must_use = must_use_transition();
+
+        tokens.extend(quote! {
+            impl #name {
+                /// Revert the transition recorded in `step` by taking the edge
+                /// leading back to its source. Returns `None` if the machine
+                /// is not in the state the step led to, or if there is not
+                /// exactly one edge leading back.
+                #must_use
+                pub fn undo(&self, step: #step_struct) -> Option<#name> {
+                    match (self, step) {
+                        #(#arms,)*
+                        _ => None,
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::Event,
+        final_state::FinalStates,
+        initial_state::{InitialState, InitialStates},
+        label::Labels,
+        options::Options,
+        state::State,
+        transition::{Transition, Transitions},
+    };
+    use syn::{parse2, parse_quote};
+
+    #[test]
+    fn test_undo_to_tokens() {
+        let machine = Machine {
+            name: parse_quote! { TurnStile },
+            options: Options::default(),
+            initial_states: InitialStates(vec![InitialState {
+                name: parse_quote! { Locked },
+            }]),
+            final_states: FinalStates::default(),
+            declared_states: None,
+            declared_events: None,
+            event_groups: Vec::new(),
+            event_parents: Vec::new(),
+            state_sets: Vec::new(),
+            regions: Vec::new(),
+            properties: None,
+            hooks: None,
+            on_invalid: None,
+            completions: Vec::new(),
+            labels: Labels::default(),
+            transitions: Transitions(vec![
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Coin },
+                    },
+                    from: State {
+                        name: parse_quote! { Locked },
+                    },
+                    to: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Push },
+                    },
+                    from: State {
+                        name: parse_quote! { Unlocked },
+                    },
+                    to: State {
+                        name: parse_quote! { Locked },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+                Transition {
+                    event: Event {
+                        name: parse_quote! { Break },
+                    },
+                    from: State {
+                        name: parse_quote! { Locked },
+                    },
+                    to: State {
+                        name: parse_quote! { Broken },
+                    },
+                    label: None,
+                    weight: None,
+                    deprecated: None,
+                    timeout: None,
+                },
+            ]),
+        };
+
+        let left = quote! {
+            impl TurnStile {
+                /// Revert the transition recorded in `step` by taking the edge
+                /// leading back to its source. Returns `None` if the machine
+                /// is not in the state the step led to, or if there is not
+                /// exactly one edge leading back.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn undo(&self, step: TurnStileStep) -> Option<TurnStile> {
+                    match (self, step) {
+                        (
+                            TurnStile::Unlocked(state),
+                            TurnStileStep {
+                                from: TurnStileKind::Locked,
+                                event: TurnStileEvent::Coin,
+                                to: TurnStileKind::Unlocked,
+                            },
+                        ) => Some(state.push()),
+                        (
+                            TurnStile::Locked(state),
+                            TurnStileStep {
+                                from: TurnStileKind::Unlocked,
+                                event: TurnStileEvent::Push,
+                                to: TurnStileKind::Locked,
+                            },
+                        ) => Some(state.coin()),
+                        _ => None,
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Undo { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+
+    #[test]
+    fn test_undo_to_tokens_completions() {
+        let machine: Machine = parse2(quote! {
+            Order {
+                InitialStates { Draft }
+                on entry Validating => Validated
+
+                Submit { Draft => Validating }
+                Edit { Validated => Draft }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            impl Order {
+                /// Revert the transition recorded in `step` by taking the edge
+                /// leading back to its source. Returns `None` if the machine
+                /// is not in the state the step led to, or if there is not
+                /// exactly one edge leading back.
+                #[must_use = "transitions return the next state instead of changing the current one"]
+                pub fn undo(&self, step: OrderStep) -> Option<Order> {
+                    match (self, step) {
+                        (
+                            Order::Validated(state),
+                            OrderStep {
+                                from: OrderKind::Draft,
+                                event: OrderEvent::Submit,
+                                to: OrderKind::Validated,
+                            },
+                        ) => Some(state.edit()),
+                        (
+                            Order::Draft(_),
+                            OrderStep {
+                                from: OrderKind::Validated,
+                                event: OrderEvent::Edit,
+                                to: OrderKind::Draft,
+                            },
+                        ) => self.transition(OrderEvent::Submit),
+                        _ => None,
+                    }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        Undo { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
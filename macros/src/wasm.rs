@@ -0,0 +1,286 @@
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+use crate::{
+    event::event_enum_name,
+    ident::{snake_case, unraw},
+    machine::Machine,
+};
+
+/// The name of the type exporting a machine to JavaScript.
+pub(crate) fn wasm_struct_name(machine: &Ident) -> Ident {
+    Ident::new(&format!("{}Wasm", unraw(machine)), machine.span())
+}
+
+/// `{Machine}Wasm`, generated for machines with `#[wasm]`, which exports the
+/// machine to JavaScript with `wasm-bindgen`, taking events by name or by
+/// index.
+#[derive(Debug)]
+#[allow(single_use_lifetimes)]
+pub(crate) struct WasmBindings<'a> {
+    pub machine: &'a Machine,
+}
+
+#[allow(single_use_lifetimes)]
+impl<'a> ToTokens for WasmBindings<'a> {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let name = &self.machine.name;
+        let wasm_struct = wasm_struct_name(name);
+        let event_enum = event_enum_name(name);
+
+        let initial_arms = self.machine.initial_states.0.iter().map(|s| {
+            let string = self.machine.labels.state(&s.name);
+            let fn_name = snake_case(&s.name);
+
+            quote! { #string => #name::#fn_name() }
+        });
+
+        let events = self.machine.events();
+
+        let name_arms = events.iter().map(|e| {
+            let event = &e.name;
+            let string = self.machine.labels.event(event);
+
+            quote! { #string => #event_enum::#event }
+        });
+
+        let code_arms = events.iter().enumerate().map(|(i, e)| {
+            let event = &e.name;
+            let i = Literal::u32_unsuffixed(i as u32);
+
+            quote! { #i => #event_enum::#event }
+        });
+
+        tokens.extend(quote! {
+            /// The machine, exported to JavaScript by `wasm-bindgen`.
+            #[::wasm_bindgen::prelude::wasm_bindgen]
+            #[derive(Debug, Clone)]
+            pub struct #wasm_struct {
+                machine: #name,
+            }
+
+            #[::wasm_bindgen::prelude::wasm_bindgen]
+            impl #wasm_struct {
+                /// Start the machine in the initial state named `state`.
+                #[wasm_bindgen(constructor)]
+                pub fn new(state: &str) -> Result<#wasm_struct, ::wasm_bindgen::JsError> {
+                    let machine = match state {
+                        #(#initial_arms,)*
+                        _ => {
+                            return Err(::wasm_bindgen::JsError::new(&::std::format!(
+                                "`{}` is not an initial state",
+                                state
+                            )))
+                        }
+                    };
+
+                    Ok(#wasm_struct { machine })
+                }
+
+                /// The name of the current state.
+                #[wasm_bindgen(getter)]
+                pub fn state(&self) -> ::std::string::String {
+                    ::std::string::String::from(self.machine.name())
+                }
+
+                /// The index of the current state in the declaration order.
+                #[wasm_bindgen(getter, js_name = stateCode)]
+                pub fn state_code(&self) -> u32 {
+                    self.machine.kind() as u32
+                }
+
+                /// Whether the current state is final.
+                #[wasm_bindgen(getter, js_name = isFinal)]
+                pub fn is_final(&self) -> bool {
+                    self.machine.is_final()
+                }
+
+                /// Apply the event named `event`. Returns whether the current
+                /// state accepts it, and throws if there is no such event.
+                pub fn transition(&mut self, event: &str) -> Result<bool, ::wasm_bindgen::JsError> {
+                    let event = match event {
+                        #(#name_arms,)*
+                        _ => {
+                            return Err(::wasm_bindgen::JsError::new(&::std::format!(
+                                "unknown event `{}`",
+                                event
+                            )))
+                        }
+                    };
+
+                    Ok(self.apply(event))
+                }
+
+                /// Apply the event at index `code` in the declaration order.
+                /// Returns whether the current state accepts it, and throws if
+                /// there is no such event.
+                #[wasm_bindgen(js_name = transitionCode)]
+                pub fn transition_code(&mut self, code: u32) -> Result<bool, ::wasm_bindgen::JsError> {
+                    let event = match code {
+                        #(#code_arms,)*
+                        _ => {
+                            return Err(::wasm_bindgen::JsError::new(&::std::format!(
+                                "unknown event code {}",
+                                code
+                            )))
+                        }
+                    };
+
+                    Ok(self.apply(event))
+                }
+            }
+
+            impl #wasm_struct {
+                /// The machine driven from JavaScript.
+                pub fn machine(&self) -> &#name {
+                    &self.machine
+                }
+
+                fn apply(&mut self, event: #event_enum) -> bool {
+                    match self.machine.transition(event) {
+                        Some(next) => {
+                            self.machine = next;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            }
+
+            impl From<#name> for #wasm_struct {
+                fn from(machine: #name) -> Self {
+                    #wasm_struct { machine }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse2;
+
+    #[test]
+    fn test_wasm_bindings_to_tokens() {
+        let machine: Machine = parse2(quote! {
+            TurnStile {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        })
+        .unwrap();
+
+        let left = quote! {
+            /// The machine, exported to JavaScript by `wasm-bindgen`.
+            #[::wasm_bindgen::prelude::wasm_bindgen]
+            #[derive(Debug, Clone)]
+            pub struct TurnStileWasm {
+                machine: TurnStile,
+            }
+
+            #[::wasm_bindgen::prelude::wasm_bindgen]
+            impl TurnStileWasm {
+                /// Start the machine in the initial state named `state`.
+                #[wasm_bindgen(constructor)]
+                pub fn new(state: &str) -> Result<TurnStileWasm, ::wasm_bindgen::JsError> {
+                    let machine = match state {
+                        "Locked" => TurnStile::locked(),
+                        _ => {
+                            return Err(::wasm_bindgen::JsError::new(&::std::format!(
+                                "`{}` is not an initial state",
+                                state
+                            )))
+                        }
+                    };
+
+                    Ok(TurnStileWasm { machine })
+                }
+
+                /// The name of the current state.
+                #[wasm_bindgen(getter)]
+                pub fn state(&self) -> ::std::string::String {
+                    ::std::string::String::from(self.machine.name())
+                }
+
+                /// The index of the current state in the declaration order.
+                #[wasm_bindgen(getter, js_name = stateCode)]
+                pub fn state_code(&self) -> u32 {
+                    self.machine.kind() as u32
+                }
+
+                /// Whether the current state is final.
+                #[wasm_bindgen(getter, js_name = isFinal)]
+                pub fn is_final(&self) -> bool {
+                    self.machine.is_final()
+                }
+
+                /// Apply the event named `event`. Returns whether the current
+                /// state accepts it, and throws if there is no such event.
+                pub fn transition(&mut self, event: &str) -> Result<bool, ::wasm_bindgen::JsError> {
+                    let event = match event {
+                        "Coin" => TurnStileEvent::Coin,
+                        "Push" => TurnStileEvent::Push,
+                        _ => {
+                            return Err(::wasm_bindgen::JsError::new(&::std::format!(
+                                "unknown event `{}`",
+                                event
+                            )))
+                        }
+                    };
+
+                    Ok(self.apply(event))
+                }
+
+                /// Apply the event at index `code` in the declaration order.
+                /// Returns whether the current state accepts it, and throws if
+                /// there is no such event.
+                #[wasm_bindgen(js_name = transitionCode)]
+                pub fn transition_code(&mut self, code: u32) -> Result<bool, ::wasm_bindgen::JsError> {
+                    let event = match code {
+                        0 => TurnStileEvent::Coin,
+                        1 => TurnStileEvent::Push,
+                        _ => {
+                            return Err(::wasm_bindgen::JsError::new(&::std::format!(
+                                "unknown event code {}",
+                                code
+                            )))
+                        }
+                    };
+
+                    Ok(self.apply(event))
+                }
+            }
+
+            impl TurnStileWasm {
+                /// The machine driven from JavaScript.
+                pub fn machine(&self) -> &TurnStile {
+                    &self.machine
+                }
+
+                fn apply(&mut self, event: TurnStileEvent) -> bool {
+                    match self.machine.transition(event) {
+                        Some(next) => {
+                            self.machine = next;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            }
+
+            impl From<TurnStile> for TurnStileWasm {
+                fn from(machine: TurnStile) -> Self {
+                    TurnStileWasm { machine }
+                }
+            }
+        };
+
+        let mut right = TokenStream::new();
+        WasmBindings { machine: &machine }.to_tokens(&mut right);
+
+        assert_eq!(format!("{}", left), format!("{}", right))
+    }
+}
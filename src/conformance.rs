@@ -0,0 +1,185 @@
+use core::fmt::{self, Debug, Display};
+use std::vec::Vec;
+
+use crate::StateMachine;
+
+/// A real system modelled by the machine `M`, driven by [`Conformance`].
+pub trait SystemUnderTest<M: StateMachine> {
+    /// Apply `event` to the system, returning `false` if the system rejects
+    /// it in its current state.
+    fn apply(&mut self, event: M::Event) -> bool;
+
+    /// The state the system is in, as the state of the machine it stands
+    /// for.
+    fn observe(&self) -> M::Kind;
+}
+
+/// The first point where a system under test and the machine modelling it
+/// disagree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Divergence<K, E> {
+    /// The events applied to both, the last of which they disagreed on. If
+    /// it is empty, they disagreed on the initial state.
+    pub events: Vec<E>,
+
+    /// The state the machine reached, or `None` if it rejected the last
+    /// event.
+    pub expected: Option<K>,
+
+    /// The state the system reported, or `None` if it rejected the last
+    /// event.
+    pub observed: Option<K>,
+}
+
+impl<K: Debug, E: Debug> Display for Divergence<K, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "after the events {:?}, the machine is in {:?} but the system is in {:?}",
+            self.events, self.expected, self.observed
+        )
+    }
+}
+
+impl<K: Debug, E: Debug> std::error::Error for Divergence<K, E> {}
+
+/// A model-based test, which drives fresh instances of a system under test
+/// and of the machine modelling it in lockstep, and checks after every event
+/// that they both accepted or rejected it and that they are in the same
+/// state.
+pub struct Conformance<M: StateMachine, F> {
+    initial: M,
+    new_system: F,
+}
+
+impl<M, S, F> Conformance<M, F>
+where
+    M: StateMachine + Clone,
+    S: SystemUnderTest<M>,
+    F: FnMut() -> S,
+{
+    /// Check the systems built by `new_system` against machines starting
+    /// from `initial`.
+    pub fn new(initial: M, new_system: F) -> Self {
+        Conformance {
+            initial,
+            new_system,
+        }
+    }
+
+    /// Apply `events` to a fresh system and machine, stopping at the first
+    /// divergence.
+    pub fn check(&mut self, events: &[M::Event]) -> Result<(), Divergence<M::Kind, M::Event>> {
+        let mut system = (self.new_system)();
+        let mut machine = self.initial.clone();
+
+        if system.observe() != machine.kind() {
+            return Err(Divergence {
+                events: Vec::new(),
+                expected: Some(machine.kind()),
+                observed: Some(system.observe()),
+            });
+        }
+
+        for (i, &event) in events.iter().enumerate() {
+            let accepted = system.apply(event);
+            let expected = machine.transition(event);
+
+            let observed = if accepted {
+                Some(system.observe())
+            } else {
+                None
+            };
+
+            if observed != expected.as_ref().map(StateMachine::kind) {
+                return Err(Divergence {
+                    events: events[..=i].to_vec(),
+                    expected: expected.as_ref().map(StateMachine::kind),
+                    observed,
+                });
+            }
+
+            if let Some(next) = expected {
+                machine = next;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check every sequence of at most `max_len` events which the machine
+    /// accepts, along with every sequence made of one of them followed by an
+    /// event which the machine rejects.
+    pub fn exhaustive(&mut self, max_len: usize) -> Result<(), Divergence<M::Kind, M::Event>> {
+        let initial = self.initial.clone();
+
+        self.explore(&initial, &mut Vec::new(), max_len)
+    }
+
+    fn explore(
+        &mut self,
+        machine: &M,
+        events: &mut Vec<M::Event>,
+        remaining: usize,
+    ) -> Result<(), Divergence<M::Kind, M::Event>> {
+        if remaining == 0 {
+            return Ok(());
+        }
+
+        for &event in M::EVENTS {
+            events.push(event);
+            self.check(events)?;
+
+            if let Some(next) = machine.transition(event) {
+                self.explore(&next, events, remaining - 1)?;
+            }
+
+            let _ = events.pop();
+        }
+
+        Ok(())
+    }
+
+    /// Check `runs` sequences of `len` events picked at random among all the
+    /// events of the machine, accepted or not. The same `seed` always picks
+    /// the same sequences.
+    pub fn random(
+        &mut self,
+        seed: u64,
+        runs: usize,
+        len: usize,
+    ) -> Result<(), Divergence<M::Kind, M::Event>> {
+        if M::EVENTS.is_empty() {
+            return Ok(());
+        }
+
+        // xorshift64*, whose state must not be zero.
+        let mut state = seed | 1;
+        let mut events = Vec::with_capacity(len);
+
+        for _ in 0..runs {
+            events.clear();
+
+            for _ in 0..len {
+                state ^= state >> 12;
+                state ^= state << 25;
+                state ^= state >> 27;
+                let random = state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+                events.push(M::EVENTS[(random % M::EVENTS.len() as u64) as usize]);
+            }
+
+            self.check(&events)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<M: StateMachine + Debug, F> Debug for Conformance<M, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Conformance")
+            .field("initial", &self.initial)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,108 @@
+use core::fmt::{self, Debug};
+use std::{vec, vec::Vec};
+
+use crate::{Edge, InvalidTransition, StateMachine};
+
+/// A machine which counts how many times each edge of its diagram is taken,
+/// to measure tests against the diagram rather than against lines of code.
+///
+/// The counts are kept across [`restart`](Coverage::restart), so that one
+/// `Coverage` can gather the edges taken by several runs.
+pub struct Coverage<M: StateMachine> {
+    machine: M,
+    hits: Vec<usize>,
+}
+
+impl<M: StateMachine> Coverage<M> {
+    /// Wrap `machine`, with no edge taken yet.
+    pub fn new(machine: M) -> Self {
+        Coverage {
+            machine,
+            hits: vec![0; M::TRANSITIONS.len()],
+        }
+    }
+
+    /// The state the machine is in.
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    /// Unwrap the machine, dropping the counts.
+    pub fn into_inner(self) -> M {
+        self.machine
+    }
+
+    /// Replace the machine with `machine`, keeping the counts.
+    pub fn restart(&mut self, machine: M) {
+        self.machine = machine;
+    }
+
+    /// Apply `event` to the machine and count the edge it takes. If the
+    /// current state does not accept it, nothing is counted.
+    #[track_caller]
+    pub fn transition(
+        &mut self,
+        event: M::Event,
+    ) -> Result<&M, InvalidTransition<M::Kind, M::Event>> {
+        let next = self.machine.try_transition(event)?;
+
+        let from = self.machine.kind();
+
+        // The declared edge is found by its source and event alone, since
+        // the completion transitions taken on entering its target make the
+        // machine end up elsewhere.
+        if let Some(i) = M::TRANSITIONS
+            .iter()
+            .position(|e| e.from == from && e.event == event)
+        {
+            self.hits[i] += 1;
+        }
+
+        self.machine = next;
+
+        Ok(&self.machine)
+    }
+
+    /// How many times `edge` was taken.
+    pub fn hits(&self, edge: Edge<M::Kind, M::Event>) -> usize {
+        M::TRANSITIONS
+            .iter()
+            .position(|e| *e == edge)
+            .map_or(0, |i| self.hits[i])
+    }
+
+    /// The edges taken at least once, in declaration order.
+    pub fn covered(&self) -> impl Iterator<Item = Edge<M::Kind, M::Event>> + '_ {
+        self.edges(|hits| hits > 0)
+    }
+
+    /// The edges never taken, in declaration order.
+    pub fn uncovered(&self) -> impl Iterator<Item = Edge<M::Kind, M::Event>> + '_ {
+        self.edges(|hits| hits == 0)
+    }
+
+    fn edges(
+        &self,
+        keep: impl Fn(usize) -> bool + 'static,
+    ) -> impl Iterator<Item = Edge<M::Kind, M::Event>> + '_ {
+        M::TRANSITIONS
+            .iter()
+            .zip(&self.hits)
+            .filter(move |&(_, &hits)| keep(hits))
+            .map(|(&edge, _)| edge)
+    }
+
+    /// Whether every edge was taken at least once.
+    pub fn is_complete(&self) -> bool {
+        self.hits.iter().all(|&hits| hits > 0)
+    }
+}
+
+impl<M: StateMachine + Debug> Debug for Coverage<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Coverage")
+            .field("machine", &self.machine)
+            .field("hits", &self.hits)
+            .finish()
+    }
+}
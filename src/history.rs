@@ -0,0 +1,99 @@
+use crate::{InvalidTransition, StateMachine};
+
+/// A machine which records its last `N` steps, as the event applied and the
+/// kind of state it led to, to find out how it ended up where it is.
+///
+/// The steps are kept in a ring buffer which doesn't allocate, so that
+/// histories are available without `std`.
+#[derive(Debug, Clone)]
+pub struct History<M: StateMachine, const N: usize> {
+    machine: M,
+    steps: [Option<(M::Event, M::Kind)>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<M: StateMachine, const N: usize> History<M, N> {
+    /// Wrap `machine`, with an empty history.
+    pub fn new(machine: M) -> Self {
+        History {
+            machine,
+            steps: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// The state the machine is in.
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    /// Unwrap the machine, dropping the history.
+    pub fn into_inner(self) -> M {
+        self.machine
+    }
+
+    /// Apply `event` to the machine and record the step, forgetting the
+    /// oldest one if `N` steps are already recorded. If the current state
+    /// does not accept it, nothing is recorded.
+    #[track_caller]
+    pub fn transition(
+        &mut self,
+        event: M::Event,
+    ) -> Result<&M, InvalidTransition<M::Kind, M::Event>> {
+        self.machine = self.machine.try_transition(event)?;
+        self.record(event, self.machine.kind());
+
+        Ok(&self.machine)
+    }
+
+    fn record(&mut self, event: M::Event, kind: M::Kind) {
+        if N == 0 {
+            return;
+        }
+
+        if self.len < N {
+            self.steps[(self.head + self.len) % N] = Some((event, kind));
+            self.len += 1;
+        } else {
+            self.steps[self.head] = Some((event, kind));
+            self.head = (self.head + 1) % N;
+        }
+    }
+
+    /// The recorded steps, from the oldest to the most recent.
+    pub fn steps(&self) -> impl DoubleEndedIterator<Item = (M::Event, M::Kind)> + '_ {
+        (0..self.len).filter_map(move |i| self.steps[(self.head + i) % N])
+    }
+
+    /// The most recent step.
+    pub fn last(&self) -> Option<(M::Event, M::Kind)> {
+        self.steps().next_back()
+    }
+
+    /// The event of the most recent recorded step which led to `kind`.
+    pub fn last_entered(&self, kind: M::Kind) -> Option<M::Event> {
+        self.steps()
+            .rev()
+            .find(|&(_, to)| to == kind)
+            .map(|(event, _)| event)
+    }
+
+    /// The number of recorded steps, at most `N`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no step is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Forget the recorded steps, keeping the machine as it is.
+    pub fn clear(&mut self) {
+        self.steps = [None; N];
+        self.head = 0;
+        self.len = 0;
+    }
+}
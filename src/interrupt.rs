@@ -0,0 +1,96 @@
+use core::{cell::RefCell, panic::Location};
+
+use critical_section::Mutex;
+
+use crate::{EventQueue, InvalidTransition, StateMachine};
+
+/// A machine which interrupt handlers can post events to, while the events
+/// are processed in thread context. It can be stored in a `static`, and only
+/// relies on `critical_section` to share the queue and the machine with the
+/// interrupts.
+#[derive(Debug)]
+pub struct InterruptRunner<M: StateMachine, const N: usize = 16> {
+    machine: Mutex<RefCell<Option<M>>>,
+    queue: Mutex<RefCell<EventQueue<M::Event, N>>>,
+}
+
+impl<M: StateMachine, const N: usize> InterruptRunner<M, N> {
+    /// A runner without a machine, to be given one with `start`.
+    pub const fn new() -> Self {
+        InterruptRunner {
+            machine: Mutex::new(RefCell::new(None)),
+            queue: Mutex::new(RefCell::new(EventQueue::new())),
+        }
+    }
+
+    /// Drive `machine`, replacing the current one if any. The events already
+    /// posted are kept.
+    pub fn start(&self, machine: M) {
+        critical_section::with(|cs| *self.machine.borrow_ref_mut(cs) = Some(machine));
+    }
+
+    /// Add `event` at the end of the queue, or give it back if the queue is
+    /// full. Can be called from interrupt handlers.
+    pub fn post(&self, event: M::Event) -> Result<(), M::Event> {
+        critical_section::with(|cs| self.queue.borrow_ref_mut(cs).post(event))
+    }
+
+    /// The kind of state the machine is in, or `None` before `start`. Can be
+    /// called from interrupt handlers.
+    pub fn kind(&self) -> Option<M::Kind> {
+        critical_section::with(|cs| self.machine.borrow_ref(cs).as_ref().map(M::kind))
+    }
+}
+
+impl<M: StateMachine + Clone, const N: usize> InterruptRunner<M, N> {
+    /// Process the queued events one at a time until the queue is empty,
+    /// calling `action` with the state left, the event and the state entered
+    /// after every transition. The interrupts are only disabled while an
+    /// event is taken from the queue and applied, not while `action` runs.
+    ///
+    /// Does nothing before `start`. If the current state does not accept an
+    /// event, the event is dropped, the machine is left in that state and
+    /// the rest of the queue is kept.
+    #[track_caller]
+    pub fn run(
+        &self,
+        mut action: impl FnMut(&M, M::Event, &M),
+    ) -> Result<(), InvalidTransition<M::Kind, M::Event>> {
+        // The transitions are taken in a closure, which cannot pass the
+        // location of the caller on.
+        let location = Location::caller();
+
+        loop {
+            let step = critical_section::with(|cs| {
+                let mut machine = self.machine.borrow_ref_mut(cs);
+                let machine = match machine.as_mut() {
+                    Some(machine) => machine,
+                    None => return Ok(None),
+                };
+
+                let event = match self.queue.borrow_ref_mut(cs).pop() {
+                    Some(event) => event,
+                    None => return Ok(None),
+                };
+
+                let next = machine
+                    .try_transition(event)
+                    .map_err(|error| InvalidTransition { location, ..error })?;
+                let previous = core::mem::replace(machine, next.clone());
+
+                Ok(Some((previous, event, next)))
+            })?;
+
+            match step {
+                Some((from, event, to)) => action(&from, event, &to),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+impl<M: StateMachine, const N: usize> Default for InterruptRunner<M, N> {
+    fn default() -> Self {
+        InterruptRunner::new()
+    }
+}
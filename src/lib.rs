@@ -1,5 +1,7 @@
-// quote! macro needs a higher recursion limit
-#![recursion_limit = "512"]
+//! `sad_machine` provides the [`state_machine!`] macro to declaratively define
+//! a state machine and the transitions between its states, and the traits
+//! implemented by the generated machines.
+#![no_std]
 #![forbid(
     future_incompatible,
     macro_use_extern_crate,
@@ -22,22 +24,2185 @@
     unused
 )]
 
-use crate::machine::Machine;
-use proc_macro::TokenStream;
-use quote::quote;
-use syn::parse_macro_input;
+// The generated code refers to this crate as `::sad_machine`.
+#[cfg(test)]
+extern crate self as sad_machine;
 
-mod event;
-mod initial_state;
-mod machine;
-mod state;
-mod state_transition;
-mod transition;
+#[cfg(any(test, feature = "std"))]
+extern crate std;
 
-/// Generate the declaratively described state machine diagram.
-#[proc_macro]
-pub fn state_machine(input: TokenStream) -> TokenStream {
-    let machines: Machine = parse_macro_input!(input as Machine);
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
-    quote!(#machines).into()
+use core::{
+    fmt::{self, Debug, Display},
+    hash::{Hash, Hasher},
+    panic::Location,
+};
+
+pub use sad_machine_macros::{state_machine, state_machine_from_file};
+
+#[cfg(feature = "std")]
+mod conformance;
+#[cfg(feature = "std")]
+mod coverage;
+mod history;
+#[cfg(feature = "critical-section")]
+mod interrupt;
+mod macros;
+#[cfg(feature = "std")]
+mod observed;
+mod path;
+mod persist;
+mod runner;
+#[cfg(feature = "std")]
+mod shared;
+
+#[cfg(feature = "std")]
+pub use conformance::{Conformance, Divergence, SystemUnderTest};
+#[cfg(feature = "std")]
+pub use coverage::Coverage;
+pub use history::History;
+#[cfg(feature = "critical-section")]
+pub use interrupt::InterruptRunner;
+#[cfg(feature = "std")]
+pub use observed::Observed;
+pub use path::Path;
+pub use persist::{LoadError, Persist};
+pub use runner::{EventQueue, Runner};
+#[cfg(feature = "std")]
+pub use shared::Shared;
+
+/// Items the generated code refers to, which are not part of the API.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::path::shortest_path;
+    pub use alloc::vec::Vec;
+}
+
+/// The interface shared by every machine generated by [`state_machine!`], so
+/// that supervisors, persistence layers and other generic code can drive any
+/// of them.
+pub trait StateMachine: Sized {
+    /// The states of the machine, ignoring how they were entered.
+    type Kind: Copy + Eq + Debug + 'static;
+
+    /// The events accepted by the machine.
+    type Event: Copy + Eq + Debug + 'static;
+
+    /// The ways in which the states of the machine can be entered.
+    type Provenance: Copy + Eq + Debug;
+
+    /// The number of states of the machine.
+    const STATE_COUNT: usize;
+
+    /// The number of events accepted by the machine.
+    const EVENT_COUNT: usize;
+
+    /// Every event accepted by the machine, in declaration order.
+    const EVENTS: &'static [Self::Event];
+
+    /// Every edge of the diagram, in declaration order.
+    const TRANSITIONS: &'static [Edge<Self::Kind, Self::Event>];
+
+    /// The state the machine is in.
+    fn kind(&self) -> Self::Kind;
+
+    /// How the machine entered its current state.
+    fn provenance(&self) -> Self::Provenance;
+
+    /// Apply `event` to the machine, returning the next state, or `None` if
+    /// the current state does not accept it. A machine declared with
+    /// `OnInvalid => ...` enters that state instead, so it never returns
+    /// `None`.
+    fn transition(&self, event: Self::Event) -> Option<Self>;
+
+    /// Whether the current state accepts `event`, that is whether one of the
+    /// [`TRANSITIONS`](StateMachine::TRANSITIONS) leaves it on `event`. The
+    /// fallback of `OnInvalid => ...` is not one of them, so this is the test
+    /// to use rather than `transition(event).is_some()`.
+    fn accepts(&self, event: Self::Event) -> bool {
+        let kind = self.kind();
+
+        Self::TRANSITIONS
+            .iter()
+            .any(|edge| edge.from == kind && edge.event == event)
+    }
+
+    /// Like [`transition`](StateMachine::transition), but return an error
+    /// carrying the current state, the rejected event and the location of
+    /// the call.
+    #[track_caller]
+    fn try_transition(
+        &self,
+        event: Self::Event,
+    ) -> Result<Self, InvalidTransition<Self::Kind, Self::Event>> {
+        self.transition(event)
+            .ok_or(InvalidTransition::new(self.kind(), event))
+    }
+
+    /// Take the completion transition declared with `on entry ... => ...`
+    /// from the current state, returning the next state, or `None` if the
+    /// current state has none.
+    fn complete_once(&self) -> Option<Self> {
+        None
+    }
+
+    /// Take the completion transitions from the current state until reaching
+    /// a stable state, one without a completion transition.
+    ///
+    /// A machine in which they loop would never get there, so this gives up
+    /// with an error after [`STATE_COUNT`](StateMachine::STATE_COUNT)
+    /// completion transitions. `state_machine!` rejects such diagrams, but
+    /// other implementations of the trait may not.
+    fn run_to_completion(&self) -> Result<Self, CompletionLoop<Self::Kind>>
+    where
+        Self: Clone,
+    {
+        let mut machine = self.clone();
+
+        for _ in 0..Self::STATE_COUNT {
+            match machine.complete_once() {
+                Some(next) => machine = next,
+                None => return Ok(machine),
+            }
+        }
+
+        Err(CompletionLoop {
+            kind: machine.kind(),
+        })
+    }
+}
+
+/// A transition declared in the diagram of a machine.
+///
+/// Two edges are equal when they take the same event between the same
+/// states, whatever their label.
+#[derive(Debug, Clone, Copy)]
+pub struct Edge<K, E> {
+    /// The state the transition leaves.
+    pub from: K,
+
+    /// The event taking the transition.
+    pub event: E,
+
+    /// The state the transition enters.
+    pub to: K,
+
+    /// The description given with `Coin "..." { ... }`, if any.
+    pub label: Option<&'static str>,
+}
+
+impl<K: PartialEq, E: PartialEq> PartialEq for Edge<K, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.from == other.from && self.event == other.event && self.to == other.to
+    }
+}
+
+impl<K: Eq, E: Eq> Eq for Edge<K, E> {}
+
+impl<K: Hash, E: Hash> Hash for Edge<K, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.from.hash(state);
+        self.event.hash(state);
+        self.to.hash(state);
+    }
+}
+
+impl<K: Debug, E: Debug> Display for Edge<K, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} --{:?}--> {:?}", self.from, self.event, self.to)
+    }
+}
+
+/// The error returned when a machine is given an event which its current
+/// state does not accept.
+///
+/// Two errors are equal when they reject the same event in the same state,
+/// wherever they were raised.
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidTransition<K, E> {
+    /// The state the machine was in.
+    pub kind: K,
+
+    /// The rejected event.
+    pub event: E,
+
+    /// Where the event was given to the machine, so that logs point at the
+    /// offending call instead of the generated code.
+    pub location: &'static Location<'static>,
+}
+
+impl<K, E> InvalidTransition<K, E> {
+    /// The error for `event` rejected in the state `kind`, located at the
+    /// caller.
+    #[track_caller]
+    pub fn new(kind: K, event: E) -> Self {
+        InvalidTransition {
+            kind,
+            event,
+            location: Location::caller(),
+        }
+    }
+}
+
+impl<K: PartialEq, E: PartialEq> PartialEq for InvalidTransition<K, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.event == other.event
+    }
+}
+
+impl<K: Eq, E: Eq> Eq for InvalidTransition<K, E> {}
+
+impl<K: Hash, E: Hash> Hash for InvalidTransition<K, E> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.event.hash(state);
+    }
+}
+
+impl<K: Debug, E: Debug> Display for InvalidTransition<K, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "event {:?} is not accepted in state {:?}, at {}",
+            self.event, self.kind, self.location
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug, E: Debug> std::error::Error for InvalidTransition<K, E> {}
+
+/// The error returned when the completion transitions of a machine do not
+/// reach a stable state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompletionLoop<K> {
+    /// The state the machine was in when giving up.
+    pub kind: K,
+}
+
+impl<K: Debug> Display for CompletionLoop<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the completion transitions loop through state {:?}",
+            self.kind
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug> std::error::Error for CompletionLoop<K> {}
+
+/// The error returned when converting a machine into the enum of a state it
+/// is not in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WrongState<K> {
+    /// The state the conversion required.
+    pub expected: K,
+
+    /// The state the machine was in.
+    pub actual: K,
+}
+
+impl<K: Debug> Display for WrongState<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected state {:?}, but the machine is in state {:?}",
+            self.expected, self.actual
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug> std::error::Error for WrongState<K> {}
+
+/// The error returned when an event log cannot be replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReplayError<K, E> {
+    /// The log starts in a state which is not an initial state.
+    NotInitial(K),
+
+    /// The event at `index` in the log is not accepted in the state reached
+    /// by the events before it.
+    InvalidEvent {
+        /// The index of the rejected event in the log.
+        index: usize,
+
+        /// The state the machine was in.
+        kind: K,
+
+        /// The rejected event.
+        event: E,
+    },
+}
+
+impl<K: Debug, E: Debug> Display for ReplayError<K, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::NotInitial(kind) => write!(f, "state {:?} is not an initial state", kind),
+            ReplayError::InvalidEvent { index, kind, event } => write!(
+                f,
+                "event {:?} at index {} is not accepted in state {:?}",
+                event, index, kind
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug, E: Debug> std::error::Error for ReplayError<K, E> {}
+
+/// The error returned when a snapshot cannot be restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RestoreError {
+    /// The snapshot was taken with a different diagram, whose fingerprint is
+    /// given.
+    Fingerprint(u64),
+
+    /// The snapshot has the right fingerprint but an unknown state, so it
+    /// was not taken by `snapshot`.
+    UnknownState(u32),
+}
+
+impl Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestoreError::Fingerprint(fingerprint) => write!(
+                f,
+                "the snapshot was taken with a different diagram, of fingerprint {:#018x}",
+                fingerprint
+            ),
+            RestoreError::UnknownState(state) => write!(f, "unknown state {} in snapshot", state),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RestoreError {}
+
+/// Implemented by every state enum which accepts the event `E`, so that
+/// generic code can drive any state accepting it without matching on concrete
+/// types. `E` is the zero-sized `{Event}Event` type generated for each event.
+pub trait Transition<E> {
+    /// The machine the state belongs to.
+    type Machine;
+
+    /// Apply the event, returning the next state.
+    fn transition(&self, event: E) -> Self::Machine;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    state_machine! {
+        #[generated_tests]
+        #[async_hooks]
+        Lock {
+            InitialStates { Locked }
+            FinalStates { Broken }
+            Properties {
+                Always reachable(Locked => Broken);
+                Never reachable(Broken => Locked);
+            }
+
+            TurnKey {
+                Locked => Unlocked
+                Unlocked => Locked
+            }
+
+            BreakKeyhole {
+                Locked, Unlocked => Broken
+            }
+        }
+
+        #[generated_tests]
+        Keywords {
+            InitialStates { Match }
+            FinalStates { Done }
+
+            r#Loop { Match => Match }
+            Move { Match => Done }
+        }
+    }
+
+    mod turn_stile {
+        use crate::state_machine;
+
+        state_machine! {
+            #[allow(unused_events)]
+            TurnStile {
+                InitialStates { Locked }
+                States { Unlocked, Locked }
+                Events { Coin, Push, Refund }
+
+                Coin { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        }
+    }
+
+    fn run<M: StateMachine>(machine: M, events: &[M::Event]) -> Option<M> {
+        events
+            .iter()
+            .try_fold(machine, |machine, &event| machine.transition(event))
+    }
+
+    fn break_keyhole<S: Transition<BreakKeyholeEvent>>(state: &S) -> S::Machine {
+        state.transition(BreakKeyholeEvent)
+    }
+
+    #[test]
+    fn test_transition() {
+        assert_eq!(
+            break_keyhole(&LockedState::FromInit),
+            Lock::Broken(BrokenState::FromBreakKeyhole)
+        );
+        assert_eq!(
+            break_keyhole(&UnlockedState::FromTurnKey),
+            Lock::Broken(BrokenState::FromBreakKeyhole)
+        );
+    }
+
+    #[test]
+    fn test_apply_all() {
+        assert_eq!(
+            Lock::locked().apply_all([LockEvent::TurnKey, LockEvent::BreakKeyhole]),
+            Ok(Lock::Broken(BrokenState::FromBreakKeyhole))
+        );
+        assert_eq!(
+            Lock::locked().apply_all([
+                LockEvent::TurnKey,
+                LockEvent::BreakKeyhole,
+                LockEvent::TurnKey
+            ]),
+            Err((
+                Lock::Broken(BrokenState::FromBreakKeyhole),
+                LockEvent::TurnKey
+            ))
+        );
+    }
+
+    #[test]
+    fn test_try_transition() {
+        let broken = Lock::Broken(BrokenState::FromBreakKeyhole);
+        let error = broken.try_transition(LockEvent::TurnKey).unwrap_err();
+        let line = line!() - 1;
+
+        assert_eq!(error, LockError::new(LockKind::Broken, LockEvent::TurnKey));
+        assert_eq!(error.location.file(), file!());
+        assert_eq!(error.location.line(), line);
+        assert_eq!(
+            std::format!("{}", error),
+            std::format!(
+                "event TurnKey is not accepted in state Broken, at {}",
+                error.location
+            )
+        );
+
+        let error = StateMachine::try_transition(&broken, LockEvent::TurnKey).unwrap_err();
+        assert_eq!(error.location.line(), line!() - 1);
+        assert_eq!(
+            StateMachine::try_transition(&Lock::locked(), LockEvent::TurnKey),
+            Ok(Lock::Unlocked(UnlockedState::FromTurnKey))
+        );
+    }
+
+    #[test]
+    fn test_step() {
+        let (lock, step) = Lock::locked().step(LockEvent::TurnKey).unwrap();
+
+        assert_eq!(lock, Lock::Unlocked(UnlockedState::FromTurnKey));
+        assert_eq!(
+            step,
+            LockStep {
+                from: LockKind::Locked,
+                event: LockEvent::TurnKey,
+                to: LockKind::Unlocked,
+            }
+        );
+    }
+
+    #[test]
+    fn test_undo() {
+        let (lock, step) = Lock::locked().step(LockEvent::TurnKey).unwrap();
+        assert_eq!(
+            lock.undo(step),
+            Some(Lock::Locked(LockedState::FromTurnKey))
+        );
+
+        let (broken, step) = lock.step(LockEvent::BreakKeyhole).unwrap();
+        assert_eq!(broken.undo(step), None);
+    }
+
+    #[test]
+    fn test_in_state() {
+        let lock = Lock::locked();
+
+        assert!(in_state!(lock, Lock::Locked));
+        assert!(in_state!(lock, Lock::Unlocked | Lock::Locked));
+        assert!(!in_state!(lock, Lock::Unlocked | Lock::Broken));
+    }
+
+    #[test]
+    fn test_match_state() {
+        let describe = |lock: &Lock| {
+            match_state!(lock, {
+                Lock::Locked | Lock::Unlocked => "working",
+                Lock::Broken => "broken",
+            })
+        };
+
+        assert_eq!(describe(&Lock::locked()), "working");
+        assert_eq!(
+            match_state!(Lock::locked(), {
+                Lock::Broken => "broken",
+                _ => "working",
+            }),
+            "working"
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        assert_eq!(Lock::STATE_COUNT, 3);
+        assert_eq!(<Lock as StateMachine>::EVENT_COUNT, 2);
+    }
+
+    #[test]
+    fn test_all() {
+        assert_eq!(Lock::ALL.len(), 4);
+        assert!(Lock::ALL.contains(&Lock::locked()));
+
+        for lock in &Lock::ALL {
+            assert_eq!(
+                Lock::at(lock.kind(), lock.provenance()).as_ref(),
+                Some(lock)
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_final() {
+        assert!(!Lock::locked().is_final());
+        assert!(Lock::Broken(BrokenState::FromBreakKeyhole).is_final());
+    }
+
+    #[test]
+    fn test_same_kind() {
+        let relocked = Lock::Locked(LockedState::FromTurnKey);
+
+        assert_ne!(relocked, Lock::locked());
+        assert!(relocked.same_kind(&Lock::locked()));
+        assert!(!relocked.same_kind(&Lock::Unlocked(UnlockedState::FromTurnKey)));
+    }
+
+    #[test]
+    fn test_kind_equality() {
+        assert_eq!(Lock::locked(), LockKind::Locked);
+        assert_eq!(LockKind::Locked, Lock::locked());
+        assert_ne!(Lock::locked(), LockKind::Unlocked);
+        assert_ne!(LockKind::Broken, Lock::locked());
+    }
+
+    #[test]
+    fn test_kind_and_name_conversions() {
+        let lock = Lock::locked();
+        let kind: LockKind = (&lock).into();
+        let name: &'static str = (&lock).into();
+
+        assert_eq!(kind, LockKind::Locked);
+        assert_eq!(name, "Locked");
+        assert_eq!(LockEvent::BreakKeyhole.name(), "BreakKeyhole");
+    }
+
+    #[test]
+    fn test_from_state() {
+        let lock: Lock = UnlockedState::FromTurnKey.into();
+
+        assert_eq!(lock, Lock::Unlocked(UnlockedState::FromTurnKey));
+    }
+
+    #[test]
+    fn test_keywords() {
+        let machine = Keywords::r#match();
+
+        assert_eq!(
+            machine.transition(KeywordsEvent::Loop),
+            Some(Keywords::Match(MatchState::FromLoop))
+        );
+        assert_eq!(
+            MatchState::FromInit.r#move(),
+            Keywords::Done(DoneState::FromMove)
+        );
+    }
+
+    #[test]
+    fn test_state_machine_transition() {
+        let lock = run(
+            Lock::locked(),
+            &[
+                LockEvent::TurnKey,
+                LockEvent::TurnKey,
+                LockEvent::BreakKeyhole,
+            ],
+        );
+
+        assert_eq!(lock, Some(Lock::Broken(BrokenState::FromBreakKeyhole)));
+        assert_eq!(
+            run(
+                Lock::locked(),
+                &[LockEvent::BreakKeyhole, LockEvent::TurnKey]
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn declared_states_and_events() {
+        use turn_stile::{TurnStile, TurnStileEvent, TurnStileKind};
+
+        assert_eq!(TurnStile::EVENT_COUNT, 3);
+        assert_eq!(TurnStile::ALL[0].kind(), TurnStileKind::Unlocked);
+        assert!(TurnStile::locked()
+            .transition(TurnStileEvent::Refund)
+            .is_none());
+    }
+
+    #[test]
+    fn dot() {
+        assert!(Lock::DOT.starts_with("digraph Lock {"));
+        assert!(Lock::DOT.contains("Locked -> Unlocked [label=\"TurnKey\"];"));
+        assert!(Lock::DOT.contains("Broken [shape=doublecircle];"));
+    }
+
+    #[test]
+    fn mermaid() {
+        assert!(Lock::MERMAID.starts_with("stateDiagram-v2\n    [*] --> Locked"));
+        assert!(Lock::MERMAID.contains("Locked --> Unlocked : TurnKey"));
+        assert!(Lock::MERMAID.ends_with("Broken --> [*]"));
+    }
+
+    #[test]
+    fn description() {
+        assert_eq!(
+            Keywords::DESCRIPTION,
+            "{\"name\":\"Keywords\",\"states\":[\"Match\",\"Done\"],\"events\":[\"Loop\",\"Move\"],\
+             \"initial_states\":[\"Match\"],\"final_states\":[\"Done\"],\
+             \"transitions\":[{\"event\":\"Loop\",\"from\":\"Match\",\"to\":\"Match\"},\
+             {\"event\":\"Move\",\"from\":\"Match\",\"to\":\"Done\"}]}"
+        );
+    }
+
+    mod counted {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static TRANSITIONS: AtomicUsize = AtomicUsize::new(0);
+
+        pub(super) struct Counter;
+
+        impl DoorHooks for Counter {
+            fn on_transition(from: DoorKind, _event: DoorEvent, to: &Door) {
+                assert_ne!(from, to.kind());
+                let _ = TRANSITIONS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        crate::state_machine! {
+            Door {
+                InitialStates { Closed }
+                Hooks = Counter
+
+                Open { Closed => Opened }
+                Close { Opened => Closed }
+            }
+        }
+
+        #[test]
+        fn hooks() {
+            let door = ClosedState::FromInit.open();
+            let door = door.transition(DoorEvent::Close);
+
+            assert_eq!(door.map(|door| door.kind()), Some(DoorKind::Closed));
+            assert_eq!(TRANSITIONS.load(Ordering::Relaxed), 2);
+        }
+    }
+
+    mod table {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static TRANSITIONS: AtomicUsize = AtomicUsize::new(0);
+
+        pub(super) struct Counter;
+
+        impl SafeHooks for Counter {
+            fn on_transition(from: SafeKind, _event: SafeEvent, to: &Safe) {
+                assert_ne!(from, to.kind());
+                let _ = TRANSITIONS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        crate::state_machine! {
+            #[dispatch(table)]
+            #[generated_tests]
+            Safe {
+                InitialStates { Locked }
+                FinalStates { Broken }
+                Hooks = Counter
+
+                TurnKey {
+                    Locked => Unlocked
+                    Unlocked => Locked
+                }
+
+                BreakKeyhole {
+                    Locked, Unlocked => Broken
+                }
+            }
+        }
+
+        #[test]
+        fn dispatch() {
+            assert_eq!(
+                Safe::TRANSITION_TABLE[SafeKind::Locked as usize][SafeEvent::TurnKey as usize],
+                Some((SafeKind::Unlocked, SafeProvenance::FromTurnKey))
+            );
+            assert_eq!(
+                Safe::TRANSITION_TABLE[SafeKind::Broken as usize][SafeEvent::TurnKey as usize],
+                None
+            );
+
+            let (safe, step) = Safe::locked().step(SafeEvent::TurnKey).unwrap();
+            assert_eq!(safe, Safe::Unlocked(UnlockedState::FromTurnKey));
+            assert_eq!(
+                safe.undo(step),
+                Some(Safe::Locked(LockedState::FromTurnKey))
+            );
+
+            let safe = safe.transition(SafeEvent::BreakKeyhole);
+            assert_eq!(safe, Some(Safe::Broken(BrokenState::FromBreakKeyhole)));
+            assert!(TRANSITIONS.load(Ordering::Relaxed) >= 3);
+        }
+    }
+
+    mod unit {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static TRANSITIONS: AtomicUsize = AtomicUsize::new(0);
+
+        pub(super) struct Counter;
+
+        impl GateHooks for Counter {
+            fn on_transition(from: GateKind, _event: GateEvent, to: &Gate) {
+                assert_ne!(from, to.kind());
+                let _ = TRANSITIONS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        crate::state_machine! {
+            #[provenance(none)]
+            #[generated_tests]
+            Gate {
+                InitialStates { Closed }
+                FinalStates { Broken }
+                Hooks = Counter
+
+                Toggle {
+                    Closed => Open
+                    Open => Closed
+                }
+
+                Force {
+                    Closed, Open => Broken
+                }
+            }
+        }
+
+        #[test]
+        fn provenance_none() {
+            let (gate, step) = Gate::closed().step(GateEvent::Toggle).unwrap();
+            assert_eq!(gate, Gate::Open);
+            assert_eq!(gate.undo(step), Some(Gate::Closed));
+
+            let gate = match gate.transition(GateEvent::Force) {
+                Some(Gate::Broken) => Gate::Broken,
+                other => panic!("unexpected {:?}", other),
+            };
+            assert_eq!(gate.transition(GateEvent::Toggle), None);
+            assert_eq!(Gate::restore(gate.snapshot()), Ok(Gate::Broken));
+            assert!(TRANSITIONS.load(Ordering::Relaxed) >= 2);
+        }
+    }
+
+    mod packed {
+        crate::state_machine! {
+            #[packed]
+            #[inline(always)]
+            #[generated_tests]
+            Latch {
+                InitialStates { Open }
+                FinalStates { Jammed }
+
+                Flip {
+                    Open => Shut
+                    Shut => Open
+                }
+
+                Jam {
+                    Open, Shut => Jammed
+                }
+            }
+        }
+
+        #[test]
+        fn pack() {
+            assert_eq!(size_of::<LatchPacked>(), 1);
+
+            let latch = Latch::open().transition(LatchEvent::Flip).unwrap();
+            let packed = latch.pack();
+            assert_eq!(packed.kind(), LatchKind::Shut);
+            assert_eq!(packed.provenance(), LatchProvenance::FromFlip);
+            assert_eq!(packed.unpack(), latch);
+
+            assert_eq!(LatchPacked::from_bits(packed.to_bits()), Some(packed));
+            assert_eq!(LatchPacked::from_bits(Latch::ALL.len() as u8), None);
+        }
+    }
+
+    mod prelude {
+        mod valve {
+            crate::state_machine! {
+                #[prelude]
+                Valve {
+                    InitialStates { Shut }
+
+                    Open { Shut => Flowing }
+                    Close { Flowing => Shut }
+                }
+            }
+        }
+
+        use valve::valve_prelude::*;
+
+        #[test]
+        fn prelude() {
+            let valve: Valve = Valve::shut().transition(ValveEvent::Open).unwrap();
+            assert_eq!(valve, Valve::Flowing(FlowingState::FromOpen));
+            assert_eq!(valve.kind(), ValveKind::Flowing);
+            assert_eq!(valve.provenance(), ValveProvenance::FromOpen);
+            assert_eq!(
+                valve.transition(ValveEvent::Close),
+                Some(Valve::Shut(ShutState::FromClose))
+            );
+        }
+    }
+
+    mod renamed {
+        crate::state_machine! {
+            Link {
+                InitialStates { idle }
+                States { idle, "wait-for-ack" as WaitForAck }
+                Events { "send-frame" as SendFrame, ack }
+
+                SendFrame { idle => WaitForAck }
+                ack { WaitForAck => idle }
+            }
+        }
+
+        #[test]
+        fn renamed() {
+            let link = Link::idle().transition(LinkEvent::SendFrame).unwrap();
+            assert_eq!(link.kind(), LinkKind::WaitForAck);
+            assert_eq!(link.kind().name(), "wait-for-ack");
+            assert_eq!(LinkKind::Idle.name(), "idle");
+            assert_eq!(LinkEvent::SendFrame.name(), "send-frame");
+            assert_eq!(LinkEvent::Ack.name(), "ack");
+            assert!(Link::DOT.contains("WaitForAck [shape=circle, label=\"wait-for-ack\"];"));
+            assert!(Link::MERMAID.contains("state \"wait-for-ack\" as WaitForAck"));
+            assert!(Link::DESCRIPTION.contains("\"states\":[\"idle\",\"wait-for-ack\"]"));
+        }
+    }
+
+    // The keywords which cannot be raw identifiers keep a trailing underscore,
+    // which the types named after them drop.
+    #[deny(warnings)]
+    mod keyword_names {
+        crate::state_machine! {
+            #[provenance(full)]
+            Mirror {
+                InitialStates { Self_ }
+                FinalStates { Other }
+
+                Reflect { Self_ => Other }
+            }
+
+            #[provenance(full)]
+            Echo {
+                InitialStates { Call }
+                FinalStates { Answer }
+
+                Self_ { Call => Answer }
+            }
+        }
+
+        #[test]
+        fn keyword_names() {
+            let mirror = Mirror::self_();
+            assert_eq!(mirror, Mirror::Self_(SelfState::FromInit));
+            assert_eq!(
+                mirror.transition(MirrorEvent::Reflect),
+                Some(Mirror::Other(OtherState::FromReflectAtSelf))
+            );
+
+            assert_eq!(
+                Echo::call().transition(SelfEvent.into()),
+                Some(Echo::Answer(AnswerState::FromSelfAtCall))
+            );
+        }
+    }
+
+    mod initial_states {
+        use std::vec::Vec;
+
+        crate::state_machine! {
+            Sluice {
+                InitialStates { Closed, Open }
+
+                Shut { Open => Closed }
+                Lift { Closed => Open }
+            }
+        }
+
+        #[test]
+        fn initial_states() {
+            let kinds = Sluice::initial_kinds().collect::<Vec<_>>();
+            assert_eq!(kinds, [SluiceKind::Closed, SluiceKind::Open]);
+
+            let valves = Sluice::initial_states().collect::<Vec<_>>();
+            assert_eq!(valves, [Sluice::closed(), Sluice::open()]);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    mod shortest_path {
+        crate::state_machine! {
+            #[shortest_path]
+            Service {
+                InitialStates { Starting }
+                FinalStates { Stopped }
+
+                Ready { Starting => Running }
+                Overload { Running => Degraded }
+                Shed { Degraded => Draining }
+                Drain { Draining => Running }
+                Restart { Degraded => Starting }
+                Recover { Running => Running }
+                Stop { Draining => Stopped }
+            }
+        }
+
+        #[test]
+        fn path() {
+            assert_eq!(
+                Service::path(ServiceKind::Degraded, ServiceKind::Running).unwrap(),
+                [ServiceEvent::Shed, ServiceEvent::Drain]
+            );
+            assert_eq!(
+                Service::path(ServiceKind::Running, ServiceKind::Running).unwrap(),
+                []
+            );
+            assert_eq!(
+                Service::path(ServiceKind::Running, ServiceKind::Starting).unwrap(),
+                [ServiceEvent::Overload, ServiceEvent::Restart]
+            );
+            assert_eq!(
+                Service::path(ServiceKind::Starting, ServiceKind::Degraded).unwrap(),
+                [ServiceEvent::Ready, ServiceEvent::Overload]
+            );
+            assert_eq!(
+                Service::path(ServiceKind::Stopped, ServiceKind::Running),
+                None
+            );
+        }
+    }
+
+    mod reachable {
+        crate::state_machine! {
+            Service {
+                InitialStates { Starting }
+                FinalStates { Crashed }
+
+                Ready { Starting => Running }
+                Overload { Running => Degraded }
+                Recover { Degraded => Running }
+                Crash { Running, Degraded => Crashed }
+            }
+        }
+
+        #[test]
+        fn reachable_from() {
+            assert_eq!(
+                Service::reachable_from(ServiceKind::Degraded),
+                [
+                    ServiceKind::Running,
+                    ServiceKind::Degraded,
+                    ServiceKind::Crashed
+                ]
+            );
+            assert_eq!(
+                Service::reachable_from(ServiceKind::Starting),
+                [
+                    ServiceKind::Running,
+                    ServiceKind::Degraded,
+                    ServiceKind::Crashed
+                ]
+            );
+            assert!(Service::reachable_from(ServiceKind::Crashed).is_empty());
+        }
+    }
+
+    mod state_map {
+        use std::vec::Vec;
+
+        crate::state_machine! {
+            Valve {
+                InitialStates { Closed }
+
+                Crack { Closed => Ajar }
+                Widen { Ajar => Wide }
+                Seal { Ajar, Wide => Closed }
+            }
+        }
+
+        #[test]
+        fn from_fn() {
+            let names = ValveStateMap::from_fn(ValveKind::name);
+
+            assert_eq!(names.values(), ["Closed", "Ajar", "Wide"]);
+            assert_eq!(names[ValveKind::Wide], "Wide");
+        }
+
+        #[test]
+        fn index_mut() {
+            let mut visits: ValveStateMap<u32> = ValveStateMap::default();
+            let mut valve = Valve::closed();
+
+            for event in [ValveEvent::Crack, ValveEvent::Widen, ValveEvent::Seal] {
+                valve = valve.transition(event).unwrap();
+                visits[valve.kind()] += 1;
+            }
+
+            for (_, count) in visits.iter_mut() {
+                *count *= 10;
+            }
+
+            assert_eq!(
+                visits.iter().collect::<Vec<_>>(),
+                [
+                    (ValveKind::Closed, &10),
+                    (ValveKind::Ajar, &10),
+                    (ValveKind::Wide, &10)
+                ]
+            );
+        }
+    }
+
+    mod event_map {
+        use std::vec::Vec;
+
+        crate::state_machine! {
+            Kettle {
+                InitialStates { Cold }
+
+                Heat { Cold => Boiling }
+                Cool { Boiling => Cold }
+                Descale { Cold => Cold }
+            }
+        }
+
+        #[test]
+        fn rate_limits() {
+            let mut limits = KettleEventMap::from_fn(|event| match event {
+                KettleEvent::Descale => 1,
+                _ => 10,
+            });
+            limits[KettleEvent::Heat] -= 1;
+
+            assert_eq!(limits.values().len(), Kettle::EVENT_COUNT);
+            assert_eq!(
+                limits.iter().collect::<Vec<_>>(),
+                [
+                    (KettleEvent::Heat, &9),
+                    (KettleEvent::Cool, &10),
+                    (KettleEvent::Descale, &1)
+                ]
+            );
+        }
+
+        #[test]
+        fn default() {
+            let mut seen: KettleEventMap<bool> = KettleEventMap::default();
+            *seen.get_mut(KettleEvent::Cool) = true;
+
+            assert!(!seen[KettleEvent::Heat]);
+            assert!(seen[KettleEvent::Cool]);
+        }
+    }
+
+    mod state_bitset {
+        use std::vec::Vec;
+
+        crate::state_machine! {
+            Lift {
+                InitialStates { Parked }
+                Set Moving = { Rising, Sinking }
+                Region Doors { Parked, Loading }
+
+                Call { Parked => Rising }
+                Arrive { Moving => Loading }
+                Close { Loading => Sinking }
+                Rest { Loading => Parked }
+            }
+        }
+
+        #[test]
+        fn visited() {
+            let mut lift = Lift::parked();
+            let mut visited = LiftStateSet::of(lift.kind());
+
+            for event in [LiftEvent::Call, LiftEvent::Arrive, LiftEvent::Close] {
+                lift = lift.transition(event).unwrap();
+                assert!(visited.insert(lift.kind()));
+            }
+
+            assert!(!visited.insert(LiftKind::Loading));
+            assert_eq!(visited, LiftStateSet::ALL);
+            assert_eq!(visited.len(), Lift::STATE_COUNT);
+        }
+
+        #[test]
+        fn constants() {
+            assert_eq!(
+                LiftStateSet::MOVING.iter().collect::<Vec<_>>(),
+                [LiftKind::Rising, LiftKind::Sinking]
+            );
+            assert_eq!(!LiftStateSet::MOVING, LiftStateSet::DOORS);
+            assert_eq!(
+                LiftStateSet::MOVING & LiftStateSet::DOORS,
+                LiftStateSet::EMPTY
+            );
+            assert!(LiftStateSet::of(LiftKind::Parked).is_subset(LiftStateSet::DOORS));
+            assert_eq!(
+                [LiftKind::Parked, LiftKind::Loading]
+                    .iter()
+                    .copied()
+                    .collect::<LiftStateSet>(),
+                LiftStateSet::DOORS
+            );
+        }
+
+        #[test]
+        fn bits() {
+            let set = LiftStateSet::of(LiftKind::Parked) | LiftKind::Sinking.into();
+
+            assert_eq!(set.to_bits(), 0b1001);
+            assert_eq!(LiftStateSet::from_bits(0b1001), Some(set));
+            assert_eq!(LiftStateSet::from_bits(0b10000), None);
+            assert!((set - LiftStateSet::DOORS).contains(LiftKind::Sinking));
+        }
+    }
+
+    mod state_accessors {
+        crate::state_machine! {
+            Hatch {
+                InitialStates { Sealed }
+
+                Vent { Sealed => Venting }
+                Reseal { Venting => Sealed }
+            }
+        }
+
+        #[test]
+        fn as_state() {
+            let hatch = Hatch::sealed().transition(HatchEvent::Vent).unwrap();
+
+            assert_eq!(hatch.as_venting(), Some(&VentingState::FromVent));
+            assert_eq!(hatch.as_sealed(), None);
+            assert_eq!(hatch.into_venting(), Some(VentingState::FromVent));
+        }
+
+        #[test]
+        fn as_state_mut() {
+            let mut hatch = Hatch::sealed()
+                .transition(HatchEvent::Vent)
+                .and_then(|hatch| hatch.transition(HatchEvent::Reseal))
+                .unwrap();
+
+            *hatch.as_sealed_mut().unwrap() = SealedState::FromInit;
+
+            assert_eq!(hatch, Hatch::sealed());
+            assert!(hatch.as_venting_mut().is_none());
+        }
+    }
+
+    mod try_from {
+        use crate::WrongState;
+        use core::convert::{TryFrom, TryInto};
+
+        crate::state_machine! {
+            Drawbridge {
+                InitialStates { Raised }
+
+                Lower { Raised => Lowered }
+                Raise { Lowered => Raised }
+            }
+        }
+
+        fn cross(_: LoweredState) {}
+
+        #[test]
+        fn try_from() {
+            let bridge = Drawbridge::raised();
+
+            assert_eq!(
+                LoweredState::try_from(bridge.clone()),
+                Err(WrongState {
+                    expected: DrawbridgeKind::Lowered,
+                    actual: DrawbridgeKind::Raised,
+                })
+            );
+
+            let bridge = bridge.transition(DrawbridgeEvent::Lower).unwrap();
+            cross(bridge.try_into().unwrap());
+        }
+    }
+
+    mod regions {
+        crate::state_machine! {
+            Player {
+                InitialStates { Stopped }
+                Region Operational { Playing, Paused }
+                Region Audible { Playing }
+
+                Play { Stopped, Paused => Playing }
+                Pause { Playing => Paused }
+                Stop { Playing, Paused => Stopped }
+            }
+        }
+
+        #[test]
+        fn regions() {
+            let player = Player::stopped();
+            assert!(!player.in_operational());
+            assert!(!player.in_region(PlayerRegion::Operational));
+
+            let player = player.transition(PlayerEvent::Play).unwrap();
+            assert!(player.in_operational());
+            assert!(player.in_audible());
+
+            let player = player.transition(PlayerEvent::Pause).unwrap();
+            assert!(player.in_region(PlayerRegion::Operational));
+            assert!(!player.in_region(PlayerRegion::Audible));
+        }
+    }
+
+    mod state_sets {
+        crate::state_machine! {
+            Pump {
+                InitialStates { Running }
+                FinalStates { Stopped }
+                Set Operational = { Running, Paused, Degraded }
+
+                Shutdown { Operational => Stopped }
+                Pause { Running => Paused }
+                Fail { Running, Paused => Degraded }
+                Resume { Paused, Degraded => Running }
+            }
+        }
+
+        #[test]
+        fn state_sets() {
+            let pump = Pump::running();
+            assert!(pump.is_operational());
+
+            let pump = pump.transition(PumpEvent::Fail).unwrap();
+            assert!(pump.is_operational());
+
+            let pump = pump.transition(PumpEvent::Shutdown).unwrap();
+            assert_eq!(pump, Pump::Stopped(StoppedState::FromShutdown));
+            assert!(!pump.is_operational());
+        }
+    }
+
+    mod completions {
+        use crate::{CompletionLoop, Edge, StateMachine};
+
+        crate::state_machine! {
+            Order {
+                InitialStates { Draft }
+                on entry Validating => Validated
+
+                Submit { Draft => Validating }
+                Edit { Validated => Draft }
+            }
+        }
+
+        #[test]
+        fn transition() {
+            let order = Order::draft().transition(OrderEvent::Submit).unwrap();
+            assert_eq!(order, Order::Validated(ValidatedState::FromSubmit));
+            assert_eq!(order.provenance(), OrderProvenance::FromSubmit);
+        }
+
+        #[test]
+        fn settle() {
+            let order = DraftState::FromInit.submit();
+            assert_eq!(order.kind(), OrderKind::Validating);
+            assert_eq!(order.settle(), Order::Validated(ValidatedState::FromSubmit));
+        }
+
+        #[test]
+        fn run_to_completion() {
+            let order = DraftState::FromInit.submit();
+            assert_eq!(
+                order.complete_once(),
+                Some(Order::Validated(ValidatedState::FromSubmit))
+            );
+            assert_eq!(
+                order.run_to_completion(),
+                Ok(Order::Validated(ValidatedState::FromSubmit))
+            );
+            assert_eq!(Order::draft().complete_once(), None);
+        }
+
+        #[test]
+        fn undo() {
+            let (order, step) = Order::draft().step(OrderEvent::Submit).unwrap();
+            assert_eq!(step.to, OrderKind::Validated);
+            assert_eq!(order.undo(step), Some(Order::Draft(DraftState::FromEdit)));
+        }
+
+        /// A machine spinning between two states forever, which
+        /// `state_machine!` would reject.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        enum Spin {
+            Left,
+            Right,
+        }
+
+        impl StateMachine for Spin {
+            type Kind = Spin;
+            type Event = ();
+            type Provenance = ();
+
+            const STATE_COUNT: usize = 2;
+            const EVENT_COUNT: usize = 0;
+            const EVENTS: &'static [()] = &[];
+            const TRANSITIONS: &'static [Edge<Spin, ()>] = &[];
+
+            fn kind(&self) -> Spin {
+                *self
+            }
+
+            fn provenance(&self) {}
+
+            fn transition(&self, _: ()) -> Option<Spin> {
+                None
+            }
+
+            fn complete_once(&self) -> Option<Spin> {
+                match self {
+                    Spin::Left => Some(Spin::Right),
+                    Spin::Right => Some(Spin::Left),
+                }
+            }
+        }
+
+        #[test]
+        fn completion_loop() {
+            assert_eq!(
+                Spin::Left.run_to_completion(),
+                Err(CompletionLoop { kind: Spin::Left })
+            );
+        }
+
+        #[cfg(feature = "std")]
+        #[test]
+        fn coverage() {
+            let mut order = crate::Coverage::new(Order::draft());
+            let _ = order.transition(OrderEvent::Submit).unwrap();
+            let _ = order.transition(OrderEvent::Edit).unwrap();
+            assert!(order.is_complete());
+        }
+    }
+
+    mod templates {
+        crate::state_machine! {
+            Template Retry(Attempt, Backoff, Fail, Timer) {
+                Fail { Attempt => Backoff }
+                Timer { Backoff => Attempt }
+            }
+
+            Uplink {
+                InitialStates { Dialing }
+                FinalStates { Online }
+                Retry(Dialing, DialBackoff, DialFailed, DialTimer)
+                Retry(Authenticating, AuthBackoff, AuthFailed, AuthTimer)
+
+                Connected { Dialing => Authenticating }
+                Authenticated { Authenticating => Online }
+            }
+        }
+
+        #[test]
+        fn templates() {
+            let uplink = Uplink::dialing()
+                .transition(UplinkEvent::DialFailed)
+                .and_then(|u| u.transition(UplinkEvent::DialTimer))
+                .and_then(|u| u.transition(UplinkEvent::Connected))
+                .unwrap();
+            assert_eq!(uplink.kind(), UplinkKind::Authenticating);
+
+            let uplink = uplink.transition(UplinkEvent::AuthFailed).unwrap();
+            assert_eq!(uplink.kind(), UplinkKind::AuthBackoff);
+            assert_eq!(uplink.transition(UplinkEvent::DialTimer), None);
+        }
+    }
+
+    mod event_groups {
+        crate::state_machine! {
+            Kiosk {
+                InitialStates { Idle }
+                Group UserInput { Touch, Swipe, Tap }
+
+                UserInput { Idle => Active }
+                Timeout { Active => Idle }
+            }
+        }
+
+        #[test]
+        fn event_groups() {
+            for event in [KioskEvent::Touch, KioskEvent::Swipe, KioskEvent::Tap] {
+                assert_eq!(event.group(), Some(KioskEventGroup::UserInput));
+
+                let kiosk = Kiosk::idle().transition(event).unwrap();
+                assert_eq!(kiosk.kind(), KioskKind::Active);
+            }
+
+            assert_eq!(KioskEvent::Timeout.group(), None);
+        }
+    }
+
+    mod event_parents {
+        crate::state_machine! {
+            Terminal {
+                InitialStates { Idle }
+                KeypadPress : UserInput
+                Enter : KeypadPress
+
+                UserInput { Idle => Active }
+                KeypadPress { Active => Typing }
+                Enter { Typing => Idle }
+                Timeout { Active, Typing => Idle }
+            }
+        }
+
+        #[test]
+        fn event_parents() {
+            let terminal = Terminal::idle().transition(TerminalEvent::Enter).unwrap();
+            assert_eq!(terminal, Terminal::Active(ActiveState::FromEnter));
+
+            let terminal = terminal.transition(TerminalEvent::Enter).unwrap();
+            assert_eq!(terminal, Terminal::Typing(TypingState::FromEnter));
+            assert_eq!(terminal.transition(TerminalEvent::UserInput), None);
+
+            assert_eq!(
+                TerminalEvent::Enter.parent(),
+                Some(TerminalEvent::KeypadPress)
+            );
+            assert!(TerminalEvent::Enter.is_a(TerminalEvent::UserInput));
+            assert!(!TerminalEvent::UserInput.is_a(TerminalEvent::Enter));
+            assert!(!TerminalEvent::Timeout.is_a(TerminalEvent::UserInput));
+        }
+    }
+
+    mod deprecated {
+        crate::state_machine! {
+            Barrier {
+                InitialStates { Locked }
+
+                Coin { Locked => Unlocked }
+                #[deprecated(note = "pay with `Coin` instead")]
+                Kick { Locked => Unlocked }
+                Push { Unlocked => Locked }
+            }
+        }
+
+        #[test]
+        #[allow(deprecated)]
+        fn deprecated() {
+            assert_eq!(
+                LockedState::FromPush.kick(),
+                Barrier::Unlocked(UnlockedState::FromKick)
+            );
+            assert_eq!(
+                Barrier::locked().transition(BarrierEvent::Kick),
+                Some(Barrier::Unlocked(UnlockedState::FromKick))
+            );
+            assert!(Barrier::DOT.contains("Locked -> Unlocked [label=\"Kick\", style=dashed];"));
+        }
+    }
+
+    mod labels {
+        use crate::{Edge, StateMachine};
+
+        crate::state_machine! {
+            Gate {
+                InitialStates { Closed }
+
+                Coin "customer inserted a coin" { Closed => Open }
+                Push { Open => Closed }
+            }
+        }
+
+        #[test]
+        fn labels() {
+            assert_eq!(Gate::TRANSITIONS[0].label, Some("customer inserted a coin"));
+            assert_eq!(Gate::TRANSITIONS[1].label, None);
+            assert_eq!(
+                Gate::TRANSITIONS[0],
+                Edge {
+                    from: GateKind::Closed,
+                    event: GateEvent::Coin,
+                    to: GateKind::Open,
+                    label: None,
+                }
+            );
+
+            let (gate, step) = Gate::closed().step(GateEvent::Coin).unwrap();
+            assert_eq!(step.label(), Some("customer inserted a coin"));
+
+            let (_, step) = gate.step(GateEvent::Push).unwrap();
+            assert_eq!(step.label(), None);
+            assert!(Gate::DESCRIPTION.contains("\"label\":\"customer inserted a coin\""));
+        }
+    }
+
+    mod timeouts {
+        use core::time::Duration;
+
+        crate::state_machine! {
+            #[tokio]
+            Door {
+                InitialStates { Closed }
+
+                Open { Closed => Opened }
+                Close { Opened => Closed }
+                Timeout(30s) { Opened => Closed }
+            }
+        }
+
+        #[test]
+        fn timeout() {
+            assert_eq!(Door::closed().timeout(), None);
+            assert_eq!(
+                Door::closed()
+                    .transition(DoorEvent::Open)
+                    .unwrap()
+                    .timeout(),
+                Some((DoorEvent::Timeout, Duration::from_secs(30)))
+            );
+        }
+
+        #[cfg(feature = "std")]
+        #[tokio::test(start_paused = true)]
+        async fn spawn_runner() {
+            use tokio::time::{sleep, Instant};
+
+            let (sender, handle) = Door::closed().spawn_runner();
+            let mut states = sender.subscribe();
+
+            sender.send(DoorEvent::Open).unwrap();
+            states.changed().await.unwrap();
+            assert_eq!(states.borrow_and_update().kind(), DoorKind::Opened);
+            let opened = Instant::now();
+
+            // The events the state ignores do not restart its timer.
+            sleep(Duration::from_secs(20)).await;
+            sender.send(DoorEvent::Open).unwrap();
+            states.changed().await.unwrap();
+            assert_eq!(states.borrow_and_update().kind(), DoorKind::Closed);
+            assert_eq!(opened.elapsed().as_secs(), 30);
+
+            // Leaving the state cancels its timer.
+            sender.send(DoorEvent::Open).unwrap();
+            sender.send(DoorEvent::Close).unwrap();
+            sleep(Duration::from_secs(60)).await;
+            assert_eq!(states.borrow_and_update().kind(), DoorKind::Closed);
+            assert_eq!(states.borrow().provenance(), DoorProvenance::FromClose);
+
+            drop(sender);
+            assert_eq!(handle.await.unwrap().kind(), DoorKind::Closed);
+        }
+    }
+
+    mod on_invalid {
+        use crate::StateMachine;
+
+        crate::state_machine! {
+            Modem {
+                InitialStates { Idle }
+                FinalStates { Faulted }
+                OnInvalid => Faulted
+
+                Dial { Idle => Connected }
+                HangUp { Connected => Idle }
+            }
+        }
+
+        #[test]
+        fn on_invalid() {
+            let modem = Modem::idle().transition(ModemEvent::HangUp).unwrap();
+            assert_eq!(modem.kind(), ModemKind::Faulted);
+            assert_eq!(modem, Modem::Faulted(FaultedState::FromHangUp));
+            assert_eq!(modem.provenance(), ModemProvenance::FromHangUp);
+
+            let modem = Modem::idle().transition(ModemEvent::Dial).unwrap();
+            assert_eq!(modem, Modem::Connected(ConnectedState::FromDial));
+
+            let modem = modem.try_transition(ModemEvent::Dial).unwrap();
+            assert_eq!(modem, Modem::Faulted(FaultedState::FromDial));
+            assert!(modem.is_final());
+            assert!(!Modem::DOT.contains("-> Faulted"));
+        }
+
+        #[test]
+        fn accepts() {
+            assert!(Modem::idle().accepts(ModemEvent::Dial));
+            assert!(!Modem::idle().accepts(ModemEvent::HangUp));
+            assert!(!Modem::idle()
+                .transition(ModemEvent::HangUp)
+                .unwrap()
+                .accepts(ModemEvent::Dial));
+        }
+
+        #[cfg(feature = "std")]
+        mod walks {
+            use crate::StateMachine;
+            use proptest::test_runner::TestRunner;
+            use rand::{rngs::StdRng, SeedableRng};
+
+            crate::state_machine! {
+                #[proptest]
+                #[rand]
+                #[simulate]
+                Phone {
+                    InitialStates { Idle }
+                    FinalStates { Faulted }
+                    OnInvalid => Faulted
+
+                    Dial { Idle => Connected }
+                    HangUp { Connected => Idle }
+                }
+            }
+
+            #[test]
+            fn random_walk() {
+                let rng = StdRng::seed_from_u64(0);
+
+                for (_, phone) in Phone::idle().random_walk(rng).take(20) {
+                    assert_ne!(phone.kind(), PhoneKind::Faulted);
+                }
+
+                let mut rng = StdRng::seed_from_u64(0);
+                let mut phone = Phone::idle();
+
+                for _ in 0..20 {
+                    phone = phone.simulate_step(&mut rng).unwrap().1;
+                    assert_ne!(phone.kind(), PhoneKind::Faulted);
+                }
+            }
+
+            #[test]
+            fn sequences() {
+                let mut runner = TestRunner::default();
+
+                runner
+                    .run(&Phone::idle().valid_sequences(4), |events| {
+                        let mut phone = Phone::idle();
+
+                        for event in events {
+                            assert!(phone.accepts(event));
+                            phone = phone.transition(event).unwrap();
+                        }
+
+                        Ok(())
+                    })
+                    .unwrap();
+
+                runner
+                    .run(&Phone::idle().invalid_sequences(4), |events| {
+                        let (last, events) = events.split_last().unwrap();
+                        let mut phone = Phone::idle();
+
+                        for &event in events {
+                            phone = phone.transition(event).unwrap();
+                        }
+
+                        assert!(!phone.accepts(*last));
+                        assert_eq!(phone.transition(*last).unwrap().kind(), PhoneKind::Faulted);
+
+                        Ok(())
+                    })
+                    .unwrap();
+            }
+
+            #[test]
+            fn simulate() {
+                let mut output = std::vec::Vec::new();
+                let _ = Phone::idle()
+                    .simulate_with("Dial\n".as_bytes(), &mut output)
+                    .unwrap();
+                let output = std::string::String::from_utf8(output).unwrap();
+
+                assert!(output.contains("events: Dial\n"));
+                assert!(output.contains("events: HangUp\n"));
+            }
+        }
+    }
+
+    #[test]
+    fn runner() {
+        let mut runner = LockRunner::<2>::new(Lock::locked());
+        let mut kinds = [None; 3];
+        let mut steps = 0;
+
+        assert_eq!(runner.post(LockEvent::TurnKey), Ok(()));
+        assert_eq!(runner.post(LockEvent::TurnKey), Ok(()));
+        assert_eq!(runner.post(LockEvent::TurnKey), Err(LockEvent::TurnKey));
+
+        let result = runner.run(|_, _, to, queue| {
+            kinds[steps] = Some(to.kind());
+            steps += 1;
+
+            // Posted after the second `TurnKey`, which is still queued.
+            if steps == 1 {
+                assert_eq!(queue.post(LockEvent::BreakKeyhole), Ok(()));
+            }
+        });
+
+        assert_eq!(result.map(|lock| lock.kind()), Ok(LockKind::Broken));
+        assert_eq!(
+            kinds,
+            [
+                Some(LockKind::Unlocked),
+                Some(LockKind::Locked),
+                Some(LockKind::Broken)
+            ]
+        );
+
+        assert_eq!(runner.post(LockEvent::TurnKey), Ok(()));
+        let error = runner.run(|_, _, _, _| {}).unwrap_err();
+        assert_eq!(
+            error,
+            InvalidTransition::new(LockKind::Broken, LockEvent::TurnKey)
+        );
+        assert_eq!(error.location.line(), line!() - 5);
+        assert!(runner.queue().is_empty());
+    }
+
+    #[test]
+    fn replay() {
+        assert_eq!(
+            Lock::replay(
+                LockKind::Locked,
+                &[LockEvent::TurnKey, LockEvent::BreakKeyhole]
+            ),
+            Ok(Lock::Broken(BrokenState::FromBreakKeyhole))
+        );
+        assert_eq!(
+            Lock::replay(
+                LockKind::Locked,
+                &[LockEvent::BreakKeyhole, LockEvent::TurnKey]
+            ),
+            Err(ReplayError::InvalidEvent {
+                index: 1,
+                kind: LockKind::Broken,
+                event: LockEvent::TurnKey
+            })
+        );
+        assert_eq!(
+            Lock::replay(LockKind::Unlocked, &[]),
+            Err(LockReplayError::NotInitial(LockKind::Unlocked))
+        );
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(Lock::PATHS.len(), 3);
+
+        for path in Lock::PATHS {
+            let machine = Lock::replay(path.from, path.events).unwrap();
+
+            assert_eq!(machine.kind(), path.to);
+        }
+
+        assert!(Lock::paths(1, true)
+            .map(|path| path.events)
+            .eq([&[LockEvent::BreakKeyhole][..]]));
+        assert!(Lock::paths(2, true).map(|path| path.events).eq([
+            &[LockEvent::BreakKeyhole][..],
+            &[LockEvent::TurnKey, LockEvent::BreakKeyhole][..],
+        ]));
+        assert_eq!(Lock::paths(1, false).count(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn conformance() {
+        struct Padlock {
+            open: bool,
+            broken: bool,
+            turns: usize,
+        }
+
+        impl SystemUnderTest<Lock> for Padlock {
+            fn apply(&mut self, event: LockEvent) -> bool {
+                if self.broken {
+                    return false;
+                }
+
+                match event {
+                    LockEvent::TurnKey => {
+                        // Wears out after three turns.
+                        self.turns += 1;
+                        self.open = !self.open || self.turns > 3;
+                    }
+                    LockEvent::BreakKeyhole => self.broken = true,
+                }
+
+                true
+            }
+
+            fn observe(&self) -> LockKind {
+                match (self.broken, self.open) {
+                    (true, _) => LockKind::Broken,
+                    (false, true) => LockKind::Unlocked,
+                    (false, false) => LockKind::Locked,
+                }
+            }
+        }
+
+        let padlock = || Padlock {
+            open: false,
+            broken: false,
+            turns: 0,
+        };
+
+        let mut conformance = Conformance::new(Lock::locked(), padlock);
+
+        assert_eq!(conformance.exhaustive(3), Ok(()));
+        assert_eq!(
+            conformance.exhaustive(4),
+            Err(Divergence {
+                events: std::vec![LockEvent::TurnKey; 4],
+                expected: Some(LockKind::Locked),
+                observed: Some(LockKind::Unlocked),
+            })
+        );
+        assert!(conformance.random(7, 10, 3).is_ok());
+        assert!(conformance.random(7, 100, 20).is_err());
+        assert_eq!(
+            conformance.check(&[LockEvent::BreakKeyhole, LockEvent::TurnKey]),
+            Ok(())
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn coverage() {
+        let mut lock = Coverage::new(Lock::locked());
+
+        assert!(lock.transition(LockEvent::TurnKey).is_ok());
+        assert!(lock.transition(LockEvent::TurnKey).is_ok());
+        assert!(lock.transition(LockEvent::BreakKeyhole).is_ok());
+        assert!(lock.transition(LockEvent::TurnKey).is_err());
+
+        lock.restart(Lock::locked());
+        assert!(lock.transition(LockEvent::TurnKey).is_ok());
+
+        let turn_key = Edge {
+            from: LockKind::Locked,
+            event: LockEvent::TurnKey,
+            to: LockKind::Unlocked,
+            label: None,
+        };
+
+        assert_eq!(lock.hits(turn_key), 2);
+        assert_eq!(lock.covered().count(), 3);
+        assert!(lock.uncovered().eq([Edge {
+            from: LockKind::Unlocked,
+            event: LockEvent::BreakKeyhole,
+            to: LockKind::Broken,
+            label: None,
+        }]));
+        assert!(!lock.is_complete());
+        assert_eq!(std::format!("{}", turn_key), "Locked --TurnKey--> Unlocked");
+    }
+
+    #[cfg(feature = "critical-section")]
+    #[test]
+    fn interrupt_runner() {
+        static LOCK: LockInterruptRunner<2> = InterruptRunner::new();
+
+        let mut steps = 0;
+
+        assert_eq!(LOCK.post(LockEvent::TurnKey), Ok(()));
+        assert_eq!(LOCK.kind(), None);
+        // Nothing happens before the machine is started.
+        assert_eq!(LOCK.run(|_, _, _| steps += 1), Ok(()));
+
+        LOCK.start(Lock::locked());
+
+        assert_eq!(LOCK.post(LockEvent::BreakKeyhole), Ok(()));
+        assert_eq!(LOCK.post(LockEvent::TurnKey), Err(LockEvent::TurnKey));
+        assert_eq!(LOCK.run(|_, _, _| steps += 1), Ok(()));
+        assert_eq!(LOCK.kind(), Some(LockKind::Broken));
+        assert_eq!(steps, 2);
+
+        assert_eq!(LOCK.post(LockEvent::TurnKey), Ok(()));
+        let error = LOCK.run(|_, _, _| steps += 1).unwrap_err();
+        assert_eq!(
+            error,
+            InvalidTransition::new(LockKind::Broken, LockEvent::TurnKey)
+        );
+        assert_eq!(error.location.line(), line!() - 5);
+    }
+
+    #[test]
+    fn persist() {
+        use core::cell::Cell;
+
+        let store = Cell::new(None);
+
+        assert_eq!(
+            Lock::load_or_init(&store, Lock::locked()),
+            Ok(Lock::locked())
+        );
+
+        let lock = Lock::Unlocked(UnlockedState::FromTurnKey);
+        assert_eq!(lock.save(&store), Ok(()));
+
+        assert_eq!(Lock::load_or_init(&store, Lock::locked()), Ok(lock));
+
+        store.set(Some((LockKind::Unlocked, LockProvenance::FromInit)));
+
+        assert_eq!(
+            Lock::load_or_init(&store, Lock::locked()),
+            Err(LoadError::InvalidState(
+                LockKind::Unlocked,
+                LockProvenance::FromInit
+            ))
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let lock = Lock::Unlocked(UnlockedState::FromTurnKey);
+        let snapshot = lock.snapshot();
+
+        assert_eq!(snapshot.fingerprint, Lock::FINGERPRINT);
+        assert_ne!(Lock::FINGERPRINT, Keywords::FINGERPRINT);
+        assert_eq!(Lock::restore(snapshot), Ok(lock));
+
+        let stale = LockSnapshot {
+            fingerprint: Keywords::FINGERPRINT,
+            ..snapshot
+        };
+
+        assert_eq!(
+            Lock::restore(stale),
+            Err(RestoreError::Fingerprint(Keywords::FINGERPRINT))
+        );
+        assert_eq!(
+            Lock::restore_or_migrate(stale, |_| Some(Lock::locked())),
+            Ok(Lock::locked())
+        );
+        assert_eq!(
+            Lock::restore(LockSnapshot {
+                state: 100,
+                ..snapshot
+            }),
+            Err(RestoreError::UnknownState(100))
+        );
+    }
+
+    #[test]
+    fn history() {
+        let mut lock = History::<_, 2>::new(Lock::locked());
+
+        assert!(lock.transition(LockEvent::TurnKey).is_ok());
+        assert!(lock.transition(LockEvent::TurnKey).is_ok());
+        assert!(lock.transition(LockEvent::BreakKeyhole).is_ok());
+        assert!(lock.transition(LockEvent::TurnKey).is_err());
+
+        assert_eq!(lock.len(), 2);
+        assert!(lock.steps().eq([
+            (LockEvent::TurnKey, LockKind::Locked),
+            (LockEvent::BreakKeyhole, LockKind::Broken),
+        ]));
+        assert_eq!(
+            lock.last(),
+            Some((LockEvent::BreakKeyhole, LockKind::Broken))
+        );
+        assert_eq!(
+            lock.last_entered(LockKind::Locked),
+            Some(LockEvent::TurnKey)
+        );
+        assert_eq!(lock.last_entered(LockKind::Unlocked), None);
+
+        lock.clear();
+
+        assert!(lock.is_empty());
+        assert_eq!(lock.machine().kind(), LockKind::Broken);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn transition_async() {
+        use core::{
+            future::Future,
+            pin::pin,
+            task::{Context, Poll},
+        };
+        use std::{
+            sync::Arc,
+            task::{Wake, Waker},
+            vec::Vec,
+        };
+
+        struct NoopWaker;
+
+        impl Wake for NoopWaker {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        // The hooks below never wait, so a single poll completes the future.
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let waker = Waker::from(Arc::new(NoopWaker));
+
+            match pin!(future).poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(output) => output,
+                Poll::Pending => panic!("the future is pending"),
+            }
+        }
+
+        #[derive(Default)]
+        struct Log(Vec<&'static str>);
+
+        impl LockAsyncHooks for Log {
+            async fn on_exit(&mut self, _from: &Lock, _event: LockEvent) {
+                self.0.push("exit");
+            }
+
+            async fn on_entry(&mut self, _event: LockEvent, _to: &Lock) {
+                self.0.push("entry");
+            }
+        }
+
+        let mut log = Log::default();
+        let lock = block_on(Lock::locked().transition_async(LockEvent::TurnKey, &mut log));
+
+        assert_eq!(lock.map(|lock| lock.kind()), Some(LockKind::Unlocked));
+        assert_eq!(log.0, ["exit", "entry"]);
+
+        let broken = Lock::Broken(BrokenState::FromBreakKeyhole);
+
+        assert_eq!(
+            block_on(broken.transition_async(LockEvent::TurnKey, &mut log)),
+            None
+        );
+        assert_eq!(log.0, ["exit", "entry"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn hash() {
+        use std::collections::HashSet;
+
+        let machines = [
+            Lock::locked(),
+            Lock::Broken(BrokenState::FromBreakKeyhole),
+            Lock::locked(),
+        ];
+        let states = machines.iter().cloned().collect::<HashSet<_>>();
+
+        assert_eq!(states.len(), 2);
+        assert!(states.contains(&Lock::Locked(LockedState::FromInit)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn shared() {
+        use std::{thread, vec::Vec};
+
+        let lock = Shared::new(Lock::locked());
+
+        let results = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+
+                thread::spawn(move || lock.transition(LockEvent::BreakKeyhole).is_ok())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|&ok| ok)
+            .count();
+
+        // Only the first thread breaks the keyhole, the others find it
+        // broken.
+        assert_eq!(results, 1);
+        assert_eq!(lock.kind(), LockKind::Broken);
+        assert_eq!(
+            lock.transition(LockEvent::TurnKey),
+            Err(InvalidTransition::new(LockKind::Broken, LockEvent::TurnKey))
+        );
+        assert!(lock.with(|lock| lock.is_final()));
+        assert_eq!(lock.get(), Lock::Broken(BrokenState::FromBreakKeyhole));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn observed() {
+        use std::{cell::RefCell, rc::Rc, vec::Vec};
+
+        let steps = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&steps);
+
+        let mut lock = Observed::new(Lock::locked()).with_observer(move |from, event, to| {
+            log.borrow_mut().push((from.kind(), event, to.kind()))
+        });
+
+        assert!(lock.transition(LockEvent::TurnKey).is_ok());
+        assert!(lock.transition(LockEvent::BreakKeyhole).is_ok());
+        assert!(lock.transition(LockEvent::TurnKey).is_err());
+
+        assert_eq!(lock.into_inner().kind(), LockKind::Broken);
+        assert_eq!(
+            *steps.borrow(),
+            [
+                (LockKind::Locked, LockEvent::TurnKey, LockKind::Unlocked),
+                (
+                    LockKind::Unlocked,
+                    LockEvent::BreakKeyhole,
+                    LockKind::Broken
+                ),
+            ]
+        );
+    }
 }
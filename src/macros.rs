@@ -0,0 +1,47 @@
+/// Check whether a machine is in one of the given states, ignoring how the
+/// state was entered.
+///
+/// ```text
+/// in_state!(lock, Lock::Locked | Lock::Broken)
+/// ```
+#[macro_export]
+macro_rules! in_state {
+    ($machine:expr, $($state:path)|+ $(,)?) => {
+        matches!($machine, $($state { .. })|+)
+    };
+}
+
+/// Match on the state a machine is in, ignoring how the state was entered.
+/// Every arm must be followed by a comma, and a final `_` arm may be used as
+/// a catch-all.
+///
+/// ```text
+/// match_state!(lock, {
+///     Lock::Locked | Lock::Unlocked => "working",
+///     Lock::Broken => "broken",
+/// })
+/// ```
+#[macro_export]
+macro_rules! match_state {
+    (@arms $machine:expr; [$($arms:tt)*]; _ => $default:expr $(,)?) => {
+        match $machine {
+            $($arms)*
+            _ => $default,
+        }
+    };
+    (@arms $machine:expr; [$($arms:tt)*]; $($state:path)|+ => $body:expr $(, $($rest:tt)*)?) => {
+        $crate::match_state!(
+            @arms $machine;
+            [$($arms)* $($state { .. })|+ => $body,];
+            $($($rest)*)?
+        )
+    };
+    (@arms $machine:expr; [$($arms:tt)*];) => {
+        match $machine {
+            $($arms)*
+        }
+    };
+    ($machine:expr, { $($arms:tt)* }) => {
+        $crate::match_state!(@arms $machine; []; $($arms)*)
+    };
+}
@@ -0,0 +1,76 @@
+use core::fmt::{self, Debug};
+use std::{boxed::Box, vec::Vec};
+
+use crate::{InvalidTransition, StateMachine};
+
+/// A callback invoked with the state left, the event and the state entered.
+type Observer<M> = Box<dyn FnMut(&M, <M as StateMachine>::Event, &M)>;
+
+/// A machine which calls its observers on every transition it takes, so that
+/// logging, persistence or UI updates can hang off the transitions without
+/// touching every place which drives the machine.
+pub struct Observed<M: StateMachine> {
+    machine: M,
+    observers: Vec<Observer<M>>,
+}
+
+impl<M: StateMachine> Observed<M> {
+    /// Wrap `machine`, without any observer.
+    pub fn new(machine: M) -> Self {
+        Observed {
+            machine,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Call `observer` with the state left, the event and the state entered
+    /// on every transition, after the observers registered before it.
+    pub fn observe(&mut self, observer: impl FnMut(&M, M::Event, &M) + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Like [`observe`](Observed::observe), but consuming and returning the
+    /// wrapper.
+    pub fn with_observer(mut self, observer: impl FnMut(&M, M::Event, &M) + 'static) -> Self {
+        self.observe(observer);
+        self
+    }
+
+    /// The state the machine is in.
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    /// Unwrap the machine, dropping the observers.
+    pub fn into_inner(self) -> M {
+        self.machine
+    }
+
+    /// Apply `event` to the machine and notify the observers. If the current
+    /// state does not accept it, the machine is left as it is and the
+    /// observers are not called.
+    #[track_caller]
+    pub fn transition(
+        &mut self,
+        event: M::Event,
+    ) -> Result<&M, InvalidTransition<M::Kind, M::Event>> {
+        let next = self.machine.try_transition(event)?;
+
+        for observer in &mut self.observers {
+            observer(&self.machine, event, &next);
+        }
+
+        self.machine = next;
+
+        Ok(&self.machine)
+    }
+}
+
+impl<M: StateMachine + Debug> Debug for Observed<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Observed")
+            .field("machine", &self.machine)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// A path through the diagram of a machine, as listed by the generated
+/// `paths`: the initial state it starts from, the events it takes and the
+/// state it ends in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Path<K: 'static, E: 'static> {
+    /// The initial state the path starts from.
+    pub from: K,
+
+    /// The events taken, in order.
+    pub events: &'static [E],
+
+    /// The state reached by the events.
+    pub to: K,
+}
+
+/// A shortest sequence of events leading from `from` to `to` through `edges`,
+/// found breadth first. Called by the generated `path`.
+#[cfg(feature = "alloc")]
+pub fn shortest_path<K: Copy + Eq, E: Copy>(edges: &[(K, E, K)], from: K, to: K) -> Option<Vec<E>> {
+    // The states reached so far, with the index of the state each was
+    // reached from and the event taken.
+    let mut reached: Vec<(K, Option<(usize, E)>)> = Vec::new();
+    reached.push((from, None));
+
+    let mut next = 0;
+
+    while next < reached.len() {
+        let (state, _) = reached[next];
+
+        if state == to {
+            let mut events = Vec::new();
+            let mut current = next;
+
+            while let Some((previous, event)) = reached[current].1 {
+                events.push(event);
+                current = previous;
+            }
+
+            events.reverse();
+
+            return Some(events);
+        }
+
+        for &(edge_from, event, edge_to) in edges {
+            if edge_from == state && !reached.iter().any(|&(s, _)| s == edge_to) {
+                reached.push((edge_to, Some((next, event))));
+            }
+        }
+
+        next += 1;
+    }
+
+    None
+}
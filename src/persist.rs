@@ -0,0 +1,65 @@
+use core::{
+    cell::Cell,
+    convert::Infallible,
+    fmt::{self, Debug, Display},
+};
+
+use crate::StateMachine;
+
+/// The state of a machine, as saved by a store.
+type Saved<M> = (<M as StateMachine>::Kind, <M as StateMachine>::Provenance);
+
+/// A store for the state of a machine, as its kind and provenance, so that
+/// it can be resumed with `load_or_init` after a restart.
+pub trait Persist<M: StateMachine> {
+    /// The error returned when the store fails.
+    type Error;
+
+    /// Save the state of the machine, replacing the saved one.
+    fn save(&self, kind: M::Kind, provenance: M::Provenance) -> Result<(), Self::Error>;
+
+    /// The saved state, or `None` if nothing was saved yet.
+    fn load(&self) -> Result<Option<Saved<M>>, Self::Error>;
+}
+
+/// An in-memory store, mostly useful in tests.
+impl<M: StateMachine> Persist<M> for Cell<Option<Saved<M>>> {
+    type Error = Infallible;
+
+    fn save(&self, kind: M::Kind, provenance: M::Provenance) -> Result<(), Infallible> {
+        self.set(Some((kind, provenance)));
+
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<Saved<M>>, Infallible> {
+        Ok(self.get())
+    }
+}
+
+/// The error returned when a machine cannot be loaded from a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoadError<K, P, E> {
+    /// The store failed.
+    Persist(E),
+
+    /// The saved state cannot be entered with the saved provenance, so it
+    /// was not saved by this machine.
+    InvalidState(K, P),
+}
+
+impl<K: Debug, P: Debug, E: Display> Display for LoadError<K, P, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Persist(error) => write!(f, "cannot load the machine: {}", error),
+            LoadError::InvalidState(kind, provenance) => write!(
+                f,
+                "state {:?} cannot be entered with provenance {:?}",
+                kind, provenance
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Debug, P: Debug, E: Debug + Display> std::error::Error for LoadError<K, P, E> {}
@@ -0,0 +1,121 @@
+use crate::{InvalidTransition, StateMachine};
+
+/// A queue holding at most `N` events, in the order they were posted.
+#[derive(Debug, Clone)]
+pub struct EventQueue<E: Copy, const N: usize> {
+    events: [Option<E>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<E: Copy, const N: usize> EventQueue<E, N> {
+    pub(crate) const fn new() -> Self {
+        EventQueue {
+            events: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Add `event` at the end of the queue, or give it back if the queue is
+    /// full.
+    pub fn post(&mut self, event: E) -> Result<(), E> {
+        if self.len == N {
+            return Err(event);
+        }
+
+        self.events[(self.head + self.len) % N] = Some(event);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// The number of events waiting in the queue.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no event is waiting in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<E> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+
+        event
+    }
+}
+
+/// A machine driven by a queue of events with run-to-completion semantics:
+/// each event is applied and its action returns before the next one is taken
+/// from the queue, so the events posted by an action are only processed after
+/// it.
+///
+/// The queue holds at most `N` events and doesn't allocate, so that runners
+/// are available without `std`.
+#[derive(Debug, Clone)]
+pub struct Runner<M: StateMachine, const N: usize = 16> {
+    machine: M,
+    queue: EventQueue<M::Event, N>,
+}
+
+impl<M: StateMachine, const N: usize> Runner<M, N> {
+    /// Drive `machine`, starting with an empty queue.
+    pub fn new(machine: M) -> Self {
+        Runner {
+            machine,
+            queue: EventQueue::new(),
+        }
+    }
+
+    /// The state the machine is in.
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    /// Unwrap the machine, dropping the events still in the queue.
+    pub fn into_inner(self) -> M {
+        self.machine
+    }
+
+    /// The events waiting to be processed.
+    pub fn queue(&self) -> &EventQueue<M::Event, N> {
+        &self.queue
+    }
+
+    /// Add `event` at the end of the queue, or give it back if the queue is
+    /// full.
+    pub fn post(&mut self, event: M::Event) -> Result<(), M::Event> {
+        self.queue.post(event)
+    }
+
+    /// Process the queued events one at a time until the queue is empty,
+    /// calling `action` with the state left, the event, the state entered
+    /// and the queue after every transition, so that it can post further
+    /// events.
+    ///
+    /// If the current state does not accept an event, the event is dropped,
+    /// the machine is left in that state and the rest of the queue is kept.
+    #[track_caller]
+    pub fn run(
+        &mut self,
+        mut action: impl FnMut(&M, M::Event, &M, &mut EventQueue<M::Event, N>),
+    ) -> Result<&M, InvalidTransition<M::Kind, M::Event>> {
+        while let Some(event) = self.queue.pop() {
+            let next = self.machine.try_transition(event)?;
+
+            action(&self.machine, event, &next, &mut self.queue);
+
+            self.machine = next;
+        }
+
+        Ok(&self.machine)
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{InvalidTransition, StateMachine};
+
+/// A machine which can be driven from several threads at once. Clones share
+/// the same machine, and every transition happens under a lock so that each
+/// event is applied to the state left by the previous one.
+#[derive(Debug)]
+pub struct Shared<M: StateMachine> {
+    machine: Arc<Mutex<M>>,
+}
+
+impl<M: StateMachine> Shared<M> {
+    /// Share `machine`.
+    pub fn new(machine: M) -> Self {
+        Shared {
+            machine: Arc::new(Mutex::new(machine)),
+        }
+    }
+
+    // A transition replaces the machine in a single assignment, so a thread
+    // panicking while holding the lock cannot leave it in an invalid state.
+    fn lock(&self) -> MutexGuard<'_, M> {
+        self.machine.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Apply `event` to the machine, returning the kind of state it entered.
+    /// If the current state does not accept it, the machine is left as it
+    /// is.
+    #[track_caller]
+    pub fn transition(
+        &self,
+        event: M::Event,
+    ) -> Result<M::Kind, InvalidTransition<M::Kind, M::Event>> {
+        let mut machine = self.lock();
+        *machine = machine.try_transition(event)?;
+
+        Ok(machine.kind())
+    }
+
+    /// The kind of state the machine is in.
+    pub fn kind(&self) -> M::Kind {
+        self.lock().kind()
+    }
+
+    /// Call `f` with the machine, holding the lock so that no transition
+    /// happens in the meantime.
+    pub fn with<T>(&self, f: impl FnOnce(&M) -> T) -> T {
+        f(&self.lock())
+    }
+}
+
+impl<M: StateMachine + Clone> Shared<M> {
+    /// A copy of the machine as it is now.
+    pub fn get(&self) -> M {
+        self.lock().clone()
+    }
+}
+
+impl<M: StateMachine> Clone for Shared<M> {
+    fn clone(&self) -> Self {
+        Shared {
+            machine: Arc::clone(&self.machine),
+        }
+    }
+}